@@ -0,0 +1,590 @@
+use axum::body::{Bytes, HttpBody};
+use hyper::body::Frame;
+use rusqlite::{params, Connection, Result, Row};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::convert::Infallible;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::mpsc;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::config::config;
+use crate::db::{SWAP_VOLUME_A, SWAP_VOLUME_B};
+use crate::features::{is_enabled, FeatureFlags};
+use crate::query_log;
+use crate::wash::wash_trade_condition;
+
+/// Candle intervals, as (label, width in milliseconds). Buckets are aligned
+/// to the Unix epoch, so `1d` candles cover UTC days.
+pub const CANDLE_INTERVALS: [(&str, i64); 4] = [
+    ("1m", 60 * 1000),
+    ("5m", 5 * 60 * 1000),
+    ("1h", 60 * 60 * 1000),
+    ("1d", 24 * 60 * 60 * 1000),
+];
+
+/// Default interval between aggregation passes (in seconds)
+pub const DEFAULT_CANDLE_AGGREGATION_INTERVAL_SECS: u64 = 10;
+
+/// Number of new swaps rolled into candles per batch
+const CANDLE_SWAP_BATCH: usize = 1000;
+
+/// Number of candles a candle download reads per query
+const DOWNLOAD_CHUNK_ROWS: usize = 1000;
+
+/// Number of read chunks a candle download buffers ahead of the client
+const DOWNLOAD_CHANNEL_CHUNKS: usize = 4;
+
+/// Points a candle download returns when `max_points` is not given
+pub const DEFAULT_DOWNLOAD_MAX_POINTS: usize = 100_000;
+
+/// Most points a candle download may be asked for
+pub const MAX_DOWNLOAD_MAX_POINTS: usize = 1_000_000;
+
+/// Looks up the width of a candle interval by its label.
+pub fn interval_ms(label: &str) -> Option<i64> {
+    CANDLE_INTERVALS.iter().find(|(l, _)| *l == label).map(|(_, ms)| *ms)
+}
+
+/// Returns the configured aggregation interval (`CANDLE_AGGREGATION_INTERVAL_SECS`).
+fn aggregation_interval_secs() -> u64 {
+    config().candle_aggregation_interval_secs
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// OHLCV summary of one pool's swaps in one bucket.
+/// 
+/// Prices are execution prices in token B per token A, as on the trade
+/// tape; volumes are summed amounts of token A and of token B the swaps
+/// moved, whichever way they traded.
+#[derive(Serialize)]
+pub struct Candle {
+    /// Start of the bucket (ms)
+    pub bucket_start: i64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    pub volume_a: f64,
+    pub volume_b: f64,
+    pub trades: i64,
+}
+
+/// Execution price of a swap in token B per token A, whichever way it traded
+const SWAP_PRICE: &str = "CASE direction WHEN 'b_to_a' THEN amount_in / amount_out ELSE amount_out / amount_in END";
+
+/// Swaps that have an execution price; one of unknown direction has none
+const PRICED_SWAP: &str = "amount_in > 0 AND (direction = 'a_to_b' OR (direction = 'b_to_a' AND amount_out > 0))";
+
+/// Columns `Candle::from_row` reads, in order
+pub const CANDLE_COLUMNS: &str = "bucket_start, open, high, low, close, volume_a, volume_b, trades";
+
+impl Candle {
+    /// Maps a row selecting `CANDLE_COLUMNS`.
+    pub fn from_row(row: &Row<'_>) -> Result<Candle> {
+        Ok(Candle {
+            bucket_start: row.get(0)?,
+            open: row.get(1)?,
+            high: row.get(2)?,
+            low: row.get(3)?,
+            close: row.get(4)?,
+            volume_a: row.get(5)?,
+            volume_b: row.get(6)?,
+            trades: row.get(7)?,
+        })
+    }
+}
+
+/// Computes candles straight from the swaps, leaving out those the
+/// wash-trading heuristics flag (see `wash::wash_trade_condition`).
+/// 
+/// Unlike the `candles` table these aren't rolled up ahead of time, so they
+/// are current as of the last indexed swap, and cost a pass over the pool's
+/// swaps in the requested range.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool to chart
+/// * `width` - Width of a bucket in milliseconds, from `CANDLE_INTERVALS`
+/// * `from` - Only buckets starting at or after this time (ms)
+/// * `to` - Only buckets starting before this time (ms)
+/// * `limit` - `LIMIT`/`OFFSET` clause selecting the page of buckets
+/// 
+/// # Returns
+/// * `Result<Vec<Candle>>` - Candles oldest first; buckets left without swaps have none
+pub fn filtered_candles(
+    conn: &Connection,
+    pool_id: &str,
+    width: i64,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: &str,
+) -> Result<Vec<Candle>> {
+    query_log::query_all(
+        conn,
+        "filtered_candles",
+        &format!(
+            "WITH kept AS (
+                 SELECT s.id, s.timestamp, s.timestamp - s.timestamp % ?2 AS bucket_start,
+                        {price} AS price, {volume_a} AS volume_a, {volume_b} AS volume_b
+                 FROM swaps s
+                 WHERE s.pool_id = ?1 AND {priced}
+                   AND (?3 IS NULL OR s.timestamp - s.timestamp % ?2 >= ?3)
+                   AND (?4 IS NULL OR s.timestamp - s.timestamp % ?2 < ?4)
+                   AND NOT {wash}
+             )
+             SELECT k.bucket_start,
+                    (SELECT o.price FROM kept o WHERE o.bucket_start = k.bucket_start
+                     ORDER BY o.timestamp, o.id LIMIT 1),
+                    MAX(k.price),
+                    MIN(k.price),
+                    (SELECT c.price FROM kept c WHERE c.bucket_start = k.bucket_start
+                     ORDER BY c.timestamp DESC, c.id DESC LIMIT 1),
+                    SUM(k.volume_a),
+                    SUM(k.volume_b),
+                    COUNT(*)
+             FROM kept k
+             GROUP BY k.bucket_start
+             ORDER BY k.bucket_start{limit}",
+            price = SWAP_PRICE,
+            priced = PRICED_SWAP,
+            volume_a = SWAP_VOLUME_A,
+            volume_b = SWAP_VOLUME_B,
+            wash = wash_trade_condition("s"),
+            limit = limit
+        ),
+        params![pool_id, width, from, to],
+        Candle::from_row,
+    )
+}
+
+/// Reads rolled-up candles from the `candles` table.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool to chart
+/// * `interval` - Candle interval label, from `CANDLE_INTERVALS`
+/// * `from` - Only buckets starting at or after this time (ms)
+/// * `to` - Only buckets starting before this time (ms)
+/// * `limit` - `LIMIT`/`OFFSET` clause selecting the page of buckets
+/// 
+/// # Returns
+/// * `Result<Vec<Candle>>` - Candles oldest first
+pub fn rolled_up_candles(
+    conn: &Connection,
+    pool_id: &str,
+    interval: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+    limit: &str,
+) -> Result<Vec<Candle>> {
+    query_log::query_all(
+        conn,
+        "rolled_up_candles",
+        &format!(
+            "SELECT {}
+             FROM candles
+             WHERE pool_id = ?1 AND interval = ?2
+               AND (?3 IS NULL OR bucket_start >= ?3) AND (?4 IS NULL OR bucket_start < ?4)
+             ORDER BY bucket_start{}",
+            CANDLE_COLUMNS, limit
+        ),
+        params![pool_id, interval, from, to],
+        Candle::from_row,
+    )
+}
+
+/// Recomputes one candle from the swaps in its bucket.
+/// 
+/// The whole bucket is re-read rather than updated incrementally, so a swap
+/// indexed late, with an earlier timestamp than swaps already rolled up,
+/// still lands in the right place for `open` and `close`. A bucket left
+/// without priced swaps loses its candle.
+fn rebuild_candle(conn: &Connection, pool_id: &str, interval: &str, bucket_start: i64, width: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "rebuild_candle:delete",
+        "DELETE FROM candles WHERE pool_id = ?1 AND interval = ?2 AND bucket_start = ?3",
+        params![pool_id, interval, bucket_start],
+    )?;
+    query_log::execute(
+        conn,
+        "rebuild_candle",
+        &format!(
+            "INSERT INTO candles
+                 (pool_id, interval, bucket_start, open, high, low, close, volume_a, volume_b, trades)
+             SELECT ?1, ?2, ?3,
+                    (SELECT {price} FROM swaps
+                     WHERE pool_id = ?1 AND timestamp >= ?3 AND timestamp < ?4 AND {priced}
+                     ORDER BY timestamp, id LIMIT 1),
+                    MAX({price}),
+                    MIN({price}),
+                    (SELECT {price} FROM swaps
+                     WHERE pool_id = ?1 AND timestamp >= ?3 AND timestamp < ?4 AND {priced}
+                     ORDER BY timestamp DESC, id DESC LIMIT 1),
+                    SUM({volume_a}),
+                    SUM({volume_b}),
+                    COUNT(*)
+             FROM swaps
+             WHERE pool_id = ?1 AND timestamp >= ?3 AND timestamp < ?4 AND {priced}
+             GROUP BY pool_id",
+            price = SWAP_PRICE,
+            priced = PRICED_SWAP,
+            volume_a = SWAP_VOLUME_A,
+            volume_b = SWAP_VOLUME_B
+        ),
+        params![pool_id, interval, bucket_start, bucket_start + width],
+    )?;
+    Ok(())
+}
+
+/// Rebuilds the candles of a swap the aggregator already rolled up, after
+/// its direction changed. Swaps it hasn't reached yet are left to it.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `swap_id` - Row ID of the swap
+/// * `pool_id` - Pool of the swap
+/// * `timestamp` - Timestamp of the swap
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn rebuild_swap_candles(conn: &Connection, swap_id: i64, pool_id: &str, timestamp: i64) -> Result<()> {
+    if swap_id > rolled_up_to(conn)? {
+        return Ok(());
+    }
+    for (interval, width) in CANDLE_INTERVALS {
+        rebuild_candle(conn, pool_id, interval, timestamp - timestamp.rem_euclid(width), width)?;
+    }
+    Ok(())
+}
+
+/// Reads the row ID of the last swap the aggregator rolled into candles.
+fn rolled_up_to(conn: &Connection) -> Result<i64> {
+    Ok(query_log::query_opt(
+        conn,
+        "candle_progress",
+        "SELECT last_swap_id FROM candle_progress WHERE id = 1",
+        params![],
+        |row| row.get(0),
+    )?
+    .unwrap_or(0))
+}
+
+/// Rolls up the candles of a pool's range that hold swaps the aggregator
+/// hasn't reached yet, e.g. history indexed before candles were enabled,
+/// which it rolls up oldest row first.
+/// 
+/// Such buckets are rebuilt from the swaps and persisted like the
+/// aggregator's own candles, so a range is served complete and stays so.
+/// Only swaps past the aggregator's progress are scanned, so this costs
+/// next to nothing once it has caught up. The aggregator rebuilds the
+/// buckets again when it gets to their swaps, which leaves them unchanged.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool to chart
+/// * `interval` - Candle interval label, from `CANDLE_INTERVALS`
+/// * `from` - Only buckets starting at or after this time (ms)
+/// * `to` - Only buckets starting before this time (ms)
+/// 
+/// # Returns
+/// * `Result<usize>` - Number of candles rolled up
+pub fn backfill_candles(
+    conn: &Connection,
+    pool_id: &str,
+    interval: &str,
+    from: Option<i64>,
+    to: Option<i64>,
+) -> Result<usize> {
+    let Some(width) = interval_ms(interval) else {
+        return Ok(0);
+    };
+    // `+pool_id` keeps SQLite on the row ID range of the swaps not rolled up
+    let buckets: Vec<i64> = query_log::query_all(
+        conn,
+        "backfill_candles:buckets",
+        &format!(
+            "SELECT DISTINCT timestamp - timestamp % ?3 FROM swaps
+             WHERE id > ?1 AND +pool_id = ?2 AND {priced}
+               AND (?4 IS NULL OR timestamp - timestamp % ?3 >= ?4)
+               AND (?5 IS NULL OR timestamp - timestamp % ?3 < ?5)",
+            priced = PRICED_SWAP
+        ),
+        params![rolled_up_to(conn)?, pool_id, width, from, to],
+        |row| row.get(0),
+    )?;
+    if buckets.is_empty() {
+        return Ok(0);
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for &bucket_start in &buckets {
+        rebuild_candle(&tx, pool_id, interval, bucket_start, width)?;
+    }
+    tx.commit()?;
+    Ok(buckets.len())
+}
+
+/// Rolls the next batch of swaps indexed since the last pass into candles.
+/// 
+/// Progress is tracked by swap row ID in `candle_progress`, so every swap is
+/// rolled up once whatever its timestamp, and a restart resumes where the
+/// last pass stopped.
+/// 
+/// # Returns
+/// * `Result<usize>` - Number of swaps rolled up; less than a full batch once caught up
+pub fn aggregate_candles(conn: &Connection) -> Result<usize> {
+    let last_id = rolled_up_to(conn)?;
+    let swaps: Vec<(i64, String, i64)> = query_log::query_all(
+        conn,
+        "aggregate_candles:swaps",
+        "SELECT id, pool_id, timestamp FROM swaps WHERE id > ?1 ORDER BY id LIMIT ?2",
+        params![last_id, CANDLE_SWAP_BATCH as i64],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let Some((new_last_id, _, _)) = swaps.last() else {
+        return Ok(0);
+    };
+
+    let mut buckets = BTreeSet::new();
+    for (_, pool_id, timestamp) in &swaps {
+        for (interval, width) in CANDLE_INTERVALS {
+            buckets.insert((pool_id.as_str(), interval, timestamp - timestamp.rem_euclid(width), width));
+        }
+    }
+    for (pool_id, interval, bucket_start, width) in buckets {
+        rebuild_candle(conn, pool_id, interval, bucket_start, width)?;
+    }
+
+    query_log::execute(
+        conn,
+        "aggregate_candles:save",
+        "INSERT INTO candle_progress (id, last_swap_id, updated_at) VALUES (1, ?1, ?2)
+         ON CONFLICT(id) DO UPDATE SET last_swap_id = excluded.last_swap_id, updated_at = excluded.updated_at",
+        params![new_last_id, now_ms()],
+    )?;
+    Ok(swaps.len())
+}
+
+/// Periodically rolls newly indexed swaps into the `candles` table.
+/// 
+/// Runs indefinitely, every `CANDLE_AGGREGATION_INTERVAL_SECS` seconds.
+/// Each pass works through new swaps a batch at a time, releasing the
+/// connection between batches, until it has caught up.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `flags` - Feature flags; passes are skipped while `candles` is disabled
+pub async fn run_candle_aggregator(conn_arc: Arc<Mutex<Connection>>, flags: FeatureFlags) {
+    let interval = Duration::from_secs(aggregation_interval_secs());
+    loop {
+        if is_enabled(&flags, "candles") {
+            loop {
+                let aggregated = match conn_arc.lock() {
+                    Ok(conn) => aggregate_candles(&conn),
+                    Err(_) => return,
+                };
+                match aggregated {
+                    Ok(count) if count == CANDLE_SWAP_BATCH => tokio::task::yield_now().await,
+                    Ok(_) => break,
+                    Err(e) => {
+                        warn!(error = %e, "failed to aggregate candles");
+                        break;
+                    }
+                }
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+/// A chunked download of one pool's candles
+pub struct CandleDownload {
+    pub pool_id: String,
+    pub interval: String,
+    /// Only buckets starting at or after this time (ms)
+    pub from: Option<i64>,
+    /// Only buckets starting before this time (ms)
+    pub to: Option<i64>,
+    /// Most candles written before the download stops as truncated
+    pub max_points: usize,
+}
+
+/// Response body streaming NDJSON lines as a download produces them
+pub struct NdjsonBody {
+    rx: mpsc::Receiver<Bytes>,
+}
+
+impl HttpBody for NdjsonBody {
+    type Data = Bytes;
+    type Error = Infallible;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<std::result::Result<Frame<Bytes>, Infallible>>> {
+        self.rx.poll_recv(cx).map(|chunk| chunk.map(|bytes| Ok(Frame::data(bytes))))
+    }
+}
+
+/// Streams a pool's candles as NDJSON, reading the range
+/// `DOWNLOAD_CHUNK_ROWS` candles at a time.
+/// 
+/// Buckets of the range the aggregator hasn't reached are rolled up first
+/// (see `backfill_candles`). Each chunk continues after the last bucket of the previous one. The
+/// connection is locked only while a chunk is read, and a chunk is only
+/// read once the client has taken the ones before it (up to
+/// `DOWNLOAD_CHANNEL_CHUNKS` ahead), so a multi-month range neither holds
+/// the database nor the whole series in memory. Each candle is a line; the
+/// last line is a summary with `done`, `points`, `truncated` and, when
+/// `max_points` cut the range short, the `next_from` to resume at. A read
+/// that fails mid-stream ends it with an `error` line instead.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `download` - Pool, interval, range and point limit of the download
+/// 
+/// # Returns
+/// * `NdjsonBody` - Body the lines are streamed into
+pub fn download_candles(conn_arc: Arc<Mutex<Connection>>, download: CandleDownload) -> NdjsonBody {
+    let (tx, rx) = mpsc::channel(DOWNLOAD_CHANNEL_CHUNKS);
+    tokio::spawn(async move {
+        let summary = match stream_candles(&conn_arc, &download, &tx).await {
+            Ok(Some(summary)) => summary,
+            // The client went away
+            Ok(None) => return,
+            Err(e) => {
+                warn!(pool_id = %download.pool_id, error = %e, "candle download failed");
+                json!({ "done": false, "error": "Failed to read candles" })
+            }
+        };
+        let _ = tx.send(Bytes::from(format!("{}\n", summary))).await;
+    });
+    NdjsonBody { rx }
+}
+
+/// Sends the candles of a download chunk by chunk.
+/// 
+/// # Returns
+/// * `Result<Option<serde_json::Value>>` - The summary line, or `None` if the client went away
+async fn stream_candles(
+    conn_arc: &Arc<Mutex<Connection>>,
+    download: &CandleDownload,
+    tx: &mpsc::Sender<Bytes>,
+) -> std::result::Result<Option<serde_json::Value>, String> {
+    {
+        let conn = conn_arc.lock().map_err(|_| "database lock poisoned".to_string())?;
+        backfill_candles(&conn, &download.pool_id, &download.interval, download.from, download.to)
+            .map_err(|e| e.to_string())?;
+    }
+
+    let mut points = 0;
+    let mut after = download.from;
+    loop {
+        // One row past the limit shows whether the range continues beyond it
+        let remaining = download.max_points - points;
+        let limit = DOWNLOAD_CHUNK_ROWS.min(remaining + 1);
+        let candles = {
+            let conn = conn_arc.lock().map_err(|_| "database lock poisoned".to_string())?;
+            query_log::query_all(
+                &conn,
+                "download_candles",
+                &format!(
+                    "SELECT {} FROM candles
+                     WHERE pool_id = ?1 AND interval = ?2
+                       AND (?3 IS NULL OR bucket_start >= ?3) AND (?4 IS NULL OR bucket_start < ?4)
+                     ORDER BY bucket_start LIMIT ?5",
+                    CANDLE_COLUMNS
+                ),
+                params![download.pool_id, download.interval, after, download.to, limit as i64],
+                Candle::from_row,
+            )
+            .map_err(|e| e.to_string())?
+        };
+
+        let next_from = candles.get(remaining).map(|candle| candle.bucket_start);
+        let mut lines = String::new();
+        for candle in candles.iter().take(remaining) {
+            lines.push_str(&serde_json::to_string(candle).map_err(|e| e.to_string())?);
+            lines.push('\n');
+            points += 1;
+        }
+        if !lines.is_empty() && tx.send(Bytes::from(lines)).await.is_err() {
+            return Ok(None);
+        }
+        if let Some(next_from) = next_from {
+            return Ok(Some(json!({ "done": true, "points": points, "truncated": true, "next_from": next_from })));
+        }
+        match candles.last() {
+            Some(last) if candles.len() == limit => after = Some(last.bucket_start + 1),
+            _ => return Ok(Some(json!({ "done": true, "points": points, "truncated": false, "next_from": null }))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn insert_swap(conn: &Connection, amount_in: f64, amount_out: f64, timestamp: i64, tx_digest: &str) {
+        conn.execute(
+            "INSERT INTO swaps (pool_id, amount_in, amount_out, timestamp, tx_digest) VALUES ('0xpool', ?1, ?2, ?3, ?4)",
+            params![amount_in, amount_out, timestamp, tx_digest],
+        )
+        .unwrap();
+    }
+
+    fn candle_count(conn: &Connection) -> i64 {
+        conn.query_row("SELECT COUNT(*) FROM candles WHERE interval = '1m'", [], |row| row.get(0)).unwrap()
+    }
+
+    #[test]
+    fn backfill_rolls_up_only_swaps_the_aggregator_has_not_reached() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        insert_swap(&conn, 100.0, 50.0, 60_000, "0xtx1");
+        aggregate_candles(&conn).unwrap();
+        // Indexed after the aggregator's last pass, in an earlier and a later minute
+        insert_swap(&conn, 100.0, 40.0, 10_000, "0xtx2");
+        insert_swap(&conn, 100.0, 30.0, 130_000, "0xtx3");
+        assert_eq!(candle_count(&conn), 1);
+
+        assert_eq!(backfill_candles(&conn, "0xpool", "1m", None, Some(60_000)).unwrap(), 1);
+        let open: f64 = conn
+            .query_row("SELECT open FROM candles WHERE interval = '1m' AND bucket_start = 0", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(open, 0.4);
+        assert_eq!(candle_count(&conn), 2);
+
+        assert_eq!(backfill_candles(&conn, "0xpool", "1m", None, None).unwrap(), 2);
+        assert_eq!(candle_count(&conn), 3);
+        while aggregate_candles(&conn).unwrap() > 0 {}
+        assert_eq!(backfill_candles(&conn, "0xpool", "1m", None, None).unwrap(), 0);
+    }
+
+    #[test]
+    fn volumes_are_kept_per_token_whichever_way_swaps_traded() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        insert_swap(&conn, 100.0, 50.0, 0, "0xtx1");
+        insert_swap(&conn, 40.0, 80.0, 1_000, "0xtx2");
+        conn.execute("UPDATE swaps SET direction = 'b_to_a' WHERE tx_digest = '0xtx2'", []).unwrap();
+        aggregate_candles(&conn).unwrap();
+
+        let candle = rolled_up_candles(&conn, "0xpool", "1m", None, None, "").unwrap().remove(0);
+        assert_eq!((candle.volume_a, candle.volume_b), (180.0, 90.0));
+        assert_eq!((candle.open, candle.close), (0.5, 0.5));
+        let filtered = filtered_candles(&conn, "0xpool", 60_000, None, None, "").unwrap().remove(0);
+        assert_eq!((filtered.volume_a, filtered.volume_b), (180.0, 90.0));
+    }
+}
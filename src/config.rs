@@ -0,0 +1,750 @@
+use serde::Deserialize;
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::warn;
+
+use crate::candles::DEFAULT_CANDLE_AGGREGATION_INTERVAL_SECS;
+use crate::db::DEFAULT_DB_PATH;
+use crate::exports::DEFAULT_EXPORT_DIR;
+use crate::features::feature;
+use crate::graph_cache::DEFAULT_GRAPH_REFRESH_THRESHOLD;
+use crate::growth::{DEFAULT_GROWTH_THRESHOLDS_BYTES, DEFAULT_GROWTH_WINDOW_DAYS};
+use crate::freshness::{StaleMode, DEFAULT_STALE_AFTER_SECS};
+use crate::idle::DEFAULT_IDLE_POLL_INTERVAL_SECS;
+use crate::indexer::{
+    DEFAULT_DEX_PACKAGE_ID, DEFAULT_FAILED_SWAP_SCAN_INTERVAL_SECS, DEFAULT_MAX_BUFFERED_EVENTS,
+    DEFAULT_POLL_INTERVAL_SECS, DEFAULT_PRUNING_HORIZON_SECS, DEFAULT_RPC_BATCH_SIZE, DEFAULT_RPC_URL,
+};
+use crate::journal::DEFAULT_JOURNAL_PATH;
+use crate::listen::DEFAULT_UNIX_SOCKET_MODE;
+use crate::live::DEFAULT_WS_MAX_SUBSCRIPTIONS;
+use crate::maintenance::{
+    DEFAULT_MAINTENANCE_INTERVAL_SECS, DEFAULT_VACUUM_FREE_RATIO, DEFAULT_VACUUM_MIN_FREE_BYTES,
+};
+use crate::precision::DEFAULT_TOLERANCE as DEFAULT_PRECISION_TOLERANCE;
+use crate::pricing::DEFAULT_SUI_TOKEN;
+use crate::query_log::DEFAULT_SLOW_QUERY_MS;
+use crate::reference::{
+    parse_reference_source, ReferenceSource, DEFAULT_DEVIATION_THRESHOLD, DEFAULT_REFERENCE_INTERVAL_SECS,
+};
+use crate::response_cache::{default_cache_policies, CachePolicy, DEFAULT_CACHE_KEY};
+use crate::routes::DEFAULT_MAX_RESPONSE_ROWS;
+use crate::routing::DEFAULT_POOL_FEE_BPS;
+use crate::slo::{
+    DEFAULT_AVAILABILITY_TARGET, DEFAULT_FRESH_DATA_TARGET, DEFAULT_INDEXER_LAG_TARGET,
+    DEFAULT_MAX_INDEXER_LAG_SECS, DEFAULT_SLO_WINDOW_DAYS,
+};
+use crate::verify::DEFAULT_TOLERANCE;
+use crate::ws_auth::{DEFAULT_WS_MAX_CONNECTIONS, DEFAULT_WS_MAX_CONNECTIONS_PER_OWNER};
+
+/// Config file read when `CONFIG_FILE` is unset, if it exists
+const DEFAULT_CONFIG_FILE: &str = "fooswap.toml";
+
+/// Address the API binds to when neither `BIND_ADDRESS` nor `LISTEN_ADDRS` is set
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1";
+
+/// Port the API binds to when neither `PORT` nor `LISTEN_ADDRS` is set
+const DEFAULT_PORT: u16 = 3000;
+
+/// Settings read from the config file. Every key is optional and is
+/// overridden by its environment variable.
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileConfig {
+    bind_address: Option<String>,
+    port: Option<u16>,
+    listen_addrs: Option<String>,
+    db_path: Option<String>,
+    rpc_url: Option<String>,
+    archive_rpc_url: Option<String>,
+    rpc_proxy: Option<String>,
+    pruning_horizon_secs: Option<u64>,
+    package_id: Option<String>,
+    poll_interval_secs: Option<u64>,
+    max_buffered_events: Option<usize>,
+    rpc_batch_size: Option<usize>,
+    failed_swap_scan_interval_secs: Option<u64>,
+    idle_after_secs: Option<u64>,
+    idle_poll_interval_secs: Option<u64>,
+    db_growth_window_days: Option<i64>,
+    db_growth_thresholds_bytes: Option<Vec<i64>>,
+    stale_data_mode: Option<String>,
+    stale_data_threshold_secs: Option<u64>,
+    verify_tolerance: Option<f64>,
+    pool_fee_bps: Option<u32>,
+    admin_token: Option<String>,
+    cache: Option<Vec<FileCachePolicy>>,
+    max_response_rows: Option<usize>,
+    candle_aggregation_interval_secs: Option<u64>,
+    indexer_enabled: Option<bool>,
+    sui_token: Option<String>,
+    usd_token: Option<String>,
+    quote_token: Option<String>,
+    slo_window_days: Option<i64>,
+    slo_max_indexer_lag_secs: Option<i64>,
+    slo_availability_target: Option<f64>,
+    slo_indexer_lag_target: Option<f64>,
+    slo_fresh_data_target: Option<f64>,
+    db_maintenance_interval_secs: Option<u64>,
+    db_vacuum_free_ratio: Option<f64>,
+    db_vacuum_min_free_bytes: Option<i64>,
+    slow_query_ms: Option<u64>,
+    event_journal_path: Option<String>,
+    export_dir: Option<String>,
+    graph_refresh_threshold: Option<f64>,
+    pool_cache: Option<bool>,
+    reference_price_interval_secs: Option<u64>,
+    price_deviation_threshold: Option<f64>,
+    reference_prices: Option<Vec<String>>,
+    disabled_features: Option<Vec<String>>,
+    invalidation_peers: Option<Vec<String>>,
+    precision_audit: Option<bool>,
+    precision_audit_tolerance: Option<f64>,
+    rpc_no_proxy: Option<String>,
+    unix_socket_mode: Option<u32>,
+    ws_jwt_secret: Option<String>,
+    ws_max_connections: Option<usize>,
+    ws_max_connections_per_owner: Option<usize>,
+    ws_max_subscriptions: Option<usize>,
+}
+
+/// A `[[cache]]` entry of the config file. Entries for a route with a
+/// default policy override only the keys they set.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FileCachePolicy {
+    route: String,
+    ttl_secs: Option<u64>,
+    vary_by: Option<Vec<String>>,
+    key: Option<String>,
+    stale_fallback: Option<bool>,
+}
+
+/// Process configuration, loaded once at startup
+pub struct Config {
+    /// Addresses the API is served on, in the `LISTEN_ADDRS` format
+    pub listen_addrs: String,
+    /// SQLite database file
+    pub db_path: String,
+    /// Sui RPC endpoints, the primary first and then its fallbacks in order
+    pub rpc_urls: Vec<String>,
+    /// Archival Sui RPC endpoint for data the primary node has pruned, if any
+    pub archive_rpc_url: Option<String>,
+    /// Proxy URL for Sui RPC calls, or `none` to connect directly; `None`
+    /// leaves the standard proxy variables in charge
+    pub rpc_proxy: Option<String>,
+    /// Age beyond which the primary node is assumed to have pruned data (in seconds)
+    pub pruning_horizon_secs: u64,
+    /// Sui Move package ID of the DEX contract
+    pub package_id: String,
+    /// Interval between indexer polling cycles (in seconds)
+    pub poll_interval_secs: u64,
+    /// Most events held in memory before a page is committed
+    pub max_buffered_events: usize,
+    /// Most `suix_queryEvents` calls sent in one JSON-RPC batch request
+    pub rpc_batch_size: usize,
+    /// Interval between scans of the pools' transactions for failed swaps (in seconds)
+    pub failed_swap_scan_interval_secs: u64,
+    /// Time without API traffic after which the process counts as idle (in
+    /// seconds), or `None` if idle mode is off
+    pub idle_after_secs: Option<u64>,
+    /// Interval between indexer polling cycles while idle (in seconds)
+    pub idle_poll_interval_secs: u64,
+    /// Period database growth rates are averaged over (in days)
+    pub db_growth_window_days: i64,
+    /// Database sizes the growth forecast projects, ascending (in bytes)
+    pub db_growth_thresholds_bytes: Vec<i64>,
+    /// How market-data endpoints behave while indexed data is stale
+    pub stale_data_mode: StaleMode,
+    /// Indexer lag after which market data is considered stale (in seconds)
+    pub stale_data_threshold_secs: u64,
+    /// Relative difference tolerated between replayed and stored reserves,
+    /// and between a swap's reserve deltas and its amounts
+    pub verify_tolerance: f64,
+    /// Fee quotes take from the input of every swap (in basis points, below
+    /// 10000); the deployed contract charges none, see `DEFAULT_POOL_FEE_BPS`
+    pub pool_fee_bps: u32,
+    /// Bearer token required by admin endpoints, or `None` if they are disabled
+    pub admin_token: Option<String>,
+    /// Response cache policies, one per cached route
+    pub cache_policies: Vec<CachePolicy>,
+    /// Most rows a list endpoint returns per request
+    pub max_response_rows: usize,
+    /// Interval between candle aggregation runs (in seconds)
+    pub candle_aggregation_interval_secs: u64,
+    /// Whether this process runs the indexer
+    pub indexer_enabled: bool,
+    /// Token treated as SUI
+    pub sui_token: String,
+    /// USD-pegged reference token, if any
+    pub usd_token: Option<String>,
+    /// Default denomination of prices: `QUOTE_TOKEN`, else the USD token,
+    /// else the SUI token
+    pub quote_token: String,
+    /// Period error budgets are computed over (in days)
+    pub slo_window_days: i64,
+    /// Indexer lag above which a sample counts against the lag SLO (in seconds)
+    pub slo_max_indexer_lag_secs: i64,
+    /// Target share of API requests answered without a 5xx
+    pub slo_availability_target: f64,
+    /// Target share of indexer lag samples within `slo_max_indexer_lag_secs`
+    pub slo_indexer_lag_target: f64,
+    /// Target share of market-data responses served from fresh data
+    pub slo_fresh_data_target: f64,
+    /// Interval between database maintenance checks (in seconds), or 0 to
+    /// disable maintenance
+    pub db_maintenance_interval_secs: u64,
+    /// Share of the file that must be free pages before compacting
+    pub db_vacuum_free_ratio: f64,
+    /// Minimum reclaimable size before compacting (in bytes)
+    pub db_vacuum_min_free_bytes: i64,
+    /// Duration above which a query is logged as slow (in milliseconds), or
+    /// 0 to disable the log
+    pub slow_query_ms: u64,
+    /// Event journal file, or `None` if journaling is disabled
+    pub event_journal_path: Option<String>,
+    /// Directory export files are written to
+    pub export_dir: String,
+    /// Relative reserve change after which the pool graph is rebuilt
+    pub graph_refresh_threshold: f64,
+    /// Whether quotes and prices are served from the in-memory pool cache
+    pub pool_cache: bool,
+    /// Interval between reference price checks (in seconds)
+    pub reference_price_interval_secs: u64,
+    /// Relative deviation from the reference beyond which price responses
+    /// carry a warning
+    pub price_deviation_threshold: f64,
+    /// External price sources to compare pool prices with
+    pub reference_prices: Vec<ReferenceSource>,
+    /// Features disabled unless an admin overrides them
+    pub disabled_features: Vec<String>,
+    /// Base URLs of the API replicas cache invalidations are sent to
+    pub invalidation_peers: Vec<String>,
+    /// Whether audited computations are repeated in exact arithmetic
+    pub precision_audit: bool,
+    /// Relative error tolerated by the precision audit
+    pub precision_audit_tolerance: f64,
+    /// Hosts Sui RPC calls reach without `rpc_proxy`, in the `NO_PROXY`
+    /// format; `None` falls back to `NO_PROXY`
+    pub rpc_no_proxy: Option<String>,
+    /// Permissions of Unix socket files the API listens on
+    pub unix_socket_mode: u32,
+    /// HMAC key WebSocket JWTs are signed with (HS256), or `None` if JWT
+    /// authentication is off
+    pub ws_jwt_secret: Option<String>,
+    /// Most WebSocket connections open at once
+    pub ws_max_connections: usize,
+    /// Most WebSocket connections open at once for one authenticated owner
+    pub ws_max_connections_per_owner: usize,
+    /// Most channels one WebSocket connection may subscribe to
+    pub ws_max_subscriptions: usize,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// Reads an environment variable, treating an empty value as unset.
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|v| !v.trim().is_empty())
+}
+
+/// Reads a numeric environment variable.
+fn env_number<T: std::str::FromStr>(name: &str) -> Result<Option<T>, String> {
+    env(name)
+        .map(|v| v.trim().parse().map_err(|_| format!("{} `{}` is not a valid number", name, v)))
+        .transpose()
+}
+
+/// Reads an on/off environment variable: `1` or `true`, `0` or `false`.
+fn env_flag(name: &str) -> Result<Option<bool>, String> {
+    env(name)
+        .map(|v| match v.trim() {
+            "1" | "true" => Ok(true),
+            "0" | "false" => Ok(false),
+            _ => Err(format!("{} `{}` is not 1, true, 0 or false", name, v)),
+        })
+        .transpose()
+}
+
+/// Reads a comma-separated environment variable, skipping empty entries.
+fn env_list(name: &str) -> Option<Vec<String>> {
+    env(name).map(|v| v.split(',').map(|entry| entry.trim().to_string()).filter(|e| !e.is_empty()).collect())
+}
+
+/// Reads a share that must lie strictly between 0 and 1.
+fn fraction(name: &str, value: f64) -> Result<f64, String> {
+    if value > 0.0 && value < 1.0 {
+        Ok(value)
+    } else {
+        Err(format!("{} `{}` must be between 0 and 1", name, value))
+    }
+}
+
+/// Reads a threshold that must be a finite number of at least 0.
+fn non_negative(name: &str, value: f64) -> Result<f64, String> {
+    if value >= 0.0 && value.is_finite() {
+        Ok(value)
+    } else {
+        Err(format!("{} `{}` is not a non-negative number", name, value))
+    }
+}
+
+/// Reads a count or interval that must be at least 1.
+fn positive<T: PartialOrd + Default>(name: &str, value: T) -> Result<T, String> {
+    if value > T::default() {
+        Ok(value)
+    } else {
+        Err(format!("{} must be at least 1", name))
+    }
+}
+
+/// Builds the response cache policies: the defaults, then the config file's
+/// `[[cache]]` entries, then the `route=seconds` TTL overrides of
+/// `CACHE_TTL_SECS`.
+fn cache_policies(entries: Vec<FileCachePolicy>) -> Result<Vec<CachePolicy>, String> {
+    let mut policies = default_cache_policies();
+    for entry in entries {
+        let index = match policies.iter().position(|p| p.route == entry.route) {
+            Some(index) => index,
+            None => {
+                let ttl_secs = entry
+                    .ttl_secs
+                    .ok_or_else(|| format!("cache policy for {} needs a ttl_secs", entry.route))?;
+                policies.push(CachePolicy {
+                    route: entry.route.clone(),
+                    ttl_secs,
+                    vary_by: Vec::new(),
+                    key: DEFAULT_CACHE_KEY.to_string(),
+                    stale_fallback: true,
+                });
+                policies.len() - 1
+            }
+        };
+        let policy = &mut policies[index];
+        policy.ttl_secs = entry.ttl_secs.unwrap_or(policy.ttl_secs);
+        policy.vary_by = entry.vary_by.unwrap_or(std::mem::take(&mut policy.vary_by));
+        policy.key = entry.key.unwrap_or(std::mem::take(&mut policy.key));
+        policy.stale_fallback = entry.stale_fallback.unwrap_or(policy.stale_fallback);
+    }
+
+    for entry in env("CACHE_TTL_SECS").iter().flat_map(|v| v.split(',')) {
+        let invalid = || format!("CACHE_TTL_SECS entry `{}` is not `route=seconds`", entry);
+        let (route, secs) = entry.split_once('=').ok_or_else(invalid)?;
+        let secs = secs.trim().parse().map_err(|_| invalid())?;
+        let policy = policies
+            .iter_mut()
+            .find(|p| p.route == route.trim())
+            .ok_or_else(|| format!("CACHE_TTL_SECS names {}, which has no cache policy", route.trim()))?;
+        policy.ttl_secs = secs;
+    }
+
+    for policy in &policies {
+        policy.validate()?;
+    }
+    Ok(policies)
+}
+
+/// Reads the config file from `CONFIG_FILE`, or from `fooswap.toml` if that exists.
+fn read_config_file() -> Result<FileConfig, String> {
+    let path = match env("CONFIG_FILE") {
+        Some(path) => path,
+        None if Path::new(DEFAULT_CONFIG_FILE).exists() => DEFAULT_CONFIG_FILE.to_string(),
+        None => return Ok(FileConfig::default()),
+    };
+    let raw = std::fs::read_to_string(&path).map_err(|e| format!("cannot read config file {}: {}", path, e))?;
+    toml::from_str(&raw).map_err(|e| format!("invalid config file {}: {}", path, e))
+}
+
+impl Config {
+    /// Loads the configuration.
+    /// 
+    /// Each setting is taken from its environment variable, else from the
+    /// config file, else from its default. `LISTEN_ADDRS` takes precedence
+    /// over `BIND_ADDRESS` and `PORT`, wherever each is set.
+    /// 
+    /// # Returns
+    /// * `Result<Config, String>` - The configuration, or a description of the invalid setting
+    pub fn load() -> Result<Config, String> {
+        let file = read_config_file()?;
+
+        let bind_address = env("BIND_ADDRESS")
+            .or(file.bind_address)
+            .unwrap_or_else(|| DEFAULT_BIND_ADDRESS.to_string());
+        let port = env_number("PORT")?.or(file.port).unwrap_or(DEFAULT_PORT);
+        let listen_addrs = env("LISTEN_ADDRS").or(file.listen_addrs).unwrap_or_else(|| {
+            // IPv6 addresses need brackets to take a port
+            if bind_address.contains(':') && !bind_address.starts_with('[') {
+                format!("[{}]:{}", bind_address, port)
+            } else {
+                format!("{}:{}", bind_address, port)
+            }
+        });
+
+        let poll_interval_secs = env_number("POLL_INTERVAL_SECS")?
+            .or(file.poll_interval_secs)
+            .unwrap_or(DEFAULT_POLL_INTERVAL_SECS);
+        if poll_interval_secs == 0 {
+            return Err("POLL_INTERVAL_SECS must be at least 1".to_string());
+        }
+
+        let rpc_urls: Vec<String> = env("SUI_RPC_URL")
+            .or(file.rpc_url)
+            .unwrap_or_else(|| DEFAULT_RPC_URL.to_string())
+            .split(',')
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+            .collect();
+        if rpc_urls.is_empty() {
+            return Err("SUI_RPC_URL must list at least one endpoint".to_string());
+        }
+
+        let rpc_proxy = env("SUI_RPC_PROXY").or(file.rpc_proxy);
+        let rpc_no_proxy = env("SUI_RPC_NO_PROXY").or(file.rpc_no_proxy);
+        if let Some(url) = rpc_proxy.as_deref().filter(|url| *url != "none") {
+            if let Err(e) = crate::indexer::rpc_proxy(url, rpc_no_proxy.as_deref()) {
+                return Err(format!("SUI_RPC_PROXY is not a valid proxy URL: {}", e));
+            }
+        }
+
+        let verify_tolerance = non_negative(
+            "VERIFY_TOLERANCE",
+            env_number("VERIFY_TOLERANCE")?
+                .or(file.verify_tolerance)
+                .unwrap_or(DEFAULT_TOLERANCE),
+        )?;
+
+        let pool_fee_bps = env_number("POOL_FEE_BPS")?
+            .or(file.pool_fee_bps)
+            .unwrap_or(DEFAULT_POOL_FEE_BPS);
+        if pool_fee_bps >= 10_000 {
+            return Err(format!("POOL_FEE_BPS `{}` must be below 10000", pool_fee_bps));
+        }
+
+        let mut db_growth_thresholds_bytes = match env("DB_GROWTH_THRESHOLDS_BYTES") {
+            Some(list) => list
+                .split(',')
+                .map(|v| {
+                    v.trim()
+                        .parse::<i64>()
+                        .map_err(|_| format!("DB_GROWTH_THRESHOLDS_BYTES `{}` is not a valid number", v))
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+            None => file
+                .db_growth_thresholds_bytes
+                .unwrap_or_else(|| DEFAULT_GROWTH_THRESHOLDS_BYTES.to_vec()),
+        };
+        if db_growth_thresholds_bytes.iter().any(|bytes| *bytes <= 0) {
+            return Err("DB_GROWTH_THRESHOLDS_BYTES must list positive byte counts".to_string());
+        }
+        db_growth_thresholds_bytes.sort_unstable();
+        db_growth_thresholds_bytes.dedup();
+
+        let price_deviation_threshold = env_number("PRICE_DEVIATION_THRESHOLD")?
+            .or(file.price_deviation_threshold)
+            .unwrap_or(DEFAULT_DEVIATION_THRESHOLD);
+        if !(price_deviation_threshold > 0.0 && price_deviation_threshold.is_finite()) {
+            return Err(format!("PRICE_DEVIATION_THRESHOLD `{}` is not a positive number", price_deviation_threshold));
+        }
+
+        let sui_token = env("SUI_TOKEN").or(file.sui_token).unwrap_or_else(|| DEFAULT_SUI_TOKEN.to_string());
+        let usd_token = env("USD_TOKEN").or(file.usd_token);
+        let quote_token = env("QUOTE_TOKEN")
+            .or(file.quote_token)
+            .or_else(|| usd_token.clone())
+            .unwrap_or_else(|| sui_token.clone());
+
+        // Unlike the other settings, an empty EVENT_JOURNAL_PATH is not
+        // unset: it disables the journal
+        let event_journal_path = match std::env::var("EVENT_JOURNAL_PATH").ok().or(file.event_journal_path) {
+            Some(path) if path.trim().is_empty() => None,
+            Some(path) => Some(path),
+            None => Some(DEFAULT_JOURNAL_PATH.to_string()),
+        };
+
+        let reference_prices = env_list("REFERENCE_PRICES")
+            .or(file.reference_prices)
+            .unwrap_or_default()
+            .iter()
+            .map(|entry| {
+                parse_reference_source(entry)
+                    .ok_or_else(|| format!("REFERENCE_PRICES entry `{}` is not `TOKENA/TOKENB=URL`", entry))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let disabled_features = env_list("DISABLED_FEATURES").or(file.disabled_features).unwrap_or_default();
+        if let Some(name) = disabled_features.iter().find(|name| feature(name).is_none()) {
+            return Err(format!("DISABLED_FEATURES lists unknown feature `{}`", name));
+        }
+
+        let invalidation_peers: Vec<String> = env_list("INVALIDATION_PEERS")
+            .or(file.invalidation_peers)
+            .unwrap_or_default()
+            .iter()
+            .map(|peer| peer.trim().trim_end_matches('/').to_string())
+            .filter(|peer| !peer.is_empty())
+            .collect();
+        if let Some(peer) = invalidation_peers
+            .iter()
+            .find(|peer| !peer.starts_with("http://") && !peer.starts_with("https://"))
+        {
+            return Err(format!("INVALIDATION_PEERS entry `{}` is not an http(s) URL", peer));
+        }
+
+        let unix_socket_mode = match env("UNIX_SOCKET_MODE") {
+            Some(v) => u32::from_str_radix(v.trim().trim_start_matches("0o"), 8)
+                .map_err(|_| format!("UNIX_SOCKET_MODE `{}` is not an octal mode", v))?,
+            None => file.unix_socket_mode.unwrap_or(DEFAULT_UNIX_SOCKET_MODE),
+        };
+        if unix_socket_mode > 0o777 {
+            return Err(format!("UNIX_SOCKET_MODE `{:o}` is not a permission mode", unix_socket_mode));
+        }
+
+        Ok(Config {
+            listen_addrs,
+            db_path: env("DB_PATH").or(file.db_path).unwrap_or_else(|| DEFAULT_DB_PATH.to_string()),
+            rpc_urls,
+            archive_rpc_url: env("SUI_ARCHIVE_RPC_URL").or(file.archive_rpc_url),
+            rpc_proxy,
+            pruning_horizon_secs: env_number("RPC_PRUNING_HORIZON_SECS")?
+                .or(file.pruning_horizon_secs)
+                .unwrap_or(DEFAULT_PRUNING_HORIZON_SECS),
+            package_id: env("DEX_PACKAGE_ID")
+                .or(file.package_id)
+                .unwrap_or_else(|| DEFAULT_DEX_PACKAGE_ID.to_string()),
+            poll_interval_secs,
+            max_buffered_events: positive(
+                "INDEXER_MAX_BUFFERED_EVENTS",
+                env_number("INDEXER_MAX_BUFFERED_EVENTS")?
+                    .or(file.max_buffered_events)
+                    .unwrap_or(DEFAULT_MAX_BUFFERED_EVENTS),
+            )?,
+            rpc_batch_size: positive(
+                "INDEXER_RPC_BATCH_SIZE",
+                env_number("INDEXER_RPC_BATCH_SIZE")?
+                    .or(file.rpc_batch_size)
+                    .unwrap_or(DEFAULT_RPC_BATCH_SIZE),
+            )?,
+            failed_swap_scan_interval_secs: positive(
+                "FAILED_SWAP_SCAN_INTERVAL_SECS",
+                env_number("FAILED_SWAP_SCAN_INTERVAL_SECS")?
+                    .or(file.failed_swap_scan_interval_secs)
+                    .unwrap_or(DEFAULT_FAILED_SWAP_SCAN_INTERVAL_SECS),
+            )?,
+            idle_after_secs: env_number("IDLE_AFTER_SECS")?.or(file.idle_after_secs).filter(|secs| *secs > 0),
+            idle_poll_interval_secs: env_number("IDLE_POLL_INTERVAL_SECS")?
+                .or(file.idle_poll_interval_secs)
+                .unwrap_or(DEFAULT_IDLE_POLL_INTERVAL_SECS),
+            db_growth_window_days: positive(
+                "DB_GROWTH_WINDOW_DAYS",
+                env_number("DB_GROWTH_WINDOW_DAYS")?
+                    .or(file.db_growth_window_days)
+                    .unwrap_or(DEFAULT_GROWTH_WINDOW_DAYS),
+            )?,
+            db_growth_thresholds_bytes,
+            stale_data_mode: env("STALE_DATA_MODE")
+                .or(file.stale_data_mode)
+                .map(|mode| StaleMode::parse(&mode))
+                .transpose()?
+                .unwrap_or_default(),
+            stale_data_threshold_secs: env_number("STALE_DATA_THRESHOLD_SECS")?
+                .or(file.stale_data_threshold_secs)
+                .unwrap_or(DEFAULT_STALE_AFTER_SECS),
+            verify_tolerance,
+            pool_fee_bps,
+            admin_token: env("ADMIN_TOKEN").or(file.admin_token),
+            cache_policies: cache_policies(file.cache.unwrap_or_default())?,
+            max_response_rows: positive(
+                "MAX_RESPONSE_ROWS",
+                env_number("MAX_RESPONSE_ROWS")?
+                    .or(file.max_response_rows)
+                    .unwrap_or(DEFAULT_MAX_RESPONSE_ROWS),
+            )?,
+            candle_aggregation_interval_secs: positive(
+                "CANDLE_AGGREGATION_INTERVAL_SECS",
+                env_number("CANDLE_AGGREGATION_INTERVAL_SECS")?
+                    .or(file.candle_aggregation_interval_secs)
+                    .unwrap_or(DEFAULT_CANDLE_AGGREGATION_INTERVAL_SECS),
+            )?,
+            indexer_enabled: env_flag("INDEXER_ENABLED")?.or(file.indexer_enabled).unwrap_or(true),
+            sui_token,
+            usd_token,
+            quote_token,
+            slo_window_days: positive(
+                "SLO_WINDOW_DAYS",
+                env_number("SLO_WINDOW_DAYS")?
+                    .or(file.slo_window_days)
+                    .unwrap_or(DEFAULT_SLO_WINDOW_DAYS),
+            )?,
+            slo_max_indexer_lag_secs: positive(
+                "SLO_MAX_INDEXER_LAG_SECS",
+                env_number("SLO_MAX_INDEXER_LAG_SECS")?
+                    .or(file.slo_max_indexer_lag_secs)
+                    .unwrap_or(DEFAULT_MAX_INDEXER_LAG_SECS),
+            )?,
+            slo_availability_target: fraction(
+                "SLO_AVAILABILITY_TARGET",
+                env_number("SLO_AVAILABILITY_TARGET")?
+                    .or(file.slo_availability_target)
+                    .unwrap_or(DEFAULT_AVAILABILITY_TARGET),
+            )?,
+            slo_indexer_lag_target: fraction(
+                "SLO_INDEXER_LAG_TARGET",
+                env_number("SLO_INDEXER_LAG_TARGET")?
+                    .or(file.slo_indexer_lag_target)
+                    .unwrap_or(DEFAULT_INDEXER_LAG_TARGET),
+            )?,
+            slo_fresh_data_target: fraction(
+                "SLO_FRESH_DATA_TARGET",
+                env_number("SLO_FRESH_DATA_TARGET")?
+                    .or(file.slo_fresh_data_target)
+                    .unwrap_or(DEFAULT_FRESH_DATA_TARGET),
+            )?,
+            db_maintenance_interval_secs: env_number("DB_MAINTENANCE_INTERVAL_SECS")?
+                .or(file.db_maintenance_interval_secs)
+                .unwrap_or(DEFAULT_MAINTENANCE_INTERVAL_SECS),
+            db_vacuum_free_ratio: fraction(
+                "DB_VACUUM_FREE_RATIO",
+                env_number("DB_VACUUM_FREE_RATIO")?
+                    .or(file.db_vacuum_free_ratio)
+                    .unwrap_or(DEFAULT_VACUUM_FREE_RATIO),
+            )?,
+            db_vacuum_min_free_bytes: env_number("DB_VACUUM_MIN_FREE_BYTES")?
+                .or(file.db_vacuum_min_free_bytes)
+                .unwrap_or(DEFAULT_VACUUM_MIN_FREE_BYTES)
+                .max(0),
+            slow_query_ms: env_number("SLOW_QUERY_MS")?
+                .or(file.slow_query_ms)
+                .unwrap_or(DEFAULT_SLOW_QUERY_MS),
+            event_journal_path,
+            export_dir: env("EXPORT_DIR")
+                .or(file.export_dir)
+                .unwrap_or_else(|| DEFAULT_EXPORT_DIR.to_string()),
+            graph_refresh_threshold: non_negative(
+                "GRAPH_REFRESH_THRESHOLD",
+                env_number("GRAPH_REFRESH_THRESHOLD")?
+                    .or(file.graph_refresh_threshold)
+                    .unwrap_or(DEFAULT_GRAPH_REFRESH_THRESHOLD),
+            )?,
+            pool_cache: env_flag("POOL_CACHE")?.or(file.pool_cache).unwrap_or(false),
+            reference_price_interval_secs: positive(
+                "REFERENCE_PRICE_INTERVAL_SECS",
+                env_number("REFERENCE_PRICE_INTERVAL_SECS")?
+                    .or(file.reference_price_interval_secs)
+                    .unwrap_or(DEFAULT_REFERENCE_INTERVAL_SECS),
+            )?,
+            price_deviation_threshold,
+            reference_prices,
+            disabled_features,
+            invalidation_peers,
+            precision_audit: env_flag("PRECISION_AUDIT")?.or(file.precision_audit).unwrap_or(false),
+            precision_audit_tolerance: non_negative(
+                "PRECISION_AUDIT_TOLERANCE",
+                env_number("PRECISION_AUDIT_TOLERANCE")?
+                    .or(file.precision_audit_tolerance)
+                    .unwrap_or(DEFAULT_PRECISION_TOLERANCE),
+            )?,
+            rpc_no_proxy,
+            unix_socket_mode,
+            ws_jwt_secret: env("WS_JWT_SECRET").or(file.ws_jwt_secret),
+            ws_max_connections: positive(
+                "WS_MAX_CONNECTIONS",
+                env_number("WS_MAX_CONNECTIONS")?
+                    .or(file.ws_max_connections)
+                    .unwrap_or(DEFAULT_WS_MAX_CONNECTIONS),
+            )?,
+            ws_max_connections_per_owner: positive(
+                "WS_MAX_CONNECTIONS_PER_OWNER",
+                env_number("WS_MAX_CONNECTIONS_PER_OWNER")?
+                    .or(file.ws_max_connections_per_owner)
+                    .unwrap_or(DEFAULT_WS_MAX_CONNECTIONS_PER_OWNER),
+            )?,
+            ws_max_subscriptions: positive(
+                "WS_MAX_SUBSCRIPTIONS",
+                env_number("WS_MAX_SUBSCRIPTIONS")?
+                    .or(file.ws_max_subscriptions)
+                    .unwrap_or(DEFAULT_WS_MAX_SUBSCRIPTIONS),
+            )?,
+        })
+    }
+
+    /// The configuration with every setting at its default.
+    fn defaults() -> Config {
+        Config {
+            listen_addrs: format!("{}:{}", DEFAULT_BIND_ADDRESS, DEFAULT_PORT),
+            db_path: DEFAULT_DB_PATH.to_string(),
+            rpc_urls: vec![DEFAULT_RPC_URL.to_string()],
+            archive_rpc_url: None,
+            rpc_proxy: None,
+            pruning_horizon_secs: DEFAULT_PRUNING_HORIZON_SECS,
+            package_id: DEFAULT_DEX_PACKAGE_ID.to_string(),
+            poll_interval_secs: DEFAULT_POLL_INTERVAL_SECS,
+            max_buffered_events: DEFAULT_MAX_BUFFERED_EVENTS,
+            rpc_batch_size: DEFAULT_RPC_BATCH_SIZE,
+            failed_swap_scan_interval_secs: DEFAULT_FAILED_SWAP_SCAN_INTERVAL_SECS,
+            idle_after_secs: None,
+            idle_poll_interval_secs: DEFAULT_IDLE_POLL_INTERVAL_SECS,
+            db_growth_window_days: DEFAULT_GROWTH_WINDOW_DAYS,
+            db_growth_thresholds_bytes: DEFAULT_GROWTH_THRESHOLDS_BYTES.to_vec(),
+            stale_data_mode: StaleMode::default(),
+            stale_data_threshold_secs: DEFAULT_STALE_AFTER_SECS,
+            verify_tolerance: DEFAULT_TOLERANCE,
+            pool_fee_bps: DEFAULT_POOL_FEE_BPS,
+            admin_token: None,
+            cache_policies: default_cache_policies(),
+            max_response_rows: DEFAULT_MAX_RESPONSE_ROWS,
+            candle_aggregation_interval_secs: DEFAULT_CANDLE_AGGREGATION_INTERVAL_SECS,
+            indexer_enabled: true,
+            sui_token: DEFAULT_SUI_TOKEN.to_string(),
+            usd_token: None,
+            quote_token: DEFAULT_SUI_TOKEN.to_string(),
+            slo_window_days: DEFAULT_SLO_WINDOW_DAYS,
+            slo_max_indexer_lag_secs: DEFAULT_MAX_INDEXER_LAG_SECS,
+            slo_availability_target: DEFAULT_AVAILABILITY_TARGET,
+            slo_indexer_lag_target: DEFAULT_INDEXER_LAG_TARGET,
+            slo_fresh_data_target: DEFAULT_FRESH_DATA_TARGET,
+            db_maintenance_interval_secs: DEFAULT_MAINTENANCE_INTERVAL_SECS,
+            db_vacuum_free_ratio: DEFAULT_VACUUM_FREE_RATIO,
+            db_vacuum_min_free_bytes: DEFAULT_VACUUM_MIN_FREE_BYTES,
+            slow_query_ms: DEFAULT_SLOW_QUERY_MS,
+            event_journal_path: Some(DEFAULT_JOURNAL_PATH.to_string()),
+            export_dir: DEFAULT_EXPORT_DIR.to_string(),
+            graph_refresh_threshold: DEFAULT_GRAPH_REFRESH_THRESHOLD,
+            pool_cache: false,
+            reference_price_interval_secs: DEFAULT_REFERENCE_INTERVAL_SECS,
+            price_deviation_threshold: DEFAULT_DEVIATION_THRESHOLD,
+            reference_prices: Vec::new(),
+            disabled_features: Vec::new(),
+            invalidation_peers: Vec::new(),
+            precision_audit: false,
+            precision_audit_tolerance: DEFAULT_PRECISION_TOLERANCE,
+            rpc_no_proxy: None,
+            unix_socket_mode: DEFAULT_UNIX_SOCKET_MODE,
+            ws_jwt_secret: None,
+            ws_max_connections: DEFAULT_WS_MAX_CONNECTIONS,
+            ws_max_connections_per_owner: DEFAULT_WS_MAX_CONNECTIONS_PER_OWNER,
+            ws_max_subscriptions: DEFAULT_WS_MAX_SUBSCRIPTIONS,
+        }
+    }
+}
+
+/// Loads the configuration for the rest of the process.
+/// 
+/// # Returns
+/// * `Result<&'static Config, String>` - The configuration, or a description of the invalid setting
+pub fn init() -> Result<&'static Config, String> {
+    let config = Config::load()?;
+    Ok(CONFIG.get_or_init(|| config))
+}
+
+/// Returns the process configuration.
+/// 
+/// If `init` was not called first, e.g. by the `doctor` command, the
+/// configuration is loaded now; an invalid one is logged and replaced by
+/// the defaults.
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        Config::load().unwrap_or_else(|e| {
+            warn!("{}; using the default configuration", e);
+            Config::defaults()
+        })
+    })
+}
@@ -0,0 +1,344 @@
+use rusqlite::{params, Connection, Result};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::db::{list_custom_metrics, pool_volumes, save_custom_metric_values, CustomMetric, PoolVolume};
+use crate::features::{is_enabled, FeatureFlags};
+use crate::query_log;
+use crate::routing::spot_price;
+
+/// Interval between recomputations of every custom metric (in seconds)
+const CUSTOM_METRICS_INTERVAL_SECS: u64 = 60;
+
+/// Longest expression accepted for a custom metric
+pub const MAX_EXPRESSION_LEN: usize = 200;
+
+/// Columns a custom metric expression can use, with a description. Each is
+/// read per pool.
+pub const METRIC_COLUMNS: [(&str, &str); 10] = [
+    ("reserve_a", "Current reserve of token A"),
+    ("reserve_b", "Current reserve of token B"),
+    ("price", "Spot price in token B per token A; undefined for drained pools"),
+    ("tvl", "Liquidity valued in token B, 2 × reserve_b"),
+    ("volume_a_24h", "Token A moved by the swaps of the last 24 hours, in or out"),
+    ("volume_b_24h", "Token B moved by the swaps of the last 24 hours, in or out"),
+    ("volume_a_7d", "Token A moved by the swaps of the last 7 days, in or out"),
+    ("volume_b_7d", "Token B moved by the swaps of the last 7 days, in or out"),
+    ("swaps_24h", "Number of swaps in the last 24 hours"),
+    ("swaps_7d", "Number of swaps in the last 7 days"),
+];
+
+/// Arithmetic operator of a metric expression
+#[derive(Clone, Copy)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+/// Parsed metric expression
+pub enum Expr {
+    Number(f64),
+    Column(&'static str),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Op, Box<Expr>),
+}
+
+impl Expr {
+    /// Parses an expression of numbers, columns from `METRIC_COLUMNS`, `+`,
+    /// `-`, `*`, `/` and parentheses, e.g. `volume_b_24h / tvl`.
+    /// 
+    /// # Arguments
+    /// * `source` - The expression
+    /// 
+    /// # Returns
+    /// * `Result<Expr, String>` - The parsed expression, or why it is invalid
+    pub fn parse(source: &str) -> Result<Expr, String> {
+        if source.trim().is_empty() {
+            return Err("Expression is empty".to_string());
+        }
+        if source.len() > MAX_EXPRESSION_LEN {
+            return Err(format!("Expression must be at most {} characters", MAX_EXPRESSION_LEN));
+        }
+        let mut parser = Parser { source, pos: 0 };
+        let expr = parser.sum()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(format!("Unexpected `{}` at position {}", c, parser.pos)),
+        }
+    }
+
+    /// Evaluates the expression for one pool.
+    /// 
+    /// # Arguments
+    /// * `column` - Value of a column for the pool, `None` if undefined
+    /// 
+    /// # Returns
+    /// * `Option<f64>` - The value, or `None` if a column is undefined or the
+    ///   result isn't finite, e.g. after a division by zero
+    pub fn eval(&self, column: &impl Fn(&str) -> Option<f64>) -> Option<f64> {
+        let value = match self {
+            Expr::Number(n) => *n,
+            Expr::Column(name) => column(name)?,
+            Expr::Neg(inner) => -inner.eval(column)?,
+            Expr::Binary(left, op, right) => {
+                let (left, right) = (left.eval(column)?, right.eval(column)?);
+                match op {
+                    Op::Add => left + right,
+                    Op::Sub => left - right,
+                    Op::Mul => left * right,
+                    Op::Div => left / right,
+                }
+            }
+        };
+        value.is_finite().then_some(value)
+    }
+}
+
+/// Recursive-descent parser over an expression's source
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    /// Skips whitespace and returns the next character without consuming it.
+    fn peek(&mut self) -> Option<char> {
+        let rest = &self.source[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+        self.source[self.pos..].chars().next()
+    }
+
+    /// Consumes characters while `accept` holds and returns them.
+    fn take_while(&mut self, accept: impl Fn(char) -> bool) -> &str {
+        let start = self.pos;
+        let rest = &self.source[start..];
+        self.pos += rest.find(|c| !accept(c)).unwrap_or(rest.len());
+        &self.source[start..self.pos]
+    }
+
+    /// sum := product (('+' | '-') product)*
+    fn sum(&mut self) -> Result<Expr, String> {
+        let mut expr = self.product()?;
+        while let Some(op) = self.peek().and_then(|c| match c {
+            '+' => Some(Op::Add),
+            '-' => Some(Op::Sub),
+            _ => None,
+        }) {
+            self.pos += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.product()?));
+        }
+        Ok(expr)
+    }
+
+    /// product := factor (('*' | '/') factor)*
+    fn product(&mut self) -> Result<Expr, String> {
+        let mut expr = self.factor()?;
+        while let Some(op) = self.peek().and_then(|c| match c {
+            '*' => Some(Op::Mul),
+            '/' => Some(Op::Div),
+            _ => None,
+        }) {
+            self.pos += 1;
+            expr = Expr::Binary(Box::new(expr), op, Box::new(self.factor()?));
+        }
+        Ok(expr)
+    }
+
+    /// factor := '-' factor | '(' sum ')' | number | column
+    fn factor(&mut self) -> Result<Expr, String> {
+        match self.peek() {
+            None => Err("Expression ends unexpectedly".to_string()),
+            Some('-') => {
+                self.pos += 1;
+                Ok(Expr::Neg(Box::new(self.factor()?)))
+            }
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.sum()?;
+                if self.peek() != Some(')') {
+                    return Err(format!("Expected `)` at position {}", self.pos));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let number = self.take_while(|c| c.is_ascii_digit() || c == '.');
+                number.parse().map(Expr::Number).map_err(|_| format!("`{}` is not a valid number", number))
+            }
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+                let columns = METRIC_COLUMNS.map(|(column, _)| column);
+                match columns.iter().find(|column| **column == name) {
+                    Some(column) => Ok(Expr::Column(column)),
+                    None => Err(format!("Unknown column `{}`; available columns are {}", name, columns.join(", "))),
+                }
+            }
+            Some(c) => Err(format!("Unexpected `{}` at position {}", c, self.pos)),
+        }
+    }
+}
+
+/// Columns of one pool, as read for the metric computation
+struct PoolColumns {
+    pool_id: String,
+    reserve_a: f64,
+    reserve_b: f64,
+    volume: PoolVolume,
+}
+
+impl PoolColumns {
+    /// Value of a column from `METRIC_COLUMNS`.
+    fn get(&self, column: &str) -> Option<f64> {
+        match column {
+            "reserve_a" => Some(self.reserve_a),
+            "reserve_b" => Some(self.reserve_b),
+            "price" => spot_price(self.reserve_a, self.reserve_b),
+            "tvl" => Some(2.0 * self.reserve_b),
+            "volume_a_24h" => Some(self.volume.volume_a_24h),
+            "volume_b_24h" => Some(self.volume.volume_b_24h),
+            "volume_a_7d" => Some(self.volume.volume_a_7d),
+            "volume_b_7d" => Some(self.volume.volume_b_7d),
+            "swaps_24h" => Some(self.volume.swaps_24h as f64),
+            "swaps_7d" => Some(self.volume.swaps_7d as f64),
+            _ => None,
+        }
+    }
+}
+
+/// Computes custom metrics for every pool and stores their values.
+/// 
+/// Metrics whose stored expression no longer parses, e.g. after a column
+/// was removed, are logged and skipped.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `metrics` - Metrics to compute
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn compute_custom_metrics(conn: &Connection, metrics: &[CustomMetric], now: i64) -> Result<()> {
+    let volumes = pool_volumes(conn, None, now)?;
+    let pools = query_log::query_all(
+        conn,
+        "compute_custom_metrics",
+        "SELECT pool_id, reserve_a, reserve_b FROM pools ORDER BY pool_id",
+        params![],
+        |row| {
+            let pool_id: String = row.get(0)?;
+            let volume = volumes.get(&pool_id).copied().unwrap_or_default();
+            Ok(PoolColumns { pool_id, reserve_a: row.get(1)?, reserve_b: row.get(2)?, volume })
+        },
+    )?;
+
+    for metric in metrics {
+        let expr = match Expr::parse(&metric.expression) {
+            Ok(expr) => expr,
+            Err(e) => {
+                warn!(metric = %metric.name, error = %e, "skipping custom metric with an invalid expression");
+                continue;
+            }
+        };
+        let values: Vec<(String, Option<f64>)> = pools
+            .iter()
+            .map(|pool| (pool.pool_id.clone(), expr.eval(&|column| pool.get(column))))
+            .collect();
+        save_custom_metric_values(conn, &metric.name, &values, now)?;
+    }
+    Ok(())
+}
+
+/// Recomputes every registered custom metric once a minute while the
+/// `custom_metrics` feature is enabled.
+pub async fn run_custom_metrics(conn_arc: Arc<Mutex<Connection>>, flags: FeatureFlags) {
+    let interval = Duration::from_secs(CUSTOM_METRICS_INTERVAL_SECS);
+    loop {
+        if is_enabled(&flags, "custom_metrics") {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            let computed = match conn_arc.lock() {
+                Ok(conn) => list_custom_metrics(&conn).and_then(|metrics| compute_custom_metrics(&conn, &metrics, now)),
+                Err(_) => return,
+            };
+            if let Err(e) = computed {
+                warn!(error = %e, "failed to compute custom metrics");
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses and evaluates an expression with reserve_a = 1 and reserve_b = 3,
+    /// and price undefined as for a drained pool.
+    fn eval(source: &str) -> Option<f64> {
+        let expr = Expr::parse(source).unwrap_or_else(|e| panic!("`{}` didn't parse: {}", source, e));
+        expr.eval(&|column| match column {
+            "reserve_a" => Some(1.0),
+            "reserve_b" => Some(3.0),
+            _ => None,
+        })
+    }
+
+    fn parse_error(source: &str) -> String {
+        match Expr::parse(source) {
+            Ok(_) => panic!("`{}` parsed", source),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn products_bind_tighter_than_sums() {
+        assert_eq!(eval("reserve_a + reserve_b * 2"), Some(7.0));
+        assert_eq!(eval("(reserve_a + reserve_b) * 2"), Some(8.0));
+        assert_eq!(eval("10 - 4 - 3"), Some(3.0));
+        assert_eq!(eval("8 / 4 / 2"), Some(1.0));
+    }
+
+    #[test]
+    fn unary_minus() {
+        assert_eq!(eval("-reserve_b * 2"), Some(-6.0));
+        assert_eq!(eval("2 * -reserve_b"), Some(-6.0));
+        assert_eq!(eval("--reserve_b"), Some(3.0));
+        assert_eq!(eval("reserve_a - -1"), Some(2.0));
+    }
+
+    #[test]
+    fn unbalanced_parentheses_are_rejected() {
+        assert!(parse_error("(reserve_a + 1").contains("Expected `)`"));
+        assert!(parse_error("reserve_a + 1)").contains("Unexpected `)`"));
+        assert!(parse_error("()").contains("Unexpected `)`"));
+    }
+
+    #[test]
+    fn unknown_columns_are_rejected() {
+        let error = parse_error("reserve_c / 2");
+        assert!(error.contains("Unknown column `reserve_c`"), "{}", error);
+        assert!(error.contains("volume_a_24h"), "{}", error);
+    }
+
+    #[test]
+    fn malformed_numbers_are_rejected() {
+        assert_eq!(parse_error("1..2"), "`1..2` is not a valid number");
+        assert!(parse_error("1 2").contains("Unexpected `2`"));
+        assert_eq!(parse_error("reserve_a +"), "Expression ends unexpectedly");
+    }
+
+    #[test]
+    fn undefined_results_are_none() {
+        assert_eq!(eval("reserve_a / 0"), None);
+        assert_eq!(eval("0 / 0"), None);
+        assert_eq!(eval("reserve_a / (reserve_b - 3)"), None);
+        assert_eq!(eval("price * 2"), None);
+    }
+}
@@ -1,6 +1,64 @@
-use rusqlite::{params, Connection, Result};
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
 
+use crate::config::config;
+use crate::events::EventId;
+use crate::migrations::{baseline_schema, check_schema_version, run_online_migrations};
+use crate::query_log;
+use crate::reports::create_views;
+
+/// SQLite database file used when `DB_PATH` is not configured, created in
+/// the working directory
+pub const DEFAULT_DB_PATH: &str = "fooswap.db";
+
+/// Columns every table must have for the current schema, as (table, columns)
+pub const EXPECTED_SCHEMA: [(&str, &[&str]); 16] = [
+    ("pools", &["pool_id", "token_a", "token_b", "reserve_a", "reserve_b", "last_updated", "version", "sync_version"]),
+    ("swaps", &["id", "pool_id", "amount_in", "amount_out", "timestamp", "tx_digest", "event_seq", "epoch", "checkpoint", "value_quote", "value_usd", "inconsistent", "sender", "direction", "new_reserve_a", "new_reserve_b"]),
+    ("failed_swaps", &["id", "pool_id", "error", "timestamp", "tx_digest"]),
+    ("pool_snapshots", &["id", "pool_id", "reserve_a", "reserve_b", "timestamp"]),
+    ("pool_reconciliations", &["id", "pool_id", "reserve_a", "reserve_b", "swap_id", "timestamp", "reconciled_at"]),
+    ("ingest_log", &["id", "batch_hash", "event_count", "state_hash", "first_seen", "last_verified", "mismatch"]),
+    (
+        "route_recommendations",
+        &[
+            "id", "from_token", "to_token", "route", "hops", "first_pool", "last_pool",
+            "amount_in", "quoted_out", "created_at", "realized_out", "matched_tx",
+        ],
+    ),
+    (
+        "quotes",
+        &[
+            "id", "pool_id", "direction", "amount_in", "quoted_out", "min_amount_out", "key_hash",
+            "user_agent", "created_at", "expires_at", "realized_out", "matched_tx",
+        ],
+    ),
+    ("usage", &["key_hash", "day", "endpoint", "requests", "bytes_out"]),
+    ("feature_flags", &["name", "enabled", "updated_at"]),
+    (
+        "candles",
+        &["pool_id", "interval", "bucket_start", "open", "high", "low", "close", "volume_a", "volume_b", "trades"],
+    ),
+    ("pool_stats", &["pool_id", "bucket_start", "volume_a", "volume_b", "swaps"]),
+    ("tokens", &["coin_type", "symbol", "name", "decimals", "fetched_at"]),
+    ("custom_metrics", &["name", "expression", "created_at", "updated_at"]),
+    ("custom_metric_values", &["name", "pool_id", "value", "computed_at"]),
+    ("wallet_retention", &["cohort_start", "week", "wallets", "cohort_size", "computed_at"]),
+];
+
+/// Amount of token A a swap moved, in or out according to its direction;
+/// nothing for a swap of unknown direction
+pub const SWAP_VOLUME_A: &str = "CASE direction WHEN 'a_to_b' THEN amount_in WHEN 'b_to_a' THEN amount_out ELSE 0.0 END";
+
+/// Amount of token B a swap moved, the mirror of `SWAP_VOLUME_A`
+pub const SWAP_VOLUME_B: &str = "CASE direction WHEN 'a_to_b' THEN amount_out WHEN 'b_to_a' THEN amount_in ELSE 0.0 END";
+
+/// Width of the `pool_stats` buckets volume windows are summed from (in milliseconds)
+const POOL_STATS_BUCKET_MS: i64 = 60 * 60 * 1000;
+
 /// Initializes the SQLite database and creates the required schema.
 /// 
 /// This function creates the database file if it doesn't exist and sets up
@@ -20,6 +78,9 @@ use std::path::Path;
 /// - `reserve_a`: Current reserve of token A
 /// - `reserve_b`: Current reserve of token B
 /// - `last_updated`: Timestamp of last update
+/// - `version`: Counter incremented on every reserve update
+/// - `sync_version`: Table-wide sequence number of the pool's last change,
+///   which differential sync clients fetch changes after
 /// 
 /// ## swaps table
 /// Stores historical swap transactions:
@@ -28,11 +89,237 @@ use std::path::Path;
 /// - `amount_in`: Amount of input token
 /// - `amount_out`: Amount of output token
 /// - `timestamp`: Transaction timestamp
+/// - `tx_digest`: Digest of the transaction that emitted the swap event
+/// - `event_seq`: Index of the event within its transaction; `(tx_digest, event_seq)`
+///   is UNIQUE for deduplication, as one transaction can swap several times
+/// - `value_quote`, `value_usd`: Notional of `amount_in` at the derived token price, once priced
+/// - `inconsistent`: Set to 1 if the reserves before the swap match no snapshot of the pool
+/// - `sender`: Address of the trader, if the event carried one
+/// - `direction`: `a_to_b` if token A was sold for token B, `b_to_a` if token B
+///   was sold, `unknown` if no snapshot of the pool tells which
+/// - `new_reserve_a`, `new_reserve_b`: Reserves the swap event reports after
+///   the swap, `NULL` for swaps stored before they were recorded
+/// 
+/// ## failed_swaps table
+/// Stores transactions that touched a pool but failed on chain:
+/// - `id`: Auto-incrementing primary key
+/// - `pool_id`: Pool object the failed transaction used as input
+/// - `error`: Execution error reported in the transaction effects
+/// - `timestamp`: Transaction timestamp
 /// - `tx_digest`: Unique transaction digest (UNIQUE constraint for deduplication)
+/// 
+/// ## failed_swap_cursors table
+/// Stores how far each pool's transactions were scanned for failures:
+/// - `pool_id`: Pool the cursor belongs to
+/// - `tx_digest`: Digest of the last transaction scanned, passed back as the
+///   `suix_queryTransactionBlocks` cursor
+/// - `updated_at`: When the cursor was last moved
+/// 
+/// ## pool_snapshots table
+/// Stores pool reserves as of each reserve change, for historical lookups:
+/// - `id`: Auto-incrementing primary key
+/// - `pool_id`: Pool the snapshot belongs to
+/// - `reserve_a`: Reserve of token A after the change
+/// - `reserve_b`: Reserve of token B after the change
+/// - `timestamp`: Timestamp of the change
+/// 
+/// ## pool_reconciliations table
+/// Stores the on-chain state pools with inconsistent swaps were reset to,
+/// which `verify` replays from:
+/// - `id`: Auto-incrementing primary key
+/// - `pool_id`: Pool that was reconciled
+/// - `reserve_a`: On-chain reserve of token A
+/// - `reserve_b`: On-chain reserve of token B
+/// - `swap_id`: Row ID of the swap the on-chain state was read after
+/// - `timestamp`: Timestamp of that swap
+/// - `reconciled_at`: When the on-chain state was read
+/// 
+/// ## ingest_log table
+/// Stores a checksum per ingested event batch:
+/// - `id`: Auto-incrementing primary key
+/// - `batch_hash`: SHA-256 of the batch's sorted event IDs (UNIQUE)
+/// - `event_count`: Number of events in the batch
+/// - `state_hash`: SHA-256 of the rows derived from the batch
+/// - `first_seen`: When the batch was first ingested
+/// - `last_verified`: When a replay of the batch was last checked
+/// - `mismatch`: Set to 1 if a replay ever produced different derived rows
+/// 
+/// ## route_recommendations table
+/// Stores the router's top recommendation per best-price request:
+/// - `id`: Auto-incrementing primary key
+/// - `from_token` / `to_token`: Requested pair
+/// - `route`: Comma-separated pool IDs in hop order
+/// - `hops`: Number of pools in the route
+/// - `first_pool` / `last_pool`: Entry and exit pools, used for matching
+/// - `amount_in`: Requested input amount
+/// - `quoted_out`: Output the router expected
+/// - `created_at`: When the recommendation was served
+/// - `realized_out`: Output of the matching on-chain swap, once found
+/// - `matched_tx`: Digest of the matching swap transaction, once found
+/// 
+/// ## quotes table
+/// Stores every quote served by `/api/quote`, later matched to realized swaps:
+/// - `id`: Auto-incrementing primary key, returned to clients as `quote_id`
+/// - `pool_id` / `direction`: Quoted pool and swap direction
+/// - `amount_in`: Quoted input amount
+/// - `quoted_out` / `min_amount_out`: Expected output and its slippage bound
+/// - `key_hash`: Hash of the requesting API key, if any
+/// - `user_agent`: User agent of the requesting client, if any
+/// - `created_at` / `expires_at`: When the quote was served and stops matching swaps
+/// - `realized_out`: Output of the matching on-chain swap, once found
+/// - `matched_tx`: Digest of the matching swap transaction, once found
+/// 
+/// ## candles table
+/// Stores OHLCV candles rolled up from swaps by the candle aggregator:
+/// - `pool_id`, `interval`, `bucket_start`: Pool, interval label (`1m`, `5m`, `1h`, `1d`) and bucket start (primary key)
+/// - `open` / `high` / `low` / `close`: Execution prices in token B per token A in the bucket
+/// - `volume_a` / `volume_b`: Summed amounts of token A and of token B the swaps moved
+/// - `trades`: Number of swaps in the bucket
+/// 
+/// ## tokens table
+/// Stores coin metadata fetched by the indexer for every token in a pool:
+/// - `coin_type`: Coin type as stored in `pools` (primary key)
+/// - `symbol`, `name`, `decimals`: Metadata from `suix_getCoinMetadata`;
+///   all `NULL` if the coin has none
+/// - `fetched_at`: When the metadata was fetched
+/// 
+/// ## pool_stats table
+/// Stores swap volume per pool and hour, added to as each new swap is stored:
+/// - `pool_id`, `bucket_start`: Pool and start of the hour (primary key)
+/// - `volume_a`, `volume_b`: Summed amounts of token A and of token B the
+///   swaps in the hour moved, whichever way they traded; swaps of unknown
+///   direction count towards neither
+/// - `swaps`: Number of swaps in the hour
+/// 
+/// ## custom_metrics table
+/// Stores metric expressions registered by admins, see `custom_metrics`:
+/// - `name`: Name the metric is served under (primary key)
+/// - `expression`: Arithmetic over the columns in `custom_metrics::METRIC_COLUMNS`
+/// - `created_at` / `updated_at`: When the metric was registered and last changed
+/// 
+/// ## custom_metric_values table
+/// Stores the latest value of each custom metric per pool:
+/// - `name`, `pool_id`: Metric and pool (primary key)
+/// - `value`: Result of the expression, `NULL` if it is undefined for the pool
+/// - `computed_at`: When the value was computed
+/// 
+/// ## wallet_retention table
+/// Stores weekly wallet retention, recomputed from the swap history, see
+/// `retention`:
+/// - `cohort_start`, `week`: Monday of the week of the cohort's first swaps,
+///   and weeks since then (primary key)
+/// - `wallets`: Wallets of the cohort that swapped in that week
+/// - `cohort_size`: Wallets in the cohort
+/// - `computed_at`: When the cohorts were computed
+/// 
+/// ## api_key_addresses table
+/// Stores the Sui addresses admins have registered an API key for, whose
+/// private WebSocket channels the key may subscribe to:
+/// - `key_hash`, `address`: Hash of the key (see `api_key_hash`) and
+///   lowercase address (primary key)
+/// - `created_at`: When the address was registered
+/// 
+/// ## idempotency_keys table
+/// Stores the responses of resource-creating requests sent with an
+/// `Idempotency-Key`, see `idempotency`:
+/// - `scope`, `key`: Hash of the caller's API key (empty without one) and
+///   the key (primary key)
+/// - `fingerprint`: Hash of the request's method, path and body
+/// - `status`, `body`: The response, `NULL` while the request is handled
+/// - `created_at`: When the key was first used; keys expire after 24 hours
+/// 
+/// ## migration_lock table
+/// Holds at most one row, naming the process currently running online
+/// migrations so concurrent processes don't migrate the same schema twice.
+/// 
+/// ## schema_version table
+/// Records every online migration applied to the database; the highest
+/// `version` is the database's schema version. Startup fails on a database
+/// whose version is newer than the build's.
 pub fn init_db() -> Result<Connection> {
-    // Database file path in project root
-    let db_path = Path::new("fooswap.db");
-    let conn = Connection::open(db_path)?;
+    let conn = open_db()?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+/// Builds an error for a database that can't be opened as configured.
+fn open_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CANTOPEN), Some(message))
+}
+
+/// Reads the encryption key from `DB_ENCRYPTION_KEY`, or from the file named
+/// by `DB_ENCRYPTION_KEY_FILE` (e.g. a secret mounted by a KMS agent).
+fn encryption_key() -> Result<Option<String>> {
+    if let Ok(key) = std::env::var("DB_ENCRYPTION_KEY") {
+        return Ok(Some(key).filter(|k| !k.is_empty()));
+    }
+    match std::env::var("DB_ENCRYPTION_KEY_FILE") {
+        Ok(path) if !path.is_empty() => std::fs::read_to_string(&path)
+            .map(|key| Some(key.trim().to_string()).filter(|k| !k.is_empty()))
+            .map_err(|e| open_error(format!("cannot read DB_ENCRYPTION_KEY_FILE {}: {}", path, e))),
+        _ => Ok(None),
+    }
+}
+
+/// Returns the configured SQLite database file (`DB_PATH`).
+pub fn db_path() -> &'static str {
+    &config().db_path
+}
+
+/// Opens the database file, unlocking it when encryption is configured.
+/// 
+/// With an encryption key set, the key is applied with `PRAGMA key` before the
+/// file is read. This needs a build with the `sqlcipher` feature: plain SQLite
+/// silently ignores the pragma and would keep writing plaintext, so opening
+/// fails instead. A wrong key also fails here rather than on the first query.
+/// 
+/// # Returns
+/// * `Result<Connection>` - The open connection or error
+pub fn open_db() -> Result<Connection> {
+    let conn = Connection::open(Path::new(db_path()))?;
+    let Some(key) = encryption_key()? else {
+        return Ok(conn);
+    };
+
+    conn.pragma_update(None, "key", &key)?;
+    let cipher: Option<String> = conn
+        .query_row("PRAGMA cipher_version", [], |row| row.get(0))
+        .optional()?;
+    if cipher.is_none() {
+        return Err(open_error(
+            "a database encryption key is set but this build has no SQLCipher support; rebuild with `--features sqlcipher`"
+                .to_string(),
+        ));
+    }
+    conn.query_row("SELECT COUNT(*) FROM sqlite_master", [], |row| row.get::<_, i64>(0))
+        .map_err(|e| open_error(format!("cannot unlock {} with the configured key: {}", db_path(), e)))?;
+    Ok(conn)
+}
+
+/// Applies connection settings, creates the schema and runs online migrations
+/// on an open connection.
+/// 
+/// `init_db` uses this for the database file; tests use it to set up an
+/// in-memory database with the same schema.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn init_schema(conn: &Connection) -> Result<()> {
+    // WAL lets API reads proceed while the indexer or a migration is writing,
+    // and the busy timeout makes short write locks wait instead of failing
+    conn.execute_batch("PRAGMA journal_mode = WAL;")?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+
+    // Allow free pages to be reclaimed incrementally. This only takes effect on
+    // new databases, or on existing ones after their next full VACUUM.
+    conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL;")?;
+
+    // Refuse a database a newer build migrated, before touching its schema
+    check_schema_version(conn)?;
+    let fresh = table_columns(conn, "pools")?.is_empty();
 
     // Create database schema with proper indexing
     conn.execute_batch(
@@ -44,7 +331,9 @@ pub fn init_db() -> Result<Connection> {
             token_b     TEXT NOT NULL,
             reserve_a   REAL NOT NULL DEFAULT 0.0,
             reserve_b   REAL NOT NULL DEFAULT 0.0,
-            last_updated INTEGER NOT NULL DEFAULT 0
+            last_updated INTEGER NOT NULL DEFAULT 0,
+            version     INTEGER NOT NULL DEFAULT 1,
+            sync_version INTEGER NOT NULL DEFAULT 0  -- Table-wide sequence number of the last change
         );
         CREATE INDEX IF NOT EXISTS idx_pools_last_updated ON pools(last_updated);
 
@@ -55,20 +344,366 @@ pub fn init_db() -> Result<Connection> {
             amount_in    REAL NOT NULL,
             amount_out   REAL NOT NULL,
             timestamp    INTEGER NOT NULL,
-            tx_digest    TEXT NOT NULL UNIQUE  -- Prevents duplicate transaction processing
+            tx_digest    TEXT NOT NULL,
+            event_seq    INTEGER NOT NULL DEFAULT 0,  -- Index of the event within its transaction
+            epoch        INTEGER,  -- Sui epoch the transaction executed in, once looked up
+            checkpoint   INTEGER,  -- Checkpoint that included the transaction, once looked up
+            value_quote  REAL,     -- Notional of amount_in in the quote token, once priced
+            value_usd    REAL,     -- Notional of amount_in in USD, once priced
+            inconsistent INTEGER NOT NULL DEFAULT 0,  -- 1 if the reserves before the swap match no snapshot
+            sender       TEXT,     -- Trader address, if the event carried one
+            direction    TEXT NOT NULL DEFAULT 'a_to_b',  -- a_to_b, b_to_a or unknown
+            new_reserve_a REAL,    -- Reserves the swap event reports after the swap
+            new_reserve_b REAL
         );
         CREATE INDEX IF NOT EXISTS idx_swaps_pool_ts ON swaps(pool_id, timestamp DESC);
+
+        -- Failed transactions that touched a pool (e.g. slippage reverts)
+        CREATE TABLE IF NOT EXISTS failed_swaps (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            pool_id      TEXT NOT NULL,
+            error        TEXT NOT NULL,
+            timestamp    INTEGER NOT NULL,
+            tx_digest    TEXT NOT NULL UNIQUE
+        );
+        CREATE INDEX IF NOT EXISTS idx_failed_swaps_pool_ts ON failed_swaps(pool_id, timestamp DESC);
+
+        -- Last transaction scanned for failures per pool, so each scan reads only newer ones
+        CREATE TABLE IF NOT EXISTS failed_swap_cursors (
+            pool_id      TEXT PRIMARY KEY,
+            tx_digest    TEXT NOT NULL,  -- Digest of the last transaction scanned
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Point-in-time pool reserves, recorded on every reserve change
+        CREATE TABLE IF NOT EXISTS pool_snapshots (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            pool_id      TEXT NOT NULL,
+            reserve_a    REAL NOT NULL,
+            reserve_b    REAL NOT NULL,
+            timestamp    INTEGER NOT NULL,
+            UNIQUE (pool_id, timestamp, reserve_a, reserve_b)  -- Re-indexing must not duplicate snapshots
+        );
+        CREATE INDEX IF NOT EXISTS idx_pool_snapshots_pool_ts ON pool_snapshots(pool_id, timestamp DESC);
+
+        -- On-chain state pools were reset to after an inconsistent swap
+        CREATE TABLE IF NOT EXISTS pool_reconciliations (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            pool_id       TEXT NOT NULL,
+            reserve_a     REAL NOT NULL,
+            reserve_b     REAL NOT NULL,
+            swap_id       INTEGER NOT NULL,  -- Swap the on-chain state was read after
+            timestamp     INTEGER NOT NULL,  -- Timestamp of that swap
+            reconciled_at INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_pool_reconciliations_pool ON pool_reconciliations(pool_id, id DESC);
+
+        -- Checksums of ingested event batches, used to verify replays are idempotent
+        CREATE TABLE IF NOT EXISTS ingest_log (
+            id             INTEGER PRIMARY KEY AUTOINCREMENT,
+            batch_hash     TEXT NOT NULL UNIQUE,
+            event_count    INTEGER NOT NULL,
+            state_hash     TEXT NOT NULL,
+            first_seen     INTEGER NOT NULL,
+            last_verified  INTEGER NOT NULL,
+            mismatch       INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- Routes recommended by the router, later matched to realized swaps
+        CREATE TABLE IF NOT EXISTS route_recommendations (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            from_token   TEXT NOT NULL,
+            to_token     TEXT NOT NULL,
+            route        TEXT NOT NULL,  -- Comma-separated pool IDs in hop order
+            hops         INTEGER NOT NULL,
+            first_pool   TEXT NOT NULL,
+            last_pool    TEXT NOT NULL,
+            amount_in    REAL NOT NULL,
+            quoted_out   REAL NOT NULL,
+            created_at   INTEGER NOT NULL,
+            realized_out REAL,
+            matched_tx   TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_route_recs_unmatched ON route_recommendations(matched_tx, created_at);
+
+        -- Quotes served by /api/quote, later matched to realized swaps
+        CREATE TABLE IF NOT EXISTS quotes (
+            id             INTEGER PRIMARY KEY AUTOINCREMENT,
+            pool_id        TEXT NOT NULL,
+            direction      TEXT NOT NULL,  -- a_to_b or b_to_a
+            amount_in      REAL NOT NULL,
+            quoted_out     REAL NOT NULL,
+            min_amount_out REAL NOT NULL,
+            key_hash       TEXT,           -- SHA-256 of the API key; raw keys are never stored
+            user_agent     TEXT,
+            created_at     INTEGER NOT NULL,
+            expires_at     INTEGER NOT NULL,
+            realized_out   REAL,
+            matched_tx     TEXT
+        );
+        CREATE INDEX IF NOT EXISTS idx_quotes_unmatched ON quotes(matched_tx, created_at);
+
+        -- Daily request and bandwidth counters per API key and endpoint
+        CREATE TABLE IF NOT EXISTS usage (
+            key_hash     TEXT NOT NULL,  -- SHA-256 of the API key; raw keys are never stored
+            day          INTEGER NOT NULL,  -- Start of the UTC day (ms)
+            endpoint     TEXT NOT NULL,  -- Route template, e.g. /api/pools/:pool_id/at
+            requests     INTEGER NOT NULL DEFAULT 0,
+            bytes_out    INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (key_hash, day, endpoint)
+        );
+
+        -- Feature flag overrides set by admins at runtime
+        CREATE TABLE IF NOT EXISTS feature_flags (
+            name         TEXT PRIMARY KEY,
+            enabled      INTEGER NOT NULL,
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Coin metadata of every token in a pool, fetched by the indexer
+        CREATE TABLE IF NOT EXISTS tokens (
+            coin_type    TEXT PRIMARY KEY,
+            symbol       TEXT,     -- NULL if the coin has no metadata
+            name         TEXT,
+            decimals     INTEGER,
+            fetched_at   INTEGER NOT NULL
+        );
+
+        -- Display metadata curated by admins, merged into pool responses
+        CREATE TABLE IF NOT EXISTS pool_overrides (
+            pool_id       TEXT PRIMARY KEY,
+            display_name  TEXT,
+            logo_url      TEXT,
+            verified      INTEGER NOT NULL DEFAULT 0,
+            warning       TEXT,
+            updated_at    INTEGER NOT NULL
+        );
+
+        -- Labels admins import for known addresses (market makers, team wallets, bots)
+        CREATE TABLE IF NOT EXISTS labels (
+            address      TEXT PRIMARY KEY,  -- Lowercase Sui address
+            label        TEXT NOT NULL,
+            category     TEXT,
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Short names admins give token pairs, accepted wherever a `pair` parameter is
+        CREATE TABLE IF NOT EXISTS pair_aliases (
+            alias        TEXT PRIMARY KEY COLLATE NOCASE,  -- e.g. SUI-USDC, matched case-insensitively
+            token_a      TEXT NOT NULL,
+            token_b      TEXT NOT NULL,
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Addresses an API key owns, whose private WebSocket channels it may subscribe to
+        CREATE TABLE IF NOT EXISTS api_key_addresses (
+            key_hash     TEXT NOT NULL,  -- SHA-256 of the API key, hex
+            address      TEXT NOT NULL,  -- Lowercase Sui address
+            created_at   INTEGER NOT NULL,
+            PRIMARY KEY (key_hash, address)
+        );
+
+        -- Responses of resource-creating requests, replayed to retries with the same Idempotency-Key
+        CREATE TABLE IF NOT EXISTS idempotency_keys (
+            scope        TEXT NOT NULL,  -- SHA-256 of the caller's API key, hex, or empty without one
+            key          TEXT NOT NULL,  -- Idempotency-Key header
+            fingerprint  TEXT NOT NULL,  -- SHA-256 of the request's method, path and body
+            status       INTEGER,        -- HTTP status of the response, NULL while the request is handled
+            body         BLOB,
+            created_at   INTEGER NOT NULL,
+            PRIMARY KEY (scope, key)
+        );
+        CREATE INDEX IF NOT EXISTS idx_idempotency_keys_created ON idempotency_keys(created_at);
+
+        -- Daily size of each table, for the database growth forecast
+        CREATE TABLE IF NOT EXISTS db_growth (
+            day          INTEGER NOT NULL,  -- Start of the UTC day (ms)
+            table_name   TEXT NOT NULL,     -- `*` for the whole database file
+            row_count    INTEGER NOT NULL,
+            bytes        INTEGER,           -- Pages of the table and its indexes, if dbstat is available
+            recorded_at  INTEGER NOT NULL,
+            PRIMARY KEY (day, table_name)
+        );
+
+        -- Single-row indexer progress: the checkpoint the last completed polling cycle reached
+        CREATE TABLE IF NOT EXISTS indexer_state (
+            id               INTEGER PRIMARY KEY CHECK (id = 1),
+            package_id       TEXT NOT NULL,  -- DEX package the state belongs to
+            last_ts          INTEGER NOT NULL,  -- Timestamp of that checkpoint (ms)
+            last_checkpoint  INTEGER NOT NULL,
+            updated_at       INTEGER NOT NULL
+        );
+
+        -- Sui event cursor per Move event type, so restarts resume where the last run stopped
+        CREATE TABLE IF NOT EXISTS indexer_cursors (
+            event_type   TEXT PRIMARY KEY,  -- Fully qualified type, including the DEX package
+            tx_digest    TEXT NOT NULL,  -- ID of the last committed event of this type
+            event_seq    TEXT NOT NULL,
+            event_ts     INTEGER,  -- Timestamp of that event (ms)
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Background export jobs and their outcome
+        CREATE TABLE IF NOT EXISTS export_jobs (
+            id           INTEGER PRIMARY KEY AUTOINCREMENT,
+            request      TEXT NOT NULL,  -- JSON of the posted export request
+            status       TEXT NOT NULL,  -- queued, running, complete or failed
+            rows         INTEGER NOT NULL DEFAULT 0,
+            error        TEXT,
+            created_at   INTEGER NOT NULL,
+            finished_at  INTEGER
+        );
+
+        -- OHLCV candles per pool, interval and bucket, rolled up from swaps
+        CREATE TABLE IF NOT EXISTS candles (
+            pool_id      TEXT NOT NULL,
+            interval     TEXT NOT NULL,  -- 1m, 5m, 1h or 1d
+            bucket_start INTEGER NOT NULL,  -- Start of the bucket (ms)
+            open         REAL NOT NULL,
+            high         REAL NOT NULL,
+            low          REAL NOT NULL,
+            close        REAL NOT NULL,
+            volume_a     REAL NOT NULL,  -- Token A moved by the swaps, in or out
+            volume_b     REAL NOT NULL,  -- Token B moved by the swaps, in or out
+            trades       INTEGER NOT NULL,
+            PRIMARY KEY (pool_id, interval, bucket_start)
+        );
+
+        -- Single-row candle aggregator progress: the last swap rolled into candles
+        CREATE TABLE IF NOT EXISTS candle_progress (
+            id            INTEGER PRIMARY KEY CHECK (id = 1),
+            last_swap_id  INTEGER NOT NULL,
+            updated_at    INTEGER NOT NULL
+        );
+
+        -- Swap volume per pool and hour, added to as swaps are stored
+        CREATE TABLE IF NOT EXISTS pool_stats (
+            pool_id      TEXT NOT NULL,
+            bucket_start INTEGER NOT NULL,  -- Start of the hour (ms)
+            volume_a     REAL NOT NULL DEFAULT 0.0,  -- Token A moved by the swaps, in or out
+            volume_b     REAL NOT NULL DEFAULT 0.0,  -- Token B moved by the swaps, in or out
+            swaps        INTEGER NOT NULL,
+            PRIMARY KEY (pool_id, bucket_start)
+        );
+        CREATE INDEX IF NOT EXISTS idx_pool_stats_bucket ON pool_stats(bucket_start);
+
+        -- Metric expressions registered by admins, computed per pool in the background
+        CREATE TABLE IF NOT EXISTS custom_metrics (
+            name         TEXT PRIMARY KEY,
+            expression   TEXT NOT NULL,
+            created_at   INTEGER NOT NULL,
+            updated_at   INTEGER NOT NULL
+        );
+
+        -- Latest value of each custom metric per pool
+        CREATE TABLE IF NOT EXISTS custom_metric_values (
+            name         TEXT NOT NULL,
+            pool_id      TEXT NOT NULL,
+            value        REAL,     -- NULL if the expression is undefined for the pool, e.g. divides by zero
+            computed_at  INTEGER NOT NULL,
+            PRIMARY KEY (name, pool_id)
+        );
+
+        -- Wallets trading in each week, by week of their first swap
+        CREATE TABLE IF NOT EXISTS wallet_retention (
+            cohort_start INTEGER NOT NULL,  -- Monday 00:00 UTC of the cohort's first week (ms)
+            week         INTEGER NOT NULL,  -- Weeks since cohort_start, 0 for the first
+            wallets      INTEGER NOT NULL,
+            cohort_size  INTEGER NOT NULL,
+            computed_at  INTEGER NOT NULL,
+            PRIMARY KEY (cohort_start, week)
+        );
+
+        -- Single-row lock held by whichever process is running online migrations
+        CREATE TABLE IF NOT EXISTS migration_lock (
+            id           INTEGER PRIMARY KEY CHECK (id = 1),
+            holder       TEXT NOT NULL,
+            acquired_at  INTEGER NOT NULL
+        );
+
+        -- Online migrations applied to the database, see `migrations`
+        CREATE TABLE IF NOT EXISTS schema_version (
+            version      INTEGER PRIMARY KEY,
+            name         TEXT NOT NULL,
+            applied_at   INTEGER NOT NULL
+        );
         "#,
     )?;
 
-    Ok(conn)
+    // Tables created just now already have the latest schema
+    if fresh {
+        baseline_schema(conn)?;
+    }
+
+    // Bring databases created by older versions up to the current schema
+    // without blocking an indexer or API process already using them
+    run_online_migrations(conn, &format!("pid-{}", std::process::id()), false)?;
+
+    // Indexes on migrated columns, unless another process is still adding them
+    if table_columns(conn, "pools")?.iter().any(|column| column == "sync_version") {
+        conn.execute_batch("CREATE INDEX IF NOT EXISTS idx_pools_sync_version ON pools(sync_version);")?;
+    }
+    // Prevents duplicate event processing
+    if table_columns(conn, "swaps")?.iter().any(|column| column == "event_seq") {
+        conn.execute_batch("CREATE UNIQUE INDEX IF NOT EXISTS idx_swaps_event ON swaps(tx_digest, event_seq);")?;
+    }
+
+    // Databases that stored swaps before `pool_stats` existed start it from them
+    let (has_stats, has_swaps): (bool, bool) = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pool_stats), EXISTS (SELECT 1 FROM swaps)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if has_swaps && !has_stats {
+        conn.execute(
+            &format!(
+                "INSERT INTO pool_stats (pool_id, bucket_start, volume_a, volume_b, swaps)
+                 SELECT pool_id, timestamp - timestamp % ?1, SUM({}), SUM({}), COUNT(*)
+                 FROM swaps GROUP BY 1, 2",
+                SWAP_VOLUME_A, SWAP_VOLUME_B
+            ),
+            [POOL_STATS_BUCKET_MS],
+        )?;
+    }
+
+    // Views behind the named report endpoints
+    create_views(conn)?;
+
+    Ok(())
+}
+
+/// Lists the column names of a table (empty if the table does not exist).
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `table` - Name of the table to inspect
+/// 
+/// # Returns
+/// * `Result<Vec<String>>` - Column names in declaration order
+pub fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| row.get::<_, String>(1))?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(columns)
 }
 
 /// Updates or inserts pool data in the database.
 /// 
 /// This function uses SQLite's `ON CONFLICT` clause to perform an upsert operation.
 /// If a pool with the given `pool_id` already exists, the reserves and timestamp
-/// are updated. Otherwise, a new pool record is created.
+/// are updated and its `version` is incremented. Otherwise, a new pool record
+/// is created with version 1. Either way the pool's `sync_version` moves past
+/// every other pool's. Updates older than the stored `last_updated` don't
+/// change the reserves, so replaying an earlier page never rolls them back.
+/// 
+/// `version` counts the distinct reserve updates indexed for the pool, so it
+/// ends up the same whatever order events arrive in. An update already
+/// recorded in `pool_snapshots`, e.g. from a duplicate or replayed event,
+/// changes nothing, so it neither bumps the versions nor wakes long-polling
+/// clients. Callers record the snapshot after calling this. Token
+/// addresses are filled in whenever the stored row lacks them, so a pool first
+/// seen through a swap still gets its pair when the creation event arrives late.
 /// 
 /// # Arguments
 /// * `conn` - SQLite database connection
@@ -90,26 +725,110 @@ pub fn upsert_pool(
     reserve_b: f64,
     last_updated: i64,
 ) -> Result<()> {
-    conn.execute(
+    query_log::execute(
+        conn,
+        "upsert_pool",
         r#"
-        INSERT INTO pools (pool_id, token_a, token_b, reserve_a, reserve_b, last_updated)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+        INSERT INTO pools (pool_id, token_a, token_b, reserve_a, reserve_b, last_updated, version, sync_version)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1, (SELECT COALESCE(MAX(sync_version), 0) + 1 FROM pools))
         ON CONFLICT(pool_id) DO UPDATE SET
-            reserve_a = excluded.reserve_a,
-            reserve_b = excluded.reserve_b,
-            last_updated = excluded.last_updated
+            token_a = CASE WHEN pools.token_a = '' THEN excluded.token_a ELSE pools.token_a END,
+            token_b = CASE WHEN pools.token_b = '' THEN excluded.token_b ELSE pools.token_b END,
+            reserve_a = CASE WHEN excluded.last_updated >= pools.last_updated
+                THEN excluded.reserve_a ELSE pools.reserve_a END,
+            reserve_b = CASE WHEN excluded.last_updated >= pools.last_updated
+                THEN excluded.reserve_b ELSE pools.reserve_b END,
+            last_updated = MAX(excluded.last_updated, pools.last_updated),
+            version = pools.version + CASE WHEN NOT EXISTS (
+                SELECT 1 FROM pool_snapshots
+                WHERE pool_id = ?1 AND timestamp = ?6 AND reserve_a = ?4 AND reserve_b = ?5
+            ) THEN 1 ELSE 0 END,
+            sync_version = excluded.sync_version
+        WHERE NOT EXISTS (
+                SELECT 1 FROM pool_snapshots
+                WHERE pool_id = ?1 AND timestamp = ?6 AND reserve_a = ?4 AND reserve_b = ?5
+            )
+           OR (pools.token_a = '' AND excluded.token_a != '')
         "#,
         params![pool_id, token_a, token_b, reserve_a, reserve_b, last_updated],
     )?;
     Ok(())
 }
 
-/// Inserts a swap transaction record if it doesn't already exist.
+/// Fingerprint of the `pools` table for differential sync
+#[derive(Serialize)]
+pub struct PoolsChecksum {
+    /// SHA-256 over every pool's ID, tokens, reserves, `last_updated` and
+    /// `version`, in pool ID order
+    pub checksum: String,
+    /// Highest `sync_version`; changes after it are not in the table yet
+    pub version: i64,
+    pub pool_count: usize,
+    /// `version` of each pool, keyed by pool ID
+    pub versions: BTreeMap<String, i64>,
+}
+
+/// Hashes the current state of every pool.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<PoolsChecksum>` - Checksum, table version and per-pool versions
+pub fn pools_checksum(conn: &Connection) -> Result<PoolsChecksum> {
+    let rows = query_log::query_all(
+        conn,
+        "pools_checksum",
+        "SELECT pool_id, token_a, token_b, reserve_a, reserve_b, last_updated, version, sync_version
+         FROM pools ORDER BY pool_id",
+        params![],
+        |row| {
+            let line = format!(
+                "pool|{}|{}|{}|{}|{}|{}|{}\n",
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, i64>(5)?,
+                row.get::<_, i64>(6)?
+            );
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(6)?, row.get::<_, i64>(7)?, line))
+        },
+    )?;
+
+    let mut hasher = Sha256::new();
+    let mut version = 0;
+    let mut versions = BTreeMap::new();
+    for (pool_id, pool_version, sync_version, line) in rows {
+        hasher.update(line.as_bytes());
+        version = version.max(sync_version);
+        versions.insert(pool_id, pool_version);
+    }
+    Ok(PoolsChecksum {
+        checksum: format!("{:x}", hasher.finalize()),
+        version,
+        pool_count: versions.len(),
+        versions,
+    })
+}
+
+/// Who made a swap and the pool state it left
+pub struct SwapSide {
+    /// Address of the trader, if the event carried one
+    pub sender: Option<String>,
+    /// Reserves (A, B) the swap event reports after the swap
+    pub new_reserves: (f64, f64),
+}
+
+/// Inserts a swap event record if it doesn't already exist.
 /// 
-/// This function uses `INSERT OR IGNORE` to prevent duplicate transaction
-/// processing. The `tx_digest` field has a UNIQUE constraint, so if a
-/// transaction with the same digest already exists, the insert is silently
-/// ignored.
+/// This function uses `INSERT OR IGNORE` to prevent duplicate event
+/// processing. `(tx_digest, event_seq)` has a UNIQUE index, so if the
+/// event was already stored, the insert is silently ignored. Other swap
+/// events of the same transaction, e.g. the legs of a multi-hop route, are
+/// stored separately. The swap is stored with an `unknown` direction, for
+/// the indexer to work out from the pool's snapshots (see `set_swap_check`).
 /// 
 /// # Arguments
 /// * `conn` - SQLite database connection
@@ -117,7 +836,8 @@ pub fn upsert_pool(
 /// * `amount_in` - Amount of input token swapped
 /// * `amount_out` - Amount of output token received
 /// * `timestamp` - Transaction timestamp
-/// * `tx_digest` - Unique transaction digest for deduplication
+/// * `id` - Transaction digest and index of the swap event, for deduplication
+/// * `side` - Trader of the swap and the reserves it left
 /// 
 /// # Returns
 /// * `Result<()>` - Success or error
@@ -127,14 +847,1905 @@ pub fn insert_swap(
     amount_in: f64,
     amount_out: f64,
     timestamp: i64,
-    tx_digest: &str,
+    id: &EventId,
+    side: &SwapSide,
 ) -> Result<()> {
-    let _ = conn.execute(
+    let inserted = query_log::execute(
+        conn,
+        "insert_swap",
         r#"
-        INSERT OR IGNORE INTO swaps (pool_id, amount_in, amount_out, timestamp, tx_digest)
-        VALUES (?1, ?2, ?3, ?4, ?5)
+        INSERT OR IGNORE INTO swaps
+            (pool_id, amount_in, amount_out, timestamp, tx_digest, event_seq, sender, direction, new_reserve_a, new_reserve_b)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, 'unknown', ?8, ?9)
         "#,
-        params![pool_id, amount_in, amount_out, timestamp, tx_digest],
+        params![
+            pool_id,
+            amount_in,
+            amount_out,
+            timestamp,
+            id.tx_digest,
+            id.event_seq as i64,
+            side.sender,
+            side.new_reserves.0,
+            side.new_reserves.1
+        ],
+    )?;
+
+    // Count a swap towards its pool's swaps only the first time it is stored;
+    // its volume is added once its direction is known, see `set_swap_check`
+    if inserted > 0 {
+        query_log::execute(
+            conn,
+            "add_pool_stats",
+            "INSERT INTO pool_stats (pool_id, bucket_start, swaps) VALUES (?1, ?2, 1)
+             ON CONFLICT (pool_id, bucket_start) DO UPDATE SET swaps = swaps + 1",
+            params![pool_id, timestamp - timestamp.rem_euclid(POOL_STATS_BUCKET_MS)],
+        )?;
+    }
+    Ok(())
+}
+
+/// Swap volume of a pool over the last 24 hours and 7 days
+#[derive(Serialize, Clone, Copy, Default)]
+pub struct PoolVolume {
+    /// Summed amounts of token A and of token B the swaps in the window moved
+    pub volume_a_24h: f64,
+    pub volume_b_24h: f64,
+    pub volume_a_7d: f64,
+    pub volume_b_7d: f64,
+    pub swaps_24h: i64,
+    pub swaps_7d: i64,
+}
+
+/// Reads the 24h and 7d volume of every pool with swaps from `pool_stats`.
+/// 
+/// Windows are made of whole hours: the 24h window is the current hour and
+/// the 23 before it, the 7d window the current hour and the 167 before it.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Only this pool, or every pool if `None`
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<HashMap<String, PoolVolume>>` - Volume keyed by pool ID; pools without swaps in the last 7 days are left out
+pub fn pool_volumes(conn: &Connection, pool_id: Option<&str>, now: i64) -> Result<HashMap<String, PoolVolume>> {
+    let current_bucket = now - now.rem_euclid(POOL_STATS_BUCKET_MS);
+    let rows = query_log::query_all(
+        conn,
+        "pool_volumes",
+        "SELECT pool_id,
+                COALESCE(SUM(CASE WHEN bucket_start >= ?1 THEN volume_a END), 0.0),
+                COALESCE(SUM(CASE WHEN bucket_start >= ?1 THEN volume_b END), 0.0),
+                COALESCE(SUM(volume_a), 0.0),
+                COALESCE(SUM(volume_b), 0.0),
+                COALESCE(SUM(CASE WHEN bucket_start >= ?1 THEN swaps END), 0),
+                COALESCE(SUM(swaps), 0)
+         FROM pool_stats
+         WHERE bucket_start >= ?2 AND (?3 IS NULL OR pool_id = ?3)
+         GROUP BY pool_id",
+        params![current_bucket - 23 * POOL_STATS_BUCKET_MS, current_bucket - 167 * POOL_STATS_BUCKET_MS, pool_id],
+        |row| {
+            let volume = PoolVolume {
+                volume_a_24h: row.get(1)?,
+                volume_b_24h: row.get(2)?,
+                volume_a_7d: row.get(3)?,
+                volume_b_7d: row.get(4)?,
+                swaps_24h: row.get(5)?,
+                swaps_7d: row.get(6)?,
+            };
+            Ok((row.get::<_, String>(0)?, volume))
+        },
+    )?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Lists swaps whose epoch has not been looked up yet, in ID order.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `after_id` - Only swaps with a larger row ID
+/// * `limit` - Maximum number of swaps returned
+/// 
+/// # Returns
+/// * `Result<Vec<(i64, String, i64)>>` - `(id, tx_digest, timestamp)` of each swap
+pub fn swaps_missing_epoch(conn: &Connection, after_id: i64, limit: usize) -> Result<Vec<(i64, String, i64)>> {
+    query_log::query_all(
+        conn,
+        "swaps_missing_epoch",
+        "SELECT id, tx_digest, timestamp FROM swaps WHERE epoch IS NULL AND id > ?1 ORDER BY id LIMIT ?2",
+        params![after_id, limit as i64],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+/// Lists swaps whose notional value has not been recorded yet, in ID order.
+/// Swaps of unknown direction are left out, as the sold token isn't known.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `after_id` - Only swaps with a larger row ID
+/// * `limit` - Maximum number of swaps returned
+/// 
+/// # Returns
+/// * `Result<Vec<(i64, String, f64)>>` - `(id, sold token, amount_in)` of each swap
+pub fn swaps_missing_value(conn: &Connection, after_id: i64, limit: usize) -> Result<Vec<(i64, String, f64)>> {
+    query_log::query_all(
+        conn,
+        "swaps_missing_value",
+        "SELECT s.id, CASE s.direction WHEN 'b_to_a' THEN p.token_b ELSE p.token_a END, s.amount_in
+         FROM swaps s JOIN pools p ON p.pool_id = s.pool_id
+         WHERE s.value_quote IS NULL AND s.value_usd IS NULL AND s.direction <> 'unknown' AND s.id > ?1
+         ORDER BY s.id LIMIT ?2",
+        params![after_id, limit as i64],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+/// Records the notional value of a swap.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `id` - Row ID of the swap
+/// * `value_quote` - `amount_in` valued in the quote token, if priced
+/// * `value_usd` - `amount_in` valued in USD, if priced
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_swap_value(conn: &Connection, id: i64, value_quote: Option<f64>, value_usd: Option<f64>) -> Result<()> {
+    query_log::execute(
+        conn,
+        "set_swap_value",
+        "UPDATE swaps SET value_quote = ?2, value_usd = ?3 WHERE id = ?1",
+        params![id, value_quote, value_usd],
+    )?;
+    Ok(())
+}
+
+/// Coin metadata of a token
+#[derive(Serialize, Clone)]
+pub struct TokenMetadata {
+    pub symbol: String,
+    pub name: String,
+    /// Number of decimal places raw amounts of the coin are scaled by
+    pub decimals: u8,
+}
+
+/// Lists tokens traded in a pool whose metadata has not been fetched yet.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `limit` - Maximum number of tokens returned
+/// 
+/// # Returns
+/// * `Result<Vec<String>>` - Coin types of the tokens
+pub fn tokens_missing_metadata(conn: &Connection, limit: usize) -> Result<Vec<String>> {
+    query_log::query_all(
+        conn,
+        "tokens_missing_metadata",
+        "SELECT token FROM (SELECT token_a AS token FROM pools UNION SELECT token_b FROM pools)
+         WHERE token <> '' AND token NOT IN (SELECT coin_type FROM tokens)
+         ORDER BY token
+         LIMIT ?1",
+        params![limit as i64],
+        |row| row.get(0),
+    )
+}
+
+/// Stores a token's metadata, or that it has none.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `coin_type` - Coin type of the token
+/// * `metadata` - Metadata reported by the node, or `None` if the coin has none
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn save_token_metadata(conn: &Connection, coin_type: &str, metadata: Option<&TokenMetadata>, now: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "save_token_metadata",
+        "INSERT INTO tokens (coin_type, symbol, name, decimals, fetched_at) VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT (coin_type) DO UPDATE SET
+            symbol = excluded.symbol,
+            name = excluded.name,
+            decimals = excluded.decimals,
+            fetched_at = excluded.fetched_at",
+        params![
+            coin_type,
+            metadata.map(|m| &m.symbol),
+            metadata.map(|m| &m.name),
+            metadata.map(|m| m.decimals),
+            now
+        ],
+    )?;
+    Ok(())
+}
+
+/// Reads the metadata of the given tokens, or of every token if `None`.
+/// 
+/// # Returns
+/// * `Result<HashMap<String, TokenMetadata>>` - Metadata keyed by coin type; tokens without any are left out
+pub fn token_metadata(conn: &Connection, coin_types: Option<&[&str]>) -> Result<HashMap<String, TokenMetadata>> {
+    let mut sql = String::from("SELECT coin_type, symbol, name, decimals FROM tokens WHERE decimals IS NOT NULL");
+    if let Some(types) = coin_types {
+        sql.push_str(&format!(" AND coin_type IN ({})", vec!["?"; types.len()].join(",")));
+    }
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(rusqlite::params_from_iter(coin_types.unwrap_or_default()), |row| {
+        let metadata = TokenMetadata { symbol: row.get(1)?, name: row.get(2)?, decimals: row.get(3)? };
+        Ok((row.get::<_, String>(0)?, metadata))
+    })?;
+    rows.collect()
+}
+
+/// Records the epoch and checkpoint a swap's transaction executed in.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `tx_digest` - Digest of the swap's transaction
+/// * `epoch` - Sui epoch
+/// * `checkpoint` - Checkpoint sequence number
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_swap_epoch(conn: &Connection, tx_digest: &str, epoch: u64, checkpoint: u64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "set_swap_epoch",
+        "UPDATE swaps SET epoch = ?2, checkpoint = ?3 WHERE tx_digest = ?1",
+        params![tx_digest, epoch as i64, checkpoint as i64],
+    )?;
+    Ok(())
+}
+
+/// A stored swap with the reserves it left, as checked against its pool's snapshots
+pub struct SwapCheck {
+    pub id: i64,
+    pub tx_digest: String,
+    pub event_seq: i64,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    /// Reserves (A, B) the swap event reported after the swap
+    pub new_reserves: (f64, f64),
+    pub direction: String,
+    pub inconsistent: bool,
+}
+
+/// Lists a pool's swaps at a timestamp that recorded the reserves they left.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// * `timestamp` - Timestamp of the swaps
+/// 
+/// # Returns
+/// * `Result<Vec<SwapCheck>>` - The swaps, in ID order
+pub fn swaps_to_check(conn: &Connection, pool_id: &str, timestamp: i64) -> Result<Vec<SwapCheck>> {
+    query_log::query_all(
+        conn,
+        "swaps_to_check",
+        "SELECT id, tx_digest, event_seq, amount_in, amount_out, new_reserve_a, new_reserve_b, direction, inconsistent
+         FROM swaps
+         WHERE pool_id = ?1 AND timestamp = ?2 AND new_reserve_a IS NOT NULL AND new_reserve_b IS NOT NULL
+         ORDER BY id",
+        params![pool_id, timestamp],
+        |row| {
+            Ok(SwapCheck {
+                id: row.get(0)?,
+                tx_digest: row.get(1)?,
+                event_seq: row.get(2)?,
+                amount_in: row.get(3)?,
+                amount_out: row.get(4)?,
+                new_reserves: (row.get(5)?, row.get(6)?),
+                direction: row.get(7)?,
+                inconsistent: row.get(8)?,
+            })
+        },
+    )
+}
+
+/// Reserves (A, B) of a pool's snapshots around a swap, see `reserves_before`
+pub struct ReservesBefore {
+    /// Snapshots at the latest timestamp before the swap's
+    pub earlier: Vec<(f64, f64)>,
+    /// Snapshots at the swap's timestamp
+    pub same: Vec<(f64, f64)>,
+}
+
+/// Reads the reserves a pool could have had just before a swap at a
+/// timestamp: its snapshots at the latest earlier timestamp, and those at
+/// the same timestamp, left by swaps of the same checkpoint.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// * `timestamp` - Timestamp of the swap
+/// 
+/// # Returns
+/// * `Result<ReservesBefore>` - Reserves of the snapshots found
+pub fn reserves_before(conn: &Connection, pool_id: &str, timestamp: i64) -> Result<ReservesBefore> {
+    let rows: Vec<(i64, f64, f64)> = query_log::query_all(
+        conn,
+        "reserves_before",
+        "SELECT timestamp, reserve_a, reserve_b FROM pool_snapshots
+         WHERE pool_id = ?1 AND timestamp IN (
+             ?2, (SELECT MAX(timestamp) FROM pool_snapshots WHERE pool_id = ?1 AND timestamp < ?2)
+         )",
+        params![pool_id, timestamp],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+    let (same, earlier): (Vec<_>, Vec<_>) = rows.into_iter().partition(|(ts, _, _)| *ts == timestamp);
+    let reserves = |rows: Vec<(i64, f64, f64)>| rows.into_iter().map(|(_, a, b)| (a, b)).collect();
+    Ok(ReservesBefore { earlier: reserves(earlier), same: reserves(same) })
+}
+
+/// Finds the timestamp of a pool's first snapshot after a timestamp.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// * `timestamp` - Only snapshots after this time
+/// 
+/// # Returns
+/// * `Result<Option<i64>>` - The timestamp, or `None` if there is no later snapshot
+pub fn next_snapshot_at(conn: &Connection, pool_id: &str, timestamp: i64) -> Result<Option<i64>> {
+    query_log::query_row(
+        conn,
+        "next_snapshot_at",
+        "SELECT MIN(timestamp) FROM pool_snapshots WHERE pool_id = ?1 AND timestamp > ?2",
+        params![pool_id, timestamp],
+        |row| row.get(0),
+    )
+}
+
+/// Amounts of token A and token B a swap moved, see `SWAP_VOLUME_A`.
+fn token_volumes(direction: &str, amount_in: f64, amount_out: f64) -> (f64, f64) {
+    match direction {
+        "a_to_b" => (amount_in, amount_out),
+        "b_to_a" => (amount_out, amount_in),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// Records the direction a swap was worked out to trade in, and whether its
+/// reserves were inconsistent with the pool's snapshots.
+/// 
+/// A swap whose direction changed loses its notional value, which was taken
+/// from the token it was thought to sell, to be valued again, and its volume
+/// is moved in `pool_stats` from the tokens it was thought to trade.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `id` - Row ID of the swap
+/// * `direction` - `a_to_b`, `b_to_a` or `unknown`
+/// * `inconsistent` - Whether the reserves before the swap match no snapshot
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_swap_check(conn: &Connection, id: i64, direction: &str, inconsistent: bool) -> Result<()> {
+    let previous: Option<(String, i64, f64, f64, String)> = query_log::query_opt(
+        conn,
+        "set_swap_check:swap",
+        "SELECT pool_id, timestamp, amount_in, amount_out, direction FROM swaps WHERE id = ?1",
+        params![id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+    )?;
+    if let Some((pool_id, timestamp, amount_in, amount_out, previous_direction)) = previous {
+        let (old_a, old_b) = token_volumes(&previous_direction, amount_in, amount_out);
+        let (new_a, new_b) = token_volumes(direction, amount_in, amount_out);
+        if (old_a, old_b) != (new_a, new_b) {
+            query_log::execute(
+                conn,
+                "set_swap_check:pool_stats",
+                "UPDATE pool_stats SET volume_a = volume_a + ?3, volume_b = volume_b + ?4
+                 WHERE pool_id = ?1 AND bucket_start = ?2",
+                params![
+                    pool_id,
+                    timestamp - timestamp.rem_euclid(POOL_STATS_BUCKET_MS),
+                    new_a - old_a,
+                    new_b - old_b
+                ],
+            )?;
+        }
+    }
+    query_log::execute(
+        conn,
+        "set_swap_check",
+        "UPDATE swaps SET
+            value_quote = CASE WHEN direction = ?2 THEN value_quote END,
+            value_usd = CASE WHEN direction = ?2 THEN value_usd END,
+            direction = ?2,
+            inconsistent = ?3
+         WHERE id = ?1",
+        params![id, direction, inconsistent],
+    )?;
+    Ok(())
+}
+
+/// Lists swaps flagged as inconsistent, in ID order.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `after_id` - Only swaps with a larger row ID
+/// * `limit` - Maximum number of swaps returned
+/// 
+/// # Returns
+/// * `Result<Vec<(i64, String)>>` - `(id, pool_id)` of each swap
+pub fn inconsistent_swaps(conn: &Connection, after_id: i64, limit: usize) -> Result<Vec<(i64, String)>> {
+    query_log::query_all(
+        conn,
+        "inconsistent_swaps",
+        "SELECT id, pool_id FROM swaps WHERE inconsistent = 1 AND id > ?1 ORDER BY id LIMIT ?2",
+        params![after_id, limit as i64],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+}
+
+/// Finds the last indexed swap of a pool, in `(timestamp, id)` order.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// 
+/// # Returns
+/// * `Result<Option<(i64, String, i64)>>` - `(id, tx_digest, timestamp)`, or `None` if the pool has no swaps
+pub fn last_pool_swap(conn: &Connection, pool_id: &str) -> Result<Option<(i64, String, i64)>> {
+    query_log::query_opt(
+        conn,
+        "last_pool_swap",
+        "SELECT id, tx_digest, timestamp FROM swaps WHERE pool_id = ?1 ORDER BY timestamp DESC, id DESC LIMIT 1",
+        params![pool_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )
+}
+
+/// Records a pool's reserves at a point in time.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// * `reserve_a` - Reserve of token A after the change
+/// * `reserve_b` - Reserve of token B after the change
+/// * `timestamp` - Timestamp of the change
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn insert_pool_snapshot(
+    conn: &Connection,
+    pool_id: &str,
+    reserve_a: f64,
+    reserve_b: f64,
+    timestamp: i64,
+) -> Result<()> {
+    query_log::execute(
+        conn,
+        "insert_pool_snapshot",
+        r#"
+        INSERT OR IGNORE INTO pool_snapshots (pool_id, reserve_a, reserve_b, timestamp)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+        params![pool_id, reserve_a, reserve_b, timestamp],
+    )?;
+    Ok(())
+}
+
+/// Records that a pool's reserves were replaced by its on-chain state.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool that was reconciled
+/// * `reserve_a`, `reserve_b` - On-chain reserves stored for the pool
+/// * `swap_id`, `timestamp` - Row ID and timestamp of the swap the reserves were read after
+/// * `now` - Timestamp recorded as `reconciled_at`
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn insert_pool_reconciliation(
+    conn: &Connection,
+    pool_id: &str,
+    reserve_a: f64,
+    reserve_b: f64,
+    swap_id: i64,
+    timestamp: i64,
+    now: i64,
+) -> Result<()> {
+    query_log::execute(
+        conn,
+        "insert_pool_reconciliation",
+        "INSERT INTO pool_reconciliations (pool_id, reserve_a, reserve_b, swap_id, timestamp, reconciled_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![pool_id, reserve_a, reserve_b, swap_id, timestamp, now],
+    )?;
+    Ok(())
+}
+
+/// Inserts a failed transaction record if it doesn't already exist.
+/// 
+/// Like `insert_swap`, this relies on the UNIQUE `tx_digest` constraint and
+/// `INSERT OR IGNORE` so the same failure can be observed repeatedly.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool object the failed transaction touched
+/// * `error` - Execution error from the transaction effects
+/// * `timestamp` - Transaction timestamp
+/// * `tx_digest` - Unique transaction digest for deduplication
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn insert_failed_swap(
+    conn: &Connection,
+    pool_id: &str,
+    error: &str,
+    timestamp: i64,
+    tx_digest: &str,
+) -> Result<()> {
+    query_log::execute(
+        conn,
+        "insert_failed_swap",
+        r#"
+        INSERT OR IGNORE INTO failed_swaps (pool_id, error, timestamp, tx_digest)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+        params![pool_id, error, timestamp, tx_digest],
+    )?;
+    Ok(())
+}
+
+/// Loads the digest of the last transaction scanned for failures on a pool.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool whose transactions are scanned
+/// 
+/// # Returns
+/// * `Result<Option<String>>` - The digest, or `None` if the pool was never scanned
+pub fn load_failed_swap_cursor(conn: &Connection, pool_id: &str) -> Result<Option<String>> {
+    query_log::query_opt(
+        conn,
+        "load_failed_swap_cursor",
+        "SELECT tx_digest FROM failed_swap_cursors WHERE pool_id = ?1",
+        params![pool_id],
+        |row| row.get(0),
+    )
+}
+
+/// Saves the digest of the last transaction scanned for failures on a pool.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool whose transactions were scanned
+/// * `tx_digest` - Digest of the last transaction scanned
+/// * `now` - Timestamp recorded as `updated_at`
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn save_failed_swap_cursor(conn: &Connection, pool_id: &str, tx_digest: &str, now: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "save_failed_swap_cursor",
+        "INSERT INTO failed_swap_cursors (pool_id, tx_digest, updated_at)
+         VALUES (?1, ?2, ?3)
+         ON CONFLICT(pool_id) DO UPDATE SET
+             tx_digest = excluded.tx_digest,
+             updated_at = excluded.updated_at",
+        params![pool_id, tx_digest, now],
+    )?;
+    Ok(())
+}
+
+/// Returns the IDs of all indexed pools.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<Vec<String>>` - Pool IDs or error
+pub fn list_pool_ids(conn: &Connection) -> Result<Vec<String>> {
+    query_log::query_all(conn, "list_pool_ids", "SELECT pool_id FROM pools", params![], |row| row.get(0))
+}
+
+/// Result of checking an ingested batch against the ingest log
+#[derive(Debug, PartialEq)]
+pub enum IngestCheck {
+    /// The batch had not been ingested before and was recorded
+    New,
+    /// The batch was replayed and produced identical derived rows
+    Verified,
+    /// The batch was replayed but its derived rows differ from the first run
+    Mismatch,
+}
+
+/// Hashes the rows derived from a batch of events.
+/// 
+/// Covers the swap rows keyed by the batch's swap event IDs and the
+/// immutable fields of the pools it created. Reserves are deliberately left
+/// out because later batches legitimately change them.
+fn derived_state_hash(conn: &Connection, swap_events: &[(String, i64)], created_pools: &[String]) -> Result<String> {
+    let mut hasher = Sha256::new();
+
+    let mut events = swap_events.to_vec();
+    events.sort();
+    events.dedup();
+    let mut swap_stmt = conn.prepare(
+        "SELECT pool_id, amount_in, amount_out, timestamp FROM swaps WHERE tx_digest = ?1 AND event_seq = ?2",
+    )?;
+    for (digest, event_seq) in &events {
+        let row = swap_stmt
+            .query_row(params![digest, event_seq], |row| {
+                Ok(format!(
+                    "swap|{}|{}|{}|{}|{}\n",
+                    digest,
+                    row.get::<_, String>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, i64>(3)?
+                ))
+            })
+            .optional()?;
+        if let Some(line) = row {
+            hasher.update(line.as_bytes());
+        }
+    }
+
+    let mut pools = created_pools.to_vec();
+    pools.sort();
+    pools.dedup();
+    let mut pool_stmt = conn.prepare("SELECT token_a, token_b FROM pools WHERE pool_id = ?1")?;
+    for pool_id in &pools {
+        let row = pool_stmt
+            .query_row([pool_id], |row| {
+                Ok(format!(
+                    "pool|{}|{}|{}\n",
+                    pool_id,
+                    row.get::<_, String>(0)?,
+                    row.get::<_, String>(1)?
+                ))
+            })
+            .optional()?;
+        if let Some(line) = row {
+            hasher.update(line.as_bytes());
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Records a processed event batch in the ingest log, or verifies it if the
+/// same batch was ingested before.
+/// 
+/// A batch is identified by the hash of its sorted event IDs. On first sight
+/// its event count and derived-state hash are stored; on a replay (e.g. a
+/// re-index) the derived state is hashed again and compared, so operators can
+/// confirm that reprocessing did not change any numbers.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `event_ids` - Unique IDs (`txDigest:eventSeq`) of the events in the batch
+/// * `swap_events` - `(tx_digest, event_seq)` of the batch's swap events
+/// * `created_pools` - Pool IDs created by the batch
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<IngestCheck>` - Whether the batch was new, verified, or mismatched
+pub fn record_ingest_batch(
+    conn: &Connection,
+    event_ids: &[String],
+    swap_events: &[(String, i64)],
+    created_pools: &[String],
+    now: i64,
+) -> Result<IngestCheck> {
+    let mut ids = event_ids.to_vec();
+    ids.sort();
+    let batch_hash = format!("{:x}", Sha256::digest(ids.join("\n").as_bytes()));
+    let state_hash = derived_state_hash(conn, swap_events, created_pools)?;
+
+    let existing = query_log::query_opt(
+        conn,
+        "record_ingest_batch:lookup",
+        "SELECT event_count, state_hash FROM ingest_log WHERE batch_hash = ?1",
+        params![batch_hash],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)),
+    )?;
+
+    match existing {
+        None => {
+            query_log::execute(
+                conn,
+                "record_ingest_batch:insert",
+                r#"
+                INSERT INTO ingest_log (batch_hash, event_count, state_hash, first_seen, last_verified)
+                VALUES (?1, ?2, ?3, ?4, ?4)
+                "#,
+                params![batch_hash, ids.len() as i64, state_hash, now],
+            )?;
+            Ok(IngestCheck::New)
+        }
+        Some((count, stored_hash)) => {
+            let matches = count == ids.len() as i64 && stored_hash == state_hash;
+            query_log::execute(
+                conn,
+                "record_ingest_batch:verify",
+                "UPDATE ingest_log SET last_verified = ?2, mismatch = MAX(mismatch, ?3) WHERE batch_hash = ?1",
+                params![batch_hash, now, !matches as i64],
+            )?;
+            Ok(if matches { IngestCheck::Verified } else { IngestCheck::Mismatch })
+        }
+    }
+}
+
+/// On-disk size of the database
+pub struct DbSize {
+    /// Total size of the database file in bytes
+    pub total_bytes: i64,
+    /// Bytes held by free pages that compaction could reclaim
+    pub free_bytes: i64,
+    /// Whether `PRAGMA incremental_vacuum` can reclaim space without a full VACUUM
+    pub incremental: bool,
+}
+
+/// Reports the database's total and reclaimable size.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<DbSize>` - Size breakdown or error
+pub fn db_size(conn: &Connection) -> Result<DbSize> {
+    let pragma = |name: &str| conn.query_row(&format!("PRAGMA {}", name), [], |row| row.get::<_, i64>(0));
+    let page_size = pragma("page_size")?;
+    Ok(DbSize {
+        total_bytes: pragma("page_count")? * page_size,
+        free_bytes: pragma("freelist_count")? * page_size,
+        // 2 = INCREMENTAL
+        incremental: pragma("auto_vacuum")? == 2,
+    })
+}
+
+/// Width of the `db_growth` buckets (in milliseconds)
+const GROWTH_SAMPLE_DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Name the whole database file is sampled under in `db_growth`
+pub const DATABASE_GROWTH_KEY: &str = "*";
+
+/// Size of a table at one point in time, as sampled for the growth forecast
+#[derive(Serialize, Clone)]
+pub struct GrowthSample {
+    /// Table name, or `DATABASE_GROWTH_KEY` for the whole database file
+    pub table_name: String,
+    pub row_count: i64,
+    /// Bytes of the pages holding the table and its indexes, or `None` if
+    /// SQLite was built without the `dbstat` virtual table
+    pub bytes: Option<i64>,
+    pub recorded_at: i64,
+}
+
+/// Bytes of the pages holding each table and its indexes, by table name.
+/// 
+/// Reads every page of the database, so it is only run once a day. Empty if
+/// SQLite was built without the `dbstat` virtual table.
+fn table_bytes(conn: &Connection) -> HashMap<String, i64> {
+    let read = || -> Result<HashMap<String, i64>> {
+        let mut stmt = conn.prepare(
+            "SELECT m.tbl_name, SUM(s.pgsize) FROM dbstat s JOIN sqlite_master m ON m.name = s.name
+             GROUP BY m.tbl_name",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect()
+    };
+    read().unwrap_or_default()
+}
+
+/// Records today's size of every table and of the database file, unless
+/// today was already sampled.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether a sample was recorded
+pub fn record_growth_sample(conn: &Connection, now: i64) -> Result<bool> {
+    let day = now - now.rem_euclid(GROWTH_SAMPLE_DAY_MS);
+    let sampled: bool = query_log::query_row(
+        conn,
+        "record_growth_sample:lookup",
+        "SELECT EXISTS(SELECT 1 FROM db_growth WHERE day = ?1)",
+        params![day],
+        |row| row.get(0),
+    )?;
+    if sampled {
+        return Ok(false);
+    }
+
+    let tables: Vec<String> = query_log::query_all(
+        conn,
+        "record_growth_sample:tables",
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+        params![],
+        |row| row.get(0),
+    )?;
+    let bytes = table_bytes(conn);
+    let size = db_size(conn)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let counts = tables
+        .iter()
+        .map(|table| tx.query_row(&format!("SELECT COUNT(*) FROM \"{}\"", table), [], |row| row.get::<_, i64>(0)))
+        .collect::<Result<Vec<_>>>()?;
+    for (table, rows) in tables.iter().zip(&counts) {
+        query_log::execute(
+            &tx,
+            "record_growth_sample",
+            "INSERT OR IGNORE INTO db_growth (day, table_name, row_count, bytes, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![day, table, rows, bytes.get(table), now],
+        )?;
+    }
+    query_log::execute(
+        &tx,
+        "record_growth_sample",
+        "INSERT OR IGNORE INTO db_growth (day, table_name, row_count, bytes, recorded_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![day, DATABASE_GROWTH_KEY, counts.iter().sum::<i64>(), size.total_bytes, now],
+    )?;
+    tx.commit()?;
+    Ok(true)
+}
+
+/// Lists growth samples recorded since a given time, by table and then in
+/// time order.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `since` - Earliest sample time to include (ms)
+/// 
+/// # Returns
+/// * `Result<Vec<GrowthSample>>` - The samples
+pub fn growth_samples(conn: &Connection, since: i64) -> Result<Vec<GrowthSample>> {
+    query_log::query_all(
+        conn,
+        "growth_samples",
+        "SELECT table_name, row_count, bytes, recorded_at FROM db_growth
+         WHERE recorded_at >= ?1
+         ORDER BY table_name, recorded_at",
+        params![since],
+        |row| {
+            Ok(GrowthSample {
+                table_name: row.get(0)?,
+                row_count: row.get(1)?,
+                bytes: row.get(2)?,
+                recorded_at: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Reclaims free pages from the database file.
+/// 
+/// An incremental vacuum only releases free pages and is cheap enough to run
+/// while serving traffic. A full `VACUUM` rewrites the whole file, blocking
+/// writers for its duration, and also switches older databases over to
+/// incremental auto-vacuum mode.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `full` - Run a full `VACUUM` instead of `PRAGMA incremental_vacuum`
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn compact(conn: &Connection, full: bool) -> Result<()> {
+    if full || !db_size(conn)?.incremental {
+        conn.execute_batch("PRAGMA auto_vacuum = INCREMENTAL; VACUUM;")
+    } else {
+        conn.execute_batch("PRAGMA incremental_vacuum;")
+    }
+}
+
+/// Records the router's recommended route for a best-price request.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `from_token` - Token being sold
+/// * `to_token` - Token being bought
+/// * `route` - Pool IDs in hop order
+/// * `amount_in` - Requested input amount
+/// * `quoted_out` - Output the router expects
+/// * `created_at` - Timestamp of the recommendation
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn insert_route_recommendation(
+    conn: &Connection,
+    from_token: &str,
+    to_token: &str,
+    route: &[String],
+    amount_in: f64,
+    quoted_out: f64,
+    created_at: i64,
+) -> Result<()> {
+    let (Some(first_pool), Some(last_pool)) = (route.first(), route.last()) else {
+        return Ok(());
+    };
+    query_log::execute(
+        conn,
+        "insert_route_recommendation",
+        r#"
+        INSERT INTO route_recommendations
+            (from_token, to_token, route, hops, first_pool, last_pool, amount_in, quoted_out, created_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![
+            from_token,
+            to_token,
+            route.join(","),
+            route.len() as i64,
+            first_pool,
+            last_pool,
+            amount_in,
+            quoted_out,
+            created_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Matches unmatched route recommendations with the swaps that followed them.
+/// 
+/// A recommendation matches the earliest swap into its first pool whose
+/// `amount_in` is within `tolerance` (relative) of the requested amount and
+/// which happened within `window_ms` after the recommendation. For multi-hop
+/// routes the realized output is taken from the swap on the last pool in the
+/// same transaction; if that leg isn't indexed the recommendation stays
+/// unmatched. Recommendations older than twice the window are no longer
+/// considered, leaving room for indexing lag.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Current timestamp in milliseconds
+/// * `window_ms` - How long after a recommendation a swap may still match
+/// * `tolerance` - Allowed relative difference between amounts in
+/// 
+/// # Returns
+/// * `Result<usize>` - Number of recommendations matched
+pub fn match_route_recommendations(
+    conn: &Connection,
+    now: i64,
+    window_ms: i64,
+    tolerance: f64,
+) -> Result<usize> {
+    let pending = query_log::query_all(
+        conn,
+        "match_route_recommendations:pending",
+        "SELECT id, hops, first_pool, last_pool, amount_in, created_at
+         FROM route_recommendations
+         WHERE matched_tx IS NULL AND created_at >= ?1",
+        params![now - 2 * window_ms],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        },
+    )?;
+
+    let mut matched = 0;
+    for (id, hops, first_pool, last_pool, amount_in, created_at) in pending {
+        let entry = query_log::query_opt(
+            conn,
+            "match_route_recommendations:first_leg",
+            "SELECT tx_digest, amount_out
+             FROM swaps
+             WHERE pool_id = ?1
+               AND ABS(amount_in - ?2) <= ?2 * ?3
+               AND timestamp >= ?4 AND timestamp <= ?5
+             ORDER BY timestamp ASC
+             LIMIT 1",
+            params![first_pool, amount_in, tolerance, created_at, created_at + window_ms],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+        )?;
+        let Some((tx_digest, first_out)) = entry else { continue };
+
+        let realized_out = if hops == 1 {
+            Some(first_out)
+        } else {
+            query_log::query_opt(
+                conn,
+                "match_route_recommendations:last_leg",
+                "SELECT amount_out FROM swaps WHERE pool_id = ?1 AND tx_digest = ?2",
+                params![last_pool, tx_digest],
+                |row| row.get::<_, f64>(0),
+            )?
+        };
+        if let Some(realized_out) = realized_out {
+            query_log::execute(
+                conn,
+                "match_route_recommendations:update",
+                "UPDATE route_recommendations SET realized_out = ?2, matched_tx = ?3 WHERE id = ?1",
+                params![id, realized_out, tx_digest],
+            )?;
+            matched += 1;
+        }
+    }
+    Ok(matched)
+}
+
+/// A quote served by `/api/quote`, as recorded for conversion tracking
+pub struct QuoteRecord<'a> {
+    pub pool_id: &'a str,
+    pub direction: &'a str,
+    pub amount_in: f64,
+    pub quoted_out: f64,
+    pub min_amount_out: f64,
+    /// Hash of the requesting API key (see `api_key_hash`)
+    pub key_hash: Option<&'a str>,
+    pub user_agent: Option<&'a str>,
+    pub created_at: i64,
+    pub expires_at: i64,
+}
+
+/// Records a served quote and returns its ID.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `quote` - The quote to record
+/// 
+/// # Returns
+/// * `Result<i64>` - ID of the new quote
+pub fn insert_quote(conn: &Connection, quote: &QuoteRecord) -> Result<i64> {
+    query_log::execute(
+        conn,
+        "insert_quote",
+        r#"
+        INSERT INTO quotes
+            (pool_id, direction, amount_in, quoted_out, min_amount_out, key_hash, user_agent, created_at, expires_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![
+            quote.pool_id,
+            quote.direction,
+            quote.amount_in,
+            quote.quoted_out,
+            quote.min_amount_out,
+            quote.key_hash,
+            quote.user_agent,
+            quote.created_at,
+            quote.expires_at
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Matches unmatched quotes with the swaps that followed them.
+/// 
+/// A quote matches the earliest swap on its pool in the same direction
+/// whose `amount_in` is within `tolerance` (relative) of the quoted amount
+/// and which happened between the quote and its expiry. A swap converts at
+/// most one quote: newer quotes are matched first, so a client re-quoting
+/// before swapping credits the quote it acted on. Quotes that expired more
+/// than `grace_ms` ago are no longer considered, leaving room for indexing
+/// lag.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Current timestamp in milliseconds
+/// * `grace_ms` - How long after expiry a quote is still matched
+/// * `tolerance` - Allowed relative difference between amounts in
+/// 
+/// # Returns
+/// * `Result<usize>` - Number of quotes matched
+pub fn match_quotes(conn: &Connection, now: i64, grace_ms: i64, tolerance: f64) -> Result<usize> {
+    let pending = query_log::query_all(
+        conn,
+        "match_quotes:pending",
+        "SELECT id, pool_id, direction, amount_in, created_at, expires_at
+         FROM quotes
+         WHERE matched_tx IS NULL AND expires_at >= ?1
+         ORDER BY created_at DESC, id DESC",
+        params![now - grace_ms],
+        |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
+            ))
+        },
+    )?;
+
+    let mut matched = 0;
+    for (id, pool_id, direction, amount_in, created_at, expires_at) in pending {
+        let swap = query_log::query_opt(
+            conn,
+            "match_quotes:swap",
+            "SELECT s.tx_digest, s.amount_out
+             FROM swaps s
+             WHERE s.pool_id = ?1
+               AND s.direction = ?2
+               AND ABS(s.amount_in - ?3) <= ?3 * ?4
+               AND s.timestamp >= ?5 AND s.timestamp <= ?6
+               AND NOT EXISTS (
+                   SELECT 1 FROM quotes q WHERE q.pool_id = s.pool_id AND q.matched_tx = s.tx_digest
+               )
+             ORDER BY s.timestamp ASC, s.id ASC
+             LIMIT 1",
+            params![pool_id, direction, amount_in, tolerance, created_at, expires_at],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+        )?;
+        if let Some((tx_digest, realized_out)) = swap {
+            query_log::execute(
+                conn,
+                "match_quotes:update",
+                "UPDATE quotes SET realized_out = ?2, matched_tx = ?3 WHERE id = ?1",
+                params![id, realized_out, tx_digest],
+            )?;
+            matched += 1;
+        }
+    }
+    Ok(matched)
+}
+
+/// Checks whether a swap event has already been indexed.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `tx_digest` - Transaction digest of the swap
+/// * `event_seq` - Index of the swap event within its transaction
+/// 
+/// # Returns
+/// * `Result<bool>` - `true` if the swap event is stored
+pub fn swap_exists(conn: &Connection, tx_digest: &str, event_seq: i64) -> Result<bool> {
+    query_log::query_row(
+        conn,
+        "swap_exists",
+        "SELECT EXISTS(SELECT 1 FROM swaps WHERE tx_digest = ?1 AND event_seq = ?2)",
+        params![tx_digest, event_seq],
+        |row| row.get(0),
+    )
+}
+
+/// Checks whether a pool has already been indexed.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Identifier of the pool
+/// 
+/// # Returns
+/// * `Result<bool>` - `true` if the pool is stored
+pub fn pool_exists(conn: &Connection, pool_id: &str) -> Result<bool> {
+    query_log::query_row(
+        conn,
+        "pool_exists",
+        "SELECT EXISTS(SELECT 1 FROM pools WHERE pool_id = ?1)",
+        params![pool_id],
+        |row| row.get(0),
+    )
+}
+
+/// Whether a query failed because another connection held a lock on the
+/// database for longer than the busy timeout.
+pub fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error,
+        rusqlite::Error::SqliteFailure(e, _)
+            if matches!(e.code, rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Hashes an API key for storage in the `usage` table.
+/// 
+/// # Arguments
+/// * `api_key` - Raw API key as sent by the client
+/// 
+/// # Returns
+/// * `String` - Hex-encoded SHA-256 of the key
+pub fn api_key_hash(api_key: &str) -> String {
+    format!("{:x}", Sha256::digest(api_key.as_bytes()))
+}
+
+/// Adds one request to an API key's daily usage for an endpoint.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `key_hash` - Hash of the API key (see `api_key_hash`)
+/// * `endpoint` - Route template that served the request
+/// * `bytes_out` - Size of the response body
+/// * `now` - Current timestamp, used to pick the day bucket
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn record_usage(conn: &Connection, key_hash: &str, endpoint: &str, bytes_out: u64, now: i64) -> Result<()> {
+    let day = now - now.rem_euclid(86_400_000);
+    query_log::execute(
+        conn,
+        "record_usage",
+        r#"
+        INSERT INTO usage (key_hash, day, endpoint, requests, bytes_out)
+        VALUES (?1, ?2, ?3, 1, ?4)
+        ON CONFLICT(key_hash, day, endpoint) DO UPDATE SET
+            requests = usage.requests + 1,
+            bytes_out = usage.bytes_out + excluded.bytes_out
+        "#,
+        params![key_hash, day, endpoint, bytes_out as i64],
+    )?;
+    Ok(())
+}
+
+/// Loads the feature flag overrides set by admins.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<HashMap<String, bool>>` - Enabled state per overridden feature
+pub fn list_feature_overrides(conn: &Connection) -> Result<HashMap<String, bool>> {
+    let rows = query_log::query_all(
+        conn,
+        "list_feature_overrides",
+        "SELECT name, enabled FROM feature_flags",
+        params![],
+        |row| Ok((row.get::<_, String>(0)?, row.get::<_, bool>(1)?)),
+    )?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Persists an admin override for a feature flag.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Feature name
+/// * `enabled` - Overridden state
+/// * `now` - Timestamp of the change
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_feature_override(conn: &Connection, name: &str, enabled: bool, now: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "set_feature_override",
+        "INSERT INTO feature_flags (name, enabled, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(name) DO UPDATE SET enabled = excluded.enabled, updated_at = excluded.updated_at",
+        params![name, enabled, now],
+    )?;
+    Ok(())
+}
+
+/// Removes an admin override, returning the feature to its configured state.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Feature name
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn clear_feature_override(conn: &Connection, name: &str) -> Result<()> {
+    query_log::execute(conn, "clear_feature_override", "DELETE FROM feature_flags WHERE name = ?1", params![name])?;
+    Ok(())
+}
+
+/// Display metadata an admin has set for a pool
+#[derive(Serialize)]
+pub struct PoolOverride {
+    pub pool_id: String,
+    pub display_name: Option<String>,
+    pub logo_url: Option<String>,
+    pub verified: bool,
+    /// Warning shown to users before they trade in the pool
+    pub warning: Option<String>,
+    pub updated_at: i64,
+}
+
+/// Loads every pool metadata override, ordered by pool ID.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<Vec<PoolOverride>>` - The overrides or error
+pub fn list_pool_overrides(conn: &Connection) -> Result<Vec<PoolOverride>> {
+    query_log::query_all(
+        conn,
+        "list_pool_overrides",
+        "SELECT pool_id, display_name, logo_url, verified, warning, updated_at
+         FROM pool_overrides
+         ORDER BY pool_id",
+        params![],
+        |row| {
+            Ok(PoolOverride {
+                pool_id: row.get(0)?,
+                display_name: row.get(1)?,
+                logo_url: row.get(2)?,
+                verified: row.get(3)?,
+                warning: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        },
+    )
+}
+
+/// Creates or replaces the metadata override for a pool.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `entry` - The override to store
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_pool_override(conn: &Connection, entry: &PoolOverride) -> Result<()> {
+    query_log::execute(
+        conn,
+        "set_pool_override",
+        "INSERT INTO pool_overrides (pool_id, display_name, logo_url, verified, warning, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(pool_id) DO UPDATE SET
+             display_name = excluded.display_name,
+             logo_url = excluded.logo_url,
+             verified = excluded.verified,
+             warning = excluded.warning,
+             updated_at = excluded.updated_at",
+        params![
+            entry.pool_id,
+            entry.display_name,
+            entry.logo_url,
+            entry.verified,
+            entry.warning,
+            entry.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+/// Marks a pool verified or unverified, keeping the rest of its override.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool to mark
+/// * `verified` - New verification status
+/// * `now` - Timestamp of the change
+/// 
+/// # Returns
+/// * `Result<PoolOverride>` - The pool's override after the change
+pub fn set_pool_verified(conn: &Connection, pool_id: &str, verified: bool, now: i64) -> Result<PoolOverride> {
+    query_log::execute(
+        conn,
+        "set_pool_verified:write",
+        "INSERT INTO pool_overrides (pool_id, verified, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(pool_id) DO UPDATE SET verified = excluded.verified, updated_at = excluded.updated_at",
+        params![pool_id, verified, now],
+    )?;
+    query_log::query_row(
+        conn,
+        "set_pool_verified:read",
+        "SELECT pool_id, display_name, logo_url, verified, warning, updated_at
+         FROM pool_overrides WHERE pool_id = ?1",
+        params![pool_id],
+        |row| {
+            Ok(PoolOverride {
+                pool_id: row.get(0)?,
+                display_name: row.get(1)?,
+                logo_url: row.get(2)?,
+                verified: row.get(3)?,
+                warning: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        },
+    )
+}
+
+/// Removes the metadata override for a pool.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_id` - Pool whose override is removed
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether an override existed
+pub fn clear_pool_override(conn: &Connection, pool_id: &str) -> Result<bool> {
+    let deleted = query_log::execute(
+        conn,
+        "clear_pool_override",
+        "DELETE FROM pool_overrides WHERE pool_id = ?1",
+        params![pool_id],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// A label an admin has imported for an address
+#[derive(Serialize)]
+pub struct AddressLabel {
+    pub address: String,
+    pub label: String,
+    /// Kind of address, e.g. `market_maker`, `team` or `bot`
+    pub category: Option<String>,
+    pub updated_at: i64,
+}
+
+/// Loads every address label, ordered by address.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<Vec<AddressLabel>>` - The labels or error
+pub fn list_labels(conn: &Connection) -> Result<Vec<AddressLabel>> {
+    query_log::query_all(
+        conn,
+        "list_labels",
+        "SELECT address, label, category, updated_at FROM labels ORDER BY address",
+        params![],
+        |row| {
+            Ok(AddressLabel {
+                address: row.get(0)?,
+                label: row.get(1)?,
+                category: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Creates or replaces the labels of a batch of addresses, all or none.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `labels` - The labels to store
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn import_labels(conn: &Connection, labels: &[AddressLabel]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    for entry in labels {
+        query_log::execute(
+            &tx,
+            "import_labels",
+            "INSERT INTO labels (address, label, category, updated_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(address) DO UPDATE SET
+                 label = excluded.label,
+                 category = excluded.category,
+                 updated_at = excluded.updated_at",
+            params![entry.address, entry.label, entry.category, entry.updated_at],
+        )?;
+    }
+    tx.commit()
+}
+
+/// Removes the label of an address.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `address` - Lowercase address whose label is removed
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether the address had a label
+pub fn delete_label(conn: &Connection, address: &str) -> Result<bool> {
+    let deleted = query_log::execute(conn, "delete_label", "DELETE FROM labels WHERE address = ?1", params![address])?;
+    Ok(deleted > 0)
+}
+
+/// A short name an admin has given a token pair
+#[derive(Serialize)]
+pub struct PairAlias {
+    pub alias: String,
+    pub token_a: String,
+    pub token_b: String,
+    pub updated_at: i64,
+}
+
+/// Builds a `PairAlias` from a row of `alias, token_a, token_b, updated_at`.
+fn pair_alias_from_row(row: &rusqlite::Row) -> Result<PairAlias> {
+    Ok(PairAlias {
+        alias: row.get(0)?,
+        token_a: row.get(1)?,
+        token_b: row.get(2)?,
+        updated_at: row.get(3)?,
+    })
+}
+
+/// Loads every pair alias, ordered by alias.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<Vec<PairAlias>>` - The aliases or error
+pub fn list_pair_aliases(conn: &Connection) -> Result<Vec<PairAlias>> {
+    query_log::query_all(
+        conn,
+        "list_pair_aliases",
+        "SELECT alias, token_a, token_b, updated_at FROM pair_aliases ORDER BY alias",
+        params![],
+        pair_alias_from_row,
+    )
+}
+
+/// Looks up a pair alias, ignoring case.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `alias` - Alias to resolve
+/// 
+/// # Returns
+/// * `Result<Option<PairAlias>>` - The alias, `None` if it isn't registered
+pub fn find_pair_alias(conn: &Connection, alias: &str) -> Result<Option<PairAlias>> {
+    query_log::query_opt(
+        conn,
+        "find_pair_alias",
+        "SELECT alias, token_a, token_b, updated_at FROM pair_aliases WHERE alias = ?1",
+        params![alias],
+        pair_alias_from_row,
+    )
+}
+
+/// Finds the alias registered for a token pair, in exactly that order.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `token_a` - First token of the pair
+/// * `token_b` - Second token of the pair
+/// 
+/// # Returns
+/// * `Result<Option<String>>` - The alias (the first by name if several name the pair), or `None`
+pub fn alias_for_pair(conn: &Connection, token_a: &str, token_b: &str) -> Result<Option<String>> {
+    query_log::query_opt(
+        conn,
+        "alias_for_pair",
+        "SELECT alias FROM pair_aliases WHERE token_a = ?1 AND token_b = ?2 ORDER BY alias LIMIT 1",
+        params![token_a, token_b],
+        |row| row.get(0),
+    )
+}
+
+/// Creates or replaces a pair alias.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `entry` - The alias to store
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn set_pair_alias(conn: &Connection, entry: &PairAlias) -> Result<()> {
+    query_log::execute(
+        conn,
+        "set_pair_alias",
+        "INSERT INTO pair_aliases (alias, token_a, token_b, updated_at) VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(alias) DO UPDATE SET
+             alias = excluded.alias,
+             token_a = excluded.token_a,
+             token_b = excluded.token_b,
+             updated_at = excluded.updated_at",
+        params![entry.alias, entry.token_a, entry.token_b, entry.updated_at],
+    )?;
+    Ok(())
+}
+
+/// Removes a pair alias, ignoring case.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `alias` - Alias to remove
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether the alias existed
+pub fn delete_pair_alias(conn: &Connection, alias: &str) -> Result<bool> {
+    let deleted =
+        query_log::execute(conn, "delete_pair_alias", "DELETE FROM pair_aliases WHERE alias = ?1", params![alias])?;
+    Ok(deleted > 0)
+}
+
+/// Loads the addresses registered for an API key, ordered by address.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `key_hash` - Hash of the API key (see `api_key_hash`)
+/// 
+/// # Returns
+/// * `Result<Vec<String>>` - The addresses or error
+pub fn api_key_addresses(conn: &Connection, key_hash: &str) -> Result<Vec<String>> {
+    query_log::query_all(
+        conn,
+        "api_key_addresses",
+        "SELECT address FROM api_key_addresses WHERE key_hash = ?1 ORDER BY address",
+        params![key_hash],
+        |row| row.get(0),
+    )
+}
+
+/// Registers an address for an API key; registering it again changes nothing.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `key_hash` - Hash of the API key (see `api_key_hash`)
+/// * `address` - Lowercase Sui address
+/// * `created_at` - Current time (ms since epoch)
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn add_api_key_address(conn: &Connection, key_hash: &str, address: &str, created_at: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "add_api_key_address",
+        "INSERT OR IGNORE INTO api_key_addresses (key_hash, address, created_at) VALUES (?1, ?2, ?3)",
+        params![key_hash, address, created_at],
+    )?;
+    Ok(())
+}
+
+/// Removes an address from an API key.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `key_hash` - Hash of the API key (see `api_key_hash`)
+/// * `address` - Lowercase Sui address
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether the address was registered for the key
+pub fn remove_api_key_address(conn: &Connection, key_hash: &str, address: &str) -> Result<bool> {
+    let deleted = query_log::execute(
+        conn,
+        "remove_api_key_address",
+        "DELETE FROM api_key_addresses WHERE key_hash = ?1 AND address = ?2",
+        params![key_hash, address],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// A metric expression registered by an admin
+#[derive(Serialize, Clone)]
+pub struct CustomMetric {
+    pub name: String,
+    pub expression: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Loads every registered custom metric, ordered by name.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<Vec<CustomMetric>>` - The metrics or error
+pub fn list_custom_metrics(conn: &Connection) -> Result<Vec<CustomMetric>> {
+    query_log::query_all(
+        conn,
+        "list_custom_metrics",
+        "SELECT name, expression, created_at, updated_at FROM custom_metrics ORDER BY name",
+        params![],
+        |row| {
+            Ok(CustomMetric {
+                name: row.get(0)?,
+                expression: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Loads a custom metric by name.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Name of the metric
+/// 
+/// # Returns
+/// * `Result<Option<CustomMetric>>` - The metric, or `None` if it isn't registered
+pub fn custom_metric(conn: &Connection, name: &str) -> Result<Option<CustomMetric>> {
+    query_log::query_opt(
+        conn,
+        "custom_metric",
+        "SELECT name, expression, created_at, updated_at FROM custom_metrics WHERE name = ?1",
+        params![name],
+        |row| {
+            Ok(CustomMetric {
+                name: row.get(0)?,
+                expression: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Registers a custom metric, or replaces the expression of an existing one.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Name of the metric
+/// * `expression` - Validated metric expression
+/// * `now` - Timestamp of the change
+/// 
+/// # Returns
+/// * `Result<CustomMetric>` - The metric after the change
+pub fn set_custom_metric(conn: &Connection, name: &str, expression: &str, now: i64) -> Result<CustomMetric> {
+    query_log::execute(
+        conn,
+        "set_custom_metric:write",
+        "INSERT INTO custom_metrics (name, expression, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)
+         ON CONFLICT(name) DO UPDATE SET expression = excluded.expression, updated_at = excluded.updated_at",
+        params![name, expression, now],
+    )?;
+    query_log::query_row(
+        conn,
+        "set_custom_metric:read",
+        "SELECT name, expression, created_at, updated_at FROM custom_metrics WHERE name = ?1",
+        params![name],
+        |row| {
+            Ok(CustomMetric {
+                name: row.get(0)?,
+                expression: row.get(1)?,
+                created_at: row.get(2)?,
+                updated_at: row.get(3)?,
+            })
+        },
+    )
+}
+
+/// Removes a custom metric and its computed values.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Name of the metric
+/// 
+/// # Returns
+/// * `Result<bool>` - Whether the metric existed
+pub fn clear_custom_metric(conn: &Connection, name: &str) -> Result<bool> {
+    query_log::execute(
+        conn,
+        "clear_custom_metric:values",
+        "DELETE FROM custom_metric_values WHERE name = ?1",
+        params![name],
+    )?;
+    let deleted = query_log::execute(
+        conn,
+        "clear_custom_metric",
+        "DELETE FROM custom_metrics WHERE name = ?1",
+        params![name],
+    )?;
+    Ok(deleted > 0)
+}
+
+/// Replaces the computed values of a custom metric.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `name` - Name of the metric
+/// * `values` - `(pool_id, value)` of every pool
+/// * `now` - Timestamp of the computation
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn save_custom_metric_values(conn: &Connection, name: &str, values: &[(String, Option<f64>)], now: i64) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    query_log::execute(
+        &tx,
+        "save_custom_metric_values:clear",
+        "DELETE FROM custom_metric_values WHERE name = ?1",
+        params![name],
+    )?;
+    for (pool_id, value) in values {
+        query_log::execute(
+            &tx,
+            "save_custom_metric_values:insert",
+            "INSERT INTO custom_metric_values (name, pool_id, value, computed_at) VALUES (?1, ?2, ?3, ?4)",
+            params![name, pool_id, value, now],
+        )?;
+    }
+    tx.commit()
+}
+
+/// How far the indexer got, persisted across restarts
+pub struct IndexerState {
+    /// Timestamp of `last_checkpoint` (ms since epoch)
+    pub last_ts: i64,
+    /// Checkpoint the last completed polling cycle reached
+    pub last_checkpoint: u64,
+}
+
+/// Loads the indexer progress saved for a DEX package.
+/// 
+/// Progress saved for a different package is ignored.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `package_id` - DEX package being indexed
+/// 
+/// # Returns
+/// * `Result<Option<IndexerState>>` - The saved progress, or `None` if there is none for this package
+pub fn load_indexer_state(conn: &Connection, package_id: &str) -> Result<Option<IndexerState>> {
+    query_log::query_opt(
+        conn,
+        "load_indexer_state",
+        "SELECT last_ts, last_checkpoint FROM indexer_state WHERE id = 1 AND package_id = ?1",
+        params![package_id],
+        |row| Ok(IndexerState { last_ts: row.get(0)?, last_checkpoint: row.get::<_, i64>(1)? as u64 }),
+    )
+}
+
+/// Saves the indexer progress after a polling cycle has been fully processed.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `package_id` - DEX package being indexed
+/// * `state` - Progress reached
+/// * `now` - Timestamp recorded as `updated_at`
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn save_indexer_state(conn: &Connection, package_id: &str, state: &IndexerState, now: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "save_indexer_state",
+        "INSERT INTO indexer_state (id, package_id, last_ts, last_checkpoint, updated_at)
+         VALUES (1, ?1, ?2, ?3, ?4)
+         ON CONFLICT(id) DO UPDATE SET
+             package_id = excluded.package_id,
+             last_ts = excluded.last_ts,
+             last_checkpoint = excluded.last_checkpoint,
+             updated_at = excluded.updated_at",
+        params![package_id, state.last_ts, state.last_checkpoint as i64, now],
+    )?;
+    Ok(())
+}
+
+/// Position in the Sui event stream of one Move event type: the ID of the
+/// last committed event, which `suix_queryEvents` takes as its cursor
+pub struct EventCursor {
+    pub tx_digest: String,
+    pub event_seq: String,
+    /// Timestamp of the event, `None` for cursors saved before it was recorded
+    pub timestamp_ms: Option<i64>,
+}
+
+/// Loads the saved event cursor for a Move event type.
+/// 
+/// Event types include the DEX package, so a new package starts from the
+/// beginning of its event stream.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `event_type` - Fully qualified Move event type
+/// 
+/// # Returns
+/// * `Result<Option<EventCursor>>` - The cursor, or `None` if no event of this type was committed yet
+pub fn load_event_cursor(conn: &Connection, event_type: &str) -> Result<Option<EventCursor>> {
+    query_log::query_opt(
+        conn,
+        "load_event_cursor",
+        "SELECT tx_digest, event_seq, event_ts FROM indexer_cursors WHERE event_type = ?1",
+        params![event_type],
+        |row| Ok(EventCursor { tx_digest: row.get(0)?, event_seq: row.get(1)?, timestamp_ms: row.get(2)? }),
+    )
+}
+
+/// Saves the event cursor for a Move event type after a page was committed.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `event_type` - Fully qualified Move event type
+/// * `cursor` - ID of the last committed event of this type
+/// * `now` - Timestamp recorded as `updated_at`
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn save_event_cursor(conn: &Connection, event_type: &str, cursor: &EventCursor, now: i64) -> Result<()> {
+    query_log::execute(
+        conn,
+        "save_event_cursor",
+        "INSERT INTO indexer_cursors (event_type, tx_digest, event_seq, event_ts, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)
+         ON CONFLICT(event_type) DO UPDATE SET
+             tx_digest = excluded.tx_digest,
+             event_seq = excluded.event_seq,
+             event_ts = excluded.event_ts,
+             updated_at = excluded.updated_at",
+        params![event_type, cursor.tx_digest, cursor.event_seq, cursor.timestamp_ms, now],
     )?;
     Ok(())
 }
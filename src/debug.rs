@@ -0,0 +1,118 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+use serde_json::json;
+use std::cell::RefCell;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::freshness::{indexer_lag_secs, never_synced, stale_after_secs};
+use crate::metrics::INDEXER_LAST_SYNC_MS;
+use crate::response_cache::CACHE_STATUS_HEADER;
+use crate::routes::authorize_admin;
+
+/// Largest response body the middleware will buffer to add the `_debug` field
+const MAX_DEBUG_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// A database call made while answering a debugged request
+#[derive(Serialize)]
+pub struct QueryTrace {
+    /// Name the query is counted under in `fooswap_db_queries_*`
+    pub name: &'static str,
+    /// The statement, with its whitespace collapsed
+    pub sql: String,
+    /// Summary of the bound parameters
+    pub params: String,
+    pub elapsed_ms: f64,
+}
+
+tokio::task_local! {
+    /// Database calls of the request being debugged on this task
+    static QUERIES: RefCell<Vec<QueryTrace>>;
+}
+
+/// Records a database call if the current task is answering a debugged
+/// request. Does nothing otherwise, so it costs nothing on regular requests.
+pub fn record_query(name: &'static str, sql: &str, params: impl FnOnce() -> String, elapsed: Duration) {
+    let _ = QUERIES.try_with(|queries| {
+        queries.borrow_mut().push(QueryTrace {
+            name,
+            sql: sql.split_whitespace().collect::<Vec<_>>().join(" "),
+            params: params(),
+            elapsed_ms: elapsed.as_secs_f64() * 1000.0,
+        });
+    });
+}
+
+/// Whether the request asks for debug output with `debug=true`.
+fn debug_requested(request: &Request) -> bool {
+    request
+        .uri()
+        .query()
+        .is_some_and(|q| q.split('&').any(|pair| pair == "debug=true"))
+}
+
+/// Middleware adding a `_debug` field to API responses requested with
+/// `?debug=true`, for diagnosing slow or stale responses.
+/// 
+/// Debug output requires the admin token; other callers get 401 (or 403
+/// while admin endpoints are disabled). The field holds the total time taken,
+/// every database call the handler made with its SQL, parameters and timing,
+/// the response cache outcome (`hit`, `miss`, `stale`, or `null` for routes
+/// that aren't cached), and the indexer's freshness. A cache hit runs no
+/// queries, so it lists none. Queries run outside the request's task, e.g.
+/// by export jobs, are not captured.
+/// 
+/// Responses that aren't JSON objects are served unchanged.
+pub async fn debug_response(request: Request, next: Next) -> Response {
+    if !debug_requested(&request) {
+        return next.run(request).await;
+    }
+    if let Err(e) = authorize_admin(request.headers()) {
+        return e.into_response();
+    }
+
+    let started = Instant::now();
+    let (response, queries) = QUERIES
+        .scope(RefCell::new(Vec::new()), async move {
+            let response = next.run(request).await;
+            (response, QUERIES.with(RefCell::take))
+        })
+        .await;
+    let elapsed = started.elapsed();
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_DEBUG_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let cache = parts.headers.get(CACHE_STATUS_HEADER).and_then(|v| v.to_str().ok());
+    let lag = (!never_synced()).then(indexer_lag_secs);
+    let debug = json!({
+        "elapsed_ms": elapsed.as_secs_f64() * 1000.0,
+        "query_count": queries.len(),
+        "query_ms": queries.iter().map(|q| q.elapsed_ms).sum::<f64>(),
+        "queries": queries,
+        "cache": cache,
+        "freshness": {
+            "indexer_lag_secs": lag,
+            "last_sync_ms": INDEXER_LAST_SYNC_MS.load(Ordering::Relaxed),
+            "stale_after_secs": stale_after_secs(),
+            "stale": lag.is_none_or(|lag| lag > stale_after_secs())
+        }
+    });
+    let body = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            map.insert("_debug".into(), debug);
+            serde_json::to_vec(&map).unwrap_or_else(|_| bytes.to_vec())
+        }
+        _ => bytes.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body))
+}
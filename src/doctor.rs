@@ -0,0 +1,244 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::{config, Config};
+use crate::db::{db_path, open_db, table_columns, EXPECTED_SCHEMA};
+use crate::indexer::{dex_package_id, rpc_client, rpc_url};
+use crate::migrations::{latest_schema_version, pending_blocking_migration, schema_version};
+use crate::listen::listen_addrs;
+use crate::logging::{log_filter, log_format};
+
+/// Maximum tolerated difference between the local clock and the latest
+/// checkpoint timestamp before the clock check warns (in milliseconds)
+const MAX_CLOCK_SKEW_MS: i64 = 60_000;
+
+/// Outcome of a single self-test check
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// A single named self-test check and its result
+#[derive(Serialize)]
+struct Check {
+    name: &'static str,
+    status: CheckStatus,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, status: CheckStatus, detail: impl Into<String>) -> Self {
+        Check { name, status, detail: detail.into() }
+    }
+}
+
+/// Sends a single JSON-RPC request to the Sui node and returns its `result`.
+async fn rpc_call(
+    client: &reqwest::Client,
+    url: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    let body = json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params });
+    let resp = client.post(url).json(&body).send().await?;
+    if !resp.status().is_success() {
+        return Err(format!("RPC returned error status: {}", resp.status()).into());
+    }
+    let json: Value = resp.json().await?;
+    if let Some(err) = json.get("error") {
+        return Err(format!("RPC error: {}", err).into());
+    }
+    Ok(json["result"].clone())
+}
+
+/// Checks that the configuration values parse and agree with each other.
+fn check_config() -> Check {
+    let mut problems = Vec::new();
+    match Config::load() {
+        Ok(config) => {
+            if let Some(url) = config.archive_rpc_url {
+                if !(url.starts_with("http://") || url.starts_with("https://")) {
+                    problems.push(format!("SUI_ARCHIVE_RPC_URL `{}` is not an http(s) URL", url));
+                }
+            }
+        }
+        Err(e) => problems.push(e),
+    }
+    if let Err(e) = listen_addrs() {
+        problems.push(e);
+    }
+    for rpc_url in &config().rpc_urls {
+        if !(rpc_url.starts_with("http://") || rpc_url.starts_with("https://")) {
+            problems.push(format!("SUI_RPC_URL `{}` is not an http(s) URL", rpc_url));
+        }
+    }
+    let package = dex_package_id().trim_start_matches("0x");
+    if package.len() != 64 || !package.chars().all(|c| c.is_ascii_hexdigit()) {
+        problems.push(format!("DEX_PACKAGE_ID `{}` is not a 32-byte hex address", dex_package_id()));
+    }
+    if let Err(e) = log_filter() {
+        problems.push(e);
+    }
+    if let Err(e) = log_format() {
+        problems.push(e);
+    }
+
+    if problems.is_empty() {
+        Check::new("config", CheckStatus::Pass, "configuration is consistent")
+    } else {
+        Check::new("config", CheckStatus::Fail, problems.join("; "))
+    }
+}
+
+/// Checks that the database file can be opened and written to, and that its
+/// schema matches what this build expects.
+fn check_database() -> Vec<Check> {
+    let existed = Path::new(db_path()).exists();
+    let conn = match open_db() {
+        Ok(c) => c,
+        Err(e) => {
+            return vec![Check::new("db_writable", CheckStatus::Fail, format!("cannot open {}: {}", db_path(), e))];
+        }
+    };
+
+    // Taking and releasing a write lock proves writability without changing data
+    let writable = match conn.execute_batch("BEGIN IMMEDIATE; ROLLBACK;") {
+        Ok(()) => Check::new("db_writable", CheckStatus::Pass, format!("{} is writable", db_path())),
+        Err(e) => Check::new("db_writable", CheckStatus::Fail, format!("{} is not writable: {}", db_path(), e)),
+    };
+
+    let mut missing = Vec::new();
+    for (table, columns) in EXPECTED_SCHEMA {
+        let present = table_columns(&conn, table).unwrap_or_default();
+        for column in columns.iter() {
+            if !present.iter().any(|c| c == column) {
+                missing.push(format!("{}.{}", table, column));
+            }
+        }
+    }
+    let version = schema_version(&conn).unwrap_or(0);
+    let schema = if !existed {
+        Check::new("migrations", CheckStatus::Warn, "database does not exist yet; schema will be created on first start")
+    } else if version > latest_schema_version() {
+        Check::new(
+            "migrations",
+            CheckStatus::Fail,
+            format!(
+                "schema version {} is newer than this build supports ({}); upgrade before starting",
+                version,
+                latest_schema_version()
+            ),
+        )
+    } else if missing.is_empty() && version == latest_schema_version() {
+        Check::new("migrations", CheckStatus::Pass, format!("schema is up to date (version {})", version))
+    } else if let Some(name) = pending_blocking_migration(version).filter(|_| missing.is_empty()) {
+        Check::new(
+            "migrations",
+            CheckStatus::Warn,
+            format!(
+                "schema is at version {}; migration {} blocks writes and is only applied by `migrate`, run it while no other process uses the database",
+                version, name
+            ),
+        )
+    } else if missing.is_empty() {
+        Check::new(
+            "migrations",
+            CheckStatus::Warn,
+            format!("schema will be migrated from version {} to {} on next start", version, latest_schema_version()),
+        )
+    } else {
+        Check::new(
+            "migrations",
+            CheckStatus::Warn,
+            format!("schema will be upgraded on next start; missing {}", missing.join(", ")),
+        )
+    };
+
+    vec![writable, schema]
+}
+
+/// Runs every self-test check and prints a JSON report to stdout.
+/// 
+/// Checks RPC reachability (failing on the primary endpoint and warning on
+/// its fallbacks), that the configured package exists on chain,
+/// database writability, schema status, clock skew against the latest
+/// checkpoint, and configuration consistency. Warnings do not fail the run.
+/// 
+/// # Returns
+/// * `bool` - `true` if no check failed
+pub async fn run() -> bool {
+    let url = rpc_url();
+    let client = rpc_client();
+    let mut checks = vec![check_config()];
+
+    // RPC reachability and clock sanity against the latest checkpoint
+    match rpc_call(&client, &url, "sui_getLatestCheckpointSequenceNumber", json!([])).await {
+        Ok(seq) => {
+            checks.push(Check::new("rpc_reachable", CheckStatus::Pass, format!("{} at checkpoint {}", url, seq)));
+            let checkpoint = rpc_call(&client, &url, "sui_getCheckpoint", json!([seq])).await;
+            let chain_ts = checkpoint
+                .ok()
+                .and_then(|c| c["timestampMs"].as_str().and_then(|t| t.parse::<i64>().ok()));
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+            checks.push(match chain_ts {
+                Some(ts) if (now - ts).abs() <= MAX_CLOCK_SKEW_MS => {
+                    Check::new("clock", CheckStatus::Pass, format!("skew {} ms", now - ts))
+                }
+                Some(ts) => Check::new(
+                    "clock",
+                    CheckStatus::Warn,
+                    format!("local clock differs from latest checkpoint by {} ms", now - ts),
+                ),
+                None => Check::new("clock", CheckStatus::Warn, "could not read latest checkpoint timestamp"),
+            });
+        }
+        Err(e) => {
+            checks.push(Check::new("rpc_reachable", CheckStatus::Fail, format!("{}: {}", url, e)));
+            checks.push(Check::new("clock", CheckStatus::Warn, "skipped: RPC unreachable"));
+        }
+    }
+
+    // Fallback endpoints only matter once the primary fails, so they warn
+    let mut unreachable = Vec::new();
+    for fallback in &config().rpc_urls[1..] {
+        if let Err(e) = rpc_call(&client, fallback, "sui_getLatestCheckpointSequenceNumber", json!([])).await {
+            unreachable.push(format!("{}: {}", fallback, e));
+        }
+    }
+    if config().rpc_urls.len() > 1 {
+        checks.push(if unreachable.is_empty() {
+            Check::new("rpc_fallbacks", CheckStatus::Pass, format!("{} reachable", config().rpc_urls.len() - 1))
+        } else {
+            Check::new("rpc_fallbacks", CheckStatus::Warn, unreachable.join("; "))
+        });
+    }
+
+    // The package ID must resolve to an existing on-chain object
+    let package = rpc_call(&client, &url, "sui_getObject", json!([dex_package_id(), { "showType": true }])).await;
+    checks.push(match package {
+        Ok(obj) if obj.get("data").is_some() => {
+            Check::new("package_id", CheckStatus::Pass, format!("{} exists", dex_package_id()))
+        }
+        Ok(obj) => Check::new(
+            "package_id",
+            CheckStatus::Fail,
+            format!("{} not found: {}", dex_package_id(), obj.get("error").cloned().unwrap_or(Value::Null)),
+        ),
+        Err(e) => Check::new("package_id", CheckStatus::Fail, format!("lookup failed: {}", e)),
+    });
+
+    checks.extend(check_database());
+
+    let ok = checks.iter().all(|c| c.status != CheckStatus::Fail);
+    let report = json!({ "ok": ok, "checks": checks });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    ok
+}
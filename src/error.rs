@@ -0,0 +1,94 @@
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use std::sync::PoisonError;
+
+use crate::db::is_busy;
+use crate::response_cache::DatabaseBusy;
+
+/// An error an API handler fails with.
+/// 
+/// Every variant is answered with its HTTP status and the same JSON body,
+/// `{"status": "error", "message": "..."}`.
+#[derive(Debug)]
+pub enum ApiError {
+    /// A missing or malformed parameter (400)
+    BadRequest(String),
+    /// Missing or invalid credentials (401)
+    Unauthorized(String),
+    /// The endpoint is disabled for every caller, or the resource belongs to
+    /// another caller (403)
+    Forbidden(String),
+    /// The requested pool, pair, export or feature doesn't exist (404)
+    NotFound(String),
+    /// The resource isn't in a state that allows the request (409)
+    Conflict(String),
+    /// The resource existed but is no longer available (410)
+    Gone(String),
+    /// Too much work is already in progress; the client should retry later (429)
+    TooManyRequests(String),
+    /// The Sui RPC node failed to answer (502)
+    Upstream(String),
+    /// A database read or write failed (500, or 503 if the database was locked)
+    Database(rusqlite::Error),
+    /// Shared state is unusable, e.g. a lock poisoned by a panicking task (500)
+    Internal(String),
+}
+
+impl From<rusqlite::Error> for ApiError {
+    fn from(e: rusqlite::Error) -> Self {
+        ApiError::Database(e)
+    }
+}
+
+/// A lock poisoned by a task that panicked while holding it. The data it
+/// guards may be half-updated, so the request fails instead of reading it.
+impl<T> From<PoisonError<T>> for ApiError {
+    fn from(_: PoisonError<T>) -> Self {
+        ApiError::Internal("Shared state is unavailable after an earlier failure".to_string())
+    }
+}
+
+impl ApiError {
+    /// The status the error is answered with, and its message.
+    fn status_and_message(&self) -> (StatusCode, String) {
+        match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message.clone()),
+            ApiError::Unauthorized(message) => (StatusCode::UNAUTHORIZED, message.clone()),
+            ApiError::Forbidden(message) => (StatusCode::FORBIDDEN, message.clone()),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message.clone()),
+            ApiError::Conflict(message) => (StatusCode::CONFLICT, message.clone()),
+            ApiError::Gone(message) => (StatusCode::GONE, message.clone()),
+            ApiError::TooManyRequests(message) => (StatusCode::TOO_MANY_REQUESTS, message.clone()),
+            ApiError::Upstream(message) => (StatusCode::BAD_GATEWAY, message.clone()),
+            ApiError::Database(e) if is_busy(e) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "Database is busy; try again shortly".to_string())
+            }
+            ApiError::Database(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Database error: {}", e)),
+            ApiError::Internal(message) => (StatusCode::INTERNAL_SERVER_ERROR, message.clone()),
+        }
+    }
+
+    /// The message the error is answered with, for channels other than an
+    /// HTTP response such as WebSocket messages.
+    pub fn message(&self) -> String {
+        self.status_and_message().1
+    }
+}
+
+impl IntoResponse for ApiError {
+    /// Failures caused by another connection holding a lock are answered
+    /// with 503 and marked with `DatabaseBusy`, so the response cache can
+    /// serve the last successful response instead.
+    fn into_response(self) -> Response {
+        let (status, message) = self.status_and_message();
+        let mut response = (status, Json(json!({ "status": "error", "message": message }))).into_response();
+        if matches!(&self, ApiError::Database(e) if is_busy(e)) {
+            response.extensions_mut().insert(DatabaseBusy);
+        }
+        response
+    }
+}
@@ -0,0 +1,104 @@
+use serde::de::{DeserializeOwned, Error};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// ID of an event: the transaction that emitted it and its index within it
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EventId {
+    #[serde(deserialize_with = "non_empty")]
+    pub tx_digest: String,
+    #[serde(deserialize_with = "u64_string")]
+    pub event_seq: u64,
+}
+
+/// An event as returned by `suix_queryEvents`, with its Move payload typed.
+/// 
+/// Fields of the envelope the indexer doesn't use (`packageId`, `bcs`, ...)
+/// are ignored.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuiEvent<T> {
+    pub id: EventId,
+    /// Address that signed the transaction emitting the event
+    #[serde(default)]
+    pub sender: Option<String>,
+    #[serde(deserialize_with = "u64_string")]
+    pub timestamp_ms: u64,
+    pub parsed_json: T,
+}
+
+/// Payload of the contract's `PoolCreatedEvent`
+#[derive(Deserialize)]
+pub struct PoolCreatedEvent {
+    #[serde(deserialize_with = "non_empty")]
+    pub pool_id: String,
+    #[serde(deserialize_with = "non_empty")]
+    pub token_a: String,
+    #[serde(deserialize_with = "non_empty")]
+    pub token_b: String,
+    #[serde(deserialize_with = "u64_string")]
+    pub initial_reserve_a: u64,
+    #[serde(deserialize_with = "u64_string")]
+    pub initial_reserve_b: u64,
+}
+
+/// Payload of the contract's `SwapEvent`, including the reserves after the swap
+#[derive(Deserialize)]
+pub struct SwapEvent {
+    #[serde(deserialize_with = "non_empty")]
+    pub pool_id: String,
+    #[serde(deserialize_with = "u64_string")]
+    pub amount_in: u64,
+    #[serde(deserialize_with = "u64_string")]
+    pub amount_out: u64,
+    #[serde(deserialize_with = "u64_string")]
+    pub new_reserve_a: u64,
+    #[serde(deserialize_with = "u64_string")]
+    pub new_reserve_b: u64,
+    /// Trader the contract reports, if any
+    #[serde(default)]
+    pub sender: Option<String>,
+}
+
+/// A DEX event the indexer handles
+pub enum DexEvent {
+    PoolCreated(SuiEvent<PoolCreatedEvent>),
+    Swap(SuiEvent<SwapEvent>),
+}
+
+impl DexEvent {
+    /// Parses an RPC event according to its Move event type.
+    ///
+    /// # Returns
+    /// * `Result<Option<DexEvent>, serde_json::Error>` - The event; `None` for event types the indexer doesn't handle; or why a handled event is malformed
+    pub fn parse(evt: &Value) -> Result<Option<DexEvent>, serde_json::Error> {
+        let event_type = evt["type"].as_str().unwrap_or_default();
+        if event_type.contains("PoolCreatedEvent") {
+            parse_as(evt).map(|e| Some(DexEvent::PoolCreated(e)))
+        } else if event_type.contains("SwapEvent") {
+            parse_as(evt).map(|e| Some(DexEvent::Swap(e)))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+fn parse_as<T: DeserializeOwned>(evt: &Value) -> Result<SuiEvent<T>, serde_json::Error> {
+    SuiEvent::deserialize(evt)
+}
+
+/// Deserializes a string that must not be empty.
+fn non_empty<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    if s.is_empty() {
+        return Err(D::Error::custom("empty string where a value is required"));
+    }
+    Ok(s)
+}
+
+/// Deserializes a u64 from the decimal string Sui encodes it as.
+fn u64_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(|_| D::Error::custom(format!("`{}` is not a u64", s)))
+}
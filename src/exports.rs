@@ -0,0 +1,289 @@
+use axum::body::{Bytes, HttpBody};
+use hyper::body::Frame;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, ReadBuf};
+use tracing::warn;
+
+use crate::config::config;
+use crate::db::open_db;
+
+/// Directory export files are written to when `EXPORT_DIR` is unset
+pub const DEFAULT_EXPORT_DIR: &str = "exports";
+
+/// Most export jobs that may be queued or running at once
+pub const MAX_ACTIVE_EXPORTS: i64 = 2;
+
+/// Size of the chunks export files are downloaded in
+const DOWNLOAD_CHUNK_BYTES: usize = 64 * 1024;
+
+/// Tables that can be exported, with the columns written and the timestamp
+/// column `from`/`to` filter on (if any)
+const DATASETS: [(&str, &str, Option<&str>); 3] = [
+    ("swaps", "tx_digest, pool_id, amount_in, amount_out, timestamp", Some("timestamp")),
+    ("pool_snapshots", "pool_id, reserve_a, reserve_b, timestamp", Some("timestamp")),
+    ("pools", "pool_id, token_a, token_b, reserve_a, reserve_b, last_updated", None),
+];
+
+/// Returns the directory export files are written to (`EXPORT_DIR`).
+pub fn export_dir() -> PathBuf {
+    PathBuf::from(&config().export_dir)
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// What to export, as posted to `POST /api/exports`
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ExportRequest {
+    /// `swaps`, `pool_snapshots` or `pools`
+    pub dataset: String,
+    #[serde(default = "default_format")]
+    pub format: String,
+    /// Only rows of this pool
+    pub pool_id: Option<String>,
+    /// Only rows at or after this time (ms), for timestamped datasets
+    pub from: Option<i64>,
+    /// Only rows before this time (ms), for timestamped datasets
+    pub to: Option<i64>,
+}
+
+fn default_format() -> String {
+    "csv".to_string()
+}
+
+impl ExportRequest {
+    /// Checks the request describes an export that can be produced.
+    pub fn validate(&self) -> std::result::Result<(), String> {
+        let Some((_, _, timestamp)) = DATASETS.iter().find(|(name, _, _)| *name == self.dataset) else {
+            let names: Vec<&str> = DATASETS.iter().map(|(name, _, _)| *name).collect();
+            return Err(format!("`dataset` must be one of {}", names.join(", ")));
+        };
+        if self.format != "csv" {
+            return Err("Only `csv` exports are supported".to_string());
+        }
+        if timestamp.is_none() && (self.from.is_some() || self.to.is_some()) {
+            return Err(format!("Dataset `{}` has no timestamp to filter by `from`/`to`", self.dataset));
+        }
+        Ok(())
+    }
+}
+
+/// An export job and its progress
+#[derive(Serialize)]
+pub struct ExportJob {
+    pub id: i64,
+    /// `queued`, `running`, `complete` or `failed`
+    pub status: String,
+    pub request: ExportRequest,
+    /// Rows written, once complete
+    pub rows: i64,
+    pub error: Option<String>,
+    pub created_at: i64,
+    pub finished_at: Option<i64>,
+}
+
+/// Path of a job's export file once complete.
+pub fn export_path(id: i64) -> PathBuf {
+    export_dir().join(format!("export-{}.csv", id))
+}
+
+/// Records a new queued export job.
+/// 
+/// # Returns
+/// * `Result<Option<ExportJob>>` - The job, or `None` if `MAX_ACTIVE_EXPORTS` jobs are already queued or running
+pub fn create_export_job(conn: &Connection, request: &ExportRequest) -> Result<Option<ExportJob>> {
+    let active: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM export_jobs WHERE status IN ('queued', 'running')",
+        [],
+        |row| row.get(0),
+    )?;
+    if active >= MAX_ACTIVE_EXPORTS {
+        return Ok(None);
+    }
+    let now = now_ms();
+    let spec = serde_json::to_string(request).unwrap_or_default();
+    conn.execute(
+        "INSERT INTO export_jobs (request, status, rows, created_at) VALUES (?1, 'queued', 0, ?2)",
+        params![spec, now],
+    )?;
+    get_export_job(conn, conn.last_insert_rowid())
+}
+
+/// Loads an export job by ID.
+pub fn get_export_job(conn: &Connection, id: i64) -> Result<Option<ExportJob>> {
+    conn.query_row(
+        "SELECT id, status, request, rows, error, created_at, finished_at FROM export_jobs WHERE id = ?1",
+        params![id],
+        |row| {
+            let request: String = row.get(2)?;
+            Ok(ExportJob {
+                id: row.get(0)?,
+                status: row.get(1)?,
+                request: serde_json::from_str(&request).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(2, rusqlite::types::Type::Text, Box::new(e))
+                })?,
+                rows: row.get(3)?,
+                error: row.get(4)?,
+                created_at: row.get(5)?,
+                finished_at: row.get(6)?,
+            })
+        },
+    )
+    .optional()
+}
+
+/// Marks jobs left queued or running by a previous process as failed.
+/// 
+/// Export jobs run inside the API process, so a restart abandons them.
+pub fn fail_interrupted_exports(conn: &Connection) -> Result<usize> {
+    conn.execute(
+        "UPDATE export_jobs SET status = 'failed', error = 'interrupted by a restart', finished_at = ?1
+         WHERE status IN ('queued', 'running')",
+        params![now_ms()],
+    )
+}
+
+/// Quotes a CSV field when it contains a separator, quote or line break.
+fn csv_field(value: &SqlValue) -> String {
+    match value {
+        SqlValue::Null => String::new(),
+        SqlValue::Integer(n) => n.to_string(),
+        SqlValue::Real(n) => n.to_string(),
+        SqlValue::Text(s) if s.contains([',', '"', '\n', '\r']) => format!("\"{}\"", s.replace('"', "\"\"")),
+        SqlValue::Text(s) => s.clone(),
+        SqlValue::Blob(b) => b.iter().map(|byte| format!("{:02x}", byte)).collect(),
+    }
+}
+
+/// Writes the rows selected by `request` as CSV to `path`, returning the row count.
+fn write_csv(conn: &Connection, request: &ExportRequest, path: &PathBuf) -> io::Result<i64> {
+    let (table, columns, timestamp) = DATASETS
+        .iter()
+        .find(|(name, _, _)| *name == request.dataset)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "unknown dataset"))?;
+
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    if let Some(pool_id) = &request.pool_id {
+        values.push(SqlValue::Text(pool_id.clone()));
+        conditions.push(format!("pool_id = ?{}", values.len()));
+    }
+    if let Some(column) = timestamp {
+        if let Some(from) = request.from {
+            values.push(SqlValue::Integer(from));
+            conditions.push(format!("{} >= ?{}", column, values.len()));
+        }
+        if let Some(to) = request.to {
+            values.push(SqlValue::Integer(to));
+            conditions.push(format!("{} < ?{}", column, values.len()));
+        }
+    }
+    let mut sql = format!("SELECT {} FROM {}", columns, table);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(" ORDER BY {}", timestamp.unwrap_or("pool_id")));
+
+    let to_io = io::Error::other;
+    let mut stmt = conn.prepare(&sql).map_err(to_io)?;
+    let column_count = stmt.column_count();
+    let mut out = BufWriter::new(std::fs::File::create(path)?);
+    writeln!(out, "{}", columns.replace(' ', ""))?;
+
+    let mut rows = stmt.query(params_from_iter(values)).map_err(to_io)?;
+    let mut count = 0;
+    while let Some(row) = rows.next().map_err(to_io)? {
+        let fields = (0..column_count)
+            .map(|i| row.get::<_, SqlValue>(i).map(|v| csv_field(&v)))
+            .collect::<Result<Vec<_>>>()
+            .map_err(to_io)?;
+        writeln!(out, "{}", fields.join(","))?;
+        count += 1;
+    }
+    out.flush()?;
+    out.get_ref().sync_all()?;
+    Ok(count)
+}
+
+/// Runs an export job to completion, recording its outcome.
+/// 
+/// Rows are read on a separate connection, so a long export does not hold
+/// the connection the API and indexer share; with WAL it reads a consistent
+/// snapshot while the indexer keeps writing. The file is written under a
+/// temporary name and renamed once complete, so a download never sees a
+/// partial file.
+pub fn run_export_job(id: i64, request: ExportRequest) {
+    let conn = match open_db() {
+        Ok(conn) => conn,
+        Err(e) => {
+            warn!(export = id, error = %e, "export cannot open the database");
+            return;
+        }
+    };
+    let _ = conn.busy_timeout(Duration::from_secs(5));
+    let _ = conn.execute("UPDATE export_jobs SET status = 'running' WHERE id = ?1", params![id]);
+
+    let path = export_path(id);
+    let partial = path.with_extension("csv.partial");
+    let result = std::fs::create_dir_all(export_dir())
+        .and_then(|_| write_csv(&conn, &request, &partial))
+        .and_then(|rows| std::fs::rename(&partial, &path).map(|_| rows));
+    let update = match result {
+        Ok(rows) => conn.execute(
+            "UPDATE export_jobs SET status = 'complete', rows = ?2, finished_at = ?3 WHERE id = ?1",
+            params![id, rows, now_ms()],
+        ),
+        Err(e) => {
+            let _ = std::fs::remove_file(&partial);
+            warn!(export = id, error = %e, "export failed");
+            conn.execute(
+                "UPDATE export_jobs SET status = 'failed', error = ?2, finished_at = ?3 WHERE id = ?1",
+                params![id, e.to_string(), now_ms()],
+            )
+        }
+    };
+    if let Err(e) = update {
+        warn!(export = id, error = %e, "failed to record the outcome of export");
+    }
+}
+
+/// Response body streaming an export file in chunks, so large exports are
+/// never held in memory
+pub struct FileBody {
+    file: tokio::fs::File,
+    buf: Vec<u8>,
+}
+
+impl FileBody {
+    pub fn new(file: tokio::fs::File) -> FileBody {
+        FileBody { file, buf: vec![0; DOWNLOAD_CHUNK_BYTES] }
+    }
+}
+
+impl HttpBody for FileBody {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<Frame<Bytes>>>> {
+        let this = &mut *self;
+        let mut buf = ReadBuf::new(&mut this.buf);
+        match Pin::new(&mut this.file).poll_read(cx, &mut buf) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(Err(e)) => Poll::Ready(Some(Err(e))),
+            Poll::Ready(Ok(())) if buf.filled().is_empty() => Poll::Ready(None),
+            Poll::Ready(Ok(())) => Poll::Ready(Some(Ok(Frame::data(Bytes::copy_from_slice(buf.filled()))))),
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rusqlite::{Connection, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate::config::config;
+use crate::db::list_feature_overrides;
+use crate::error::ApiError;
+
+/// Features that can be switched on and off at runtime, with a description.
+/// 
+/// Endpoint features gate their routes; the others gate background work.
+pub const FEATURES: [(&str, &str); 24] = [
+    ("velocity", "GET /api/pools/:pool_id/velocity"),
+    ("pool_volume", "GET /api/pools/:pool_id/volume"),
+    ("size_histogram", "GET /api/pools/:pool_id/size-histogram"),
+    ("tape", "GET /api/tape/:pool_id"),
+    ("failures", "GET /api/pools/:pool_id/failures"),
+    ("pool_history", "GET /api/pools/:pool_id/at"),
+    ("tokens", "GET /api/tokens"),
+    ("graph", "GET /api/graph"),
+    ("price_wait", "GET /api/price/wait"),
+    ("quote", "GET /api/quote"),
+    ("best_price", "GET /api/best-price"),
+    ("routing_accuracy", "GET /api/routing/accuracy"),
+    ("quote_conversion", "GET /api/quotes/conversion"),
+    ("checkpoint_swaps", "GET /api/checkpoints/:seq/swaps"),
+    ("reports", "GET /api/reports and GET /api/reports/:name"),
+    ("custom_metrics", "GET /api/metrics/custom/:name and background custom metric computation"),
+    ("exports", "Background CSV exports under /api/exports"),
+    ("usage", "Per-API-key usage tracking and GET /api/keys/self/usage"),
+    ("price_refresher", "Background token price derivation"),
+    ("price_reference", "Background comparison of pool prices with external reference prices"),
+    ("candles", "GET /api/candles, POST /api/candles/batch, GET /api/candles/download and background candle aggregation"),
+    ("ticker", "GET /api/ticker"),
+    ("movers", "GET /api/movers"),
+    ("live", "GET /ws live swap and pool stream"),
+];
+
+/// Where a feature's current state comes from
+#[derive(Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FlagSource {
+    /// Enabled by default
+    Default,
+    /// Disabled through `DISABLED_FEATURES`
+    Config,
+    /// Set by an admin at runtime
+    Override,
+}
+
+/// Current state of one feature
+#[derive(Clone, Serialize)]
+pub struct FeatureState {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub enabled: bool,
+    pub source: FlagSource,
+}
+
+/// Feature states shared between the API and background tasks
+pub type FeatureFlags = Arc<RwLock<HashMap<&'static str, FeatureState>>>;
+
+/// Looks up a known feature name.
+pub fn feature(name: &str) -> Option<(&'static str, &'static str)> {
+    FEATURES.iter().copied().find(|(n, _)| *n == name)
+}
+
+/// Returns a feature's state with admin overrides ignored.
+/// 
+/// Features are enabled unless listed in the comma-separated
+/// `DISABLED_FEATURES` environment variable.
+pub fn configured_state(name: &'static str, description: &'static str) -> FeatureState {
+    let disabled = config().disabled_features.iter().any(|f| f == name);
+    FeatureState {
+        name,
+        description,
+        enabled: !disabled,
+        source: if disabled { FlagSource::Config } else { FlagSource::Default },
+    }
+}
+
+/// Builds the feature flags from configuration and persisted admin overrides.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<FeatureFlags>` - Current state of every feature
+pub fn load_feature_flags(conn: &Connection) -> Result<FeatureFlags> {
+    let overrides = list_feature_overrides(conn)?;
+    let flags = FEATURES
+        .iter()
+        .map(|&(name, description)| {
+            let mut state = configured_state(name, description);
+            if let Some(&enabled) = overrides.get(name) {
+                state.enabled = enabled;
+                state.source = FlagSource::Override;
+            }
+            (name, state)
+        })
+        .collect();
+    Ok(Arc::new(RwLock::new(flags)))
+}
+
+/// Checks whether a feature is currently enabled. Unknown features are enabled.
+pub fn is_enabled(flags: &FeatureFlags, name: &str) -> bool {
+    flags
+        .read()
+        .map(|f| f.get(name).is_none_or(|state| state.enabled))
+        .unwrap_or(true)
+}
+
+/// Middleware answering 404 for routes whose feature is disabled.
+/// 
+/// The feature name is passed as the middleware state, e.g.
+/// `middleware::from_fn_with_state("tokens", require_feature)`.
+pub async fn require_feature(State(name): State<&'static str>, request: Request, next: Next) -> Response {
+    let enabled = request
+        .extensions()
+        .get::<FeatureFlags>()
+        .is_none_or(|flags| is_enabled(flags, name));
+    if !enabled {
+        return ApiError::NotFound(format!("Feature `{}` is disabled", name)).into_response();
+    }
+    next.run(request).await
+}
@@ -0,0 +1,129 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::json;
+use std::sync::atomic::Ordering;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::config;
+use crate::metrics::INDEXER_LAST_SYNC_MS;
+use crate::slo::FRESH_DATA_EVENTS;
+
+/// Default indexer lag after which market data is considered stale (in seconds)
+pub const DEFAULT_STALE_AFTER_SECS: u64 = 60;
+
+/// Largest response body the middleware will buffer to add the `degraded` flag
+const MAX_FLAGGED_BODY_BYTES: usize = 16 * 1024 * 1024;
+
+/// How endpoints behave while indexed data is stale
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StaleMode {
+    /// Serve responses unchanged
+    #[default]
+    Off,
+    /// Serve responses with `"degraded": true` and the current lag
+    Flag,
+    /// Refuse with 503 Service Unavailable
+    Reject,
+}
+
+impl StaleMode {
+    /// Parses a `STALE_DATA_MODE` value (`off`, `flag` or `reject`).
+    pub fn parse(value: &str) -> Result<StaleMode, String> {
+        match value.trim() {
+            "off" => Ok(StaleMode::Off),
+            "flag" => Ok(StaleMode::Flag),
+            "reject" => Ok(StaleMode::Reject),
+            other => Err(format!("STALE_DATA_MODE must be `off`, `flag` or `reject`, not `{}`", other)),
+        }
+    }
+}
+
+/// Returns the configured lag threshold, `STALE_DATA_THRESHOLD_SECS`.
+pub fn stale_after_secs() -> i64 {
+    config().stale_data_threshold_secs as i64
+}
+
+/// Whether the indexer has not completed a polling cycle since the process started.
+pub fn never_synced() -> bool {
+    INDEXER_LAST_SYNC_MS.load(Ordering::Relaxed) == 0
+}
+
+/// Returns how far the indexer is behind, in seconds.
+/// 
+/// Lag is measured from the end of the last successfully completed polling
+/// cycle, so it keeps growing while the RPC is failing or a backfill is still
+/// running. Before the first cycle completes the lag is effectively infinite.
+pub fn indexer_lag_secs() -> i64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    (now - INDEXER_LAST_SYNC_MS.load(Ordering::Relaxed) as i64) / 1000
+}
+
+/// Middleware protecting market-data endpoints from serving stale data as live.
+/// 
+/// When the indexer lag exceeds `STALE_DATA_THRESHOLD_SECS`, responses either
+/// get `"degraded": true`, `"stale_reason"` and `"indexer_lag_secs"` added to
+/// their JSON body (`STALE_DATA_MODE=flag`) or are replaced with a 503
+/// (`STALE_DATA_MODE=reject`), so trading bots don't act on stale prices. The
+/// default, `off`, serves them unchanged.
+/// 
+/// Until the indexer completes its first polling cycle there is no lag to
+/// report: `stale_reason` is `"never_synced"` and `indexer_lag_secs` is
+/// `null`, instead of `"lagging"` and the lag. Every response counts towards
+/// the `fresh_data` SLO, whatever the mode.
+/// 
+/// The guard wraps the response cache, so cached responses are flagged or
+/// rejected the same way.
+pub async fn degrade_when_stale(request: Request, next: Next) -> Response {
+    let mode = config().stale_data_mode;
+    let lag = (!never_synced()).then(indexer_lag_secs);
+    let stale = lag.is_none_or(|lag| lag > stale_after_secs());
+    FRESH_DATA_EVENTS.record(!stale);
+    if mode == StaleMode::Off || !stale {
+        return next.run(request).await;
+    }
+    let reason = if lag.is_some() { "lagging" } else { "never_synced" };
+
+    if mode == StaleMode::Reject {
+        let message = match lag {
+            Some(_) => "Indexed data is stale; try again once the indexer catches up",
+            None => "The indexer has not synced yet; try again once it completes its first cycle",
+        };
+        return (
+            StatusCode::SERVICE_UNAVAILABLE,
+            Json(json!({
+                "status": "error",
+                "message": message,
+                "degraded": true,
+                "stale_reason": reason,
+                "indexer_lag_secs": lag
+            })),
+        )
+            .into_response();
+    }
+
+    let response = next.run(request).await;
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_FLAGGED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let flagged = match serde_json::from_slice::<serde_json::Value>(&bytes) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            map.insert("degraded".into(), json!(true));
+            map.insert("stale_reason".into(), json!(reason));
+            map.insert("indexer_lag_secs".into(), json!(lag));
+            serde_json::to_vec(&map).unwrap_or_else(|_| bytes.to_vec())
+        }
+        _ => bytes.to_vec(),
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(flagged))
+}
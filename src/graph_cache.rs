@@ -0,0 +1,191 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::config;
+use crate::error::ApiError;
+use crate::metrics::ROUTING_GRAPH_REBUILDS;
+use crate::pool_cache::{read_pools, PoolCache};
+use crate::routing::{spot_price, PoolGraph};
+
+/// Default relative reserve change of a pool that triggers a graph rebuild
+pub const DEFAULT_GRAPH_REFRESH_THRESHOLD: f64 = 0.001;
+
+/// The routing graph as last built, and how far pools have moved since
+pub struct MemoizedGraph {
+    pub graph: Arc<PoolGraph>,
+    /// Incremented on every rebuild
+    pub version: u64,
+    pub built_at: i64,
+    /// Reserves each pool in `graph`, drained or not, was built with
+    reserves: HashMap<String, (f64, f64)>,
+    /// Pools whose reserves changed since the build by less than the
+    /// threshold, with the relative change
+    drift: HashMap<String, f64>,
+    /// When the oldest change not reflected in the graph was seen
+    stale_since: Option<i64>,
+}
+
+/// Routing graph shared by the quote endpoints, maintained by `pools_changed`.
+/// 
+/// Requests read the current graph instead of rebuilding it from every pool;
+/// it is rebuilt only when a pool is created, drained or refilled, or when a pool's
+/// reserves have moved by more than `GRAPH_REFRESH_THRESHOLD` since the last
+/// build. Smaller moves are tracked as drift so staleness stays visible.
+pub type GraphCache = Arc<RwLock<MemoizedGraph>>;
+
+/// Size and staleness of the memoized graph, as served to admins
+#[derive(Serialize)]
+pub struct GraphStats {
+    pub version: u64,
+    pub built_at: i64,
+    pub age_ms: i64,
+    pub tokens: usize,
+    pub pools: usize,
+    /// Pools left out of routing because a reserve is zero
+    pub drained_pools: usize,
+    /// Pools whose reserves changed since the build without triggering a rebuild
+    pub drifted_pools: usize,
+    /// Largest relative reserve change among `drifted_pools`
+    pub max_drift: f64,
+    /// When the oldest change not reflected in the graph was seen, if any
+    pub stale_since: Option<i64>,
+    pub refresh_threshold: f64,
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Returns the configured rebuild threshold (`GRAPH_REFRESH_THRESHOLD`, `0`
+/// rebuilds on every change).
+fn refresh_threshold() -> f64 {
+    config().graph_refresh_threshold
+}
+
+/// Builds the graph from the pool cache if enabled, otherwise from SQLite.
+fn build(conn: &Connection, pool_cache: Option<&PoolCache>, version: u64) -> Result<MemoizedGraph, ApiError> {
+    let graph = match pool_cache {
+        Some(cache) => PoolGraph::from_cache(cache)?,
+        None => PoolGraph::load(conn)?,
+    };
+    let reserves = graph
+        .pools
+        .iter()
+        .chain(&graph.drained)
+        .map(|p| (p.pool_id.clone(), (p.reserve_in, p.reserve_out)))
+        .collect();
+    Ok(MemoizedGraph {
+        graph: Arc::new(graph),
+        version,
+        built_at: now_ms(),
+        reserves,
+        drift: HashMap::new(),
+        stale_since: None,
+    })
+}
+
+/// Builds the initial graph at startup.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_cache` - In-memory pool cache to build from, if enabled
+/// 
+/// # Returns
+/// * `Result<GraphCache, ApiError>` - The graph cache
+pub fn load_graph_cache(conn: &Connection, pool_cache: Option<&PoolCache>) -> Result<GraphCache, ApiError> {
+    Ok(Arc::new(RwLock::new(build(conn, pool_cache, 1)?)))
+}
+
+/// Returns the current graph, or `Internal` if the cache lock was poisoned.
+pub fn current_graph(cache: &GraphCache) -> Result<Arc<PoolGraph>, ApiError> {
+    Ok(cache.read()?.graph.clone())
+}
+
+/// Relative change between two reserve pairs, the larger of either side.
+fn reserve_change((old_a, old_b): (f64, f64), (new_a, new_b): (f64, f64)) -> f64 {
+    ((new_a - old_a).abs() / old_a).max((new_b - old_b).abs() / old_b)
+}
+
+/// Updates the graph after pools changed, e.g. right after the indexer
+/// committed a page touching them.
+/// 
+/// Rebuilds the whole graph if any of the pools is new to it, gained or lost
+/// liquidity, or moved beyond the threshold; otherwise only records drift.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `pool_cache` - In-memory pool cache to rebuild from, if enabled
+/// * `cache` - Graph cache to update
+/// * `pool_ids` - Pools whose rows changed
+/// 
+/// # Returns
+/// * `Result<bool, ApiError>` - Whether the graph was rebuilt
+pub fn pools_changed(
+    conn: &Connection,
+    pool_cache: Option<&PoolCache>,
+    cache: &GraphCache,
+    pool_ids: &[String],
+) -> Result<bool, ApiError> {
+    if pool_ids.is_empty() {
+        return Ok(false);
+    }
+
+    let pools = read_pools(conn, Some(pool_ids))?;
+    let threshold = refresh_threshold();
+    let mut guard = cache.write()?;
+    let mut changes = Vec::new();
+    let mut rebuild = false;
+    let liquid = |(a, b): (f64, f64)| spot_price(a, b).is_some();
+    for pool in &pools {
+        if pool.token_a.is_empty() || pool.token_b.is_empty() {
+            continue;
+        }
+        let reserves = (pool.reserve_a, pool.reserve_b);
+        match guard.reserves.get(&pool.pool_id) {
+            Some(&built) if liquid(built) && liquid(reserves) => {
+                let change = reserve_change(built, reserves);
+                if change > threshold {
+                    rebuild = true;
+                } else if change > 0.0 {
+                    changes.push((pool.pool_id.clone(), change));
+                }
+            }
+            Some(&built) if liquid(built) == liquid(reserves) => {}
+            _ => rebuild = true,
+        }
+    }
+
+    if rebuild {
+        *guard = build(conn, pool_cache, guard.version + 1)?;
+        ROUTING_GRAPH_REBUILDS.fetch_add(1, Ordering::Relaxed);
+    } else if !changes.is_empty() {
+        guard.stale_since.get_or_insert_with(now_ms);
+        guard.drift.extend(changes);
+    }
+    Ok(rebuild)
+}
+
+/// Reports the size and staleness of the current graph, or `Internal` if
+/// the cache lock was poisoned.
+pub fn graph_stats(cache: &GraphCache) -> Result<GraphStats, ApiError> {
+    let guard = cache.read()?;
+    Ok(GraphStats {
+        version: guard.version,
+        built_at: guard.built_at,
+        age_ms: now_ms() - guard.built_at,
+        tokens: guard.graph.edges.len(),
+        pools: guard.graph.pools.len(),
+        drained_pools: guard.graph.drained.len(),
+        drifted_pools: guard.drift.len(),
+        max_drift: guard.drift.values().copied().fold(0.0, f64::max),
+        stale_since: guard.stale_since,
+        refresh_threshold: refresh_threshold(),
+    })
+}
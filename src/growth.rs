@@ -0,0 +1,308 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::config;
+use crate::db::{growth_samples, record_growth_sample, GrowthSample, DATABASE_GROWTH_KEY};
+
+/// Default period growth rates are averaged over (in days)
+pub const DEFAULT_GROWTH_WINDOW_DAYS: i64 = 7;
+
+/// Default database sizes projected: 1, 10 and 100 GiB
+pub const DEFAULT_GROWTH_THRESHOLDS_BYTES: [i64; 3] = [1 << 30, 10 << 30, 100 << 30];
+
+/// Shortest span between two samples a growth rate is computed over, so two
+/// samples taken either side of midnight aren't extrapolated (in milliseconds)
+const MIN_GROWTH_SPAN_MS: i64 = 12 * 60 * 60 * 1000;
+
+const DAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Latest size of a table and how fast it grew over the window
+#[derive(Serialize, Clone)]
+pub struct TableGrowth {
+    pub table: String,
+    pub rows: i64,
+    pub bytes: Option<i64>,
+    /// `None` until samples span `MIN_GROWTH_SPAN_MS`
+    pub rows_per_day: Option<f64>,
+    /// `None` until samples span `MIN_GROWTH_SPAN_MS`, or without `dbstat`
+    pub bytes_per_day: Option<f64>,
+}
+
+/// When the database file is projected to reach a size
+#[derive(Serialize, Clone)]
+pub struct ThresholdProjection {
+    pub threshold_bytes: i64,
+    pub reached: bool,
+    /// Days until the size is reached at the current growth rate; `None` if
+    /// the database isn't growing or its rate is unknown yet
+    pub days: Option<f64>,
+    pub eta_ms: Option<i64>,
+}
+
+/// Growth of the database and its projection against the size thresholds
+#[derive(Serialize, Clone)]
+pub struct GrowthForecast {
+    pub computed_at: i64,
+    pub window_days: i64,
+    /// Size of the database file in bytes, as of the latest sample
+    pub size_bytes: i64,
+    pub bytes_per_day: Option<f64>,
+    /// Every table, largest first
+    pub tables: Vec<TableGrowth>,
+    pub thresholds: Vec<ThresholdProjection>,
+}
+
+/// Forecast computed by the last maintenance pass
+static FORECAST: Mutex<Option<GrowthForecast>> = Mutex::new(None);
+
+/// Change per day between the first and last of a table's samples, if they
+/// are far enough apart.
+fn per_day(first: &GrowthSample, last: &GrowthSample, value: fn(&GrowthSample) -> Option<i64>) -> Option<f64> {
+    let span = last.recorded_at - first.recorded_at;
+    if span < MIN_GROWTH_SPAN_MS {
+        return None;
+    }
+    Some((value(last)? - value(first)?) as f64 * DAY_MS as f64 / span as f64)
+}
+
+/// Computes growth rates and threshold projections from growth samples.
+/// 
+/// Each table's rate is the change between its oldest and newest sample
+/// within `window_days` of the newest, so a single pruning run or bulk
+/// backfill is averaged out rather than extrapolated.
+/// 
+/// # Arguments
+/// * `samples` - Growth samples, by table and then in time order
+/// * `window_days` - Period rates are averaged over
+/// * `thresholds` - Database sizes to project, in bytes
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `GrowthForecast` - The forecast
+pub fn forecast(samples: &[GrowthSample], window_days: i64, thresholds: &[i64], now: i64) -> GrowthForecast {
+    let mut tables = Vec::new();
+    for group in samples.chunk_by(|a, b| a.table_name == b.table_name) {
+        let last = &group[group.len() - 1];
+        let first = group
+            .iter()
+            .find(|s| s.recorded_at >= last.recorded_at - window_days * DAY_MS)
+            .unwrap_or(last);
+        tables.push(TableGrowth {
+            table: last.table_name.clone(),
+            rows: last.row_count,
+            bytes: last.bytes,
+            rows_per_day: per_day(first, last, |s| Some(s.row_count)),
+            bytes_per_day: per_day(first, last, |s| s.bytes),
+        });
+    }
+    tables.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.rows.cmp(&a.rows)).then(a.table.cmp(&b.table)));
+
+    let database = tables.iter().position(|t| t.table == DATABASE_GROWTH_KEY).map(|i| tables.remove(i));
+    let size_bytes = database.as_ref().and_then(|d| d.bytes).unwrap_or(0);
+    let bytes_per_day = database.as_ref().and_then(|d| d.bytes_per_day);
+    let sampled_at = samples
+        .iter()
+        .filter(|s| s.table_name == DATABASE_GROWTH_KEY)
+        .map(|s| s.recorded_at)
+        .max()
+        .unwrap_or(now);
+
+    let thresholds = thresholds
+        .iter()
+        .map(|&threshold| {
+            let reached = database.is_some() && size_bytes >= threshold;
+            let days = if reached {
+                Some(0.0)
+            } else {
+                bytes_per_day.filter(|rate| *rate > 0.0).map(|rate| (threshold - size_bytes) as f64 / rate)
+            };
+            ThresholdProjection {
+                threshold_bytes: threshold,
+                reached,
+                days,
+                eta_ms: days.map(|d| sampled_at + (d * DAY_MS as f64) as i64),
+            }
+        })
+        .collect();
+
+    GrowthForecast { computed_at: now, window_days, size_bytes, bytes_per_day, tables, thresholds }
+}
+
+/// Samples today's table sizes if not done yet and recomputes the forecast
+/// served by the status endpoint and metrics.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `rusqlite::Result<GrowthForecast>` - The new forecast
+pub fn refresh(conn: &Connection) -> rusqlite::Result<GrowthForecast> {
+    let now = now_ms();
+    record_growth_sample(conn, now)?;
+    let config = config();
+    let window_days = config.db_growth_window_days;
+    // One extra day so the oldest sample of the window is included whatever
+    // the time of day it was taken
+    let samples = growth_samples(conn, now - (window_days + 1) * DAY_MS)?;
+    let forecast = forecast(&samples, window_days, &config.db_growth_thresholds_bytes, now);
+    if let Ok(mut current) = FORECAST.lock() {
+        *current = Some(forecast.clone());
+    }
+    Ok(forecast)
+}
+
+/// The forecast computed by the last maintenance pass, if any.
+pub fn current() -> Option<GrowthForecast> {
+    FORECAST.lock().ok().and_then(|f| f.clone())
+}
+
+/// Renders the growth gauges in the Prometheus text exposition format.
+pub fn render(out: &mut String) {
+    let Some(forecast) = current() else { return };
+
+    let families = [
+        ("fooswap_db_table_rows", "Rows in each table, as of the latest daily sample"),
+        ("fooswap_db_table_bytes", "Bytes of each table and its indexes, as of the latest daily sample"),
+        ("fooswap_db_table_rows_per_day", "Average daily row growth of each table over the growth window"),
+        ("fooswap_db_table_bytes_per_day", "Average daily byte growth of each table over the growth window"),
+    ];
+    for (i, (name, help)) in families.iter().enumerate() {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for table in &forecast.tables {
+            let value = match i {
+                0 => Some(table.rows as f64),
+                1 => table.bytes.map(|b| b as f64),
+                2 => table.rows_per_day,
+                _ => table.bytes_per_day,
+            };
+            if let Some(value) = value {
+                let _ = writeln!(out, "{}{{table=\"{}\"}} {}", name, table.table, value);
+            }
+        }
+    }
+
+    if let Some(rate) = forecast.bytes_per_day {
+        let name = "fooswap_db_growth_bytes_per_day";
+        let _ = writeln!(out, "# HELP {} Average daily growth of the database file over the growth window", name);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        let _ = writeln!(out, "{} {}", name, rate);
+    }
+
+    let name = "fooswap_db_size_threshold_eta_seconds";
+    let _ = writeln!(out, "# HELP {} Projected time until the database file reaches each size threshold", name);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    let now = now_ms();
+    for threshold in &forecast.thresholds {
+        if let Some(eta) = threshold.eta_ms {
+            let secs = (eta - now).max(0) / 1000;
+            let _ = writeln!(out, "{}{{threshold_bytes=\"{}\"}} {}", name, threshold.threshold_bytes, secs);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_800_000_000_000;
+
+    fn sample(table: &str, recorded_at: i64, rows: i64, bytes: Option<i64>) -> GrowthSample {
+        GrowthSample { table_name: table.to_string(), row_count: rows, bytes, recorded_at }
+    }
+
+    /// Database file samples of the given sizes, `(days before NOW, bytes)`
+    fn database(sizes: &[(i64, i64)]) -> Vec<GrowthSample> {
+        sizes.iter().map(|&(days, bytes)| sample(DATABASE_GROWTH_KEY, NOW - days * DAY_MS, 0, Some(bytes))).collect()
+    }
+
+    #[test]
+    fn projects_thresholds_at_the_average_rate() {
+        let mut samples = vec![
+            sample("pools", NOW - 2 * DAY_MS, 10, Some(100)),
+            sample("pools", NOW, 10, Some(100)),
+            sample("swaps", NOW - 2 * DAY_MS, 100, Some(400)),
+            sample("swaps", NOW, 300, Some(2400)),
+        ];
+        samples.extend(database(&[(2, 1000), (0, 3000)]));
+        let forecast = forecast(&samples, 7, &[5000], NOW);
+
+        assert_eq!(forecast.size_bytes, 3000);
+        assert_eq!(forecast.bytes_per_day, Some(1000.0));
+        let tables: Vec<&str> = forecast.tables.iter().map(|t| t.table.as_str()).collect();
+        assert_eq!(tables, ["swaps", "pools"]);
+        assert_eq!(forecast.tables[0].rows_per_day, Some(100.0));
+        assert_eq!(forecast.tables[0].bytes_per_day, Some(1000.0));
+        assert_eq!(forecast.tables[1].rows_per_day, Some(0.0));
+
+        let projection = &forecast.thresholds[0];
+        assert!(!projection.reached);
+        assert_eq!(projection.days, Some(2.0));
+        assert_eq!(projection.eta_ms, Some(NOW + 2 * DAY_MS));
+    }
+
+    #[test]
+    fn samples_closer_than_the_minimum_span_give_no_rate() {
+        let samples = vec![
+            sample(DATABASE_GROWTH_KEY, NOW - MIN_GROWTH_SPAN_MS + 1, 0, Some(1000)),
+            sample(DATABASE_GROWTH_KEY, NOW, 0, Some(3000)),
+        ];
+        let too_close = forecast(&samples, 7, &[5000], NOW);
+        assert_eq!(too_close.bytes_per_day, None);
+        assert_eq!(too_close.thresholds[0].days, None);
+        assert_eq!(too_close.thresholds[0].eta_ms, None);
+
+        let samples = vec![
+            sample(DATABASE_GROWTH_KEY, NOW - MIN_GROWTH_SPAN_MS, 0, Some(1000)),
+            sample(DATABASE_GROWTH_KEY, NOW, 0, Some(3000)),
+        ];
+        assert_eq!(forecast(&samples, 7, &[5000], NOW).bytes_per_day, Some(4000.0));
+    }
+
+    #[test]
+    fn reached_thresholds_are_due_now() {
+        let forecast = forecast(&database(&[(2, 1000), (1, 3000)]), 7, &[2000, 5000], NOW);
+        let reached = &forecast.thresholds[0];
+        assert!(reached.reached);
+        assert_eq!(reached.days, Some(0.0));
+        assert_eq!(reached.eta_ms, Some(NOW - DAY_MS));
+        assert!(!forecast.thresholds[1].reached);
+        assert_eq!(forecast.thresholds[1].days, Some(1.0));
+    }
+
+    #[test]
+    fn shrinking_database_has_no_eta() {
+        let forecast = forecast(&database(&[(2, 3000), (0, 2000)]), 7, &[1000, 5000], NOW);
+        assert_eq!(forecast.bytes_per_day, Some(-500.0));
+        assert!(forecast.thresholds[0].reached);
+        assert!(!forecast.thresholds[1].reached);
+        assert_eq!(forecast.thresholds[1].days, None);
+        assert_eq!(forecast.thresholds[1].eta_ms, None);
+    }
+
+    #[test]
+    fn samples_before_the_window_are_ignored() {
+        let forecast = forecast(&database(&[(10, 0), (2, 1000), (0, 3000)]), 7, &[], NOW);
+        assert_eq!(forecast.bytes_per_day, Some(1000.0));
+    }
+
+    #[test]
+    fn without_samples_nothing_is_reached() {
+        let forecast = forecast(&[sample("swaps", NOW, 5, None)], 7, &[1], NOW);
+        assert_eq!(forecast.size_bytes, 0);
+        assert_eq!(forecast.bytes_per_day, None);
+        assert_eq!(forecast.tables[0].bytes_per_day, None);
+        assert!(!forecast.thresholds[0].reached);
+        assert_eq!(forecast.thresholds[0].days, None);
+    }
+}
@@ -0,0 +1,265 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::Request,
+    http::{header, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use rusqlite::{params, Connection, Result};
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::db::api_key_hash;
+use crate::error::ApiError;
+use crate::query_log;
+use crate::usage::API_KEY_HEADER;
+
+/// Header clients name a request with, so retrying it replays the first
+/// response instead of creating the resource again
+pub const IDEMPOTENCY_KEY_HEADER: &str = "idempotency-key";
+
+/// Response header marking a response replayed from an earlier request
+pub const IDEMPOTENT_REPLAYED_HEADER: &str = "idempotent-replayed";
+
+/// How long a key's response is kept and replayed (24 hours)
+const IDEMPOTENCY_KEY_TTL_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Longest `Idempotency-Key` accepted
+const MAX_IDEMPOTENCY_KEY_LEN: usize = 255;
+
+/// Largest request or response body buffered, axum's default body limit
+const MAX_IDEMPOTENT_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// What an `Idempotency-Key` was first used for
+struct StoredRequest {
+    /// Fingerprint of the request (see `fingerprint`)
+    fingerprint: String,
+    /// Status and body of its response; `None` while it is being handled
+    response: Option<(u16, Vec<u8>)>,
+}
+
+/// Hashes what makes two requests the same: method, path and body.
+fn fingerprint(method: &str, path: &str, body: &[u8]) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(method.as_bytes());
+    hasher.update(b" ");
+    hasher.update(path.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(body);
+    format!("{:x}", hasher.finalize())
+}
+
+/// Claims a key for a new request, after dropping the keys that expired.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `scope` - Hash of the caller's API key, or empty without one
+/// * `key` - The `Idempotency-Key`
+/// * `fingerprint` - Fingerprint of the request
+/// * `now` - Current time (ms since epoch)
+/// 
+/// # Returns
+/// * `Result<Option<StoredRequest>>` - `None` if the key is now claimed for
+///   this request, or what it was first used for
+fn claim_key(conn: &Connection, scope: &str, key: &str, fingerprint: &str, now: i64) -> Result<Option<StoredRequest>> {
+    query_log::execute(
+        conn,
+        "expire_idempotency_keys",
+        "DELETE FROM idempotency_keys WHERE created_at <= ?1",
+        params![now - IDEMPOTENCY_KEY_TTL_MS],
+    )?;
+    let claimed = query_log::execute(
+        conn,
+        "claim_idempotency_key",
+        "INSERT OR IGNORE INTO idempotency_keys (scope, key, fingerprint, created_at) VALUES (?1, ?2, ?3, ?4)",
+        params![scope, key, fingerprint, now],
+    )?;
+    if claimed > 0 {
+        return Ok(None);
+    }
+    query_log::query_row(
+        conn,
+        "stored_idempotency_key",
+        "SELECT fingerprint, status, body FROM idempotency_keys WHERE scope = ?1 AND key = ?2",
+        params![scope, key],
+        |row| {
+            let status: Option<u16> = row.get(1)?;
+            let body: Option<Vec<u8>> = row.get(2)?;
+            Ok(StoredRequest { fingerprint: row.get(0)?, response: status.zip(body) })
+        },
+    )
+    .map(Some)
+}
+
+/// Stores the response of the request a key was claimed for.
+fn complete_key(conn: &Connection, scope: &str, key: &str, status: u16, body: &[u8]) -> Result<()> {
+    query_log::execute(
+        conn,
+        "complete_idempotency_key",
+        "UPDATE idempotency_keys SET status = ?3, body = ?4 WHERE scope = ?1 AND key = ?2",
+        params![scope, key, status, body],
+    )?;
+    Ok(())
+}
+
+/// Frees a key whose request failed without creating anything, so it can be
+/// retried.
+fn release_key(conn: &Connection, scope: &str, key: &str) -> Result<()> {
+    query_log::execute(
+        conn,
+        "release_idempotency_key",
+        "DELETE FROM idempotency_keys WHERE scope = ?1 AND key = ?2 AND status IS NULL",
+        params![scope, key],
+    )?;
+    Ok(())
+}
+
+/// Middleware making a resource-creating endpoint safe to retry.
+/// 
+/// A request carrying an `Idempotency-Key` header claims the key for the
+/// caller (its `X-API-Key`, or every caller without one) for 24 hours, and
+/// its successful response is stored with it. A retry with the same key,
+/// method, path and body gets the stored response back, marked with
+/// `Idempotent-Replayed: true`, without running the handler again. Reusing
+/// the key for a different request, or while the first is still being
+/// handled, is a conflict. A request that fails releases its key, since it
+/// created nothing. Requests without the header are served as usual.
+pub async fn idempotent(request: Request, next: Next) -> Response {
+    match replay_or_run(request, next).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn replay_or_run(request: Request, next: Next) -> Result<Response, ApiError> {
+    let Some(raw) = request.headers().get(IDEMPOTENCY_KEY_HEADER) else {
+        return Ok(next.run(request).await);
+    };
+    let key = raw
+        .to_str()
+        .ok()
+        .map(str::trim)
+        .filter(|key| !key.is_empty() && key.len() <= MAX_IDEMPOTENCY_KEY_LEN)
+        .ok_or_else(|| {
+            ApiError::BadRequest(format!(
+                "Idempotency-Key must be 1 to {} visible ASCII characters",
+                MAX_IDEMPOTENCY_KEY_LEN
+            ))
+        })?
+        .to_string();
+    let Some(conn_arc) = request.extensions().get::<Arc<Mutex<Connection>>>().cloned() else {
+        return Ok(next.run(request).await);
+    };
+    let scope = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(api_key_hash)
+        .unwrap_or_default();
+
+    let (parts, body) = request.into_parts();
+    let body = to_bytes(body, MAX_IDEMPOTENT_BODY_BYTES)
+        .await
+        .map_err(|_| ApiError::BadRequest("Request body is unreadable or too large".to_string()))?;
+    let fingerprint = fingerprint(parts.method.as_str(), parts.uri.path(), &body);
+    let stored = claim_key(&*conn_arc.lock()?, &scope, &key, &fingerprint, now_ms())?;
+    if let Some(stored) = stored {
+        if stored.fingerprint != fingerprint {
+            return Err(ApiError::Conflict("Idempotency-Key was already used for a different request".to_string()));
+        }
+        let Some((status, body)) = stored.response else {
+            return Err(ApiError::Conflict("A request with this Idempotency-Key is still in progress".to_string()));
+        };
+        return Ok(replayed_response(status, body));
+    }
+
+    let response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    if !response.status().is_success() {
+        if let Err(e) = release_key(&*conn_arc.lock()?, &scope, &key) {
+            warn!(error = %e, "failed to release idempotency key");
+        }
+        return Ok(response);
+    }
+    let (parts, body) = response.into_parts();
+    let body = to_bytes(body, MAX_IDEMPOTENT_BODY_BYTES)
+        .await
+        .map_err(|_| ApiError::Internal("Response body is unreadable or too large".to_string()))?;
+    // The resource exists by now: if storing its response fails, the key
+    // stays claimed, so retries are refused rather than creating it again
+    if let Err(e) = complete_key(&*conn_arc.lock()?, &scope, &key, parts.status.as_u16(), &body) {
+        warn!(error = %e, "failed to store idempotent response");
+    }
+    Ok(Response::from_parts(parts, Body::from(body)))
+}
+
+fn replayed_response(status: u16, body: Vec<u8>) -> Response {
+    let status = StatusCode::from_u16(status).unwrap_or(StatusCode::OK);
+    let mut response = (status, body).into_response();
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    headers.insert(IDEMPOTENT_REPLAYED_HEADER, HeaderValue::from_static("true"));
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn retry_replays_the_stored_response() {
+        let conn = test_db();
+        let request = fingerprint("POST", "/api/exports", br#"{"dataset":"swaps"}"#);
+        assert!(claim_key(&conn, "", "k1", &request, 1_000).unwrap().is_none());
+
+        let in_progress = claim_key(&conn, "", "k1", &request, 1_001).unwrap().unwrap();
+        assert!(in_progress.response.is_none());
+
+        complete_key(&conn, "", "k1", 202, b"{\"status\":\"ok\"}").unwrap();
+        let done = claim_key(&conn, "", "k1", &request, 1_002).unwrap().unwrap();
+        assert_eq!(done.fingerprint, request);
+        assert_eq!(done.response, Some((202, b"{\"status\":\"ok\"}".to_vec())));
+    }
+
+    #[test]
+    fn keys_are_scoped_per_caller_and_expire() {
+        let conn = test_db();
+        let first = fingerprint("POST", "/api/exports", br#"{"dataset":"swaps"}"#);
+        let other = fingerprint("POST", "/api/exports", br#"{"dataset":"pools"}"#);
+        assert_ne!(first, other);
+        assert!(claim_key(&conn, "alice", "k1", &first, 0).unwrap().is_none());
+        assert!(claim_key(&conn, "bob", "k1", &other, 0).unwrap().is_none());
+        assert_eq!(claim_key(&conn, "alice", "k1", &other, 0).unwrap().unwrap().fingerprint, first);
+
+        assert!(claim_key(&conn, "alice", "k1", &other, IDEMPOTENCY_KEY_TTL_MS).unwrap().is_none());
+    }
+
+    #[test]
+    fn released_key_can_be_claimed_again() {
+        let conn = test_db();
+        let request = fingerprint("POST", "/api/exports", b"{}");
+        assert!(claim_key(&conn, "", "k1", &request, 0).unwrap().is_none());
+        release_key(&conn, "", "k1").unwrap();
+        assert!(claim_key(&conn, "", "k1", &request, 1).unwrap().is_none());
+
+        // A completed response is never released
+        complete_key(&conn, "", "k1", 200, b"{}").unwrap();
+        release_key(&conn, "", "k1").unwrap();
+        assert!(claim_key(&conn, "", "k1", &request, 2).unwrap().is_some());
+    }
+}
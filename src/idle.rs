@@ -0,0 +1,96 @@
+use axum::{extract::Request, middleware::Next, response::Response, Json};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Notify;
+
+use crate::config::config;
+
+/// Default interval between indexer polling cycles while the API is idle (in seconds)
+pub const DEFAULT_IDLE_POLL_INTERVAL_SECS: u64 = 60;
+
+/// Timestamp of the last API request or WebSocket connection (in
+/// milliseconds), or of startup before the first one
+static LAST_ACTIVITY_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Wakes the indexer when traffic arrives while the process is idle
+static WAKE: Notify = Notify::const_new();
+
+fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Records activity now, waking the indexer if the process was idle.
+pub fn mark_active() {
+    let was_idle = is_idle();
+    LAST_ACTIVITY_MS.store(now_ms(), Ordering::Relaxed);
+    if was_idle {
+        WAKE.notify_waiters();
+    }
+}
+
+/// Seconds since the last API request or WebSocket connection.
+pub fn idle_for_secs() -> u64 {
+    now_ms().saturating_sub(LAST_ACTIVITY_MS.load(Ordering::Relaxed)) / 1000
+}
+
+/// Whether the process is idle: idle mode is enabled with `IDLE_AFTER_SECS`
+/// and nothing called the API for that long.
+pub fn is_idle() -> bool {
+    config().idle_after_secs.is_some_and(|after| idle_for_secs() >= after)
+}
+
+/// Interval to wait before the next indexer polling cycle: the configured
+/// one, or `IDLE_POLL_INTERVAL_SECS` while the process is idle, if longer.
+pub fn poll_interval_secs() -> u64 {
+    if is_idle() {
+        config().idle_poll_interval_secs.max(config().poll_interval_secs)
+    } else {
+        config().poll_interval_secs
+    }
+}
+
+/// Waits until traffic arrives while the process is idle.
+pub async fn woken() {
+    WAKE.notified().await;
+}
+
+/// Middleware counting every request it wraps as activity.
+/// 
+/// Health checks and metric scrapes aren't wrapped, so probes and monitoring
+/// don't keep an otherwise unused process awake.
+pub async fn track_activity(request: Request, next: Next) -> Response {
+    mark_active();
+    next.run(request).await
+}
+
+/// Reports whether the process is idle, for scale-to-zero platforms to
+/// decide when to stop it. Calling it doesn't count as activity.
+/// 
+/// # Endpoint
+/// `GET /idle`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "enabled": true,
+///   "idle": true,
+///   "idle_for_secs": 912,
+///   "idle_after_secs": 600,
+///   "poll_interval_secs": 60
+/// }
+/// ```
+pub async fn idle_handler() -> Json<Value> {
+    Json(json!({
+        "status": "ok",
+        "enabled": config().idle_after_secs.is_some(),
+        "idle": is_idle(),
+        "idle_for_secs": idle_for_secs(),
+        "idle_after_secs": config().idle_after_secs,
+        "poll_interval_secs": poll_interval_secs()
+    }))
+}
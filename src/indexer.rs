@@ -1,224 +1,2018 @@
 use rusqlite::Connection;
 use serde_json::Value;
-use std::{sync::Arc, sync::Mutex, time::{SystemTime, UNIX_EPOCH}};
+use std::collections::BTreeMap;
+use std::{sync::Arc, sync::Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
 use tokio::time::sleep;
-use std::time::Duration;
-use crate::db::{upsert_pool, insert_swap};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::db::{
+    upsert_pool, insert_swap, insert_failed_swap, insert_pool_snapshot, insert_pool_reconciliation, list_pool_ids, last_pool_swap,
+    record_ingest_batch, match_route_recommendations, match_quotes, pool_exists, swap_exists, IngestCheck,
+    load_indexer_state, save_indexer_state, IndexerState, load_event_cursor, save_event_cursor,
+    EventCursor, SwapSide, swaps_missing_epoch, set_swap_epoch, set_swap_check, swaps_to_check, reserves_before,
+    ReservesBefore, next_snapshot_at, inconsistent_swaps, tokens_missing_metadata, save_token_metadata, TokenMetadata,
+    load_failed_swap_cursor, save_failed_swap_cursor,
+};
+use crate::metrics::{
+    INDEXER_BACKLOG_EVENTS, INDEXER_CYCLES, INDEXER_CYCLE_DURATION, INDEXER_CYCLE_FAILURES, INDEXER_EVENTS_PROCESSED,
+    INDEXER_CIRCUIT_OPEN, RPC_ACTIVE_ENDPOINT, RPC_FAILOVERS, INDEXER_GAPS_DETECTED, INDEXER_INCONSISTENT_SWAPS, INDEXER_LAST_SYNC_MS, INDEXER_RPC_RETRIES, INDEXER_MALFORMED_EVENTS, INDEXER_REPLAY_MISMATCHES, INGEST_LATENCY, RPC_REQUESTS,
+};
+use crate::candles::rebuild_swap_candles;
+use crate::events::{DexEvent, SuiEvent};
+use crate::journal::{journal_path, EventJournal};
+use crate::live::{last_swap_id, publish_page, LiveFeed};
+use crate::config::config;
+use crate::idle;
+use crate::invalidation::Invalidator;
+use crate::graph_cache::{pools_changed, GraphCache};
+use crate::pool_cache::{notify_pool_updates, reload_pools, touched_pools, PoolCache};
+use crate::precision::audit_amount;
+use crate::shutdown::{requested, Shutdown};
+use crate::verify::swap_inconsistency;
+use tracing::{debug, field, info, info_span, trace, warn, Instrument, Span};
 
-/// Interval between polling cycles for new blockchain events (in seconds)
-const POLL_INTERVAL_SECS: u64 = 5;
+/// Default interval between polling cycles for new blockchain events (in seconds)
+pub const DEFAULT_POLL_INTERVAL_SECS: u64 = 5;
 
-/// Sui Move package ID for the Fooswap DEX contract
-/// This should be updated when deploying to different networks (devnet, testnet, mainnet)
-const DEX_PACKAGE_ID: &str = "0x1c2be4cfbf91fe8d71aedeb83cbe680475b70359bab87900df99ecd787ca5474";
+/// Maximum number of events requested from the Sui RPC per call
+const RPC_PAGE_LIMIT: usize = 100;
 
-/// Queries Sui blockchain for DEX events within a specified time range.
+/// Number of transactions inspected per pool and scan when looking for
+/// failed swaps
+const FAILED_TX_SCAN_LIMIT: usize = 50;
+
+/// Default interval between scans of the pools' transactions for failed
+/// swaps, when `FAILED_SWAP_SCAN_INTERVAL_SECS` is not configured (in seconds)
+pub const DEFAULT_FAILED_SWAP_SCAN_INTERVAL_SECS: u64 = 60;
+
+/// Number of swap transactions looked up per `sui_multiGetTransactionBlocks` call
+const EPOCH_LOOKUP_BATCH: usize = 50;
+
+/// Most epoch lookup calls per polling cycle, so a large backlog of swaps is
+/// worked off over several cycles instead of delaying the next one
+const EPOCH_LOOKUPS_PER_CYCLE: usize = 10;
+
+/// Most tokens whose coin metadata is fetched per polling cycle
+const TOKEN_METADATA_LOOKUPS_PER_CYCLE: usize = 20;
+
+/// Most inconsistent swaps whose pools are reconciled with their on-chain
+/// state per polling cycle
+const RECONCILIATIONS_PER_CYCLE: usize = 20;
+
+/// How long after a route recommendation a swap may still be matched to it (in milliseconds)
+const ROUTE_MATCH_WINDOW_MS: i64 = 5 * 60 * 1000;
+
+/// Allowed relative difference between a recommendation's and a swap's `amount_in`
+const ROUTE_MATCH_TOLERANCE: f64 = 0.01;
+
+/// How long after a quote expires it is still matched, leaving room for indexing lag (in milliseconds)
+const QUOTE_MATCH_GRACE_MS: i64 = 5 * 60 * 1000;
+
+/// Default number of events buffered in memory before they are committed
+/// to the database (overridable via `INDEXER_MAX_BUFFERED_EVENTS`)
+pub const DEFAULT_MAX_BUFFERED_EVENTS: usize = 1000;
+
+/// Default number of `suix_queryEvents` calls sent in one JSON-RPC batch
+/// request (overridable via `INDEXER_RPC_BATCH_SIZE`)
+pub const DEFAULT_RPC_BATCH_SIZE: usize = 10;
+
+/// Attempts made at fetching a round of event pages before the polling
+/// cycle gives up
+const RPC_RETRY_ATTEMPTS: u32 = 5;
+
+/// Delay before the first retry of a failed event query, doubled on each
+/// further retry (in milliseconds)
+const RPC_RETRY_BASE_MS: u64 = 250;
+
+/// Longest delay between two retries of an event query (in milliseconds)
+const RPC_RETRY_MAX_MS: u64 = 8_000;
+
+/// Consecutive failed polling cycles after which the circuit breaker opens
+/// and the poll interval starts widening
+const CIRCUIT_BREAKER_THRESHOLD: u32 = 3;
+
+/// Longest poll interval the circuit breaker widens to (in seconds)
+const CIRCUIT_BREAKER_MAX_INTERVAL_SECS: u64 = 5 * 60;
+
+/// Time a Sui RPC request may take before it is abandoned (in seconds)
+const RPC_TIMEOUT_SECS: u64 = 30;
+
+/// Consecutive failed requests after which the indexer fails over to the
+/// next endpoint listed in `SUI_RPC_URL`
+const RPC_FAILOVER_THRESHOLD: u64 = 3;
+
+/// Interval between probes of the primary endpoint while failed over (in seconds)
+const RPC_FAILBACK_PROBE_SECS: u64 = 60;
+
+/// Consecutive failed requests to the endpoint requests currently go to
+static ACTIVE_RPC_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Sui Move package ID of the Fooswap DEX contract on devnet, used when
+/// `DEX_PACKAGE_ID` is not configured
+pub const DEFAULT_DEX_PACKAGE_ID: &str = "0x1c2be4cfbf91fe8d71aedeb83cbe680475b70359bab87900df99ecd787ca5474";
+
+/// Sui RPC endpoint used when `SUI_RPC_URL` is not configured
+pub const DEFAULT_RPC_URL: &str = "https://fullnode.devnet.sui.io:443";
+
+/// Age beyond which the primary Sui RPC node is assumed to have pruned data
+/// when `RPC_PRUNING_HORIZON_SECS` is not configured (in seconds)
+pub const DEFAULT_PRUNING_HORIZON_SECS: u64 = 2 * 24 * 60 * 60;
+
+/// Returns the configured Sui Move package ID of the DEX contract (`DEX_PACKAGE_ID`).
+pub fn dex_package_id() -> &'static str {
+    &config().package_id
+}
+
+/// Returns the Sui RPC endpoint requests currently go to: the first one
+/// listed in `SUI_RPC_URL`, or a fallback while it is failing over.
+pub fn rpc_url() -> String {
+    let urls = &config().rpc_urls;
+    urls[RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed) as usize % urls.len()].clone()
+}
+
+/// Records whether a request to `rpc_url` failed outright (an error
+/// status, a timeout or no connection) for failover.
+/// 
+/// After `RPC_FAILOVER_THRESHOLD` consecutive failures of the endpoint
+/// requests currently go to, they move on to the next one listed in
+/// `SUI_RPC_URL`, wrapping around after the last. Outcomes of requests to
+/// any other endpoint, e.g. the archival node, are ignored.
+fn record_rpc_outcome(rpc_url: &str, failed: bool) {
+    record_endpoint_outcome(&config().rpc_urls, rpc_url, failed);
+}
+
+/// Failover of `record_rpc_outcome` among the endpoints `urls`, primary first.
+fn record_endpoint_outcome(urls: &[String], rpc_url: &str, failed: bool) {
+    let active = RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed);
+    if urls.len() < 2 || urls[active as usize % urls.len()] != rpc_url {
+        return;
+    }
+    if !failed {
+        ACTIVE_RPC_FAILURES.store(0, Ordering::Relaxed);
+        return;
+    }
+    if ACTIVE_RPC_FAILURES.fetch_add(1, Ordering::Relaxed) + 1 < RPC_FAILOVER_THRESHOLD {
+        return;
+    }
+    let next = (active + 1) % urls.len() as u64;
+    // Concurrent failures switch only once
+    if RPC_ACTIVE_ENDPOINT.compare_exchange(active, next, Ordering::Relaxed, Ordering::Relaxed).is_ok() {
+        ACTIVE_RPC_FAILURES.store(0, Ordering::Relaxed);
+        RPC_FAILOVERS.fetch_add(1, Ordering::Relaxed);
+        warn!(from = %rpc_url, to = %urls[next as usize], "Sui RPC keeps failing, failing over to the next endpoint");
+    }
+}
+
+/// Moves requests back to the primary endpoint once it answers again.
+/// 
+/// While failed over, the primary is probed at most every
+/// `RPC_FAILBACK_PROBE_SECS` with `sui_getLatestCheckpointSequenceNumber`.
+async fn fail_back(client: &reqwest::Client, last_probe: &mut Instant) {
+    if RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed) == 0 {
+        *last_probe = Instant::now();
+        return;
+    }
+    if last_probe.elapsed() < Duration::from_secs(RPC_FAILBACK_PROBE_SECS) {
+        return;
+    }
+    *last_probe = Instant::now();
+    let primary = &config().rpc_urls[0];
+    match rpc_call(client, primary, "sui_getLatestCheckpointSequenceNumber", serde_json::json!([])).await {
+        Ok(_) => {
+            RPC_ACTIVE_ENDPOINT.store(0, Ordering::Relaxed);
+            ACTIVE_RPC_FAILURES.store(0, Ordering::Relaxed);
+            info!(rpc_url = %primary, "Primary Sui RPC answers again, failing back");
+        }
+        Err(e) => debug!(rpc_url = %primary, error = %e, "primary Sui RPC still failing"),
+    }
+}
+
+/// Picks the Sui RPC endpoint for a request reading data from `data_ts`.
 /// 
-/// This function fetches both PoolCreatedEvent and SwapEvent types from the Sui RPC
-/// using the `suix_queryEvents` method. Events are retrieved in batches of 100.
+/// Data older than the primary node's pruning horizon
+/// (`RPC_PRUNING_HORIZON_SECS`) is read from the archival node when
+/// `SUI_ARCHIVE_RPC_URL` is set, and everything else from `SUI_RPC_URL`.
 /// 
 /// # Arguments
-/// * `from_ts` - Start timestamp (inclusive) in milliseconds since epoch
-/// * `to_ts` - End timestamp (exclusive) in milliseconds since epoch
+/// * `data_ts` - Timestamp of the oldest data the request reads (ms), `None` if unknown
+/// * `now` - Current timestamp in milliseconds
+pub fn rpc_url_for(data_ts: Option<i64>, now: i64) -> String {
+    let horizon_ms = config().pruning_horizon_secs.saturating_mul(1000) as i64;
+    match &config().archive_rpc_url {
+        Some(archive) if data_ts.is_some_and(|ts| ts < now - horizon_ms) => archive.clone(),
+        _ => rpc_url(),
+    }
+}
+
+/// Label of the endpoint behind `rpc_url` in the RPC metrics: `archive` for
+/// `SUI_ARCHIVE_RPC_URL`, `fallback` for the fallbacks listed in
+/// `SUI_RPC_URL`, `live` otherwise.
+fn rpc_endpoint(rpc_url: &str) -> &'static str {
+    if config().archive_rpc_url.as_deref() == Some(rpc_url) {
+        "archive"
+    } else if config().rpc_urls[1..].iter().any(|url| url == rpc_url) {
+        "fallback"
+    } else {
+        "live"
+    }
+}
+
+/// Builds the HTTP client used for Sui RPC calls.
 /// 
-/// # Returns
-/// * `Result<Vec<serde_json::Value>>` - Vector of event JSON objects or error
-async fn query_sui_events(
-    from_ts: i64,
-    to_ts: i64,
-) -> Result<Vec<serde_json::Value>, Box<dyn std::error::Error>> {
-    let rpc_url = std::env::var("SUI_RPC_URL")
-        .unwrap_or_else(|_| "https://fullnode.devnet.sui.io:443".to_string());
-    let client = reqwest::Client::new();
-    let mut all_events = Vec::new();
-    
-    // Define the event types to query from the Sui Move contract
-    let event_types = [
-        format!("{}::fooswap::PoolCreatedEvent", DEX_PACKAGE_ID),
-        format!("{}::fooswap::SwapEvent", DEX_PACKAGE_ID),
-    ];
-    
-    for event_type in event_types.iter() {
-        // Use timestamp-based filtering to avoid fetching duplicate events
-        let request_body = serde_json::json!({
-            "jsonrpc": "2.0",
-            "id": 1,
-            "method": "suix_queryEvents",
-            "params": [
-                { "MoveEventType": event_type },
-                null,  // cursor (null for latest)
-                100,   // limit
-                false, // descending order
-                {      // time range filter
-                    "TimeRange": {
-                        "start_time": from_ts,
-                        "end_time": to_ts
-                    }
+/// By default the standard `HTTP_PROXY`, `HTTPS_PROXY`, `ALL_PROXY` and
+/// `NO_PROXY` variables apply. Requests time out after `RPC_TIMEOUT_SECS`,
+/// which counts as a failure for failover. `SUI_RPC_PROXY` overrides them for RPC calls
+/// only: an `http://`, `https://` or `socks5://` URL sends every request
+/// through that proxy, and `none` connects directly. The override is
+/// validated when the configuration is loaded.
+pub fn rpc_client() -> reqwest::Client {
+    let mut builder = reqwest::Client::builder().timeout(Duration::from_secs(RPC_TIMEOUT_SECS));
+    match config().rpc_proxy.as_deref() {
+        None => {}
+        Some("none") => builder = builder.no_proxy(),
+        Some(url) => match rpc_proxy(url, config().rpc_no_proxy.as_deref()) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => warn!(error = %e, "ignoring invalid SUI_RPC_PROXY"),
+        },
+    }
+    builder.build().unwrap_or_default()
+}
+
+/// Builds the `SUI_RPC_PROXY` proxy, bypassed for the hosts listed in
+/// `SUI_RPC_NO_PROXY` (or `NO_PROXY` when that is unset).
+/// 
+/// Lists are comma-separated host names, domains and IP ranges, e.g.
+/// `localhost,.internal,10.0.0.0/8`.
+pub fn rpc_proxy(url: &str, no_proxy: Option<&str>) -> reqwest::Result<reqwest::Proxy> {
+    let no_proxy = match no_proxy {
+        Some(list) => reqwest::NoProxy::from_string(list),
+        None => reqwest::NoProxy::from_env(),
+    };
+    Ok(reqwest::Proxy::all(url)?.no_proxy(no_proxy))
+}
+
+/// Whether this process runs the indexer (`INDEXER_ENABLED`, default on).
+/// 
+/// API replicas serving a database indexed by another node set it to `0` or
+/// `false`, and receive cache invalidations from that node instead.
+pub fn indexer_enabled() -> bool {
+    config().indexer_enabled
+}
+
+/// Follows the progress the indexing process saves in `indexer_state`, for
+/// processes serving a database they don't index, so their stale-data guard
+/// and `last_sync_ms` track it even without cache invalidations. Progress
+/// also wakes requests waiting for a price update.
+/// 
+/// Rechecks every `POLL_INTERVAL_SECS`; progress reported by an invalidation
+/// in between is never moved back.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection wrapped in Arc<Mutex<Connection>>
+pub async fn follow_indexer_state(conn_arc: Arc<Mutex<Connection>>) {
+    loop {
+        let saved = conn_arc.lock().ok().map(|conn| load_indexer_state(&conn, dex_package_id()));
+        match saved {
+            Some(Ok(Some(state))) => {
+                let previous = INDEXER_LAST_SYNC_MS.fetch_max(state.last_ts as u64, Ordering::Relaxed);
+                // Without invalidations this is the only sign that pools may have changed
+                if previous < state.last_ts as u64 {
+                    notify_pool_updates();
                 }
-            ]
-        });
-        
-        println!("Querying Sui RPC: {}", rpc_url);
-        println!("Request body: {}", serde_json::to_string_pretty(&request_body).unwrap());
-        
-        let resp = client
-            .post(&rpc_url)
-            .json(&request_body)
-            .send()
-            .await?;
-            
+            }
+            Some(Err(e)) => warn!(error = %e, "failed to load indexer state"),
+            _ => {}
+        }
+        sleep(Duration::from_secs(config().poll_interval_secs)).await;
+    }
+}
+
+/// One page of events returned by `suix_queryEvents`
+struct EventPage {
+    events: Vec<Value>,
+    /// Cursor of the next page, or `None` on the last page
+    next_cursor: Option<Value>,
+}
+
+impl EventPage {
+    /// Reads a page from the `result` of a `suix_queryEvents` response.
+    fn from_result(result: &Value) -> EventPage {
+        let events = result["data"].as_array().cloned().unwrap_or_default();
+        let next_cursor = match result["hasNextPage"].as_bool() {
+            Some(true) if !result["nextCursor"].is_null() => Some(result["nextCursor"].clone()),
+            _ => None,
+        };
+        EventPage { events, next_cursor }
+    }
+}
+
+/// Builds the `suix_queryEvents` request for the page of `event_type` after `cursor`.
+fn query_events_request(id: usize, event_type: &str, cursor: &Value) -> Value {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "method": "suix_queryEvents",
+        "params": [
+            { "MoveEventType": event_type },
+            cursor,          // last event processed (null for the first page)
+            RPC_PAGE_LIMIT,  // limit
+            false            // descending order
+        ]
+    })
+}
+
+/// Sends a JSON-RPC request, single or batch, and returns the response body.
+async fn post_rpc(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    request_body: &Value,
+) -> Result<Value, Box<dyn std::error::Error>> {
+    debug!(rpc_url = %rpc_url, "Querying Sui RPC");
+    trace!(request = %request_body, "Sui RPC request");
+
+    // A batch is counted under the method of its first call
+    let method = request_body["method"].as_str().or(request_body[0]["method"].as_str()).unwrap_or_default();
+    let result = async {
+        let resp = client.post(rpc_url).json(request_body).send().await?;
         if !resp.status().is_success() {
             return Err(format!("Sui RPC returned error status: {}", resp.status()).into());
         }
-        
-        let json: serde_json::Value = resp.json().await?;
-        println!("Response: {}", serde_json::to_string_pretty(&json).unwrap());
-        
-        // Extract events from the RPC response
-        if let Some(data) = json.get("result").and_then(|r| r.get("data")).and_then(|d| d.as_array()) {
-            for event in data {
-                all_events.push(event.clone());
+        let json: Value = resp.json().await?;
+        Ok::<_, Box<dyn std::error::Error>>(json)
+    }
+    .await;
+    let failed = result.as_ref().map_or(true, |json| !json["error"].is_null());
+    RPC_REQUESTS.observe(rpc_endpoint(rpc_url), method, failed);
+    record_rpc_outcome(rpc_url, result.is_err());
+
+    let json = result?;
+    trace!(response = %json, "Sui RPC response");
+    Ok(json)
+}
+
+/// Fetches one page of events of one type.
+async fn fetch_event_page(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    event_type: &str,
+    cursor: &Value,
+) -> Result<EventPage, Box<dyn std::error::Error>> {
+    let json = post_rpc(client, rpc_url, &query_events_request(1, event_type, cursor)).await?;
+    if let Some(message) = json["error"]["message"].as_str() {
+        return Err(message.into());
+    }
+    Ok(EventPage::from_result(&json["result"]))
+}
+
+/// Delay before retry number `attempt` (from 0) of a failed RPC request.
+/// 
+/// The delay doubles with each attempt up to `RPC_RETRY_MAX_MS`, and a random
+/// half of it is jittered away, so indexers that lost the same node don't
+/// all retry in lockstep.
+fn retry_delay(attempt: u32) -> Duration {
+    let delay_ms = RPC_RETRY_BASE_MS.saturating_mul(1 << attempt.min(16)).min(RPC_RETRY_MAX_MS);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.subsec_nanos() as u64)
+        .unwrap_or(0);
+    Duration::from_millis(delay_ms / 2 + nanos % (delay_ms / 2 + 1))
+}
+
+/// Widens the poll interval while the RPC keeps failing.
+/// 
+/// The breaker opens after `CIRCUIT_BREAKER_THRESHOLD` consecutive failed
+/// cycles, and from then on doubles the poll interval with every further
+/// failure, up to `CIRCUIT_BREAKER_MAX_INTERVAL_SECS`. A single successful
+/// cycle closes it again, so catching up after an outage runs at the
+/// configured interval.
+#[derive(Default)]
+struct CircuitBreaker {
+    /// Consecutive failed polling cycles
+    failures: u32,
+}
+
+impl CircuitBreaker {
+    /// Records the outcome of a polling cycle.
+    fn record(&mut self, succeeded: bool) {
+        let was_open = self.is_open();
+        self.failures = if succeeded { 0 } else { self.failures.saturating_add(1) };
+        if self.is_open() && !was_open {
+            warn!(failures = self.failures, "Sui RPC keeps failing, widening the poll interval");
+        } else if was_open && !self.is_open() {
+            info!("Sui RPC recovered, polling at the configured interval again");
+        }
+        INDEXER_CIRCUIT_OPEN.store(self.is_open() as u64, Ordering::Relaxed);
+    }
+
+    fn is_open(&self) -> bool {
+        self.failures >= CIRCUIT_BREAKER_THRESHOLD
+    }
+
+    /// Interval to wait before the next polling cycle, given the configured one.
+    fn poll_interval(&self, configured_secs: u64) -> Duration {
+        if !self.is_open() {
+            return Duration::from_secs(configured_secs);
+        }
+        let doublings = (self.failures - CIRCUIT_BREAKER_THRESHOLD + 1).min(16);
+        let widened = configured_secs.saturating_mul(1 << doublings);
+        Duration::from_secs(widened.min(CIRCUIT_BREAKER_MAX_INTERVAL_SECS.max(configured_secs)))
+    }
+}
+
+/// Fetches one page of events for each `(event_type, cursor)` query.
+/// 
+/// Queries are sent as JSON-RPC batch requests of up to `batch_size` calls.
+/// If a batch fails, or the node answers it with anything but a result for
+/// every call (nodes without batch support do), its queries are sent one by
+/// one instead.
+/// 
+/// # Returns
+/// * `Result<Vec<EventPage>>` - A page per query, in the order of `queries`
+async fn fetch_event_pages(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    queries: &[(&str, &Value)],
+    batch_size: usize,
+) -> Result<Vec<EventPage>, Box<dyn std::error::Error>> {
+    let mut pages = Vec::with_capacity(queries.len());
+    for chunk in queries.chunks(batch_size) {
+        if chunk.len() > 1 {
+            let request_body: Vec<Value> = chunk
+                .iter()
+                .enumerate()
+                .map(|(id, (event_type, cursor))| query_events_request(id, event_type, cursor))
+                .collect();
+            match post_rpc(client, rpc_url, &Value::Array(request_body)).await {
+                Ok(Value::Array(responses)) => {
+                    // Responses to a batch may arrive in any order
+                    let results: Option<Vec<&Value>> = (0..chunk.len())
+                        .map(|id| {
+                            responses
+                                .iter()
+                                .find(|r| r["id"].as_u64() == Some(id as u64))
+                                .filter(|r| r["error"].is_null() && !r["result"].is_null())
+                                .map(|r| &r["result"])
+                        })
+                        .collect();
+                    if let Some(results) = results {
+                        pages.extend(results.into_iter().map(EventPage::from_result));
+                        continue;
+                    }
+                    warn!("Sui RPC did not answer every call of a batch request, retrying one by one");
+                }
+                Ok(_) => warn!("Sui RPC does not support batch requests, retrying one by one"),
+                Err(e) => warn!(error = %e, "batch request to Sui RPC failed, retrying one by one"),
+            }
+        }
+        for (event_type, cursor) in chunk {
+            pages.push(fetch_event_page(client, rpc_url, event_type, cursor).await?);
+        }
+    }
+    Ok(pages)
+}
+
+/// Queries Sui blockchain for DEX events of several types, each after its own cursor.
+/// 
+/// Events are retrieved in ascending order with the `suix_queryEvents`
+/// method, in pages of `RPC_PAGE_LIMIT`, following `nextCursor` until
+/// `hasNextPage` is false. The pages of one type follow each other's cursor,
+/// so each round fetches the next page of every type that has more, sharing
+/// a JSON-RPC batch request of up to `batch_size` calls.
+/// 
+/// Types are committed in the order given, so pools are indexed before the
+/// swaps against them: a page of a later type is held, and its type left out
+/// of further rounds, until every earlier type has been read to the end, so
+/// no page is fetched twice. When polling for new events, every type then
+/// fits in a single request.
+/// 
+/// Fetched events are buffered in memory and handed to `on_page` whenever a
+/// type's buffer reaches `max_buffered` events (and once more when the type
+/// is read to the end), so a large backfill never holds more than one
+/// buffer's worth of events at a time, besides at most one held page per
+/// type. Fetching stops early if `on_page` returns `false`.
+/// 
+/// Each type's pages are requested from the endpoint `rpc_url_for` picks for
+/// the timestamp of the last event read, so a backfill reads pages older
+/// than the primary node's pruning horizon from the archival node and
+/// switches to the primary once it gets past it. Types without a known
+/// position read from the primary node.
+/// 
+/// A round whose requests fail is retried up to `RPC_RETRY_ATTEMPTS` times
+/// with exponential backoff and jitter (see `retry_delay`) before the error
+/// is returned. Nothing of a round is committed before all of it arrived,
+/// so retrying never hands the same events to `on_page` twice.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC calls
+/// * `queries` - Fully qualified Move event types, each with the ID of the last event already processed (null to start from the first event) and that event's timestamp, if known
+/// * `batch_size` - Maximum number of calls per batch request
+/// * `max_buffered` - Maximum number of events to buffer before flushing
+/// * `on_page` - Callback that commits a buffered page of events of a type
+/// 
+/// # Returns
+/// * `Result<usize>` - Total number of events fetched or error
+async fn query_sui_events<F>(
+    client: &reqwest::Client,
+    queries: Vec<(&str, Value, Option<i64>)>,
+    batch_size: usize,
+    max_buffered: usize,
+    mut on_page: F,
+) -> Result<usize, Box<dyn std::error::Error>>
+where
+    F: FnMut(&str, &[Value]) -> bool,
+{
+    let mut cursors: Vec<Option<Value>> = queries.iter().map(|(_, cursor, _)| Some(cursor.clone())).collect();
+    let mut positions: Vec<Option<i64>> = queries.iter().map(|(_, _, position)| *position).collect();
+    let mut buffers: Vec<Vec<Value>> = vec![Vec::new(); queries.len()];
+    // Fetched pages waiting for earlier types to be read to the end
+    let mut held: Vec<Option<EventPage>> = queries.iter().map(|_| None).collect();
+    let mut total = 0;
+
+    // Each round fetches the next page of every type not yet read to the end
+    // and not already holding one
+    while cursors.iter().any(Option::is_some) {
+        let pending: Vec<usize> = (0..queries.len()).filter(|&i| cursors[i].is_some() && held[i].is_none()).collect();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let urls: Vec<String> = pending.iter().map(|&i| rpc_url_for(positions[i], now)).collect();
+
+        // Types read from the same endpoint share its batch requests
+        let mut pages: Vec<Option<EventPage>> = pending.iter().map(|_| None).collect();
+        let mut endpoints = urls.clone();
+        endpoints.sort();
+        endpoints.dedup();
+        for url in &endpoints {
+            let members: Vec<usize> = (0..pending.len()).filter(|&k| &urls[k] == url).collect();
+            let round: Vec<(&str, &Value)> = members
+                .iter()
+                .filter_map(|&k| Some((queries[pending[k]].0, cursors[pending[k]].as_ref()?)))
+                .collect();
+            let mut attempt = 0;
+            let fetched = loop {
+                // Retries follow a failover of the live endpoint
+                let url = if rpc_endpoint(url) == "archive" { url.clone() } else { rpc_url() };
+                let delay = match fetch_event_pages(client, &url, &round, batch_size).await {
+                    Ok(fetched) => break fetched,
+                    Err(e) if attempt + 1 >= RPC_RETRY_ATTEMPTS => return Err(e),
+                    Err(e) => {
+                        let delay = retry_delay(attempt);
+                        warn!(
+                            error = %e,
+                            attempt = attempt + 1,
+                            retry_in_ms = delay.as_millis() as u64,
+                            "failed to query Sui events, retrying"
+                        );
+                        delay
+                    }
+                };
+                INDEXER_RPC_RETRIES.fetch_add(1, Ordering::Relaxed);
+                attempt += 1;
+                sleep(delay).await;
+            };
+            for (k, page) in members.into_iter().zip(fetched) {
+                pages[k] = Some(page);
+            }
+        }
+
+        for (&i, page) in pending.iter().zip(pages.into_iter().flatten()) {
+            held[i] = Some(page);
+        }
+
+        for i in 0..queries.len() {
+            // Wait for earlier types to finish before committing a page of this one
+            if cursors[..i].iter().any(Option::is_some) {
+                break;
+            }
+            let Some(page) = held[i].take() else { continue };
+            let event_type = queries[i].0;
+            let last_ts = page.events.last().and_then(event_timestamp);
+            for event in page.events {
+                buffers[i].push(event);
+                total += 1;
+                INDEXER_BACKLOG_EVENTS.store(buffers[i].len() as u64, Ordering::Relaxed);
+
+                // Commit a full page before fetching any more events
+                if buffers[i].len() >= max_buffered {
+                    let proceed = on_page(event_type, &buffers[i]);
+                    buffers[i].clear();
+                    INDEXER_BACKLOG_EVENTS.store(0, Ordering::Relaxed);
+                    if !proceed {
+                        return Ok(total);
+                    }
+                }
+            }
+            cursors[i] = page.next_cursor;
+            positions[i] = last_ts.or(positions[i]);
+
+            // Commit whatever is left in the final, partially filled page
+            if cursors[i].is_none() && !buffers[i].is_empty() {
+                let proceed = on_page(event_type, &buffers[i]);
+                buffers[i].clear();
+                INDEXER_BACKLOG_EVENTS.store(0, Ordering::Relaxed);
+                if !proceed {
+                    return Ok(total);
+                }
+            }
+        }
+    }
+    Ok(total)
+}
+
+/// Finds failed transactions that used a pool object as input, after a cursor.
+/// 
+/// The contract emits no event when a swap aborts (e.g. on slippage), so
+/// failures are found by querying the pool's transactions via
+/// `suix_queryTransactionBlocks`, oldest first from `cursor`, and keeping
+/// those whose effects report a `failure` status.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC call
+/// * `rpc_url` - Sui RPC endpoint
+/// * `pool_id` - Pool object to inspect
+/// * `cursor` - Digest of the last transaction already scanned, if any
+/// 
+/// # Returns
+/// * `Result<(Vec<(String, i64, String)>, Option<String>)>` - `(tx_digest, timestamp, error)`
+///   for each failure, and the digest of the last transaction of the page, if any
+async fn query_failed_transactions(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    pool_id: &str,
+    cursor: Option<&str>,
+) -> Result<(Vec<(String, i64, String)>, Option<String>), Box<dyn std::error::Error>> {
+    let request_body = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "suix_queryTransactionBlocks",
+        "params": [
+            {
+                "filter": { "InputObject": pool_id },
+                "options": { "showEffects": true }
+            },
+            cursor,               // cursor (null for the pool's first transaction)
+            FAILED_TX_SCAN_LIMIT, // limit
+            false                 // ascending order (oldest first)
+        ]
+    });
+
+    let json = post_rpc(client, rpc_url, &request_body).await?;
+    if let Some(message) = json["error"]["message"].as_str() {
+        return Err(message.into());
+    }
+
+    let mut failures = Vec::new();
+    let mut last = None;
+    if let Some(data) = json["result"]["data"].as_array() {
+        for tx in data {
+            let digest = tx["digest"].as_str().unwrap_or_default().to_string();
+            if !digest.is_empty() {
+                last = Some(digest.clone());
+            }
+            let status = &tx["effects"]["status"];
+            if status["status"].as_str() != Some("failure") {
+                continue;
+            }
+            let ts = tx["timestampMs"].as_str().unwrap_or("0").parse::<i64>().unwrap_or(0);
+            let error = status["error"].as_str().unwrap_or("unknown").to_string();
+            failures.push((digest, ts, error));
+        }
+    }
+    Ok((failures, last))
+}
+
+/// Records failed transactions for every indexed pool, reading one page of
+/// each pool's transactions after its saved cursor.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `client` - HTTP client shared with the rest of the indexer
+async fn index_failed_swaps(conn_arc: &Arc<Mutex<Connection>>, client: &reqwest::Client) {
+    let pool_ids = match conn_arc.lock() {
+        Ok(conn) => list_pool_ids(&conn).unwrap_or_default(),
+        Err(_) => return,
+    };
+
+    for pool_id in pool_ids {
+        let cursor = match conn_arc.lock() {
+            Ok(conn) => load_failed_swap_cursor(&conn, &pool_id),
+            Err(_) => return,
+        };
+        let cursor = match cursor {
+            Ok(cursor) => cursor,
+            Err(e) => {
+                warn!(pool_id = %pool_id, error = %e, "failed to load failed swap cursor");
+                continue;
+            }
+        };
+        match query_failed_transactions(client, &rpc_url(), &pool_id, cursor.as_deref()).await {
+            Ok((failures, last)) => {
+                if let Ok(conn) = conn_arc.lock() {
+                    for (digest, ts, error) in failures {
+                        let _ = insert_failed_swap(&conn, &pool_id, &error, ts, &digest);
+                    }
+                    if let Some(last) = last {
+                        let now = SystemTime::now()
+                            .duration_since(UNIX_EPOCH)
+                            .map(|d| d.as_millis() as i64)
+                            .unwrap_or(0);
+                        if let Err(e) = save_failed_swap_cursor(&conn, &pool_id, &last, now) {
+                            warn!(pool_id = %pool_id, error = %e, "failed to save failed swap cursor");
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                warn!(pool_id = %pool_id, error = %e, "failed to query transactions for pool");
+                // Leave the other pools to the next scan rather than pile on a failing RPC
+                return;
             }
         }
     }
-    Ok(all_events)
 }
 
 /// Processes blockchain events and persists them to the local SQLite database.
 /// 
-/// This function parses Sui Move events from the JSON-RPC response format and
-/// extracts relevant data for pool creation and swap operations. Each event
-/// type is handled differently based on the Move contract's event structure.
+/// Each event is deserialized into the typed struct for its Move event type
+/// (see `events`). Events that don't deserialize, e.g. because a field is
+/// missing, empty or not a u64, are logged and skipped.
 /// 
 /// # Arguments
 /// * `conn` - SQLite database connection
 /// * `events` - Array of event JSON objects from Sui RPC
-fn process_events(conn: &Connection, events: &[Value]) {
+/// 
+/// # Returns
+/// * `Result<()>` - The first failed read or write, after which the rest of
+///   the page is not applied
+fn process_events(conn: &Connection, events: &[Value]) -> rusqlite::Result<()> {
+    for evt in events {
+        let event = match DexEvent::parse(evt) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(e) => {
+                skip_malformed(evt, &e);
+                continue;
+            }
+        };
+
+        match event {
+            DexEvent::PoolCreated(SuiEvent { parsed_json: created, timestamp_ms, .. }) => {
+                let ts = timestamp_ms as i64;
+                let initial_reserve_a = amount(created.initial_reserve_a, "initial_reserve_a");
+                let initial_reserve_b = amount(created.initial_reserve_b, "initial_reserve_b");
+
+                debug!(
+                    pool_id = %created.pool_id,
+                    token_a = %created.token_a,
+                    token_b = %created.token_b,
+                    reserve_a = initial_reserve_a,
+                    reserve_b = initial_reserve_b,
+                    "Processing PoolCreatedEvent"
+                );
+
+                // Persist pool data to database
+                upsert_pool(
+                    conn,
+                    &created.pool_id,
+                    &created.token_a,
+                    &created.token_b,
+                    initial_reserve_a,
+                    initial_reserve_b,
+                    ts,
+                )?;
+                insert_pool_snapshot(conn, &created.pool_id, initial_reserve_a, initial_reserve_b, ts)?;
+                check_swaps_around(conn, &created.pool_id, ts)?;
+            }
+            DexEvent::Swap(SuiEvent { id, sender, parsed_json: swap, timestamp_ms }) => {
+                let ts = timestamp_ms as i64;
+                let amount_in = amount(swap.amount_in, "amount_in");
+                let amount_out = amount(swap.amount_out, "amount_out");
+                let new_reserve_a = amount(swap.new_reserve_a, "new_reserve_a");
+                let new_reserve_b = amount(swap.new_reserve_b, "new_reserve_b");
+
+                debug!(
+                    pool_id = %swap.pool_id,
+                    amount_in,
+                    amount_out,
+                    new_reserve_a,
+                    new_reserve_b,
+                    "Processing SwapEvent"
+                );
+
+                // The trader is the one the event names, else the transaction's sender
+                let side = SwapSide {
+                    sender: swap.sender.or(sender),
+                    new_reserves: (new_reserve_a, new_reserve_b),
+                };
+
+                // Record the swap transaction
+                insert_swap(conn, &swap.pool_id, amount_in, amount_out, ts, &id, &side)?;
+
+                // Update pool reserves to reflect the swap
+                upsert_pool(conn, &swap.pool_id, "", "", new_reserve_a, new_reserve_b, ts)?;
+                insert_pool_snapshot(conn, &swap.pool_id, new_reserve_a, new_reserve_b, ts)?;
+                check_swaps_around(conn, &swap.pool_id, ts)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks the swaps a new snapshot of a pool at `timestamp` can tell about:
+/// those at the same timestamp, and those at the pool's next snapshot, for
+/// which it may now be the latest earlier one. See `check_swaps`.
+fn check_swaps_around(conn: &Connection, pool_id: &str, timestamp: i64) -> rusqlite::Result<()> {
+    check_swaps(conn, pool_id, timestamp)?;
+    if let Some(next) = next_snapshot_at(conn, pool_id, timestamp)? {
+        check_swaps(conn, pool_id, next)?;
+    }
+    Ok(())
+}
+
+/// Works out which way a pool's swaps at `timestamp` traded, and flags those
+/// whose reserves don't add up.
+/// 
+/// `SwapEvent` doesn't say which token was sold. Selling token A for token B
+/// means the pool held `(new_a - amount_in, new_b + amount_out)` before the
+/// swap, selling token B `(new_a + amount_out, new_b - amount_in)`; the
+/// direction is the one whose reserves match a snapshot of the pool just
+/// before the swap (see `reserves_before`). Without an earlier snapshot the
+/// swap can't be checked, and is `unknown` unless it follows a swap of the
+/// same checkpoint. With one, a swap matching neither is `unknown` and
+/// flagged `inconsistent`, for its pool to be reconciled with its on-chain
+/// state after the cycle.
+/// 
+/// The outcome only depends on the snapshots stored, so swaps are checked
+/// again as snapshots arrive (see `check_swaps_around`) and end up the same
+/// whatever order their events were delivered in. Candles already rolled
+/// up from a swap whose direction changed are rebuilt.
+fn check_swaps(conn: &Connection, pool_id: &str, timestamp: i64) -> rusqlite::Result<()> {
+    let ReservesBefore { earlier, same } = reserves_before(conn, pool_id, timestamp)?;
+    for swap in swaps_to_check(conn, pool_id, timestamp)? {
+        // The swap's own snapshot is not a state before it
+        let candidates: Vec<(f64, f64)> = earlier
+            .iter()
+            .chain(same.iter().filter(|reserves| **reserves != swap.new_reserves))
+            .copied()
+            .collect();
+        let matches = |direction: &str| {
+            candidates.iter().any(|&previous| {
+                swap_inconsistency(previous, swap.amount_in, swap.amount_out, swap.new_reserves, direction).is_none()
+            })
+        };
+        let (direction, inconsistent) = if matches("a_to_b") {
+            ("a_to_b", false)
+        } else if matches("b_to_a") {
+            ("b_to_a", false)
+        } else {
+            ("unknown", !earlier.is_empty())
+        };
+        if direction == swap.direction && inconsistent == swap.inconsistent {
+            continue;
+        }
+
+        if inconsistent && !swap.inconsistent {
+            INDEXER_INCONSISTENT_SWAPS.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                pool_id = %pool_id,
+                tx_digest = %swap.tx_digest,
+                event_seq = swap.event_seq,
+                "inconsistent swap: its reserves before match no snapshot of the pool"
+            );
+        }
+        set_swap_check(conn, swap.id, direction, inconsistent)?;
+        if direction != swap.direction {
+            rebuild_swap_candles(conn, swap.id, pool_id, timestamp)?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a string field that must be present and non-empty.
+fn required_str<'a>(value: &'a Value, field: &str) -> Option<&'a str> {
+    value[field].as_str().filter(|s| !s.is_empty())
+}
+
+/// Converts an event amount to the float it is stored as. In precision
+/// audit mode the float is compared against the exact integer.
+fn amount(raw: u64, field: &str) -> f64 {
+    let value = raw as f64;
+    audit_amount(field, &raw.to_string(), value);
+    value
+}
+
+/// Logs and counts an event that doesn't deserialize as its type.
+/// 
+/// Such an event is not written at all: defaulting the missing fields would
+/// record a swap on an empty pool ID or set a pool's reserves to zero.
+fn skip_malformed(evt: &Value, error: &serde_json::Error) {
+    INDEXER_MALFORMED_EVENTS.fetch_add(1, Ordering::Relaxed);
+    warn!(
+        event_type = evt["type"].as_str().and_then(|t| t.rsplit("::").next()).unwrap_or("unknown"),
+        tx_digest = evt["id"]["txDigest"].as_str().unwrap_or("without digest"),
+        error = %error,
+        "skipping malformed event"
+    );
+}
+
+/// Persists a page of events and checksums it in the ingest log.
+/// 
+/// This is the indexer's write path for every page fetched from the RPC.
+/// The page's writes, its checksum and, with `cursor`, the event cursor
+/// moved past it are committed in one transaction, so a crash or failed
+/// write leaves the page either fully applied with the cursor after it, or
+/// not applied at all with the cursor before it.
+/// 
+/// For events seen for the first time, the delay between their on-chain
+/// timestamp and the write is recorded as ingestion latency; re-indexed
+/// events are not, so replays don't skew it.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `events` - Array of event JSON objects from Sui RPC
+/// * `now` - Current timestamp in milliseconds
+/// * `cursor` - `(event_type, saved_at)` to save the page's last event as
+///   the cursor of its event type, or `None` to leave cursors alone
+/// 
+/// # Returns
+/// * `Result<()>` - Error if the page was rolled back
+pub fn commit_events(
+    conn: &Connection,
+    events: &[Value],
+    now: i64,
+    cursor: Option<(&str, i64)>,
+) -> rusqlite::Result<()> {
+    let first_seen: Vec<i64> = events
+        .iter()
+        .filter(|evt| !is_indexed(conn, evt))
+        .filter_map(event_timestamp)
+        .collect();
+
+    let tx = conn.unchecked_transaction()?;
+    process_events(&tx, events)?;
+    verify_batch(&tx, events, now)?;
+    if let Some((event_type, saved_at)) = cursor {
+        if let Some(last) = events.iter().rev().find_map(event_cursor) {
+            save_event_cursor(&tx, event_type, &last, saved_at)?;
+        }
+    }
+    tx.commit()?;
+    INDEXER_EVENTS_PROCESSED.fetch_add(events.len() as u64, Ordering::Relaxed);
+
+    let written = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    for ts in first_seen {
+        INGEST_LATENCY.observe((written - ts).max(0) as u64);
+    }
+    Ok(())
+}
+
+/// Applies a page of events behind the write-ahead journal, if enabled.
+/// 
+/// The page is journaled and fsynced first and the journal is cleared once
+/// `apply` succeeds. If the page can't be journaled it is not applied; if
+/// `apply` fails the page stays journaled, to be replayed on restart unless
+/// a later page is applied first.
+/// 
+/// # Returns
+/// * `io::Result<Result<T, E>>` - Error if the journal could not be written,
+///   otherwise the result of `apply`
+fn journaled<T, E>(
+    journal: &mut Option<EventJournal>,
+    events: &[Value],
+    apply: impl FnOnce() -> Result<T, E>,
+) -> std::io::Result<Result<T, E>> {
+    if let Some(journal) = journal.as_mut() {
+        journal.append(events)?;
+    }
+    let applied = apply();
+    if applied.is_ok() {
+        if let Some(journal) = journal.as_mut() {
+            journal.clear()?;
+        }
+    }
+    Ok(applied)
+}
+
+/// Re-applies any page a previous run left half-applied in the event
+/// journal, before the indexer starts (see `EventJournal` for why pages are
+/// journaled).
+/// 
+/// The journal is cleared only once every page in it has committed. If one
+/// fails, or the journal can't be read, it is kept for the next start and
+/// the error is returned, so the indexer doesn't start and append after
+/// pages that were never applied.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `pool_cache`, `graph_cache`, `invalidator` - Synced with the replayed pages, see `sync_pools`
+/// 
+/// # Returns
+/// * `Result<(), Box<dyn Error + Send + Sync>>` - Success, also if journaling
+///   is disabled or the journal can't be opened (see `open_journal`); an
+///   error if the journal couldn't be read, replayed or cleared
+pub fn recover_journal(
+    conn_arc: &Arc<Mutex<Connection>>,
+    pool_cache: Option<&PoolCache>,
+    graph_cache: &GraphCache,
+    invalidator: Option<&Invalidator>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let Some(path) = journal_path() else {
+        return Ok(());
+    };
+    let Ok(mut journal) = EventJournal::open(&path) else {
+        return Ok(());
+    };
+    let pages = journal.pending().map_err(|e| format!("failed to read event journal {}: {}", path, e))?;
+    if !pages.is_empty() {
+        let conn = conn_arc.lock().map_err(|_| "database connection lock poisoned")?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        for page in &pages {
+            commit_events(&conn, page, now, None)
+                .map_err(|e| format!("failed to replay journaled page from {}: {}", path, e))?;
+            sync_pool_cache(&conn, pool_cache, graph_cache, invalidator, page);
+        }
+        info!(events = pages.iter().map(Vec::len).sum::<usize>(), "Replayed journaled events from an interrupted run");
+    }
+    journal.clear().map_err(|e| format!("failed to clear event journal {}: {}", path, e))?;
+    Ok(())
+}
+
+/// Opens the event journal the indexer appends pages to, once
+/// `recover_journal` has emptied it.
+/// 
+/// # Returns
+/// * `Option<EventJournal>` - The journal, or `None` if journaling is disabled
+///   or the journal can't be opened
+fn open_journal() -> Option<EventJournal> {
+    let path = journal_path()?;
+    match EventJournal::open(&path) {
+        Ok(journal) => Some(journal),
+        Err(e) => {
+            warn!(path = %path, error = %e, "failed to open event journal, journaling disabled");
+            None
+        }
+    }
+}
+
+/// Syncs the pools a committed page touched, see `sync_pools`.
+fn sync_pool_cache(
+    conn: &Connection,
+    pool_cache: Option<&PoolCache>,
+    graph_cache: &GraphCache,
+    invalidator: Option<&Invalidator>,
+    events: &[Value],
+) {
+    sync_pools(conn, pool_cache, graph_cache, invalidator, touched_pools(events));
+}
+
+/// Reloads pools whose stored state changed into the pool cache, if enabled,
+/// updates the routing graph, wakes requests waiting for a price update, and
+/// tells API replicas to do the same, if configured.
+fn sync_pools(
+    conn: &Connection,
+    pool_cache: Option<&PoolCache>,
+    graph_cache: &GraphCache,
+    invalidator: Option<&Invalidator>,
+    pool_ids: Vec<String>,
+) {
+    if let Some(cache) = pool_cache {
+        if let Err(e) = reload_pools(conn, cache, &pool_ids) {
+            warn!(error = %e, "failed to refresh pool cache");
+        }
+    }
+    if let Err(e) = pools_changed(conn, pool_cache, graph_cache, &pool_ids) {
+        warn!(error = ?e, "failed to refresh routing graph");
+    }
+    if !pool_ids.is_empty() {
+        notify_pool_updates();
+    }
+    if let Some(invalidator) = invalidator {
+        invalidator.notify(pool_ids);
+    }
+}
+
+/// Checksums a processed batch in the ingest log and reports replays whose
+/// derived state no longer matches the original ingest.
+/// 
+/// Malformed events are left out of the batch: they derive nothing, and a
+/// malformed copy shares its ID with the well-formed event, whose row a
+/// replay would otherwise find stored.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `events` - The batch that was just processed
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<()>` - Error if the checksum couldn't be read or recorded
+fn verify_batch(conn: &Connection, events: &[Value], now: i64) -> rusqlite::Result<()> {
+    let mut event_ids = Vec::new();
+    let mut swap_events = Vec::new();
+    let mut created_pools = Vec::new();
     for evt in events {
-        // Sui event structure:
-        // {
-        //   "id": { "txDigest": "0x...", "eventSeq": "0" },
-        //   "parsedJson": { "creator": "...", "pool_id": "...", ... },
-        //   "timestampMs": "1751104133893",
-        //   "type": "0x...::fooswap::PoolCreatedEvent" OR "0x...::fooswap::SwapEvent",
-        //   ...
-        // }
-        let parsed = &evt["parsedJson"];
-        let ts = evt["timestampMs"].as_str().unwrap_or("0").parse::<i64>().unwrap_or(0);
+        let Ok(event) = DexEvent::parse(evt) else { continue };
         let tx_digest = evt["id"]["txDigest"].as_str().unwrap_or_default();
-        let event_type = evt["type"].as_str().unwrap_or_default();
-
-        if event_type.contains("PoolCreatedEvent") {
-            // Extract pool creation event data
-            let pool_id = parsed["pool_id"].as_str().unwrap_or_default();
-            let token_a = parsed["token_a"].as_str().unwrap_or_default();
-            let token_b = parsed["token_b"].as_str().unwrap_or_default();
-            let initial_reserve_a = parsed["initial_reserve_a"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            let initial_reserve_b = parsed["initial_reserve_b"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
-
-            println!("Processing PoolCreatedEvent: pool_id={}, token_a={}, token_b={}, reserve_a={}, reserve_b={}", 
-                     pool_id, token_a, token_b, initial_reserve_a, initial_reserve_b);
-
-            // Persist pool data to database
-            let _ = upsert_pool(
-                conn,
-                pool_id,
-                token_a,
-                token_b,
-                initial_reserve_a,
-                initial_reserve_b,
-                ts,
+        let event_seq = evt["id"]["eventSeq"].as_str().unwrap_or("0");
+        event_ids.push(format!("{}:{}", tx_digest, event_seq));
+
+        match event {
+            Some(DexEvent::PoolCreated(created)) => created_pools.push(created.parsed_json.pool_id),
+            Some(DexEvent::Swap(swap)) => swap_events.push((swap.id.tx_digest, swap.id.event_seq as i64)),
+            None => {}
+        }
+    }
+
+    match record_ingest_batch(conn, &event_ids, &swap_events, &created_pools, now)? {
+        IngestCheck::Mismatch => {
+            INDEXER_REPLAY_MISMATCHES.fetch_add(1, Ordering::Relaxed);
+            warn!(
+                events = events.len(),
+                "replay of a batch produced different derived state than its first ingest"
             );
         }
-        else if event_type.contains("SwapEvent") {
-            // Extract swap event data
-            let pool_id = parsed["pool_id"].as_str().unwrap_or_default();
-            let amount_in = parsed["amount_in"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            let amount_out = parsed["amount_out"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
+        IngestCheck::Verified => info!(events = events.len(), "Replayed batch verified"),
+        IngestCheck::New => {}
+    }
+    Ok(())
+}
 
-            // Extract updated reserves after the swap
-            let new_reserve_a = parsed["new_reserve_a"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
-            let new_reserve_b = parsed["new_reserve_b"]
-                .as_str()
-                .unwrap_or("0")
-                .parse::<f64>()
-                .unwrap_or(0.0);
+/// Looks up the epoch and checkpoint each transaction executed in.
+/// 
+/// Transactions the node doesn't know (e.g. pruned ones) are left out.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC call
+/// * `rpc_url` - Sui RPC endpoint
+/// * `digests` - Transaction digests, at most `EPOCH_LOOKUP_BATCH`
+/// 
+/// # Returns
+/// * `Result<Vec<(String, u64, u64)>>` - `(tx_digest, epoch, checkpoint)` for each transaction found
+async fn fetch_tx_epochs(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    digests: &[String],
+) -> Result<Vec<(String, u64, u64)>, Box<dyn std::error::Error + Send + Sync>> {
+    let params = serde_json::json!([digests, { "showEffects": true }]);
+    let result = rpc_call(client, rpc_url, "sui_multiGetTransactionBlocks", params).await?;
+    let number = |v: &Value| v.as_str().and_then(|s| s.parse::<u64>().ok());
+    Ok(result
+        .as_array()
+        .map(|txs| {
+            txs.iter()
+                .filter_map(|tx| {
+                    let digest = required_str(tx, "digest")?;
+                    Some((digest.to_string(), number(&tx["effects"]["executedEpoch"])?, number(&tx["checkpoint"])?))
+                })
+                .collect()
+        })
+        .unwrap_or_default())
+}
 
-            println!("Processing SwapEvent: pool_id={}, amount_in={}, amount_out={}, new_reserve_a={}, new_reserve_b={}", 
-                     pool_id, amount_in, amount_out, new_reserve_a, new_reserve_b);
+/// Records the epoch and checkpoint of swaps indexed without them.
+/// 
+/// Events carry neither, so each swap's transaction is looked up after it
+/// was committed. Transactions older than the primary node's pruning
+/// horizon, e.g. of swaps indexed by a backfill, are looked up on the
+/// archival node if one is configured (see `rpc_url_for`). `after_id`
+/// tracks how far the scan got, so transactions the node can't find are
+/// not asked for again until the next restart.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `client` - HTTP client used for the RPC calls
+/// * `after_id` - Row ID of the last swap looked up
+async fn index_swap_epochs(conn_arc: &Arc<Mutex<Connection>>, client: &reqwest::Client, after_id: &mut i64) {
+    for _ in 0..EPOCH_LOOKUPS_PER_CYCLE {
+        let pending = match conn_arc.lock() {
+            Ok(conn) => swaps_missing_epoch(&conn, *after_id, EPOCH_LOOKUP_BATCH),
+            Err(_) => return,
+        };
+        let pending = match pending {
+            Ok(pending) if !pending.is_empty() => pending,
+            Ok(_) => return,
+            Err(e) => {
+                warn!(error = %e, "failed to list swaps missing an epoch");
+                return;
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let mut by_endpoint: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (_, digest, timestamp) in &pending {
+            by_endpoint.entry(rpc_url_for(Some(*timestamp), now)).or_default().push(digest.clone());
+        }
+        let mut found = Vec::new();
+        for (rpc_url, digests) in &by_endpoint {
+            match fetch_tx_epochs(client, rpc_url, digests).await {
+                Ok(epochs) => found.extend(epochs),
+                Err(e) => {
+                    warn!(rpc_url = %rpc_url, error = %e, "failed to look up swap epochs");
+                    return;
+                }
+            }
+        }
+        if let Ok(conn) = conn_arc.lock() {
+            for (digest, epoch, checkpoint) in found {
+                if let Err(e) = set_swap_epoch(&conn, &digest, epoch, checkpoint) {
+                    warn!(tx_digest = %digest, error = %e, "failed to record epoch of swap");
+                }
+            }
+        }
+        *after_id = pending.last().map_or(*after_id, |(id, _, _)| *id);
+    }
+}
 
-            // Record the swap transaction
-            let _ = insert_swap(conn, pool_id, amount_in, amount_out, ts, tx_digest);
+/// Fetches a coin's metadata.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC call
+/// * `rpc_url` - Sui RPC endpoint
+/// * `coin_type` - Coin type, e.g. `0x2::sui::SUI`
+/// 
+/// # Returns
+/// * `Result<Option<TokenMetadata>>` - The metadata, or `None` if the coin has none
+async fn fetch_coin_metadata(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    coin_type: &str,
+) -> Result<Option<TokenMetadata>, Box<dyn std::error::Error + Send + Sync>> {
+    let result = rpc_call(client, rpc_url, "suix_getCoinMetadata", serde_json::json!([coin_type])).await?;
+    if result.is_null() {
+        return Ok(None);
+    }
+    let decimals = result["decimals"]
+        .as_u64()
+        .and_then(|d| u8::try_from(d).ok())
+        .ok_or("missing coin decimals")?;
+    Ok(Some(TokenMetadata {
+        symbol: result["symbol"].as_str().unwrap_or_default().to_string(),
+        name: result["name"].as_str().unwrap_or_default().to_string(),
+        decimals,
+    }))
+}
 
-            // Update pool reserves to reflect the swap
-            let _ = upsert_pool(conn, pool_id, "", "", new_reserve_a, new_reserve_b, ts);
+/// Fetches the coin metadata of tokens that appeared in new pools.
+/// 
+/// A coin without metadata is stored as such and not asked for again; a
+/// failed call is retried on the next cycle.
+async fn index_token_metadata(conn_arc: &Arc<Mutex<Connection>>, client: &reqwest::Client) {
+    let pending = match conn_arc.lock() {
+        Ok(conn) => tokens_missing_metadata(&conn, TOKEN_METADATA_LOOKUPS_PER_CYCLE),
+        Err(_) => return,
+    };
+    let pending = match pending {
+        Ok(pending) => pending,
+        Err(e) => {
+            warn!(error = %e, "failed to list tokens missing metadata");
+            return;
+        }
+    };
+    let rpc_url = rpc_url();
+    for coin_type in pending {
+        let metadata = match fetch_coin_metadata(client, &rpc_url, &coin_type).await {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                warn!(coin_type = %coin_type, error = %e, "failed to fetch coin metadata");
+                continue;
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        if let Ok(conn) = conn_arc.lock() {
+            if let Err(e) = save_token_metadata(&conn, &coin_type, metadata.as_ref(), now) {
+                warn!(coin_type = %coin_type, error = %e, "failed to save coin metadata");
+            }
         }
     }
 }
 
+/// Reads a pool's reserves from its on-chain object as a transaction left it.
+/// 
+/// The pool object's version after the transaction is read off the
+/// transaction's object changes, and the object is then read at that
+/// version, so the reserves match the indexed history however far the node
+/// has moved on since.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC calls
+/// * `rpc_url` - Sui RPC endpoint
+/// * `pool_id` - Object ID of the pool
+/// * `tx_digest` - Transaction that last changed the pool's indexed state
+/// 
+/// # Returns
+/// * `Result<(u64, u64)>` - `(reserve_a, reserve_b)` or error
+async fn fetch_pool_reserves_after(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    pool_id: &str,
+    tx_digest: &str,
+) -> Result<(u64, u64), Box<dyn std::error::Error + Send + Sync>> {
+    let tx = rpc_call(client, rpc_url, "sui_getTransactionBlock", serde_json::json!([tx_digest, { "showObjectChanges": true }])).await?;
+    let version = tx["objectChanges"]
+        .as_array()
+        .and_then(|changes| {
+            changes
+                .iter()
+                .find(|change| change["type"] == "mutated" && change["objectId"].as_str() == Some(pool_id))
+        })
+        .and_then(|change| change["version"].as_str().and_then(|v| v.parse::<u64>().ok()).or(change["version"].as_u64()))
+        .ok_or_else(|| format!("transaction {} did not mutate pool {}", tx_digest, pool_id))?;
+
+    let result = rpc_call(
+        client,
+        rpc_url,
+        "sui_tryGetPastObject",
+        serde_json::json!([pool_id, version, { "showContent": true }]),
+    )
+    .await?;
+    if result["status"] != "VersionFound" {
+        return Err(format!("pool {} version {} is not available: {}", pool_id, version, result["status"]).into());
+    }
+    let fields = &result["details"]["content"]["fields"];
+    let reserve = |name: &str| {
+        // Balances render as an amount string, or as a struct holding one
+        let field = &fields[name];
+        field
+            .as_str()
+            .or_else(|| field["fields"]["value"].as_str())
+            .and_then(|v| v.parse::<u64>().ok())
+            .ok_or_else(|| format!("pool object {} has no {}", pool_id, name))
+    };
+    Ok((reserve("reserve_a")?, reserve("reserve_b")?))
+}
+
+/// Replaces the stored reserves of pools with inconsistent swaps by the
+/// reserves of their on-chain objects.
+/// 
+/// The on-chain state is read as the pool's last indexed swap left it (see
+/// `fetch_pool_reserves_after`) and stored as of that swap, so swaps indexed
+/// later are checked and applied on top of it, and is recorded in
+/// `pool_reconciliations` for `verify` to replay from. `after_id` tracks the
+/// last inconsistent swap handled, so each pool is reconciled once per
+/// flagged swap until the next restart.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `client` - HTTP client used for the RPC calls
+/// * `after_id` - Row ID of the last inconsistent swap handled
+/// * `pool_cache`, `graph_cache`, `invalidator` - Synced with the reconciled pools, see `sync_pools`
+async fn reconcile_inconsistent_pools(
+    conn_arc: &Arc<Mutex<Connection>>,
+    client: &reqwest::Client,
+    after_id: &mut i64,
+    pool_cache: Option<&PoolCache>,
+    graph_cache: &GraphCache,
+    invalidator: Option<&Invalidator>,
+) {
+    let pending = match conn_arc.lock() {
+        Ok(conn) => inconsistent_swaps(&conn, *after_id, RECONCILIATIONS_PER_CYCLE),
+        Err(_) => return,
+    };
+    let pending = match pending {
+        Ok(pending) if !pending.is_empty() => pending,
+        Ok(_) => return,
+        Err(e) => {
+            warn!(error = %e, "failed to list inconsistent swaps");
+            return;
+        }
+    };
+    let mut pool_ids: Vec<String> = pending.iter().map(|(_, pool_id)| pool_id.clone()).collect();
+    pool_ids.sort();
+    pool_ids.dedup();
+
+    let rpc_url = rpc_url();
+    let mut reconciled = Vec::new();
+    let mut complete = true;
+    for pool_id in pool_ids {
+        let last_swap = match conn_arc.lock() {
+            Ok(conn) => last_pool_swap(&conn, &pool_id),
+            Err(_) => return,
+        };
+        let (swap_id, tx_digest, swap_ts) = match last_swap {
+            Ok(Some(last_swap)) => last_swap,
+            Ok(None) => continue,
+            Err(e) => {
+                warn!(pool_id = %pool_id, error = %e, "failed to find the pool's last swap");
+                continue;
+            }
+        };
+        let (reserve_a, reserve_b) = match fetch_pool_reserves_after(client, &rpc_url, &pool_id, &tx_digest).await {
+            Ok((a, b)) => (amount(a, "reserve_a"), amount(b, "reserve_b")),
+            Err(e) => {
+                warn!(pool_id = %pool_id, error = %e, "failed to read on-chain pool state");
+                complete = false;
+                break;
+            }
+        };
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as i64)
+            .unwrap_or(0);
+        let Ok(conn) = conn_arc.lock() else { return };
+        let stored = conn.unchecked_transaction().and_then(|tx| {
+            upsert_pool(&tx, &pool_id, "", "", reserve_a, reserve_b, swap_ts)?;
+            insert_pool_snapshot(&tx, &pool_id, reserve_a, reserve_b, swap_ts)?;
+            insert_pool_reconciliation(&tx, &pool_id, reserve_a, reserve_b, swap_id, swap_ts, now)?;
+            check_swaps_around(&tx, &pool_id, swap_ts)?;
+            tx.commit()
+        });
+        match stored {
+            Ok(()) => {
+                info!(pool_id = %pool_id, reserve_a, reserve_b, "Reconciled pool with its on-chain state");
+                reconciled.push(pool_id);
+            }
+            Err(e) => warn!(pool_id = %pool_id, error = %e, "failed to store reconciled pool state"),
+        }
+    }
+
+    if let Ok(conn) = conn_arc.lock() {
+        sync_pools(&conn, pool_cache, graph_cache, invalidator, reconciled);
+    }
+    // Retry the whole batch next cycle if the node couldn't be read
+    if complete {
+        *after_id = pending.last().map_or(*after_id, |(id, _)| *id);
+    }
+}
+
+/// A checkpoint as reported by the Sui RPC node
+pub struct Checkpoint {
+    pub seq: u64,
+    pub timestamp_ms: i64,
+    /// Digests of the transactions executed in the checkpoint
+    pub transactions: Vec<String>,
+}
+
+/// Sends a JSON-RPC request to the Sui node and returns its `result`.
+async fn rpc_call(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    method: &str,
+    params: Value,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let response = async {
+        client
+            .post(rpc_url)
+            .json(&serde_json::json!({ "jsonrpc": "2.0", "id": 1, "method": method, "params": params }))
+            .send()
+            .await?
+            .json::<Value>()
+            .await
+    }
+    .await;
+    let failed = response.as_ref().map_or(true, |json| !json["error"].is_null());
+    RPC_REQUESTS.observe(rpc_endpoint(rpc_url), method, failed);
+    record_rpc_outcome(rpc_url, response.is_err());
+
+    let json = response?;
+    if let Some(message) = json["error"]["message"].as_str() {
+        return Err(message.into());
+    }
+    Ok(json["result"].clone())
+}
+
+/// Fetches a checkpoint by sequence number.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC call
+/// * `rpc_url` - Sui RPC endpoint
+/// * `seq` - Checkpoint sequence number
+/// 
+/// # Returns
+/// * `Result<Checkpoint>` - Timestamp and transactions of the checkpoint
+pub async fn fetch_checkpoint(
+    client: &reqwest::Client,
+    rpc_url: &str,
+    seq: u64,
+) -> Result<Checkpoint, Box<dyn std::error::Error + Send + Sync>> {
+    let result = rpc_call(client, rpc_url, "sui_getCheckpoint", serde_json::json!([seq.to_string()])).await?;
+    let timestamp_ms = result["timestampMs"]
+        .as_str()
+        .and_then(|s| s.parse::<i64>().ok())
+        .ok_or("missing checkpoint timestamp")?;
+    let transactions = result["transactions"]
+        .as_array()
+        .map(|txs| txs.iter().filter_map(|tx| tx.as_str().map(str::to_string)).collect())
+        .unwrap_or_default();
+
+    Ok(Checkpoint { seq, timestamp_ms, transactions })
+}
+
+/// Fetches the node's latest executed checkpoint.
+/// 
+/// Each polling cycle is attributed to the checkpoint fetched before its
+/// events, rather than the local clock, so the reported sync time never runs
+/// ahead of what the node had executed.
+/// 
+/// # Arguments
+/// * `client` - HTTP client used for the RPC calls
+/// * `rpc_url` - Sui RPC endpoint
+/// 
+/// # Returns
+/// * `Result<Checkpoint>` - Sequence number and timestamp of the checkpoint
+async fn latest_checkpoint(
+    client: &reqwest::Client,
+    rpc_url: &str,
+) -> Result<Checkpoint, Box<dyn std::error::Error + Send + Sync>> {
+    let result = rpc_call(client, rpc_url, "sui_getLatestCheckpointSequenceNumber", serde_json::json!([])).await?;
+    let seq = result
+        .as_str()
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or("missing latest checkpoint sequence number")?;
+
+    fetch_checkpoint(client, rpc_url, seq).await
+}
+
+/// Checks whether the rows derived from an event are already stored.
+fn is_indexed(conn: &Connection, evt: &Value) -> bool {
+    let event_type = evt["type"].as_str().unwrap_or_default();
+    if event_type.contains("PoolCreatedEvent") {
+        let pool_id = evt["parsedJson"]["pool_id"].as_str().unwrap_or_default();
+        pool_exists(conn, pool_id).unwrap_or(false)
+    } else if event_type.contains("SwapEvent") {
+        let tx_digest = evt["id"]["txDigest"].as_str().unwrap_or_default();
+        let event_seq = evt["id"]["eventSeq"].as_str().and_then(|s| s.parse().ok()).unwrap_or(0);
+        swap_exists(conn, tx_digest, event_seq).unwrap_or(false)
+    } else {
+        true
+    }
+}
+
+/// On-chain timestamp of an event (ms).
+fn event_timestamp(evt: &Value) -> Option<i64> {
+    evt["timestampMs"].as_str().and_then(|t| t.parse().ok())
+}
+
+/// ID of an event in the form `suix_queryEvents` takes as a cursor.
+fn event_cursor(evt: &Value) -> Option<EventCursor> {
+    Some(EventCursor {
+        tx_digest: required_str(&evt["id"], "txDigest")?.to_string(),
+        event_seq: required_str(&evt["id"], "eventSeq")?.to_string(),
+        timestamp_ms: event_timestamp(evt),
+    })
+}
+
 /// Runs the blockchain indexer as a continuous background process.
 /// 
 /// This function implements a polling-based indexer that continuously monitors
-/// the Sui blockchain for new DEX events. It maintains a timestamp-based cursor
-/// to avoid reprocessing events and persists all events to the local SQLite database.
+/// the Sui blockchain for new DEX events and persists them to the local SQLite
+/// database. Each event type is read from the RPC's own event cursor, which
+/// orders events by transaction rather than by timestamp, so events the node
+/// indexes late can't fall behind it. The cursor of each type is saved in
+/// `indexer_cursors` after every committed page, so a restart resumes where
+/// the previous run stopped instead of at genesis.
+/// 
+/// A cycle only counts as synced once every event type has been read to the
+/// end. Progress is held while the node reports an older checkpoint than one
+/// already indexed.
 /// 
-/// The indexer runs indefinitely until the process is terminated. It polls the
-/// blockchain every `POLL_INTERVAL_SECS` seconds and processes any new events found.
+/// The indexer polls the blockchain every `POLL_INTERVAL_SECS` seconds and
+/// processes any new events found, until shutdown is requested. A page being
+/// committed is finished and its cursor saved first; events fetched after it
+/// are left for the next run. With `once` it stops after its first cycle
+/// instead, having read every event type up to the chain head.
 /// 
 /// # Arguments
 /// * `conn_arc` - Thread-safe SQLite connection wrapped in Arc<Mutex<Connection>>
-pub async fn run_indexer(conn_arc: Arc<Mutex<Connection>>) {
-    // Initialize cursor to genesis (timestamp 0)
-    let mut last_ts: i64 = 0;
+/// * `pool_cache` - In-memory pool cache refreshed after every committed page, if enabled
+/// * `graph_cache` - Routing graph updated after every committed page
+/// * `invalidator` - Notifies API replicas of the pools each committed page touched, if configured
+/// * `feed` - Streams the swaps and pool updates of each committed page to WebSocket clients
+/// * `shutdown` - Stops the indexer after the page it is committing
+/// * `once` - Whether to stop after the first polling cycle
+/// 
+/// # Returns
+/// * `bool` - Whether the last polling cycle synced every event type
+pub async fn run_indexer(
+    conn_arc: Arc<Mutex<Connection>>,
+    pool_cache: Option<PoolCache>,
+    graph_cache: GraphCache,
+    invalidator: Option<Invalidator>,
+    feed: LiveFeed,
+    shutdown: Shutdown,
+    once: bool,
+) -> bool {
+    let saved = conn_arc.lock().ok().map(|conn| load_indexer_state(&conn, dex_package_id()));
+    let mut last_checkpoint = match saved {
+        Some(Ok(Some(state))) => {
+            info!(checkpoint = state.last_checkpoint, "Resuming indexer");
+            INDEXER_LAST_SYNC_MS.store(state.last_ts as u64, Ordering::Relaxed);
+            Some(state.last_checkpoint)
+        }
+        Some(Err(e)) => {
+            warn!(error = %e, "failed to load indexer state");
+            None
+        }
+        _ => None,
+    };
+    let max_buffered = config().max_buffered_events;
+    let batch_size = config().rpc_batch_size;
+    let client = rpc_client();
+    let mut journal = open_journal();
+    let mut epoch_scan_id = 0;
+    let mut reconcile_scan_id = 0;
+    let mut breaker = CircuitBreaker::default();
+    let mut last_probe = Instant::now();
+    let failed_scan_interval = Duration::from_secs(config().failed_swap_scan_interval_secs);
+    let mut last_failed_scan: Option<Instant> = None;
+
+    // Define the event types to query from the Sui Move contract. Pools are
+    // indexed before the swaps against them.
+    let event_types = [
+        format!("{}::fooswap::PoolCreatedEvent", dex_package_id()),
+        format!("{}::fooswap::SwapEvent", dex_package_id()),
+    ];
 
     loop {
-        // Calculate current timestamp for the polling window
-        let to_ts = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
+        // Everything logged during the cycle carries its checkpoint
+        let cycle = info_span!("indexer_cycle", checkpoint = field::Empty);
+        let started = Instant::now();
+        // Whether the RPC failed this cycle, after retries
+        let mut rpc_failed = false;
+        // Whether the cycle read every event type to the end
+        let mut cycle_synced = false;
+        let running = async {
+            // Return to the primary RPC endpoint once it recovers
+            fail_back(&client, &mut last_probe).await;
 
-        println!("Indexer polling: searching for events from {} to {}", last_ts, to_ts);
+            // Checkpoint the cycle is attributed to, fetched before any events so
+            // it never claims events the node hadn't executed when they were read
+            let checkpoint = match latest_checkpoint(&client, &rpc_url()).await {
+                Ok(c) => Some(c),
+                Err(e) => {
+                    warn!(error = %e, "failed to fetch latest checkpoint");
+                    INDEXER_CYCLE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                    rpc_failed = true;
+                    None
+                }
+            };
+            let Some(checkpoint) = checkpoint else {
+                return true;
+            };
+            Span::current().record("checkpoint", checkpoint.seq);
 
-        // Query blockchain for events in the time range [last_ts, to_ts)
-        match query_sui_events(last_ts, to_ts).await {
-            Ok(events) => {
-                if !events.is_empty() {
-                    println!("Found {} new events, processing...", events.len());
-                    if let Ok(conn) = conn_arc.lock() {
-                        process_events(&conn, &events);
+            // Checkpoint continuity: a node reporting an older checkpoint than one
+            // already indexed (e.g. a lagging node behind a load balancer) may be
+            // missing events, so hold off until it catches up
+            if let Some(prev) = last_checkpoint {
+                if checkpoint.seq < prev {
+                    INDEXER_GAPS_DETECTED.fetch_add(1, Ordering::Relaxed);
+                    INDEXER_CYCLE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                    warn!(indexed = prev, "node reports an already indexed checkpoint; holding cursors");
+                    return true;
+                }
+            }
+            let to_ts = checkpoint.timestamp_ms + 1;
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis() as i64)
+                .unwrap_or(0);
+
+            // Drain every event type from its saved cursor, committing each
+            // buffered page as soon as it fills up and saving the cursor after
+            // it. A journal write failure stops the cycle with the cursor on the
+            // last committed page.
+            let mut synced = true;
+            let mut queries = Vec::new();
+            for event_type in &event_types {
+                let saved = match conn_arc.lock() {
+                    Ok(conn) => load_event_cursor(&conn, event_type),
+                    Err(_) => return false,
+                };
+                let cursor = match saved {
+                    Ok(cursor) => cursor,
+                    Err(e) => {
+                        warn!(event_type = %event_type, error = %e, "failed to load event cursor");
+                        synced = false;
+                        break;
                     }
-                    last_ts = to_ts;
+                };
+                info!(
+                    event_type = %event_type,
+                    after = cursor.as_ref().map_or("the first event", |c| c.tx_digest.as_str()),
+                    "Indexer polling"
+                );
+                // Reading from the first event starts at genesis, as old as data gets
+                let position = cursor.as_ref().map_or(Some(0), |c| c.timestamp_ms);
+                let cursor = cursor.map_or(Value::Null, |c| {
+                    serde_json::json!({ "txDigest": c.tx_digest, "eventSeq": c.event_seq })
+                });
+                queries.push((event_type.as_str(), cursor, position));
+            }
+
+            let mut count = 0;
+            let mut journal_error = None;
+            let mut commit_error: Option<rusqlite::Error> = None;
+            let mut stopped = false;
+            let commit_page = |event_type: &str, page: &[Value]| {
+                debug!(event_type = %event_type, events = page.len(), "Committing page");
+                let Ok(conn) = conn_arc.lock() else { return false };
+                let applied = journaled(&mut journal, page, || {
+                    let last_swap = last_swap_id(&conn);
+                    commit_events(&conn, page, to_ts, Some((event_type, now)))?;
+                    sync_pool_cache(&conn, pool_cache.as_ref(), &graph_cache, invalidator.as_ref(), page);
+                    if let Err(e) = last_swap.and_then(|id| publish_page(&conn, &feed, id, &touched_pools(page))) {
+                        warn!(error = %e, "failed to publish live events");
+                    }
+                    Ok(())
+                });
+                match applied {
+                    Ok(Ok(())) => {}
+                    Err(e) => {
+                        journal_error = Some(e);
+                        return false;
+                    }
+                    // A rolled-back page left the cursor before it; fetch it again next cycle
+                    Ok(Err(e)) => {
+                        commit_error = Some(e);
+                        return false;
+                    }
+                }
+                // Stop between pages once shutdown is requested
+                stopped = *shutdown.borrow();
+                !stopped
+            };
+            let result = query_sui_events(&client, queries, batch_size, max_buffered, commit_page)
+                .instrument(info_span!("query_events"))
+                .await;
+            if stopped {
+                info!("Indexer stopped after committing its current page");
+                return false;
+            }
+            match (result, journal_error, commit_error) {
+                (Ok(_), Some(e), _) => {
+                    warn!(error = %e, "failed to write event journal, holding cursor");
+                    synced = false;
+                }
+                (Ok(_), None, Some(e)) => {
+                    warn!(error = %e, "failed to commit page, holding cursor");
+                    synced = false;
+                }
+                (Ok(fetched), None, None) => count += fetched,
+                (Err(e), _, _) => {
+                    warn!(error = %e, "failed to query Sui events");
+                    synced = false;
+                    rpc_failed = true;
+                }
+            }
+
+            cycle_synced = synced;
+            if synced {
+                INDEXER_LAST_SYNC_MS.store(checkpoint.timestamp_ms as u64, Ordering::Relaxed);
+                if let Some(invalidator) = &invalidator {
+                    invalidator.synced(checkpoint.timestamp_ms as u64);
+                }
+                if count > 0 {
+                    info!(events = count, "Processed new events");
                 } else {
-                    println!("No new events found in time range");
+                    info!("No new events found");
+                }
+                last_checkpoint = Some(checkpoint.seq);
+                if let Ok(conn) = conn_arc.lock() {
+                    let state = IndexerState { last_ts: checkpoint.timestamp_ms, last_checkpoint: checkpoint.seq };
+                    if let Err(e) = save_indexer_state(&conn, dex_package_id(), &state, now) {
+                        warn!(error = %e, "failed to save indexer state");
+                    }
                 }
+            } else {
+                INDEXER_CYCLE_FAILURES.fetch_add(1, Ordering::Relaxed);
             }
-            Err(e) => {
-                eprintln!("Warning: failed to query Sui events: {}", e);
+
+            // Compare router recommendations and served quotes with the swaps that followed them
+            if let Ok(conn) = conn_arc.lock() {
+                if let Err(e) = match_route_recommendations(&conn, to_ts, ROUTE_MATCH_WINDOW_MS, ROUTE_MATCH_TOLERANCE) {
+                    warn!(error = %e, "failed to match route recommendations");
+                }
+                if let Err(e) = match_quotes(&conn, to_ts, QUOTE_MATCH_GRACE_MS, ROUTE_MATCH_TOLERANCE) {
+                    warn!(error = %e, "failed to match quotes");
+                }
+            }
+
+            // Look for failed transactions (e.g. slippage reverts) against known
+            // pools, on a slower interval as it costs an RPC call per pool
+            if last_failed_scan.is_none_or(|at| at.elapsed() >= failed_scan_interval) {
+                last_failed_scan = Some(Instant::now());
+                index_failed_swaps(&conn_arc, &client).await;
+            }
+
+            // Attribute newly indexed swaps to their epoch and checkpoint
+            index_swap_epochs(&conn_arc, &client, &mut epoch_scan_id).await;
+
+            // Resolve the symbol and decimals of tokens in new pools
+            index_token_metadata(&conn_arc, &client).await;
+
+            // Correct the reserves of pools whose swaps didn't add up
+            reconcile_inconsistent_pools(
+                &conn_arc,
+                &client,
+                &mut reconcile_scan_id,
+                pool_cache.as_ref(),
+                &graph_cache,
+                invalidator.as_ref(),
+            )
+            .await;
+
+            true
+        }
+        .instrument(cycle)
+        .await;
+        if !running {
+            return false;
+        }
+        INDEXER_CYCLES.fetch_add(1, Ordering::Relaxed);
+        INDEXER_CYCLE_DURATION.observe(started.elapsed().as_millis() as u64);
+        breaker.record(!rpc_failed);
+        if once {
+            return cycle_synced;
+        }
+
+        // Wait before the next polling cycle, or stop
+        tokio::select! {
+            _ = sleep(breaker.poll_interval(idle::poll_interval_secs())) => {}
+            // Traffic after an idle spell resumes polling at once
+            _ = idle::woken() => {}
+            _ = requested(shutdown.clone()) => {
+                info!("Indexer stopped");
+                return cycle_synced;
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn created(reserve_a: u64, reserve_b: u64, timestamp: i64) -> Value {
+        json!({
+            "id": { "txDigest": "0xcreate", "eventSeq": "0" },
+            "type": "0x1::fooswap::PoolCreatedEvent",
+            "parsedJson": {
+                "pool_id": "0xpool",
+                "token_a": "0xa",
+                "token_b": "0xb",
+                "initial_reserve_a": reserve_a.to_string(),
+                "initial_reserve_b": reserve_b.to_string(),
+            },
+            "timestampMs": timestamp.to_string(),
+        })
+    }
+
+    fn swap(tx_digest: &str, amount_in: u64, amount_out: u64, new_reserves: (u64, u64), timestamp: i64) -> Value {
+        json!({
+            "id": { "txDigest": tx_digest, "eventSeq": "0" },
+            "type": "0x1::fooswap::SwapEvent",
+            "parsedJson": {
+                "pool_id": "0xpool",
+                "amount_in": amount_in.to_string(),
+                "amount_out": amount_out.to_string(),
+                "new_reserve_a": new_reserves.0.to_string(),
+                "new_reserve_b": new_reserves.1.to_string(),
+            },
+            "timestampMs": timestamp.to_string(),
+        })
+    }
+
+    fn checks(conn: &Connection) -> Vec<(String, String, bool)> {
+        let mut stmt = conn.prepare("SELECT tx_digest, direction, inconsistent FROM swaps ORDER BY tx_digest").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap();
+        rows.map(Result::unwrap).collect()
+    }
+
+    fn check(tx_digest: &str, direction: &str, inconsistent: bool) -> (String, String, bool) {
+        (tx_digest.to_string(), direction.to_string(), inconsistent)
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_its_cap_with_jitter() {
+        for attempt in 0..20 {
+            let full = (RPC_RETRY_BASE_MS << attempt.min(16)).min(RPC_RETRY_MAX_MS);
+            let delay = retry_delay(attempt).as_millis() as u64;
+            assert!(delay >= full / 2 && delay <= full, "attempt {}: {} ms", attempt, delay);
+        }
+    }
+
+    #[test]
+    fn circuit_breaker_widens_the_poll_interval_until_a_cycle_succeeds() {
+        let mut breaker = CircuitBreaker::default();
+        let mut intervals = Vec::new();
+        for _ in 0..10 {
+            breaker.record(false);
+            intervals.push(breaker.poll_interval(5).as_secs());
+        }
+        assert_eq!(intervals, vec![5, 5, 10, 20, 40, 80, 160, 300, 300, 300]);
+        // A configured interval above the cap is never shortened
+        assert_eq!(breaker.poll_interval(600).as_secs(), 600);
+
+        breaker.record(true);
+        assert!(!breaker.is_open());
+        assert_eq!(breaker.poll_interval(5).as_secs(), 5);
+    }
+
+    #[test]
+    fn rpc_fails_over_after_repeated_failures_and_wraps_around() {
+        let urls: Vec<String> = ["http://primary", "http://fallback"].map(String::from).to_vec();
+        RPC_ACTIVE_ENDPOINT.store(0, Ordering::Relaxed);
+        ACTIVE_RPC_FAILURES.store(0, Ordering::Relaxed);
+
+        // A success in between resets the count
+        for failed in [true, true, false, true, true] {
+            record_endpoint_outcome(&urls, "http://primary", failed);
+        }
+        assert_eq!(RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed), 0);
+        record_endpoint_outcome(&urls, "http://primary", true);
+        assert_eq!(RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed), 1);
+
+        // Late failures of the endpoint left behind, or of the archive, don't count
+        for _ in 0..RPC_FAILOVER_THRESHOLD {
+            record_endpoint_outcome(&urls, "http://primary", true);
+            record_endpoint_outcome(&urls, "http://archive", true);
+        }
+        assert_eq!(RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed), 1);
+
+        for _ in 0..RPC_FAILOVER_THRESHOLD {
+            record_endpoint_outcome(&urls, "http://fallback", true);
+        }
+        assert_eq!(RPC_ACTIVE_ENDPOINT.load(Ordering::Relaxed), 0);
+    }
+
+    fn new_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn direction_is_read_off_the_previous_snapshot() {
+        let conn = new_db();
+        let events = [
+            created(1000, 1000, 1),
+            // Sells 100 A for 50 B, then 40 B for 80 A
+            swap("0xtx1", 100, 50, (1100, 950), 2),
+            swap("0xtx2", 40, 80, (1020, 990), 3),
+        ];
+        commit_events(&conn, &events, 0, None).unwrap();
+        assert_eq!(checks(&conn), vec![check("0xtx1", "a_to_b", false), check("0xtx2", "b_to_a", false)]);
+    }
+
+    #[test]
+    fn swaps_of_one_checkpoint_chain_on_each_other() {
+        let conn = new_db();
+        let events = [
+            created(1000, 1000, 1),
+            swap("0xtx1", 100, 50, (1100, 950), 2),
+            swap("0xtx2", 100, 50, (1200, 900), 2),
+        ];
+        commit_events(&conn, &events, 0, None).unwrap();
+        assert_eq!(checks(&conn), vec![check("0xtx1", "a_to_b", false), check("0xtx2", "a_to_b", false)]);
+    }
+
+    #[test]
+    fn late_snapshots_resolve_swaps_delivered_before_them() {
+        let conn = new_db();
+        // The later swap arrives first, before the pool's creation
+        commit_events(&conn, &[swap("0xtx2", 40, 80, (1020, 990), 3)], 0, None).unwrap();
+        assert_eq!(checks(&conn), vec![check("0xtx2", "unknown", false)]);
+
+        commit_events(&conn, &[created(1000, 1000, 1)], 0, None).unwrap();
+        commit_events(&conn, &[swap("0xtx1", 100, 50, (1100, 950), 2)], 0, None).unwrap();
+        assert_eq!(checks(&conn), vec![check("0xtx1", "a_to_b", false), check("0xtx2", "b_to_a", false)]);
+    }
+
+    #[test]
+    fn volume_follows_the_direction_once_known() {
+        let conn = new_db();
+        let volume = |conn: &Connection| -> (f64, f64, i64) {
+            conn.query_row("SELECT volume_a, volume_b, swaps FROM pool_stats", [], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .unwrap()
+        };
+        commit_events(&conn, &[swap("0xtx2", 40, 80, (1020, 990), 3)], 0, None).unwrap();
+        assert_eq!(volume(&conn), (0.0, 0.0, 1));
+
+        let events = [created(1000, 1000, 1), swap("0xtx1", 100, 50, (1100, 950), 2)];
+        commit_events(&conn, &events, 0, None).unwrap();
+        // 100 A in and 50 B out, then 40 B in and 80 A out
+        assert_eq!(volume(&conn), (180.0, 90.0, 2));
+    }
 
-        // Wait before the next polling cycle
-        sleep(Duration::from_secs(POLL_INTERVAL_SECS)).await;
+    #[test]
+    fn swap_matching_no_snapshot_is_flagged() {
+        let conn = new_db();
+        let events = [created(1000, 1000, 1), swap("0xtx1", 100, 50, (1100, 940), 2)];
+        commit_events(&conn, &events, 0, None).unwrap();
+        assert_eq!(checks(&conn), vec![check("0xtx1", "unknown", true)]);
     }
 }
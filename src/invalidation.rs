@@ -0,0 +1,107 @@
+use serde_json::json;
+use std::collections::BTreeSet;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinSet;
+use tracing::warn;
+
+use crate::config::config;
+use crate::metrics::CACHE_INVALIDATION_FAILURES;
+
+/// How long a replica may take to acknowledge an invalidation
+const INVALIDATION_TIMEOUT_MS: u64 = 2_000;
+
+/// Path replicas receive invalidations on
+pub const INVALIDATION_PATH: &str = "/api/admin/invalidate";
+
+/// A change the indexer node reports to replicas
+enum Notice {
+    /// These pools' rows changed
+    Pools(Vec<String>),
+    /// A polling cycle completed up to this time (ms since epoch)
+    Synced(u64),
+}
+
+/// Handle the indexer uses to tell API replicas which pools changed.
+/// 
+/// Notifications are queued and sent by a background task, so committing a
+/// page never waits on the network.
+#[derive(Clone)]
+pub struct Invalidator {
+    tx: UnboundedSender<Notice>,
+}
+
+impl Invalidator {
+    /// Queues an invalidation of the given pools on every replica.
+    pub fn notify(&self, pool_ids: Vec<String>) {
+        if !pool_ids.is_empty() {
+            let _ = self.tx.send(Notice::Pools(pool_ids));
+        }
+    }
+
+    /// Tells replicas the indexer completed a polling cycle, so their
+    /// stale-data guard measures lag from the indexer node's progress.
+    pub fn synced(&self, last_sync_ms: u64) {
+        let _ = self.tx.send(Notice::Synced(last_sync_ms));
+    }
+}
+
+/// Starts fanning invalidations out to `INVALIDATION_PEERS`.
+/// 
+/// # Returns
+/// * `Option<Invalidator>` - Handle for the indexer, or `None` when no peers are configured
+pub fn start_invalidator() -> Option<Invalidator> {
+    let peers = config().invalidation_peers.clone();
+    if peers.is_empty() {
+        return None;
+    }
+    let (tx, rx) = unbounded_channel();
+    tokio::spawn(fan_out(rx, peers));
+    Some(Invalidator { tx })
+}
+
+/// Sends each queued batch of pool IDs, with the indexer's last sync time,
+/// to every peer.
+/// 
+/// Notices that queue up while a fan-out is in flight are merged into the
+/// next request. Requests carry the admin token, which replicas require on
+/// the invalidation endpoint. A replica that can't be reached is logged and
+/// counted; its caches catch up when their TTLs expire.
+async fn fan_out(mut rx: UnboundedReceiver<Notice>, peers: Vec<String>) {
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_millis(INVALIDATION_TIMEOUT_MS))
+        .build()
+        .unwrap_or_default();
+    let token = config().admin_token.clone().unwrap_or_default();
+
+    while let Some(first) = rx.recv().await {
+        let mut pool_ids = BTreeSet::new();
+        let mut last_sync_ms = None;
+        let mut notice = Some(first);
+        while let Some(next) = notice {
+            match next {
+                Notice::Pools(ids) => pool_ids.extend(ids),
+                Notice::Synced(ms) => last_sync_ms = last_sync_ms.max(Some(ms)),
+            }
+            notice = rx.try_recv().ok();
+        }
+        let body = json!({ "pool_ids": pool_ids, "last_sync_ms": last_sync_ms });
+
+        let mut requests = JoinSet::new();
+        for peer in &peers {
+            let request = client
+                .post(format!("{}{}", peer, INVALIDATION_PATH))
+                .bearer_auth(&token)
+                .json(&body);
+            let peer = peer.clone();
+            requests.spawn(async move { (peer, request.send().await.and_then(|r| r.error_for_status())) });
+        }
+        while let Some(Ok((peer, result))) = requests.join_next().await {
+            if let Err(e) = result {
+                CACHE_INVALIDATION_FAILURES.fetch_add(1, Ordering::Relaxed);
+                warn!(peer = %peer, error = %e, "failed to invalidate caches");
+            }
+        }
+    }
+}
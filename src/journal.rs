@@ -0,0 +1,97 @@
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+
+use crate::config::config;
+
+/// Default journal file, created next to the database
+pub const DEFAULT_JOURNAL_PATH: &str = "fooswap.journal";
+
+/// Returns the configured journal path (`EVENT_JOURNAL_PATH`), or `None`
+/// when it is set to an empty string to disable journaling.
+pub fn journal_path() -> Option<String> {
+    config().event_journal_path.clone()
+}
+
+/// Write-ahead journal of event pages that are being applied to the database.
+/// 
+/// Each page is appended as a length-prefixed JSON record (4-byte
+/// little-endian length, then the JSON array of events) and fsynced before
+/// any derived table is touched. Once the page is applied the journal is
+/// truncated, so after a crash it holds exactly the page that was in flight.
+/// Replaying it is safe because applying events is idempotent.
+/// 
+/// A page's rows and the cursor moved past it are committed in one
+/// transaction, so the database never holds half a page, and a page that
+/// didn't commit would be fetched again from the saved cursor. The journal
+/// is there so that restart doesn't depend on the RPC node still serving
+/// that page: nodes prune old events, and after a failover the next node
+/// may not have them, which would leave the cursor on a page nobody can
+/// return.
+pub struct EventJournal {
+    file: File,
+}
+
+impl EventJournal {
+    /// Opens the journal, creating it if it doesn't exist.
+    /// 
+    /// # Arguments
+    /// * `path` - Journal file path
+    /// 
+    /// # Returns
+    /// * `io::Result<EventJournal>` - The journal or error
+    pub fn open(path: &str) -> io::Result<EventJournal> {
+        let file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        Ok(EventJournal { file })
+    }
+
+    /// Reads the pages left behind by an interrupted run.
+    /// 
+    /// A torn record at the end (a crash during `append`) was never applied
+    /// and is dropped.
+    /// 
+    /// # Returns
+    /// * `io::Result<Vec<Vec<Value>>>` - The complete pages, oldest first
+    pub fn pending(&mut self) -> io::Result<Vec<Vec<Value>>> {
+        let mut bytes = Vec::new();
+        (&self.file).read_to_end(&mut bytes)?;
+
+        let mut pages = Vec::new();
+        let mut rest = bytes.as_slice();
+        while rest.len() >= 4 {
+            let len = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let Some(record) = rest.get(4..4 + len) else { break };
+            match serde_json::from_slice(record) {
+                Ok(page) => pages.push(page),
+                Err(_) => break,
+            }
+            rest = &rest[4 + len..];
+        }
+        Ok(pages)
+    }
+
+    /// Durably records a page before it is applied.
+    /// 
+    /// # Arguments
+    /// * `events` - The page of events about to be applied
+    /// 
+    /// # Returns
+    /// * `io::Result<()>` - Success once the record is on disk
+    pub fn append(&mut self, events: &[Value]) -> io::Result<()> {
+        let record = serde_json::to_vec(events)?;
+        let mut buf = Vec::with_capacity(4 + record.len());
+        buf.extend_from_slice(&(record.len() as u32).to_le_bytes());
+        buf.extend_from_slice(&record);
+        self.file.write_all(&buf)?;
+        self.file.sync_data()
+    }
+
+    /// Discards journaled pages once they have been applied.
+    /// 
+    /// # Returns
+    /// * `io::Result<()>` - Success or error
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.sync_data()
+    }
+}
@@ -0,0 +1,83 @@
+use axum::{
+    http::{header, HeaderMap, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::metrics::{QUOTE_DB_MICROS, QUOTE_LATENCY, QUOTE_MATH_MICROS, QUOTE_SERIALIZE_MICROS};
+
+/// Request header asking for a `Server-Timing` breakdown in the response
+const DEBUG_TIMING_HEADER: &str = "x-debug-timing";
+
+/// Time spent in each phase of serving a quote.
+/// 
+/// Handlers wrap each phase in `db` or `math` and finish with `respond`,
+/// which times serialization and records everything in the quote metrics.
+pub struct QuoteTimings {
+    started: Instant,
+    db: Duration,
+    math: Duration,
+}
+
+impl QuoteTimings {
+    /// Starts timing a quote request.
+    pub fn start() -> Self {
+        QuoteTimings { started: Instant::now(), db: Duration::ZERO, math: Duration::ZERO }
+    }
+
+    /// Runs a database phase, including any wait for the connection lock.
+    pub fn db<T>(&mut self, phase: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = phase();
+        self.db += started.elapsed();
+        result
+    }
+
+    /// Runs a route search or simulation phase.
+    pub fn math<T>(&mut self, phase: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = phase();
+        self.math += started.elapsed();
+        result
+    }
+
+    /// Serializes the response body and records the quote's timings.
+    /// 
+    /// When the request carried `X-Debug-Timing`, the breakdown is returned in
+    /// a `Server-Timing` header (durations in milliseconds).
+    /// 
+    /// # Arguments
+    /// * `headers` - Request headers
+    /// * `body` - JSON response body
+    /// 
+    /// # Returns
+    /// * `Response` - The serialized JSON response
+    pub fn respond(self, headers: &HeaderMap, body: &serde_json::Value) -> Response {
+        let started = Instant::now();
+        let bytes = serde_json::to_vec(body).unwrap_or_default();
+        let serialize = started.elapsed();
+
+        QUOTE_DB_MICROS.fetch_add(self.db.as_micros() as u64, Ordering::Relaxed);
+        QUOTE_MATH_MICROS.fetch_add(self.math.as_micros() as u64, Ordering::Relaxed);
+        QUOTE_SERIALIZE_MICROS.fetch_add(serialize.as_micros() as u64, Ordering::Relaxed);
+        let total = self.started.elapsed();
+        QUOTE_LATENCY.observe(total);
+
+        let mut response = ([(header::CONTENT_TYPE, "application/json")], bytes).into_response();
+        if headers.contains_key(DEBUG_TIMING_HEADER) {
+            let ms = |d: Duration| d.as_secs_f64() * 1000.0;
+            let timing = format!(
+                "db;dur={:.3}, math;dur={:.3}, serialize;dur={:.3}, total;dur={:.3}",
+                ms(self.db),
+                ms(self.math),
+                ms(serialize),
+                ms(total)
+            );
+            if let Ok(value) = HeaderValue::from_str(&timing) {
+                response.headers_mut().insert("server-timing", value);
+            }
+        }
+        response
+    }
+}
@@ -0,0 +1,47 @@
+//! Fooswap DEX backend: Sui event indexer, SQLite persistence and HTTP API.
+//! 
+//! The `fooswap-backend` binary wires these modules together; they are
+//! exposed as a library so integration tests can drive the same pipeline.
+
+pub mod candles;
+pub mod config;
+pub mod custom_metrics;
+pub mod db;
+pub mod debug;
+pub mod doctor;
+pub mod error;
+pub mod events;
+pub mod exports;
+pub mod features;
+pub mod freshness;
+pub mod graph_cache;
+pub mod growth;
+pub mod idempotency;
+pub mod idle;
+pub mod indexer;
+pub mod invalidation;
+pub mod journal;
+pub mod latency;
+pub mod listen;
+pub mod live;
+pub mod logging;
+pub mod maintenance;
+pub mod metrics;
+pub mod migrations;
+pub mod pool_cache;
+pub mod precision;
+pub mod pricing;
+pub mod query_log;
+pub mod reference;
+pub mod routing;
+pub mod reports;
+pub mod risk;
+pub mod response_cache;
+pub mod retention;
+pub mod routes;
+pub mod shutdown;
+pub mod slo;
+pub mod usage;
+pub mod verify;
+pub mod wash;
+pub mod ws_auth;
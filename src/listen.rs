@@ -0,0 +1,188 @@
+use axum::Router;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use std::io::{self, ErrorKind};
+use std::net::SocketAddr;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::net::{TcpListener, UnixListener};
+use tokio::task::JoinSet;
+use tracing::{info, warn};
+
+use crate::config::config;
+use crate::shutdown::{requested, Shutdown};
+
+/// Prefix marking a Unix domain socket path in `LISTEN_ADDRS`
+const UNIX_PREFIX: &str = "unix:";
+
+/// Permissions of Unix socket files when `UNIX_SOCKET_MODE` is unset: owner
+/// and group may connect, others may not
+pub const DEFAULT_UNIX_SOCKET_MODE: u32 = 0o660;
+
+/// One address the API is served on
+#[derive(Clone, Debug)]
+pub enum ListenAddr {
+    /// TCP socket, IPv4 or IPv6
+    Tcp(SocketAddr),
+    /// Unix domain socket at the given path
+    Unix(PathBuf),
+}
+
+impl std::fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ListenAddr::Tcp(addr) => write!(f, "http://{}", addr),
+            ListenAddr::Unix(path) => write!(f, "{}{}", UNIX_PREFIX, path.display()),
+        }
+    }
+}
+
+/// Parses the configured addresses to serve the API on.
+/// 
+/// `LISTEN_ADDRS` is a comma-separated list of TCP addresses (`127.0.0.1:3000`,
+/// `[::]:3000`) and Unix socket paths prefixed with `unix:`
+/// (`unix:/run/fooswap/api.sock`). Without it, the API is served on
+/// `BIND_ADDRESS` and `PORT`.
+/// 
+/// # Returns
+/// * `Result<Vec<ListenAddr>, String>` - The addresses, or a description of the invalid entry
+pub fn listen_addrs() -> Result<Vec<ListenAddr>, String> {
+    let addrs = config()
+        .listen_addrs
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| match entry.strip_prefix(UNIX_PREFIX) {
+            Some("") => Err(format!("LISTEN_ADDRS entry `{}` has no socket path", entry)),
+            Some(path) => Ok(ListenAddr::Unix(PathBuf::from(path))),
+            None => entry
+                .parse()
+                .map(ListenAddr::Tcp)
+                .map_err(|_| format!("LISTEN_ADDRS entry `{}` is not a socket address", entry)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if addrs.is_empty() {
+        return Err("LISTEN_ADDRS lists no addresses".to_string());
+    }
+    Ok(addrs)
+}
+
+/// Binds a Unix socket, replacing a socket file left behind by a previous run.
+/// 
+/// A leftover socket nobody accepts on is removed. A socket another process
+/// is still serving on, or a path that is not a socket, is an error rather
+/// than something to delete.
+fn bind_unix(path: &Path) -> io::Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, "path exists and is not a socket"));
+        }
+        match std::os::unix::net::UnixStream::connect(path) {
+            Ok(_) => return Err(io::Error::new(ErrorKind::AddrInUse, "socket is in use by another process")),
+            Err(_) => std::fs::remove_file(path)?,
+        }
+    }
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(config().unix_socket_mode))?;
+    Ok(listener)
+}
+
+/// A bound listener, ready to serve
+pub enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener, PathBuf),
+}
+
+impl Listener {
+    /// Binds an address. Unix socket files get the `UNIX_SOCKET_MODE` permissions.
+    /// 
+    /// # Arguments
+    /// * `addr` - Address to listen on
+    /// 
+    /// # Returns
+    /// * `io::Result<Listener>` - The bound listener or error
+    pub async fn bind(addr: &ListenAddr) -> io::Result<Listener> {
+        match addr {
+            ListenAddr::Tcp(addr) => Ok(Listener::Tcp(TcpListener::bind(addr).await?)),
+            ListenAddr::Unix(path) => Ok(Listener::Unix(bind_unix(path)?, path.clone())),
+        }
+    }
+
+    /// Path of the socket file this listener created, if it is a Unix listener.
+    pub fn socket_path(&self) -> Option<PathBuf> {
+        match self {
+            Listener::Tcp(_) => None,
+            Listener::Unix(_, path) => Some(path.clone()),
+        }
+    }
+}
+
+/// Removes the socket file of a Unix listener, as part of shutting down.
+pub fn remove_socket(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        if e.kind() != ErrorKind::NotFound {
+            warn!(path = %path.display(), error = %e, "failed to remove socket");
+        }
+    }
+}
+
+/// Serves the router on a bound listener until shutdown is requested.
+/// 
+/// On shutdown the listener stops accepting connections, idle connections
+/// are closed, and requests in flight are answered before this returns.
+/// 
+/// # Arguments
+/// * `listener` - Listener to accept connections on
+/// * `app` - Router to serve
+/// * `shutdown` - Stops the listener
+pub async fn serve(listener: Listener, app: Router, shutdown: Shutdown) -> io::Result<()> {
+    match listener {
+        Listener::Tcp(listener) => {
+            info!("Server listening on http://{}", listener.local_addr()?);
+            axum::serve(listener, app.into_make_service())
+                .with_graceful_shutdown(requested(shutdown))
+                .await
+        }
+        Listener::Unix(listener, path) => {
+            info!("Server listening on {}{}", UNIX_PREFIX, path.display());
+            let mut connections = JoinSet::new();
+            loop {
+                let accepted = tokio::select! {
+                    accepted = listener.accept() => accepted,
+                    _ = requested(shutdown.clone()) => break,
+                };
+                let (socket, _) = match accepted {
+                    Ok(conn) => conn,
+                    Err(e) => {
+                        warn!(path = %path.display(), error = %e, "failed to accept connection");
+                        continue;
+                    }
+                };
+                let service = TowerToHyperService::new(app.clone());
+                let shutdown = shutdown.clone();
+                connections.spawn(async move {
+                    // Upgrades are kept so WebSocket clients can connect over the socket too
+                    let conn = hyper::server::conn::http1::Builder::new()
+                        .serve_connection(TokioIo::new(socket), service)
+                        .with_upgrades();
+                    tokio::pin!(conn);
+                    let result = tokio::select! {
+                        result = conn.as_mut() => result,
+                        _ = requested(shutdown) => {
+                            // Finish the request in flight, then close
+                            conn.as_mut().graceful_shutdown();
+                            conn.await
+                        }
+                    };
+                    if let Err(e) = result {
+                        warn!(error = %e, "connection on Unix socket failed");
+                    }
+                });
+                // Reap connections that have closed
+                while connections.try_join_next().is_some() {}
+            }
+            while connections.join_next().await.is_some() {}
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,642 @@
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query,
+    },
+    http::HeaderMap,
+    response::Response,
+    Extension,
+};
+use rusqlite::{params, Connection, Result, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::config::config;
+use crate::error::ApiError;
+use crate::query_log;
+use crate::ws_auth::{
+    authenticate, authenticate_headers, authorize_channel, ConnectionSlot, Credentials, Principal,
+    ADDRESS_CHANNEL_PREFIX,
+};
+
+/// Number of events buffered for each WebSocket client; a client further
+/// behind than this skips ahead and is told how many events it missed
+const LIVE_FEED_CAPACITY: usize = 1024;
+
+/// Default most channels one WebSocket connection may subscribe to
+pub const DEFAULT_WS_MAX_SUBSCRIPTIONS: usize = 100;
+
+/// Most swaps a reconnecting client can have missed and still be replayed;
+/// a client further behind is told how many it missed and resumes live
+const RESUME_MAX_SWAPS: i64 = 1000;
+
+/// An update streamed to WebSocket clients
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A newly indexed swap
+    Swap {
+        /// Row ID of the swap, which orders the stream and is sent to clients
+        /// as their `resume_token`
+        #[serde(skip)]
+        id: i64,
+        pool_id: String,
+        tx_digest: String,
+        amount_in: f64,
+        amount_out: f64,
+        timestamp: i64,
+        /// Trader address, which routes the swap to its private channel
+        #[serde(skip)]
+        sender: Option<String>,
+    },
+    /// A pool's state after a committed page changed it
+    Pool {
+        pool_id: String,
+        token_a: String,
+        token_b: String,
+        reserve_a: f64,
+        reserve_b: f64,
+        last_updated: i64,
+    },
+}
+
+impl LiveEvent {
+    fn pool_id(&self) -> &str {
+        match self {
+            LiveEvent::Swap { pool_id, .. } | LiveEvent::Pool { pool_id, .. } => pool_id,
+        }
+    }
+
+    /// Serializes the event for a client; swaps carry the `resume_token` to
+    /// reconnect with after them.
+    fn message(&self) -> String {
+        self.value().to_string()
+    }
+
+    /// Serializes a swap for the private channel of its sender, as an
+    /// `activity` message naming the address.
+    fn activity_message(&self, address: &str) -> String {
+        let mut message = self.value();
+        message["type"] = json!("activity");
+        message["address"] = json!(address);
+        message.to_string()
+    }
+
+    fn value(&self) -> serde_json::Value {
+        let mut message = serde_json::to_value(self).unwrap_or_default();
+        if let LiveEvent::Swap { id, .. } = self {
+            message["resume_token"] = json!(id.to_string());
+        }
+        message
+    }
+}
+
+/// Channel the indexer publishes live events on
+pub type LiveFeed = broadcast::Sender<LiveEvent>;
+
+/// Creates the live event channel.
+pub fn live_feed() -> LiveFeed {
+    broadcast::channel(LIVE_FEED_CAPACITY).0
+}
+
+/// Returns the largest swap row ID, so the swaps a page inserts can be told
+/// apart from swaps it re-indexed.
+pub fn last_swap_id(conn: &Connection) -> Result<i64> {
+    query_log::query_row(conn, "last_swap_id", "SELECT COALESCE(MAX(id), 0) FROM swaps", params![], |row| {
+        row.get(0)
+    })
+}
+
+/// Publishes the swaps and pool states a committed page wrote.
+/// 
+/// Only swaps inserted after `after_swap_id` are sent, so replayed pages
+/// don't repeat swaps clients already saw. Pools are read back from the
+/// database, like the pool cache, so clients get the state SQLite stored.
+/// Nothing is read while no client is connected.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `feed` - Channel to publish on
+/// * `after_swap_id` - `last_swap_id` before the page was committed
+/// * `pool_ids` - Pools the page touched
+pub fn publish_page(conn: &Connection, feed: &LiveFeed, after_swap_id: i64, pool_ids: &[String]) -> Result<()> {
+    if feed.receiver_count() == 0 {
+        return Ok(());
+    }
+    let mut events = query_log::query_all(
+        conn,
+        "publish_page:swaps",
+        &format!("SELECT {} FROM swaps WHERE id > ?1 ORDER BY id", SWAP_EVENT_COLUMNS),
+        params![after_swap_id],
+        swap_event,
+    )?;
+    for pool_id in pool_ids {
+        events.extend(pool_event(conn, pool_id)?);
+    }
+    for event in events {
+        // Fails only once every client has disconnected
+        let _ = feed.send(event);
+    }
+    Ok(())
+}
+
+/// Columns `swap_event` reads from `swaps`
+const SWAP_EVENT_COLUMNS: &str = "id, pool_id, tx_digest, amount_in, amount_out, timestamp, sender";
+
+fn swap_event(row: &rusqlite::Row) -> Result<LiveEvent> {
+    Ok(LiveEvent::Swap {
+        id: row.get(0)?,
+        pool_id: row.get(1)?,
+        tx_digest: row.get(2)?,
+        amount_in: row.get(3)?,
+        amount_out: row.get(4)?,
+        timestamp: row.get(5)?,
+        sender: row.get(6)?,
+    })
+}
+
+/// Reads a pool's stored state as a `pool` event.
+fn pool_event(conn: &Connection, pool_id: &str) -> Result<Option<LiveEvent>> {
+    query_log::query_opt(
+        conn,
+        "pool_event",
+        "SELECT token_a, token_b, reserve_a, reserve_b, last_updated FROM pools WHERE pool_id = ?1",
+        params![pool_id],
+        |row| {
+            Ok(LiveEvent::Pool {
+                pool_id: pool_id.to_string(),
+                token_a: row.get(0)?,
+                token_b: row.get(1)?,
+                reserve_a: row.get(2)?,
+                reserve_b: row.get(3)?,
+                last_updated: row.get(4)?,
+            })
+        },
+    )
+}
+
+/// Channels a client streams
+#[derive(Default)]
+struct Subscriptions {
+    /// Public market channels: pools whose swaps and updates are streamed
+    pools: BTreeSet<String>,
+    /// Private channels: addresses whose swaps are streamed as `activity`
+    addresses: BTreeSet<String>,
+}
+
+impl Subscriptions {
+    /// Whether every pool is streamed, as it is until the client subscribes
+    /// to any channel.
+    fn every_pool(&self) -> bool {
+        self.pools.is_empty() && self.addresses.is_empty()
+    }
+
+    fn wants_pool(&self, pool_id: &str) -> bool {
+        self.every_pool() || self.pools.contains(pool_id)
+    }
+
+    /// Applies a subscription change, all or nothing.
+    /// 
+    /// Subscribing to an address's channel requires owning the address, and
+    /// the client's channels may not exceed `WS_MAX_SUBSCRIPTIONS`.
+    /// 
+    /// # Arguments
+    /// * `subscribe` - Channels to add: pool IDs, or `address:` and an address
+    /// * `unsubscribe` - Channels to remove
+    /// * `principal` - Who the connection authenticated as, if anyone
+    /// 
+    /// # Returns
+    /// * `Result<(), ApiError>` - Success, or why the change was refused
+    fn apply(&mut self, subscribe: &[String], unsubscribe: &[String], principal: Option<&Principal>) -> Result<(), ApiError> {
+        let mut next = Subscriptions { pools: self.pools.clone(), addresses: self.addresses.clone() };
+        for channel in unsubscribe.iter().map(|c| c.trim()) {
+            match channel.strip_prefix(ADDRESS_CHANNEL_PREFIX) {
+                Some(address) => next.addresses.remove(&address.trim().to_ascii_lowercase()),
+                None => next.pools.remove(channel),
+            };
+        }
+        for channel in subscribe.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            match authorize_channel(principal, channel)? {
+                Some(address) => next.addresses.insert(address),
+                None => next.pools.insert(channel.to_string()),
+            };
+        }
+        if next.pools.len() + next.addresses.len() > config().ws_max_subscriptions {
+            return Err(ApiError::BadRequest(format!(
+                "At most {} channels may be subscribed to",
+                config().ws_max_subscriptions
+            )));
+        }
+        *self = next;
+        Ok(())
+    }
+
+    /// Drops the private channels a newly authenticated principal doesn't own.
+    fn restrict_to(&mut self, principal: &Principal) {
+        self.addresses.retain(|address| principal.owns(address));
+    }
+
+    /// The messages an event is sent to the client as: on its pool's market
+    /// channel and, for a swap, on its sender's private channel.
+    fn messages(&self, event: &LiveEvent) -> Vec<String> {
+        let mut messages = Vec::new();
+        if self.wants_pool(event.pool_id()) {
+            messages.push(event.message());
+        }
+        if let LiveEvent::Swap { sender: Some(sender), .. } = event {
+            let sender = sender.to_ascii_lowercase();
+            if self.addresses.contains(&sender) {
+                messages.push(event.activity_message(&sender));
+            }
+        }
+        messages
+    }
+
+    /// The `subscriptions` message listing the current channels.
+    fn message(&self) -> String {
+        json!({ "type": "subscriptions", "pool_ids": self.pools, "addresses": self.addresses }).to_string()
+    }
+}
+
+/// What a reconnecting client missed since its resume token
+enum Replay {
+    /// The swaps after the token, then the current state of their pools
+    Events(Vec<LiveEvent>),
+    /// More swaps than `RESUME_MAX_SWAPS` were missed
+    TooFarBehind(i64),
+}
+
+/// Reads the events a client missed after the swap `resume_token`.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `resume_token` - ID of the last swap the client received
+/// * `subscriptions` - The client's channels
+/// 
+/// # Returns
+/// * `Result<(Replay, i64)>` - The missed events, and the ID of the latest stored swap
+fn read_replay(conn: &Connection, resume_token: i64, subscriptions: &Subscriptions) -> Result<(Replay, i64)> {
+    let latest = last_swap_id(conn)?;
+    // Only the client's channels count towards `RESUME_MAX_SWAPS`, so busy
+    // pools it doesn't watch can't push it past the limit
+    let mut values: Vec<&dyn ToSql> = vec![&resume_token, &latest];
+    let channel_filter = if subscriptions.every_pool() {
+        String::new()
+    } else {
+        let mut conditions = Vec::new();
+        for (column, ids) in [("pool_id", &subscriptions.pools), ("sender", &subscriptions.addresses)] {
+            if ids.is_empty() {
+                continue;
+            }
+            let placeholders: Vec<String> =
+                (values.len() + 1..=values.len() + ids.len()).map(|i| format!("?{}", i)).collect();
+            conditions.push(format!("{} IN ({})", column, placeholders.join(", ")));
+            values.extend(ids.iter().map(|id| id as &dyn ToSql));
+        }
+        format!(" AND ({})", conditions.join(" OR "))
+    };
+
+    let missed: i64 = query_log::query_row(
+        conn,
+        "read_replay:count",
+        &format!("SELECT COUNT(*) FROM swaps WHERE id > ?1 AND id <= ?2{}", channel_filter),
+        &values,
+        |row| row.get(0),
+    )?;
+    if missed > RESUME_MAX_SWAPS {
+        return Ok((Replay::TooFarBehind(missed), latest));
+    }
+
+    let mut events: Vec<LiveEvent> = query_log::query_all(
+        conn,
+        "read_replay:swaps",
+        &format!("SELECT {} FROM swaps WHERE id > ?1 AND id <= ?2{} ORDER BY id", SWAP_EVENT_COLUMNS, channel_filter),
+        &values,
+        swap_event,
+    )?;
+    let touched: BTreeSet<String> = events
+        .iter()
+        .map(|event| event.pool_id().to_string())
+        .filter(|pool_id| subscriptions.wants_pool(pool_id))
+        .collect();
+    for pool_id in &touched {
+        events.extend(pool_event(conn, pool_id)?);
+    }
+    Ok((Replay::Events(events), latest))
+}
+
+/// Message sent by a client: credentials, then a subscription change
+#[derive(Deserialize)]
+struct ClientRequest {
+    auth: Option<Credentials>,
+    #[serde(default)]
+    subscribe: Vec<String>,
+    #[serde(default)]
+    unsubscribe: Vec<String>,
+}
+
+/// Splits a comma-separated list of channels.
+fn parse_channels(raw: &str) -> Vec<String> {
+    raw.split(',').map(str::trim).filter(|id| !id.is_empty()).map(str::to_string).collect()
+}
+
+/// Streams newly indexed swaps and pool updates over a WebSocket.
+/// 
+/// Pool channels are public. An address's private channel, streaming the
+/// swaps it sends as `activity` messages, is only open to a client
+/// authenticated as its owner: by an API key an admin registered the
+/// address for, or by a JWT signed with `WS_JWT_SECRET` naming it in `sub`
+/// or `addresses`. Credentials are sent as `X-API-Key` or `Authorization:
+/// Bearer` headers, or in an `auth` message by clients that can't set
+/// headers. Connections are limited by `WS_MAX_CONNECTIONS`, and per owner
+/// by `WS_MAX_CONNECTIONS_PER_OWNER`.
+/// 
+/// # Endpoint
+/// `GET /ws?pool_id=0x...,0x...&address=0x...`
+/// 
+/// # Query Parameters
+/// * `pool_id` (optional) - Comma-separated pools to stream
+/// * `address` (optional) - Comma-separated addresses whose private channels
+///   to stream; requires header authentication
+/// * `resume_token` (optional) - Token of the last swap received before a
+///   disconnect; the swaps missed since are replayed before live events
+/// 
+/// # Messages
+/// The server sends JSON text messages tagged by `type`: `swap` (with the
+/// `resume_token` to reconnect with after it), `pool`, `activity` (a swap on
+/// a private channel), `subscriptions` (the current channels, after every
+/// change), `authenticated` (the addresses an `auth` message unlocked),
+/// `live` (the switch to live events, once missed swaps are replayed),
+/// `lagged` (the number of events skipped because the client read too slowly
+/// or was disconnected too long) and `error` (a message the server could not
+/// parse or refused).
+/// Clients may send `{"auth": {"api_key": "..."}}` or `{"auth": {"token":
+/// "..."}}` to authenticate, and `{"subscribe": [...], "unsubscribe": [...]}`
+/// to change their channels, where `address:0x...` names a private channel.
+/// Without any channel every pool is streamed.
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    headers: HeaderMap,
+    Extension(feed): Extension<LiveFeed>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, ApiError> {
+    let mut channels = params.get("pool_id").map(|raw| parse_channels(raw)).unwrap_or_default();
+    if let Some(raw) = params.get("address") {
+        channels.extend(parse_channels(raw).iter().map(|address| format!("{}{}", ADDRESS_CHANNEL_PREFIX, address)));
+    }
+    let resume_token = match params.get("resume_token").map(|v| v.parse::<i64>()) {
+        None => None,
+        Some(Ok(token)) if token >= 0 => Some(token),
+        Some(_) => return Err(ApiError::BadRequest("Query parameter `resume_token` is not a valid token".to_string())),
+    };
+
+    let (session, events, replay, latest) = {
+        let conn = conn_arc.lock()?;
+        let principal = authenticate_headers(&conn, &headers)?;
+        let mut subscriptions = Subscriptions::default();
+        subscriptions.apply(&channels, &[], principal.as_ref())?;
+        let slot = ConnectionSlot::acquire(principal.as_ref().map(|p| p.owner.as_str()))?;
+
+        // Subscribe before reading what was missed, so nothing published in
+        // between is lost; swaps received twice are dropped by ID
+        let events = feed.subscribe();
+        let (replay, latest) = match resume_token {
+            Some(token) => read_replay(&conn, token, &subscriptions)?,
+            None => (Replay::Events(Vec::new()), last_swap_id(&conn)?),
+        };
+        (Session { principal, subscriptions, slot }, events, replay, latest)
+    };
+    if resume_token.is_some_and(|token| token > latest) {
+        return Err(ApiError::BadRequest("Query parameter `resume_token` is not a valid token".to_string()));
+    }
+    Ok(ws.on_upgrade(move |socket| stream_events(socket, events, conn_arc, session, replay, latest)))
+}
+
+/// State of one WebSocket connection
+struct Session {
+    /// Who the connection authenticated as, if anyone
+    principal: Option<Principal>,
+    subscriptions: Subscriptions,
+    /// Held for as long as the connection is open
+    slot: ConnectionSlot,
+}
+
+impl Session {
+    /// Handles a message from the client: authenticates it if it sent
+    /// credentials, then applies its subscription change.
+    /// 
+    /// # Returns
+    /// * `Vec<String>` - The messages to answer with
+    fn handle(&mut self, conn_arc: &Mutex<Connection>, text: &str) -> Vec<String> {
+        let error = |message: String| json!({ "type": "error", "message": message }).to_string();
+        let request = match serde_json::from_str::<ClientRequest>(text) {
+            Ok(request) => request,
+            Err(e) => return vec![error(format!("Invalid message: {}", e))],
+        };
+
+        let mut messages = Vec::new();
+        if let Some(credentials) = &request.auth {
+            let authenticated = conn_arc
+                .lock()
+                .map_err(ApiError::from)
+                .and_then(|conn| authenticate(&conn, credentials))
+                .and_then(|principal| self.slot.assign(&principal.owner).map(|_| principal));
+            match authenticated {
+                Ok(principal) => {
+                    self.subscriptions.restrict_to(&principal);
+                    messages.push(json!({ "type": "authenticated", "addresses": principal.addresses }).to_string());
+                    self.principal = Some(principal);
+                }
+                Err(e) => return vec![error(e.message())],
+            }
+        }
+        if let Err(e) = self.subscriptions.apply(&request.subscribe, &request.unsubscribe, self.principal.as_ref()) {
+            messages.push(error(e.message()));
+        }
+        messages.push(self.subscriptions.message());
+        messages
+    }
+}
+
+/// Replays what the client missed, then forwards live events on its
+/// channels until either side closes.
+/// 
+/// # Arguments
+/// * `socket` - The client's WebSocket
+/// * `events` - Live events published since the client connected
+/// * `conn_arc` - Thread-safe SQLite connection, to authenticate API keys sent in band
+/// * `session` - The client's principal, channels and connection slot
+/// * `replay` - Events the client missed before connecting
+/// * `position` - ID of the latest swap covered by `replay`; live swaps up to it are skipped
+async fn stream_events(
+    mut socket: WebSocket,
+    mut events: broadcast::Receiver<LiveEvent>,
+    conn_arc: Arc<Mutex<Connection>>,
+    mut session: Session,
+    replay: Replay,
+    position: i64,
+) {
+    let mut messages = vec![session.subscriptions.message()];
+    let replayed = match replay {
+        Replay::Events(events) => {
+            let swaps = events.iter().filter(|event| matches!(event, LiveEvent::Swap { .. })).count();
+            messages.extend(events.iter().flat_map(|event| session.subscriptions.messages(event)));
+            swaps
+        }
+        Replay::TooFarBehind(missed) => {
+            messages.push(json!({ "type": "lagged", "skipped": missed }).to_string());
+            0
+        }
+    };
+    messages.push(json!({ "type": "live", "resume_token": position.to_string(), "replayed": replayed }).to_string());
+    for message in messages {
+        if socket.send(Message::Text(message)).await.is_err() {
+            return;
+        }
+    }
+
+    loop {
+        let messages = tokio::select! {
+            event = events.recv() => match event {
+                Ok(LiveEvent::Swap { id, .. }) if id <= position => continue,
+                Ok(event) => session.subscriptions.messages(&event),
+                Err(RecvError::Lagged(skipped)) => vec![json!({ "type": "lagged", "skipped": skipped }).to_string()],
+                Err(RecvError::Closed) => return,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => session.handle(&conn_arc, &text),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => continue,
+            },
+        };
+        for message in messages {
+            if socket.send(Message::Text(message)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn swap(pool_id: &str, sender: &str) -> LiveEvent {
+        LiveEvent::Swap {
+            id: 7,
+            pool_id: pool_id.to_string(),
+            tx_digest: "digest".to_string(),
+            amount_in: 100.0,
+            amount_out: 49.5,
+            timestamp: 0,
+            sender: Some(sender.to_string()),
+        }
+    }
+
+    /// Swaps 1 to 4 alternate between pools 0x1 and 0x2, and swaps 3 and 4
+    /// swap the senders of 1 and 2.
+    fn replay_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO pools (pool_id, token_a, token_b) VALUES ('0x1', 'A', 'B'), ('0x2', 'A', 'C');
+             INSERT INTO swaps (id, pool_id, amount_in, amount_out, timestamp, tx_digest, sender) VALUES
+                 (1, '0x1', 1, 1, 1, 'tx1', '0xaa'),
+                 (2, '0x2', 1, 1, 2, 'tx2', '0xbb'),
+                 (3, '0x1', 1, 1, 3, 'tx3', '0xbb'),
+                 (4, '0x2', 1, 1, 4, 'tx4', '0xaa');",
+        )
+        .unwrap();
+        conn
+    }
+
+    /// The swap IDs and pool IDs of a replay, in order.
+    fn replayed(replay: Replay) -> (Vec<i64>, Vec<String>) {
+        let Replay::Events(events) = replay else { panic!("expected a replay") };
+        let mut swaps = Vec::new();
+        let mut pools = Vec::new();
+        for event in events {
+            match event {
+                LiveEvent::Swap { id, .. } => swaps.push(id),
+                LiveEvent::Pool { pool_id, .. } => pools.push(pool_id),
+            }
+        }
+        (swaps, pools)
+    }
+
+    #[test]
+    fn replay_resumes_after_the_token_on_the_client_channels() {
+        let conn = replay_db();
+        let (replay, latest) = read_replay(&conn, 0, &Subscriptions::default()).unwrap();
+        assert_eq!(latest, 4);
+        assert_eq!(replayed(replay), (vec![1, 2, 3, 4], vec!["0x1".to_string(), "0x2".to_string()]));
+
+        let pools = Subscriptions { pools: BTreeSet::from(["0x1".to_string()]), ..Default::default() };
+        let (replay, _) = read_replay(&conn, 1, &pools).unwrap();
+        assert_eq!(replayed(replay), (vec![3], vec!["0x1".to_string()]));
+
+        // An address channel replays its swaps in any pool, without their pools' state
+        let addresses = Subscriptions { addresses: BTreeSet::from(["0xaa".to_string()]), ..Default::default() };
+        let (replay, _) = read_replay(&conn, 1, &addresses).unwrap();
+        assert_eq!(replayed(replay), (vec![4], vec![]));
+
+        let (replay, latest) = read_replay(&conn, 4, &Subscriptions::default()).unwrap();
+        assert_eq!(latest, 4);
+        assert_eq!(replayed(replay), (vec![], vec![]));
+    }
+
+    #[test]
+    fn only_swaps_on_the_client_channels_count_towards_the_replay_limit() {
+        let conn = replay_db();
+        for id in 5..=5 + RESUME_MAX_SWAPS {
+            conn.execute(
+                "INSERT INTO swaps (id, pool_id, amount_in, amount_out, timestamp, tx_digest)
+                 VALUES (?1, '0x2', 1, 1, ?1, ?1)",
+                params![id],
+            )
+            .unwrap();
+        }
+        let (replay, latest) = read_replay(&conn, 0, &Subscriptions::default()).unwrap();
+        assert_eq!(latest, 5 + RESUME_MAX_SWAPS);
+        assert!(matches!(replay, Replay::TooFarBehind(missed) if missed == 5 + RESUME_MAX_SWAPS));
+
+        let pools = Subscriptions { pools: BTreeSet::from(["0x1".to_string()]), ..Default::default() };
+        let (replay, _) = read_replay(&conn, 0, &pools).unwrap();
+        assert_eq!(replayed(replay), (vec![1, 3], vec!["0x1".to_string()]));
+    }
+
+    #[test]
+    fn swaps_reach_their_pool_and_their_sender_channels() {
+        let owner = Principal { owner: "jwt:alice".to_string(), addresses: BTreeSet::from(["0xabc".to_string()]) };
+        let mut subscriptions = Subscriptions::default();
+        assert_eq!(subscriptions.messages(&swap("0xpool", "0xabc")).len(), 1);
+
+        subscriptions.apply(&["address:0xABC".to_string()], &[], Some(&owner)).unwrap();
+        assert!(subscriptions.messages(&swap("0xpool", "0xdef")).is_empty());
+        let messages = subscriptions.messages(&swap("0xpool", "0xABC"));
+        assert_eq!(messages.len(), 1);
+        let activity: serde_json::Value = serde_json::from_str(&messages[0]).unwrap();
+        assert_eq!(activity["type"], "activity");
+        assert_eq!(activity["address"], "0xabc");
+        assert_eq!(activity["resume_token"], "7");
+
+        subscriptions.apply(&["0xpool".to_string()], &[], Some(&owner)).unwrap();
+        assert_eq!(subscriptions.messages(&swap("0xpool", "0xabc")).len(), 2);
+    }
+
+    #[test]
+    fn refused_subscription_changes_nothing() {
+        let mut subscriptions = Subscriptions::default();
+        let refused = subscriptions.apply(&["0xpool".to_string(), "address:0xabc".to_string()], &[], None);
+        assert!(matches!(refused, Err(ApiError::Unauthorized(_))));
+        assert!(subscriptions.every_pool());
+
+        let too_many: Vec<String> = (0..=config().ws_max_subscriptions).map(|i| format!("0x{:x}", i)).collect();
+        assert!(matches!(subscriptions.apply(&too_many, &[], None), Err(ApiError::BadRequest(_))));
+        assert!(subscriptions.every_pool());
+    }
+}
@@ -0,0 +1,64 @@
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+/// Default log filter, used when `LOG_LEVEL` is unset or invalid
+const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Log output format, selected with `LOG_FORMAT`
+pub enum LogFormat {
+    /// Human-readable lines (default)
+    Text,
+    /// One JSON object per event, with the fields of its enclosing spans
+    Json,
+}
+
+/// Reads the log format from `LOG_FORMAT` (`text` or `json`).
+pub fn log_format() -> Result<LogFormat, String> {
+    match std::env::var("LOG_FORMAT").unwrap_or_default().trim() {
+        "" | "text" => Ok(LogFormat::Text),
+        "json" => Ok(LogFormat::Json),
+        other => Err(format!("LOG_FORMAT must be `text` or `json`, not `{}`", other)),
+    }
+}
+
+/// Reads the log filter from `LOG_LEVEL`.
+/// 
+/// The value is a level (`info`) or a list of `target=level` directives,
+/// e.g. `warn,fooswap_backend::indexer=debug`.
+pub fn log_filter() -> Result<EnvFilter, String> {
+    match std::env::var("LOG_LEVEL") {
+        Ok(level) if !level.trim().is_empty() => {
+            EnvFilter::try_new(level.trim()).map_err(|e| format!("LOG_LEVEL `{}` is not a valid filter: {}", level, e))
+        }
+        _ => Ok(EnvFilter::new(DEFAULT_LOG_LEVEL)),
+    }
+}
+
+/// Installs the global log subscriber.
+/// 
+/// Logs are written to stderr, so the JSON reports of the `doctor` and
+/// `verify` commands stay alone on stdout. An invalid `LOG_LEVEL` or
+/// `LOG_FORMAT` falls back to the default and is logged as a warning.
+pub fn init_logging() {
+    let (filter, filter_error) = match log_filter() {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new(DEFAULT_LOG_LEVEL), Some(e)),
+    };
+    let (format, format_error) = match log_format() {
+        Ok(format) => (format, None),
+        Err(e) => (LogFormat::Text, Some(e)),
+    };
+
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal());
+    match format {
+        LogFormat::Text => builder.init(),
+        LogFormat::Json => builder.json().init(),
+    }
+
+    for error in filter_error.into_iter().chain(format_error) {
+        tracing::warn!("{}; using the default", error);
+    }
+}
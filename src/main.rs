@@ -1,11 +1,41 @@
-mod db;
-mod indexer;
-mod routes;
+use fooswap_backend::{candles, custom_metrics, db, doctor, exports, features, graph_cache, idle, indexer, invalidation, config, listen, live, logging, maintenance, metrics, migrations, pool_cache, pricing, reference, response_cache, retention, routes, shutdown, slo, verify};
 
-use axum::{Router, Extension};
-use std::sync::{Arc, Mutex};
-use std::net::SocketAddr;
-use tokio::net::TcpListener;
+use axum::{middleware, Router, Extension};
+use clap::{Parser, Subcommand};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::task::JoinSet;
+use tokio::time::timeout;
+use tower_http::trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer};
+use tracing::{error, info, warn, Level};
+
+/// Fooswap DEX indexer and API server
+#[derive(Parser)]
+#[command(version, about)]
+struct Cli {
+    /// What to run; without a subcommand both the API and the indexer run
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Serve the API without indexing, from a database another process indexes
+    Serve,
+    /// Index the chain into the database without serving the API
+    Index,
+    /// Index every event up to the chain head once, then exit
+    Backfill,
+    /// Create the database schema and apply pending migrations, including blocking ones, then exit
+    Migrate,
+    /// Run the startup self-test and exit non-zero if any check fails
+    Doctor,
+    /// Replay stored swap history and exit non-zero if any pool is inconsistent
+    Verify {
+        /// Only verify this pool
+        pool_id: Option<String>,
+    },
+}
 
 /// Main entry point for the Fooswap DEX backend service.
 /// 
@@ -14,43 +44,359 @@ use tokio::net::TcpListener;
 /// - A REST API for querying pool and swap data
 /// - SQLite-based data persistence
 /// 
-/// The service runs both the indexer and API server concurrently.
+/// Without a subcommand the service runs both the indexer and API server
+/// concurrently (the indexer unless `INDEXER_ENABLED` is off). `serve` and
+/// `index` run only one of them, so the read API can be scaled out over
+/// several processes sharing the database one `index` process writes.
+/// `backfill` and `migrate` prepare the database and exit, `doctor` performs
+/// a startup self-test and exits non-zero if any check fails, and
+/// `verify [pool_id]` replays stored swap history and exits non-zero if any
+/// pool is inconsistent.
 #[tokio::main]
 async fn main() {
+    // Structured logs on stderr, filtered by LOG_LEVEL and formatted by LOG_FORMAT
+    logging::init_logging();
+
+    let (serve_api, index) = match Cli::parse().command {
+        None => (true, indexer::indexer_enabled()),
+        Some(Command::Serve) => (true, false),
+        Some(Command::Index) => (false, true),
+        Some(Command::Backfill) => exit(backfill().await),
+        Some(Command::Migrate) => exit(migrate()),
+        Some(Command::Doctor) => exit(doctor::run().await),
+        Some(Command::Verify { pool_id }) => exit(verify::run(pool_id.as_deref())),
+    };
+    run(serve_api, index).await;
+}
+
+/// Exits with status 0 if `ok`, 1 otherwise.
+fn exit(ok: bool) -> ! {
+    std::process::exit(if ok { 0 } else { 1 })
+}
+
+/// Creates the database schema and applies pending migrations, so a deploy
+/// can migrate once before starting the processes that share the database.
+/// 
+/// Unlike startup, this also applies blocking migrations, which rebuild a
+/// table under one write lock: other processes can't write to the database
+/// until they finish, so stop them first. Migration 9 rebuilds `swaps` and
+/// takes time proportional to its size; until it has run, only the first
+/// swap event of each transaction is stored.
+/// 
+/// # Returns
+/// * `bool` - Whether the schema is up to date
+fn migrate() -> bool {
+    config::init().expect("Invalid configuration");
+    let migrated = db::init_db().and_then(|conn| {
+        migrations::run_online_migrations(&conn, &format!("pid-{}", std::process::id()), true)?;
+        Ok(conn)
+    });
+    match migrated {
+        Ok(conn) => {
+            info!(version = migrations::schema_version(&conn).unwrap_or(0), "Database schema is up to date");
+            shutdown::close_database(Arc::new(Mutex::new(conn)));
+            true
+        }
+        Err(e) => {
+            error!(error = %e, "failed to migrate database");
+            false
+        }
+    }
+}
+
+/// Indexes every event type from its saved cursor up to the chain head in a
+/// single polling cycle and rolls the new swaps into candles, then exits.
+/// 
+/// Seeds a new database before `serve` processes are started on it, or
+/// catches one up after downtime. SIGINT or SIGTERM stop it after the page
+/// it is committing; a later run resumes from there.
+/// 
+/// # Returns
+/// * `bool` - Whether every event type was read to the end
+async fn backfill() -> bool {
+    config::init().expect("Invalid configuration");
+    let conn = db::init_db().expect("Failed to initialize database");
+    let graph_cache = graph_cache::load_graph_cache(&conn, None).expect("Failed to build routing graph");
+    let conn_arc = Arc::new(Mutex::new(conn));
+
+    let (stop, shutdown) = shutdown::shutdown_channel();
+    let signal = tokio::spawn(async move {
+        shutdown::shutdown_signal().await;
+        let _ = stop.send(true);
+    });
+    let invalidator = invalidation::start_invalidator();
+    if let Err(e) = indexer::recover_journal(&conn_arc, None, &graph_cache, invalidator.as_ref()) {
+        error!(error = %e, "failed to recover the event journal");
+        signal.abort();
+        return false;
+    }
+    let synced =
+        indexer::run_indexer(conn_arc.clone(), None, graph_cache, invalidator, live::live_feed(), shutdown, true)
+            .await;
+    signal.abort();
+
+    let ok = if !synced {
+        error!("Backfill stopped before reaching the chain head");
+        false
+    } else if let Ok(conn) = conn_arc.lock() {
+        loop {
+            match candles::aggregate_candles(&conn) {
+                Ok(0) => break,
+                Ok(_) => {}
+                Err(e) => {
+                    warn!(error = %e, "failed to aggregate candles");
+                    break;
+                }
+            }
+        }
+        info!("Backfill reached the chain head");
+        true
+    } else {
+        error!("Backfill reached the chain head, but the database connection lock was poisoned");
+        false
+    };
+    shutdown::close_database(conn_arc);
+    ok
+}
+
+/// Runs the API server, the indexer or both until SIGINT or SIGTERM, then
+/// shuts down gracefully: the listeners answer the requests in flight, the
+/// indexer finishes the page it is committing, and the database is
+/// checkpointed and closed.
+/// 
+/// Background jobs deriving data from indexed swaps (candles, custom metrics,
+/// retention) run alongside the indexer, and those feeding API responses
+/// (token prices, reference prices) alongside the API. A process that only
+/// indexes still serves `/health` and `/metrics`.
+/// 
+/// # Arguments
+/// * `serve_api` - Whether to serve the API and WebSocket stream
+/// * `index` - Whether to run the indexer
+async fn run(serve_api: bool, index: bool) {
+    // Settings from the environment and the optional config file
+    config::init().expect("Invalid configuration");
+    // The idle timeout counts from startup until the first request
+    idle::mark_active();
+
     // Initialize SQLite database and create schema if needed
     let conn = db::init_db().expect("Failed to initialize database");
     
+    // Export jobs run in-process, so any a previous run left unfinished are lost
+    if serve_api {
+        if let Err(e) = exports::fail_interrupted_exports(&conn) {
+            warn!(error = %e, "failed to mark interrupted export jobs");
+        }
+    }
+
+    // Feature flags from DISABLED_FEATURES plus any persisted admin overrides
+    let feature_flags = features::load_feature_flags(&conn).expect("Failed to load feature flags");
+
+    // Optionally keep pool state in memory so quotes and prices skip SQLite
+    let pool_cache = pool_cache::pool_cache_enabled()
+        .then(|| pool_cache::load_pool_cache(&conn).expect("Failed to load pool cache"));
+
+    // Routing graph shared by quote requests, rebuilt only when pools change materially
+    let graph_cache =
+        graph_cache::load_graph_cache(&conn, pool_cache.as_ref()).expect("Failed to build routing graph");
+
     // Wrap database connection in thread-safe container for sharing between indexer and API
     let conn_arc = Arc::new(Mutex::new(conn));
 
+    // Swaps and pool updates the indexer streams to WebSocket clients
+    let live_feed = live::live_feed();
+
+    // Tells the indexer and listeners to stop once a shutdown signal arrives
+    let (stop, shutdown) = shutdown::shutdown_channel();
+
+    // Background tasks that are simply cancelled on shutdown
+    let mut background = JoinSet::new();
+
     // Start the blockchain indexer as a background task
-    // This will continuously poll for new events and update the database
-    {
+    // This will continuously poll for new events and update the database,
+    // unless this process is an API replica of a database indexed elsewhere
+    let mut indexer_task = index.then(|| {
         let conn_for_indexer = conn_arc.clone();
+        let cache = pool_cache.clone();
+        let graph = graph_cache.clone();
+        let invalidator = invalidation::start_invalidator();
+        // Pages a previous run left half-applied go in before anything new
+        indexer::recover_journal(&conn_for_indexer, cache.as_ref(), &graph, invalidator.as_ref())
+            .expect("Failed to recover the event journal");
+        let feed = live_feed.clone();
+        let shutdown = shutdown.clone();
         tokio::spawn(async move {
-            indexer::run_indexer(conn_for_indexer).await;
+            indexer::run_indexer(conn_for_indexer, cache, graph, invalidator, feed, shutdown, false).await;
+        })
+    });
+
+    // Roll indexed swaps into OHLCV candles for charting
+    if index {
+        let conn_for_candles = conn_arc.clone();
+        let flags = feature_flags.clone();
+        background.spawn(async move {
+            candles::run_candle_aggregator(conn_for_candles, flags).await;
+        });
+    }
+
+    // Recompute the custom metrics admins registered
+    if index {
+        let conn_for_metrics = conn_arc.clone();
+        let flags = feature_flags.clone();
+        background.spawn(async move {
+            custom_metrics::run_custom_metrics(conn_for_metrics, flags).await;
+        });
+    }
+
+    // Recompute the wallet retention cohorts behind the retention report
+    if index {
+        let conn_for_retention = conn_arc.clone();
+        let flags = feature_flags.clone();
+        background.spawn(async move {
+            retention::run_retention(conn_for_retention, flags).await;
+        });
+    }
+
+    // Track the progress of the process indexing the shared database
+    if !index {
+        background.spawn(indexer::follow_indexer_state(conn_arc.clone()));
+    }
+
+    // Sample indexer lag for the SLO error budgets
+    background.spawn(slo::run_lag_sampler());
+
+    // Track database size and reclaim free pages when they pile up
+    {
+        let conn_for_maintenance = conn_arc.clone();
+        background.spawn(async move {
+            maintenance::run_maintenance(conn_for_maintenance).await;
         });
     }
 
-    // Configure the HTTP API routes
-    let app = Router::new()
+    // Monitoring endpoints, served whatever the process runs
+    let mut app = Router::new()
         // Health check endpoint for monitoring and load balancers
         .route("/health", axum::routing::get(|| async { "OK" }))
-        // Mount API routes under /api prefix with database connection injection
-        .nest(
-            "/api",
-            routes::api_routes().layer(Extension(conn_arc.clone())),
-        );
-
-    // Bind to localhost on port 3000
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3000));
-    let listener = TcpListener::bind(addr)
-        .await
-        .expect("Failed to bind to 127.0.0.1:3000");
-    println!("Server listening on http://{}", addr);
-
-    // Start the HTTP server
-    axum::serve(listener, app.into_make_service())
-        .await
-        .unwrap();
+        // Prometheus-style metrics for indexer and API monitoring
+        .route("/metrics", axum::routing::get(metrics::metrics_handler));
+
+    if serve_api {
+        // Keep derived token prices fresh in memory for the tokens endpoint
+        let price_cache: pricing::PriceCache = Arc::new(RwLock::new(Default::default()));
+        {
+            let conn_for_prices = conn_arc.clone();
+            let cache = price_cache.clone();
+            let flags = feature_flags.clone();
+            background.spawn(async move {
+                pricing::run_price_refresher(conn_for_prices, cache, flags).await;
+            });
+        }
+
+        // Compare prices of configured pairs with external reference prices
+        let reference_prices: reference::ReferencePrices = Arc::new(RwLock::new(Default::default()));
+        let reference_sources = reference::reference_sources();
+        if !reference_sources.is_empty() {
+            let conn_for_reference = conn_arc.clone();
+            let cache = reference_prices.clone();
+            let flags = feature_flags.clone();
+            background.spawn(async move {
+                reference::run_reference_checker(conn_for_reference, cache, reference_sources, flags).await;
+            });
+        }
+
+        // Responses of cacheable endpoints, shared across requests
+        let response_cache: response_cache::ResponseCache = Arc::new(RwLock::new(Default::default()));
+
+        // Configure the HTTP API routes
+        let mut api = routes::api_routes()
+            .layer(Extension(conn_arc.clone()))
+            .layer(Extension(price_cache))
+            .layer(Extension(graph_cache))
+            .layer(Extension(reference_prices))
+            .layer(Extension(response_cache))
+            .layer(Extension(feature_flags.clone()))
+            .layer(middleware::from_fn(idle::track_activity));
+        if let Some(cache) = pool_cache {
+            api = api.layer(Extension(cache));
+        }
+        app = app
+            // Idleness report for scale-to-zero platforms
+            .route("/idle", axum::routing::get(idle::idle_handler))
+            // Live swaps and pool updates over WebSocket
+            .merge(
+                Router::new()
+                    .route("/ws", axum::routing::get(live::ws_handler))
+                    .layer(middleware::from_fn_with_state("live", features::require_feature))
+                    .layer(Extension(live_feed))
+                    .layer(Extension(conn_arc.clone()))
+                    .layer(Extension(feature_flags))
+                    .layer(middleware::from_fn(idle::track_activity)),
+            )
+            // Mount API routes under /api prefix with database connection injection
+            .nest("/api", api);
+    }
+
+    // One span per request, with its method and path, closed by a log of the response
+    let app = app.layer(
+        TraceLayer::new_for_http()
+            .make_span_with(DefaultMakeSpan::new().level(Level::INFO))
+            .on_response(DefaultOnResponse::new().level(Level::INFO)),
+    );
+
+    // Bind every configured address (TCP and Unix sockets) before serving, so
+    // a bad address fails startup without leaving other sockets behind
+    let mut listeners = Vec::new();
+    for addr in listen::listen_addrs().expect("Invalid listen addresses") {
+        match listen::Listener::bind(&addr).await {
+            Ok(listener) => listeners.push(listener),
+            Err(e) => {
+                listeners.iter().filter_map(listen::Listener::socket_path).for_each(|p| listen::remove_socket(&p));
+                panic!("Failed to bind {}: {}", addr, e);
+            }
+        }
+    }
+    let sockets: Vec<_> = listeners.iter().filter_map(listen::Listener::socket_path).collect();
+
+    // Serve the same router on all of them
+    let mut servers = JoinSet::new();
+    for listener in listeners {
+        servers.spawn(listen::serve(listener, app.clone(), shutdown.clone()));
+    }
+    drop(app);
+
+    // Run until a shutdown signal or until any listener fails
+    let failure = tokio::select! {
+        _ = shutdown::shutdown_signal() => None,
+        Some(result) = servers.join_next() => result.unwrap().err(),
+    };
+
+    // Let the listeners answer the requests in flight and the indexer finish
+    // its current page, then cancel whatever is left
+    info!("Shutting down");
+    let _ = stop.send(true);
+    let drained = timeout(Duration::from_secs(shutdown::SHUTDOWN_TIMEOUT_SECS), async {
+        while servers.join_next().await.is_some() {}
+        if let Some(indexer) = indexer_task.as_mut() {
+            let _ = indexer.await;
+        }
+    })
+    .await;
+    if drained.is_err() {
+        warn!(timeout_secs = shutdown::SHUTDOWN_TIMEOUT_SECS, "shutdown timed out, cancelling remaining work");
+    }
+    if let Some(indexer) = indexer_task.filter(|indexer| !indexer.is_finished()) {
+        indexer.abort();
+        let _ = indexer.await;
+    }
+    servers.abort_all();
+    background.abort_all();
+    while servers.join_next().await.is_some() {}
+    while background.join_next().await.is_some() {}
+
+    // Remove the socket files this process created so the next start finds a
+    // clean path, and close the database once nothing else holds it
+    sockets.iter().for_each(|p| listen::remove_socket(p));
+    shutdown::close_database(conn_arc);
+    if let Some(e) = failure {
+        panic!("Server failed: {}", e);
+    }
 }
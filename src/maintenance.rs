@@ -0,0 +1,80 @@
+use rusqlite::Connection;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::{info, warn};
+
+use crate::config::config;
+use crate::db::{compact, db_size, DbSize};
+use crate::growth;
+use crate::metrics::{DB_FREE_BYTES, DB_SIZE_BYTES};
+
+/// Default interval between database maintenance checks (in seconds)
+pub const DEFAULT_MAINTENANCE_INTERVAL_SECS: u64 = 3600;
+
+/// Default share of the file that must be free pages before compacting
+pub const DEFAULT_VACUUM_FREE_RATIO: f64 = 0.2;
+
+/// Default minimum reclaimable size before compacting (in bytes)
+pub const DEFAULT_VACUUM_MIN_FREE_BYTES: i64 = 16 * 1024 * 1024;
+
+/// Refreshes the database size gauges and returns the current size.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `rusqlite::Result<DbSize>` - Size breakdown or error
+pub fn record_db_size(conn: &Connection) -> rusqlite::Result<DbSize> {
+    let size = db_size(conn)?;
+    DB_SIZE_BYTES.store(size.total_bytes.max(0) as u64, Ordering::Relaxed);
+    DB_FREE_BYTES.store(size.free_bytes.max(0) as u64, Ordering::Relaxed);
+    Ok(size)
+}
+
+/// Periodically records database size and compacts it when enough space is free.
+/// 
+/// Every `DB_MAINTENANCE_INTERVAL_SECS` seconds the size gauges and the growth
+/// forecast are refreshed; table sizes are sampled once a day for the latter.
+/// If free pages make up at least `DB_VACUUM_FREE_RATIO` of the file and
+/// amount to at least `DB_VACUUM_MIN_FREE_BYTES`, the database is compacted
+/// (incrementally when possible). Setting the interval to 0 disables the task.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+pub async fn run_maintenance(conn_arc: Arc<Mutex<Connection>>) {
+    let interval = config().db_maintenance_interval_secs;
+    if interval == 0 {
+        return;
+    }
+    let free_ratio = config().db_vacuum_free_ratio;
+    let min_free = config().db_vacuum_min_free_bytes;
+
+    loop {
+        if let Ok(conn) = conn_arc.lock() {
+            match record_db_size(&conn) {
+                Ok(size) => {
+                    let ratio = if size.total_bytes > 0 {
+                        size.free_bytes as f64 / size.total_bytes as f64
+                    } else {
+                        0.0
+                    };
+                    if ratio >= free_ratio && size.free_bytes >= min_free {
+                        info!(free_bytes = size.free_bytes, total_bytes = size.total_bytes, "Compacting database");
+                        if let Err(e) = compact(&conn, false) {
+                            warn!(error = %e, "database compaction failed");
+                        }
+                        let _ = record_db_size(&conn);
+                    }
+                }
+                Err(e) => warn!(error = %e, "failed to read database size"),
+            }
+            if let Err(e) = growth::refresh(&conn) {
+                warn!(error = %e, "failed to update database growth forecast");
+            }
+        }
+
+        sleep(Duration::from_secs(interval)).await;
+    }
+}
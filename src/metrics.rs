@@ -0,0 +1,608 @@
+use axum::{
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::freshness::indexer_lag_secs;
+
+/// Number of events fetched from the Sui RPC that are buffered in memory
+/// and have not yet been committed to the database
+pub static INDEXER_BACKLOG_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Total number of events committed to the database since startup
+pub static INDEXER_EVENTS_PROCESSED: AtomicU64 = AtomicU64::new(0);
+
+/// Number of replayed event batches whose derived state differed from the
+/// first time they were ingested
+pub static INDEXER_REPLAY_MISMATCHES: AtomicU64 = AtomicU64::new(0);
+
+/// Events skipped because they lacked a field their type requires
+pub static INDEXER_MALFORMED_EVENTS: AtomicU64 = AtomicU64::new(0);
+
+/// Swaps whose reserves before the swap matched no snapshot of the pool, each
+/// triggering an on-chain reconciliation of its pool
+pub static INDEXER_INCONSISTENT_SWAPS: AtomicU64 = AtomicU64::new(0);
+
+/// Size of the SQLite database file in bytes
+pub static DB_SIZE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Bytes held by free database pages that compaction could reclaim
+pub static DB_FREE_BYTES: AtomicU64 = AtomicU64::new(0);
+
+/// Number of polling gaps detected: the node reporting an older checkpoint
+/// than one already indexed
+pub static INDEXER_GAPS_DETECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Indexer polling cycles run since startup
+pub static INDEXER_CYCLES: AtomicU64 = AtomicU64::new(0);
+
+/// Indexer polling cycles that did not complete, e.g. because the RPC failed,
+/// leaving the cursors where they were
+pub static INDEXER_CYCLE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Index in `SUI_RPC_URL` of the endpoint Sui RPC requests currently go to:
+/// 0 for the primary, higher while failed over to a fallback
+pub static RPC_ACTIVE_ENDPOINT: AtomicU64 = AtomicU64::new(0);
+
+/// Failovers from one Sui RPC endpoint to the next
+pub static RPC_FAILOVERS: AtomicU64 = AtomicU64::new(0);
+
+/// Event queries retried after the Sui RPC failed to answer them
+pub static INDEXER_RPC_RETRIES: AtomicU64 = AtomicU64::new(0);
+
+/// Whether the indexer's circuit breaker is open, widening the poll interval
+/// (1) or not (0)
+pub static INDEXER_CIRCUIT_OPEN: AtomicU64 = AtomicU64::new(0);
+
+/// Float results that differed from exact decimal arithmetic by more than the
+/// tolerance while precision auditing was enabled
+pub static PRECISION_DISCREPANCIES: AtomicU64 = AtomicU64::new(0);
+
+/// Time the indexer last completed a polling cycle successfully (ms since epoch)
+pub static INDEXER_LAST_SYNC_MS: AtomicU64 = AtomicU64::new(0);
+
+/// Cached responses served in place of reads that failed on a locked database
+pub static STALE_RESPONSES_SERVED: AtomicU64 = AtomicU64::new(0);
+
+/// Cache invalidations received from the indexer node
+pub static CACHE_INVALIDATIONS_RECEIVED: AtomicU64 = AtomicU64::new(0);
+
+/// Cache invalidations that could not be delivered to a replica
+pub static CACHE_INVALIDATION_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// External reference price fetches that failed or returned no usable price
+pub static REFERENCE_PRICE_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// Rebuilds of the memoized routing graph
+pub static ROUTING_GRAPH_REBUILDS: AtomicU64 = AtomicU64::new(0);
+
+/// Upper bounds of the latency histogram buckets (in microseconds)
+const LATENCY_BUCKETS_US: [u64; 10] = [500, 1_000, 2_500, 5_000, 10_000, 25_000, 50_000, 100_000, 250_000, 1_000_000];
+
+/// Cumulative latency histogram rendered in the Prometheus histogram format
+pub struct LatencyHistogram {
+    buckets: [AtomicU64; LATENCY_BUCKETS_US.len()],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+}
+
+impl LatencyHistogram {
+    const fn new() -> Self {
+        LatencyHistogram {
+            buckets: [const { AtomicU64::new(0) }; LATENCY_BUCKETS_US.len()],
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one observation.
+    pub fn observe(&self, elapsed: Duration) {
+        let us = elapsed.as_micros() as u64;
+        for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&self.buckets) {
+            if us <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&self.buckets) {
+            let le = *bound as f64 / 1_000_000.0;
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, le, bucket.load(Ordering::Relaxed));
+        }
+        let count = self.count.load(Ordering::Relaxed);
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum_us.load(Ordering::Relaxed) as f64 / 1_000_000.0);
+        let _ = writeln!(out, "{}_count {}", name, count);
+    }
+}
+
+/// End-to-end latency of served quotes
+pub static QUOTE_LATENCY: LatencyHistogram = LatencyHistogram::new();
+
+/// Time spent reading and writing the database while serving quotes (microseconds)
+pub static QUOTE_DB_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Time spent searching and simulating routes while serving quotes (microseconds)
+pub static QUOTE_MATH_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Time spent serializing quote responses (microseconds)
+pub static QUOTE_SERIALIZE_MICROS: AtomicU64 = AtomicU64::new(0);
+
+/// Number of most recent observations kept by `RecentLatencies`
+const RECENT_LATENCY_SAMPLES: usize = 1000;
+
+/// Percentiles of the recent latency window (in milliseconds)
+pub struct LatencyPercentiles {
+    pub last: u64,
+    pub p50: u64,
+    pub p95: u64,
+    pub p99: u64,
+    pub samples: usize,
+}
+
+/// Latencies of the most recent observations, rendered as a Prometheus summary.
+/// 
+/// Unlike `LatencyHistogram`, percentiles are computed over the last
+/// `RECENT_LATENCY_SAMPLES` observations only, so they follow current behaviour
+/// rather than everything since startup.
+pub struct RecentLatencies {
+    samples: Mutex<VecDeque<u64>>,
+    count: AtomicU64,
+    sum_ms: AtomicU64,
+}
+
+impl RecentLatencies {
+    const fn new() -> Self {
+        RecentLatencies {
+            samples: Mutex::new(VecDeque::new()),
+            count: AtomicU64::new(0),
+            sum_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Records one observation, in milliseconds.
+    pub fn observe(&self, ms: u64) {
+        if let Ok(mut samples) = self.samples.lock() {
+            if samples.len() == RECENT_LATENCY_SAMPLES {
+                samples.pop_front();
+            }
+            samples.push_back(ms);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_ms.fetch_add(ms, Ordering::Relaxed);
+    }
+
+    /// Nearest-rank percentiles of the recent window, or `None` before the
+    /// first observation.
+    pub fn percentiles(&self) -> Option<LatencyPercentiles> {
+        let samples = self.samples.lock().ok()?;
+        let last = *samples.back()?;
+        let mut sorted: Vec<u64> = samples.iter().copied().collect();
+        sorted.sort_unstable();
+        let rank = |p: f64| sorted[((p * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(LatencyPercentiles { last, p50: rank(0.5), p95: rank(0.95), p99: rank(0.99), samples: sorted.len() })
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} summary", name);
+        if let Some(p) = self.percentiles() {
+            for (quantile, ms) in [("0.5", p.p50), ("0.95", p.p95), ("0.99", p.p99)] {
+                let _ = writeln!(out, "{}{{quantile=\"{}\"}} {}", name, quantile, ms as f64 / 1000.0);
+            }
+        }
+        let _ = writeln!(out, "{}_sum {}", name, self.sum_ms.load(Ordering::Relaxed) as f64 / 1000.0);
+        let _ = writeln!(out, "{}_count {}", name, self.count.load(Ordering::Relaxed));
+    }
+}
+
+/// Delay between an event's on-chain timestamp and its rows being written,
+/// for events indexed for the first time
+pub static INGEST_LATENCY: RecentLatencies = RecentLatencies::new();
+
+/// Duration of the indexer's polling cycles, including the lookups after them
+pub static INDEXER_CYCLE_DURATION: RecentLatencies = RecentLatencies::new();
+
+/// Latency histogram of one API route
+#[derive(Default)]
+struct RouteLatency {
+    buckets: [u64; LATENCY_BUCKETS_US.len()],
+    count: u64,
+    sum_us: u64,
+}
+
+/// Request counts and latencies of API routes, rendered with a `route` label
+/// holding the matched path template (e.g. `/api/pools/:pool_id`), so pool
+/// IDs don't each get a series
+pub struct HttpStats {
+    /// Requests per route and status code
+    requests: Mutex<BTreeMap<(String, u16), u64>>,
+    latencies: Mutex<BTreeMap<String, RouteLatency>>,
+}
+
+impl HttpStats {
+    const fn new() -> Self {
+        HttpStats { requests: Mutex::new(BTreeMap::new()), latencies: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Records one answered request.
+    pub fn observe(&self, route: &str, status: u16, elapsed: Duration) {
+        if let Ok(mut requests) = self.requests.lock() {
+            *requests.entry((route.to_string(), status)).or_default() += 1;
+        }
+        if let Ok(mut latencies) = self.latencies.lock() {
+            let latency = latencies.entry(route.to_string()).or_default();
+            let us = elapsed.as_micros() as u64;
+            for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&mut latency.buckets) {
+                if us <= *bound {
+                    *bucket += 1;
+                }
+            }
+            latency.count += 1;
+            latency.sum_us += us;
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        if let Ok(requests) = self.requests.lock() {
+            let name = "fooswap_http_requests_total";
+            let _ = writeln!(out, "# HELP {} API requests answered, by route and status code", name);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for ((route, status), count) in requests.iter() {
+                let _ = writeln!(out, "{}{{route=\"{}\",status=\"{}\"}} {}", name, route, status, count);
+            }
+        }
+        if let Ok(latencies) = self.latencies.lock() {
+            let name = "fooswap_http_request_duration_seconds";
+            let _ = writeln!(out, "# HELP {} Time taken to answer API requests, by route", name);
+            let _ = writeln!(out, "# TYPE {} histogram", name);
+            for (route, latency) in latencies.iter() {
+                for (bound, bucket) in LATENCY_BUCKETS_US.iter().zip(&latency.buckets) {
+                    let le = *bound as f64 / 1_000_000.0;
+                    let _ = writeln!(out, "{}_bucket{{route=\"{}\",le=\"{}\"}} {}", name, route, le, bucket);
+                }
+                let _ = writeln!(out, "{}_bucket{{route=\"{}\",le=\"+Inf\"}} {}", name, route, latency.count);
+                let _ = writeln!(out, "{}_sum{{route=\"{}\"}} {}", name, route, latency.sum_us as f64 / 1_000_000.0);
+                let _ = writeln!(out, "{}_count{{route=\"{}\"}} {}", name, route, latency.count);
+            }
+        }
+    }
+}
+
+/// Requests answered by the API
+pub static HTTP_REQUESTS: HttpStats = HttpStats::new();
+
+/// Middleware recording every API request in `HTTP_REQUESTS`, under the
+/// route that matched it or `unmatched`.
+pub async fn track_http_metrics(request: Request, next: Next) -> Response {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| "unmatched".to_string(), |path| path.as_str().to_string());
+    let started = Instant::now();
+    let response = next.run(request).await;
+    HTTP_REQUESTS.observe(&route, response.status().as_u16(), started.elapsed());
+    response
+}
+
+/// Gauge holding one value per token pair, rendered with a `pair` label
+pub struct PairGauge {
+    values: Mutex<BTreeMap<String, f64>>,
+}
+
+impl PairGauge {
+    const fn new() -> Self {
+        PairGauge { values: Mutex::new(BTreeMap::new()) }
+    }
+
+    pub fn set(&self, pair: &str, value: f64) {
+        if let Ok(mut values) = self.values.lock() {
+            values.insert(pair.to_string(), value);
+        }
+    }
+
+    fn render(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        if let Ok(values) = self.values.lock() {
+            for (pair, value) in values.iter() {
+                let label = pair.replace('\\', "\\\\").replace('"', "\\\"");
+                let _ = writeln!(out, "{}{{pair=\"{}\"}} {}", name, label, value);
+            }
+        }
+    }
+}
+
+/// Relative deviation of each checked pair's pool price from its external reference
+pub static PRICE_DEVIATION: PairGauge = PairGauge::new();
+
+/// Call counts and durations of database queries, rendered with a `query`
+/// label per query name
+pub struct QueryStats {
+    /// (calls, slow calls, total microseconds) per query name
+    values: Mutex<BTreeMap<&'static str, (u64, u64, u64)>>,
+}
+
+impl QueryStats {
+    const fn new() -> Self {
+        QueryStats { values: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Records one execution of the named query.
+    pub fn observe(&self, query: &'static str, elapsed: Duration, slow: bool) {
+        if let Ok(mut values) = self.values.lock() {
+            let entry = values.entry(query).or_default();
+            entry.0 += 1;
+            entry.1 += slow as u64;
+            entry.2 += elapsed.as_micros() as u64;
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        let Ok(values) = self.values.lock() else { return };
+        let families = [
+            ("fooswap_db_queries_total", "Database queries executed, by query"),
+            ("fooswap_db_slow_queries_total", "Database queries slower than SLOW_QUERY_MS, by query"),
+            ("fooswap_db_query_seconds_total", "Time spent executing database queries, by query"),
+        ];
+        for (i, (name, help)) in families.iter().enumerate() {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for (query, (calls, slow, micros)) in values.iter() {
+                let value = match i {
+                    0 => calls.to_string(),
+                    1 => slow.to_string(),
+                    _ => (*micros as f64 / 1_000_000.0).to_string(),
+                };
+                let _ = writeln!(out, "{}{{query=\"{}\"}} {}", name, query, value);
+            }
+        }
+    }
+}
+
+/// Executions of the named queries in `db`
+pub static DB_QUERIES: QueryStats = QueryStats::new();
+
+/// Endpoint label and JSON-RPC method of Sui RPC requests
+type RpcRequestKind = (&'static str, String);
+
+/// Sui RPC requests and their failures, rendered with an `endpoint` label
+/// (`live` or `archive`) and a `method` label
+pub struct RpcStats {
+    /// (requests, failures) per endpoint and method
+    values: Mutex<BTreeMap<RpcRequestKind, (u64, u64)>>,
+}
+
+impl RpcStats {
+    const fn new() -> Self {
+        RpcStats { values: Mutex::new(BTreeMap::new()) }
+    }
+
+    /// Records one request to an endpoint.
+    pub fn observe(&self, endpoint: &'static str, method: &str, failed: bool) {
+        if let Ok(mut values) = self.values.lock() {
+            let entry = values.entry((endpoint, method.to_string())).or_default();
+            entry.0 += 1;
+            entry.1 += failed as u64;
+        }
+    }
+
+    fn render(&self, out: &mut String) {
+        let Ok(values) = self.values.lock() else { return };
+        let families = [
+            ("fooswap_rpc_requests_total", "Sui RPC requests sent, by endpoint and method"),
+            ("fooswap_rpc_failures_total", "Sui RPC requests that failed or returned an error, by endpoint and method"),
+        ];
+        for (i, (name, help)) in families.iter().enumerate() {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            for ((endpoint, method), (requests, failures)) in values.iter() {
+                let value = if i == 0 { requests } else { failures };
+                let _ = writeln!(out, "{}{{endpoint=\"{}\",method=\"{}\"}} {}", name, endpoint, method, value);
+            }
+        }
+    }
+}
+
+/// Requests sent to the live and archival Sui RPC endpoints
+pub static RPC_REQUESTS: RpcStats = RpcStats::new();
+
+/// Renders all process metrics in the Prometheus text exposition format.
+/// 
+/// # Endpoint
+/// `GET /metrics`
+/// 
+/// # Returns
+/// * `String` - Metrics in `text/plain; version=0.0.4` format
+pub async fn metrics_handler() -> String {
+    let mut out = String::new();
+    let gauges = [
+        (
+            "fooswap_indexer_backlog_events",
+            "gauge",
+            "Events fetched from the RPC but not yet committed",
+            &INDEXER_BACKLOG_EVENTS,
+        ),
+        (
+            "fooswap_indexer_events_processed_total",
+            "counter",
+            "Events committed to the database since startup",
+            &INDEXER_EVENTS_PROCESSED,
+        ),
+        (
+            "fooswap_indexer_replay_mismatches_total",
+            "counter",
+            "Replayed batches whose derived state differed from the first ingest",
+            &INDEXER_REPLAY_MISMATCHES,
+        ),
+        (
+            "fooswap_indexer_malformed_events_total",
+            "counter",
+            "Events skipped for missing or invalid fields",
+            &INDEXER_MALFORMED_EVENTS,
+        ),
+        (
+            "fooswap_indexer_inconsistent_swaps_total",
+            "counter",
+            "Swaps whose reserves before the swap matched no snapshot of the pool",
+            &INDEXER_INCONSISTENT_SWAPS,
+        ),
+        (
+            "fooswap_indexer_gaps_detected_total",
+            "counter",
+            "Checkpoint regressions reported by the RPC node",
+            &INDEXER_GAPS_DETECTED,
+        ),
+        (
+            "fooswap_indexer_cycles_total",
+            "counter",
+            "Indexer polling cycles run since startup",
+            &INDEXER_CYCLES,
+        ),
+        (
+            "fooswap_indexer_cycle_failures_total",
+            "counter",
+            "Indexer polling cycles that did not complete",
+            &INDEXER_CYCLE_FAILURES,
+        ),
+        (
+            "fooswap_indexer_rpc_retries_total",
+            "counter",
+            "Event queries retried after the Sui RPC failed",
+            &INDEXER_RPC_RETRIES,
+        ),
+        (
+            "fooswap_rpc_active_endpoint",
+            "gauge",
+            "Index in SUI_RPC_URL of the endpoint Sui RPC requests go to",
+            &RPC_ACTIVE_ENDPOINT,
+        ),
+        (
+            "fooswap_rpc_failovers_total",
+            "counter",
+            "Failovers from one Sui RPC endpoint to the next",
+            &RPC_FAILOVERS,
+        ),
+        (
+            "fooswap_indexer_circuit_open",
+            "gauge",
+            "Whether the indexer is polling at a widened interval during an RPC outage",
+            &INDEXER_CIRCUIT_OPEN,
+        ),
+        (
+            "fooswap_precision_discrepancies_total",
+            "counter",
+            "Float results differing from exact decimal arithmetic (precision audit mode)",
+            &PRECISION_DISCREPANCIES,
+        ),
+        (
+            "fooswap_indexer_last_sync_timestamp_ms",
+            "gauge",
+            "Time the indexer last completed a polling cycle",
+            &INDEXER_LAST_SYNC_MS,
+        ),
+        (
+            "fooswap_quote_db_microseconds_total",
+            "counter",
+            "Time spent on database access while serving quotes",
+            &QUOTE_DB_MICROS,
+        ),
+        (
+            "fooswap_quote_math_microseconds_total",
+            "counter",
+            "Time spent searching and simulating routes while serving quotes",
+            &QUOTE_MATH_MICROS,
+        ),
+        (
+            "fooswap_quote_serialize_microseconds_total",
+            "counter",
+            "Time spent serializing quote responses",
+            &QUOTE_SERIALIZE_MICROS,
+        ),
+        (
+            "fooswap_db_size_bytes",
+            "gauge",
+            "Size of the SQLite database file",
+            &DB_SIZE_BYTES,
+        ),
+        (
+            "fooswap_db_free_bytes",
+            "gauge",
+            "Bytes held by free pages that compaction could reclaim",
+            &DB_FREE_BYTES,
+        ),
+        (
+            "fooswap_stale_responses_served_total",
+            "counter",
+            "Cached responses served because the database was locked",
+            &STALE_RESPONSES_SERVED,
+        ),
+        (
+            "fooswap_cache_invalidations_received_total",
+            "counter",
+            "Cache invalidations received from the indexer node",
+            &CACHE_INVALIDATIONS_RECEIVED,
+        ),
+        (
+            "fooswap_cache_invalidation_failures_total",
+            "counter",
+            "Cache invalidations that could not be delivered to a replica",
+            &CACHE_INVALIDATION_FAILURES,
+        ),
+        (
+            "fooswap_reference_price_failures_total",
+            "counter",
+            "External reference price fetches that failed",
+            &REFERENCE_PRICE_FAILURES,
+        ),
+        (
+            "fooswap_routing_graph_rebuilds_total",
+            "counter",
+            "Rebuilds of the memoized routing graph after pool changes",
+            &ROUTING_GRAPH_REBUILDS,
+        ),
+    ];
+    for (name, kind, help, value) in gauges {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{} {}", name, value.load(Ordering::Relaxed));
+    }
+    let _ = writeln!(out, "# HELP fooswap_indexer_lag_seconds Time since the indexer last completed a polling cycle");
+    let _ = writeln!(out, "# TYPE fooswap_indexer_lag_seconds gauge");
+    let _ = writeln!(out, "fooswap_indexer_lag_seconds {}", indexer_lag_secs());
+    let _ = writeln!(out, "# HELP fooswap_idle Whether the process is idle for lack of API traffic (1) or not (0)");
+    let _ = writeln!(out, "# TYPE fooswap_idle gauge");
+    let _ = writeln!(out, "fooswap_idle {}", crate::idle::is_idle() as u8);
+    let _ = writeln!(out, "# HELP fooswap_idle_seconds Time since the last API request or WebSocket connection");
+    let _ = writeln!(out, "# TYPE fooswap_idle_seconds gauge");
+    let _ = writeln!(out, "fooswap_idle_seconds {}", crate::idle::idle_for_secs());
+    INDEXER_CYCLE_DURATION.render(&mut out, "fooswap_indexer_cycle_seconds", "Duration of recent indexer polling cycles");
+    QUOTE_LATENCY.render(&mut out, "fooswap_quote_latency_seconds", "End-to-end latency of served quotes");
+    INGEST_LATENCY.render(
+        &mut out,
+        "fooswap_ingest_latency_seconds",
+        "Delay from on-chain event timestamp to database write, over recent events",
+    );
+    PRICE_DEVIATION.render(
+        &mut out,
+        "fooswap_price_deviation_ratio",
+        "Relative deviation of the pool price from the external reference price",
+    );
+    DB_QUERIES.render(&mut out);
+    RPC_REQUESTS.render(&mut out);
+    HTTP_REQUESTS.render(&mut out);
+    crate::slo::render(&mut out);
+    crate::growth::render(&mut out);
+    out
+}
@@ -0,0 +1,634 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+use crate::db::table_columns;
+
+/// How long a migration lock may be held before another process may take it
+/// over, in case its holder crashed mid-migration (in milliseconds)
+const MIGRATION_LOCK_STALE_MS: i64 = 10 * 60 * 1000;
+
+/// Number of rows updated per statement when backfilling a column, keeping
+/// each write lock short enough not to stall the indexer or API reads
+const BACKFILL_BATCH_SIZE: usize = 1000;
+
+/// A versioned schema change split into expand/contract phases.
+/// 
+/// Migrations newer than a database's `schema_version` are applied in order
+/// of `version`, which is recorded once all their phases succeeded. Each
+/// phase is idempotent so a migration interrupted midway can be re-run safely:
+/// - `add_columns` (expand): `(table, column, definition)` added if missing.
+///   Adding a column with a default is a metadata-only change in SQLite.
+/// - `sql` (migrate): statements run in one transaction, e.g. to create an
+///   index or rebuild a table; they must be safe to run again if a later
+///   phase is interrupted.
+/// - `backfills` (migrate): `(table, assignment, filter)` applied in small
+///   batches, e.g. `("swaps", "event_seq = 0", "event_seq IS NULL")`.
+/// - `drop_columns` (contract): `(table, column)` dropped if present, once
+///   no running code reads the column any more.
+pub struct OnlineMigration {
+    /// Schema version the migration brings a database to, one more than the
+    /// previous migration's
+    pub version: u32,
+    pub name: &'static str,
+    pub add_columns: &'static [(&'static str, &'static str, &'static str)],
+    pub sql: &'static str,
+    pub backfills: &'static [(&'static str, &'static str, &'static str)],
+    pub drop_columns: &'static [(&'static str, &'static str)],
+    /// Whether a phase locks a table for longer than live processes can wait,
+    /// e.g. a table rebuild. Startup stops before such a migration and leaves
+    /// it, and every later one, to the `migrate` subcommand, run while no
+    /// other process uses the database.
+    pub blocking: bool,
+}
+
+/// Recomputes each hour of `pool_stats` as per-token volumes, from the
+/// swaps it covers. `pool_stats` holds one row per pool and hour with
+/// swaps, and each is summed off `idx_swaps_pool_ts`.
+const POOL_STATS_VOLUME_BY_TOKEN_SQL: &str = r#"
+    UPDATE pool_stats SET
+        volume_a = (
+            SELECT COALESCE(SUM(CASE direction WHEN 'a_to_b' THEN amount_in WHEN 'b_to_a' THEN amount_out ELSE 0.0 END), 0.0)
+            FROM swaps s
+            WHERE s.pool_id = pool_stats.pool_id
+              AND s.timestamp >= pool_stats.bucket_start AND s.timestamp < pool_stats.bucket_start + 3600000
+        ),
+        volume_b = (
+            SELECT COALESCE(SUM(CASE direction WHEN 'a_to_b' THEN amount_out WHEN 'b_to_a' THEN amount_in ELSE 0.0 END), 0.0)
+            FROM swaps s
+            WHERE s.pool_id = pool_stats.pool_id
+              AND s.timestamp >= pool_stats.bucket_start AND s.timestamp < pool_stats.bucket_start + 3600000
+        );
+"#;
+
+/// Recreates `candles` with per-token volumes and resets the aggregator's
+/// progress, so it rolls every swap up again in the background. Charts
+/// fill back in as it catches up; `backfill_candles` serves requested
+/// ranges it hasn't reached in the meantime.
+const CANDLES_VOLUME_BY_TOKEN_SQL: &str = r#"
+    DROP TABLE IF EXISTS candles;
+    CREATE TABLE candles (
+        pool_id      TEXT NOT NULL,
+        interval     TEXT NOT NULL,
+        bucket_start INTEGER NOT NULL,
+        open         REAL NOT NULL,
+        high         REAL NOT NULL,
+        low          REAL NOT NULL,
+        close        REAL NOT NULL,
+        volume_a     REAL NOT NULL,
+        volume_b     REAL NOT NULL,
+        trades       INTEGER NOT NULL,
+        PRIMARY KEY (pool_id, interval, bucket_start)
+    );
+    DELETE FROM candle_progress;
+"#;
+
+/// Rebuilds `swaps` without the UNIQUE constraint on `tx_digest`, which
+/// rejects every swap event of a transaction after the first and can't be
+/// dropped in place. `idx_swaps_event` has deduplicated by
+/// `(tx_digest, event_seq)` since migration 8. Row IDs are kept, so swap
+/// cursors stay valid.
+/// 
+/// The copy holds the write lock for as long as it takes to rewrite the
+/// table, so this only runs from the `migrate` subcommand, see `blocking`.
+/// Legacy renaming leaves views over `swaps` (e.g. reports) pointing at the
+/// rebuilt table instead of failing on the dropped one.
+const SWAPS_DROP_TX_DIGEST_UNIQUE_SQL: &str = r#"
+    PRAGMA legacy_alter_table = ON;
+    DROP TABLE IF EXISTS swaps_rebuild;
+    CREATE TABLE swaps_rebuild (
+        id           INTEGER PRIMARY KEY AUTOINCREMENT,
+        pool_id      TEXT NOT NULL,
+        amount_in    REAL NOT NULL,
+        amount_out   REAL NOT NULL,
+        timestamp    INTEGER NOT NULL,
+        tx_digest    TEXT NOT NULL,
+        event_seq    INTEGER NOT NULL DEFAULT 0,
+        epoch        INTEGER,
+        checkpoint   INTEGER,
+        value_quote  REAL,
+        value_usd    REAL,
+        inconsistent INTEGER NOT NULL DEFAULT 0,
+        sender       TEXT,
+        direction    TEXT NOT NULL DEFAULT 'a_to_b'
+    );
+    INSERT INTO swaps_rebuild (id, pool_id, amount_in, amount_out, timestamp, tx_digest, event_seq, epoch,
+                               checkpoint, value_quote, value_usd, inconsistent, sender, direction)
+    SELECT id, pool_id, amount_in, amount_out, timestamp, tx_digest, COALESCE(event_seq, 0), epoch,
+           checkpoint, value_quote, value_usd, inconsistent, sender, direction
+    FROM swaps;
+    DROP TABLE swaps;
+    ALTER TABLE swaps_rebuild RENAME TO swaps;
+    CREATE INDEX IF NOT EXISTS idx_swaps_pool_ts ON swaps(pool_id, timestamp DESC);
+    CREATE UNIQUE INDEX IF NOT EXISTS idx_swaps_event ON swaps(tx_digest, event_seq);
+    PRAGMA legacy_alter_table = OFF;
+"#;
+
+/// Online migrations applied to existing databases, in order
+const ONLINE_MIGRATIONS: &[OnlineMigration] = &[
+    OnlineMigration {
+        version: 1,
+        name: "pools_version",
+        add_columns: &[("pools", "version", "INTEGER NOT NULL DEFAULT 1")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Filled in by the indexer, which looks up each swap's transaction
+    OnlineMigration {
+        version: 2,
+        name: "swaps_epoch",
+        add_columns: &[("swaps", "epoch", "INTEGER"), ("swaps", "checkpoint", "INTEGER")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    OnlineMigration {
+        version: 3,
+        name: "swaps_notional",
+        add_columns: &[("swaps", "value_quote", "REAL"), ("swaps", "value_usd", "REAL")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Set by the indexer's per-swap reserve check; earlier swaps were never checked
+    OnlineMigration {
+        version: 4,
+        name: "swaps_inconsistent",
+        add_columns: &[("swaps", "inconsistent", "INTEGER NOT NULL DEFAULT 0")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Existing pools get distinct sequence numbers so `since_version=0` returns every pool
+    OnlineMigration {
+        version: 5,
+        name: "pools_sync_version",
+        add_columns: &[("pools", "sync_version", "INTEGER NOT NULL DEFAULT 0")],
+        sql: "",
+        backfills: &[("pools", "sync_version = rowid", "sync_version = 0")],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Swaps stored before directions were recorded all sold token A for token B
+    OnlineMigration {
+        version: 6,
+        name: "swaps_side",
+        add_columns: &[("swaps", "sender", "TEXT"), ("swaps", "direction", "TEXT NOT NULL DEFAULT 'a_to_b'")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Set from the next committed page; until then the cursor's age is unknown
+    OnlineMigration {
+        version: 7,
+        name: "indexer_cursors_event_ts",
+        add_columns: &[("indexer_cursors", "event_ts", "INTEGER")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // Swaps stored so far were deduplicated by transaction, so each is its
+    // transaction's only row and gets event_seq 0. Their UNIQUE (tx_digest)
+    // constraint stays until migration 9.
+    OnlineMigration {
+        version: 8,
+        name: "swaps_event_seq",
+        add_columns: &[("swaps", "event_seq", "INTEGER")],
+        sql: "CREATE UNIQUE INDEX IF NOT EXISTS idx_swaps_event ON swaps(tx_digest, event_seq);",
+        backfills: &[("swaps", "event_seq = 0", "event_seq IS NULL")],
+        drop_columns: &[],
+        blocking: false,
+    },
+    OnlineMigration {
+        version: 9,
+        name: "swaps_drop_tx_digest_unique",
+        add_columns: &[],
+        sql: SWAPS_DROP_TX_DIGEST_UNIQUE_SQL,
+        backfills: &[],
+        drop_columns: &[],
+        blocking: true,
+    },
+    // Swaps stored so far keep the direction they were stored with; only
+    // swaps with the reserves they left are checked against the snapshots
+    OnlineMigration {
+        version: 10,
+        name: "swaps_new_reserves",
+        add_columns: &[("swaps", "new_reserve_a", "REAL"), ("swaps", "new_reserve_b", "REAL")],
+        sql: "",
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+    // `volume` summed `amount_in` whichever token it was in; the per-token
+    // volumes are recomputed from the swaps of each hour
+    OnlineMigration {
+        version: 11,
+        name: "pool_stats_volume_by_token",
+        add_columns: &[
+            ("pool_stats", "volume_a", "REAL NOT NULL DEFAULT 0.0"),
+            ("pool_stats", "volume_b", "REAL NOT NULL DEFAULT 0.0"),
+        ],
+        sql: POOL_STATS_VOLUME_BY_TOKEN_SQL,
+        backfills: &[],
+        drop_columns: &[("pool_stats", "volume")],
+        blocking: false,
+    },
+    // Candles summed `amount_in` and `amount_out` whichever token they were
+    // in, so they are rolled up again from the first swap
+    OnlineMigration {
+        version: 12,
+        name: "candles_volume_by_token",
+        add_columns: &[],
+        sql: CANDLES_VOLUME_BY_TOKEN_SQL,
+        backfills: &[],
+        drop_columns: &[],
+        blocking: false,
+    },
+];
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Tries to take the migration lock for `holder`.
+/// 
+/// Succeeds if the lock is free, already held by `holder`, or stale.
+/// 
+/// # Returns
+/// * `Result<bool>` - `true` if the lock is now held by `holder`
+pub fn acquire_migration_lock(conn: &Connection, holder: &str) -> Result<bool> {
+    let now = now_ms();
+    conn.execute(
+        r#"
+        INSERT INTO migration_lock (id, holder, acquired_at)
+        VALUES (1, ?1, ?2)
+        ON CONFLICT(id) DO UPDATE SET
+            holder = excluded.holder,
+            acquired_at = excluded.acquired_at
+        WHERE migration_lock.holder = excluded.holder
+           OR migration_lock.acquired_at < ?3
+        "#,
+        params![holder, now, now - MIGRATION_LOCK_STALE_MS],
+    )?;
+    let current: Option<String> = conn
+        .query_row("SELECT holder FROM migration_lock WHERE id = 1", [], |row| row.get(0))
+        .optional()?;
+    Ok(current.as_deref() == Some(holder))
+}
+
+/// Releases the migration lock if it is held by `holder`.
+pub fn release_migration_lock(conn: &Connection, holder: &str) -> Result<()> {
+    conn.execute("DELETE FROM migration_lock WHERE id = 1 AND holder = ?1", [holder])?;
+    Ok(())
+}
+
+/// Adds a column to an existing table if it is not already present.
+/// 
+/// `CREATE TABLE IF NOT EXISTS` leaves tables from older databases untouched,
+/// so columns introduced later must be added explicitly.
+fn add_column(conn: &Connection, table: &str, column: &str, definition: &str) -> Result<()> {
+    if !table_columns(conn, table)?.iter().any(|name| name == column) {
+        conn.execute_batch(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition))?;
+    }
+    Ok(())
+}
+
+/// Applies `SET assignment` to rows matching `filter` in batches of
+/// `BACKFILL_BATCH_SIZE`, each batch in its own short transaction.
+/// 
+/// `filter` must stop matching a row once it has been updated, otherwise the
+/// backfill never finishes.
+/// 
+/// # Returns
+/// * `Result<usize>` - Total number of rows updated
+fn backfill(conn: &Connection, table: &str, assignment: &str, filter: &str) -> Result<usize> {
+    let sql = format!(
+        "UPDATE {table} SET {assignment}
+         WHERE rowid IN (SELECT rowid FROM {table} WHERE {filter} LIMIT {limit})",
+        table = table,
+        assignment = assignment,
+        filter = filter,
+        limit = BACKFILL_BATCH_SIZE,
+    );
+    let mut total = 0;
+    loop {
+        let updated = conn.execute(&sql, [])?;
+        total += updated;
+        if updated == 0 {
+            return Ok(total);
+        }
+    }
+}
+
+/// Drops a column if it is still present.
+fn drop_column(conn: &Connection, table: &str, column: &str) -> Result<()> {
+    if table_columns(conn, table)?.iter().any(|name| name == column) {
+        conn.execute_batch(&format!("ALTER TABLE {} DROP COLUMN {}", table, column))?;
+    }
+    Ok(())
+}
+
+/// Schema version of the last migration this build knows.
+pub fn latest_schema_version() -> u32 {
+    ONLINE_MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Name of the first blocking migration a database at `version` still needs,
+/// which startup won't apply.
+pub fn pending_blocking_migration(version: u32) -> Option<&'static str> {
+    ONLINE_MIGRATIONS.iter().find(|m| m.version > version && m.blocking).map(|m| m.name)
+}
+
+/// Reads the schema version a database was migrated to.
+/// 
+/// Databases that predate versioning have no `schema_version` table and
+/// are at version 0; their migrations are all re-applied, which is safe as
+/// every phase is idempotent.
+/// 
+/// # Returns
+/// * `Result<u32>` - The highest applied migration version
+pub fn schema_version(conn: &Connection) -> Result<u32> {
+    if table_columns(conn, "schema_version")?.is_empty() {
+        return Ok(0);
+    }
+    conn.query_row("SELECT COALESCE(MAX(version), 0) FROM schema_version", [], |row| row.get(0))
+}
+
+/// Fails if the database was migrated by a newer build, whose schema this
+/// build doesn't know and could corrupt.
+/// 
+/// # Returns
+/// * `Result<u32>` - The database's schema version, if this build supports it
+pub fn check_schema_version(conn: &Connection) -> Result<u32> {
+    let version = schema_version(conn)?;
+    if version > latest_schema_version() {
+        return Err(rusqlite::Error::SqliteFailure(
+            rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+            Some(format!(
+                "database schema version {} is newer than this build supports ({}); upgrade before running it",
+                version,
+                latest_schema_version()
+            )),
+        ));
+    }
+    Ok(version)
+}
+
+/// Records a migration as applied.
+fn record_version(conn: &Connection, migration: &OnlineMigration) -> Result<()> {
+    conn.execute(
+        "INSERT OR IGNORE INTO schema_version (version, name, applied_at) VALUES (?1, ?2, ?3)",
+        params![migration.version, migration.name, now_ms()],
+    )?;
+    Ok(())
+}
+
+/// Marks every migration as applied without running it, for a database whose
+/// tables were just created at the latest schema.
+pub fn baseline_schema(conn: &Connection) -> Result<()> {
+    ONLINE_MIGRATIONS.iter().try_for_each(|migration| record_version(conn, migration))
+}
+
+/// Applies the online migrations newer than the database's schema version
+/// under the migration lock, recording each as it completes.
+/// 
+/// If another live process holds the lock it is already migrating this
+/// database, so this process skips migrations rather than waiting on it.
+/// Without `allow_blocking`, stops at the first blocking migration.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `holder` - Identifier recorded as the lock holder
+/// * `allow_blocking` - Whether to apply blocking migrations too, for the
+///   `migrate` subcommand
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn run_online_migrations(conn: &Connection, holder: &str, allow_blocking: bool) -> Result<()> {
+    if !acquire_migration_lock(conn, holder)? {
+        info!("Skipping online migrations: another process holds the migration lock");
+        return Ok(());
+    }
+
+    let result = (|| {
+        let current = check_schema_version(conn)?;
+        for migration in ONLINE_MIGRATIONS.iter().filter(|m| m.version > current) {
+            if migration.blocking && !allow_blocking {
+                warn!(
+                    migration = migration.name,
+                    version = migration.version,
+                    "Deferring blocking migration: run the migrate subcommand while no other process uses the database"
+                );
+                break;
+            }
+            info!(migration = migration.name, version = migration.version, "Applying migration");
+            for (table, column, definition) in migration.add_columns {
+                add_column(conn, table, column, definition)?;
+            }
+            if !migration.sql.is_empty() {
+                let tx = conn.unchecked_transaction()?;
+                tx.execute_batch(migration.sql)?;
+                tx.commit()?;
+            }
+            for (table, assignment, filter) in migration.backfills {
+                let updated = backfill(conn, table, assignment, filter)?;
+                if updated > 0 {
+                    info!(migration = migration.name, table, rows = updated, "Migration backfilled rows");
+                }
+            }
+            for (table, column) in migration.drop_columns {
+                drop_column(conn, table, column)?;
+            }
+            record_version(conn, migration)?;
+        }
+        Ok(())
+    })();
+
+    release_migration_lock(conn, holder)?;
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tables as the first release created them, before any online migration
+    fn legacy_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+            CREATE TABLE pools (
+                pool_id TEXT PRIMARY KEY, token_a TEXT NOT NULL, token_b TEXT NOT NULL,
+                reserve_a REAL NOT NULL DEFAULT 0.0, reserve_b REAL NOT NULL DEFAULT 0.0,
+                last_updated INTEGER NOT NULL DEFAULT 0
+            );
+            CREATE TABLE swaps (
+                id INTEGER PRIMARY KEY AUTOINCREMENT, pool_id TEXT NOT NULL, amount_in REAL NOT NULL,
+                amount_out REAL NOT NULL, timestamp INTEGER NOT NULL, tx_digest TEXT NOT NULL UNIQUE
+            );
+            CREATE TABLE indexer_cursors (
+                event_type TEXT PRIMARY KEY, tx_digest TEXT NOT NULL, event_seq TEXT NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE pool_stats (
+                pool_id TEXT NOT NULL, bucket_start INTEGER NOT NULL, volume REAL NOT NULL, swaps INTEGER NOT NULL,
+                PRIMARY KEY (pool_id, bucket_start)
+            );
+            CREATE TABLE candles (pool_id TEXT NOT NULL, interval TEXT NOT NULL, bucket_start INTEGER NOT NULL);
+            CREATE TABLE candle_progress (
+                id INTEGER PRIMARY KEY, last_swap_id INTEGER NOT NULL, updated_at INTEGER NOT NULL
+            );
+            CREATE TABLE migration_lock (
+                id INTEGER PRIMARY KEY CHECK (id = 1), holder TEXT NOT NULL, acquired_at INTEGER NOT NULL
+            );
+            CREATE TABLE schema_version (version INTEGER PRIMARY KEY, name TEXT NOT NULL, applied_at INTEGER NOT NULL);
+
+            INSERT INTO pools (pool_id, token_a, token_b) VALUES ('0xp1', '0xa', '0xb'), ('0xp2', '0xa', '0xc');
+            INSERT INTO swaps (id, pool_id, amount_in, amount_out, timestamp, tx_digest)
+            VALUES (7, '0xp1', 10.0, 9.0, 1000, 'tx1'), (9, '0xp1', 4.0, 3.0, 2000, 'tx2');
+            INSERT INTO pool_stats VALUES ('0xp1', 0, 14.0, 2);
+            INSERT INTO candle_progress VALUES (1, 9, 0);
+            "#,
+        )
+        .unwrap();
+        conn
+    }
+
+    fn lock_holder(conn: &Connection) -> Option<String> {
+        conn.query_row("SELECT holder FROM migration_lock", [], |row| row.get(0)).optional().unwrap()
+    }
+
+    #[test]
+    fn lock_is_exclusive_until_released() {
+        let conn = legacy_db();
+        assert!(acquire_migration_lock(&conn, "a").unwrap());
+        assert!(!acquire_migration_lock(&conn, "b").unwrap());
+        assert!(acquire_migration_lock(&conn, "a").unwrap());
+
+        // Only the holder releases it
+        release_migration_lock(&conn, "b").unwrap();
+        assert_eq!(lock_holder(&conn).as_deref(), Some("a"));
+        release_migration_lock(&conn, "a").unwrap();
+        assert!(acquire_migration_lock(&conn, "b").unwrap());
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let conn = legacy_db();
+        conn.execute(
+            "INSERT INTO migration_lock VALUES (1, 'crashed', ?1)",
+            [now_ms() - MIGRATION_LOCK_STALE_MS - 1],
+        )
+        .unwrap();
+        assert!(acquire_migration_lock(&conn, "b").unwrap());
+        assert_eq!(lock_holder(&conn).as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn locked_database_is_left_to_its_holder() {
+        let conn = legacy_db();
+        assert!(acquire_migration_lock(&conn, "a").unwrap());
+        run_online_migrations(&conn, "b", true).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        assert_eq!(lock_holder(&conn).as_deref(), Some("a"));
+    }
+
+    #[test]
+    fn startup_stops_before_blocking_migration() {
+        let conn = legacy_db();
+        run_online_migrations(&conn, "a", false).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 8);
+        assert_eq!(pending_blocking_migration(8), Some("swaps_drop_tx_digest_unique"));
+        assert_eq!(lock_holder(&conn), None);
+
+        let pools: Vec<(i64, i64)> = conn
+            .prepare("SELECT version, sync_version FROM pools ORDER BY pool_id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(pools, vec![(1, 1), (1, 2)]);
+        let event_seqs: i64 =
+            conn.query_row("SELECT COUNT(*) FROM swaps WHERE event_seq = 0", [], |row| row.get(0)).unwrap();
+        assert_eq!(event_seqs, 2);
+    }
+
+    #[test]
+    fn migrate_upgrades_legacy_database() {
+        let conn = legacy_db();
+        run_online_migrations(&conn, "a", false).unwrap();
+        run_online_migrations(&conn, "a", true).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_schema_version());
+        assert_eq!(pending_blocking_migration(latest_schema_version()), None);
+
+        // Swap IDs survive the rebuild, and a transaction may now emit several swaps
+        let ids: Vec<i64> = conn
+            .prepare("SELECT id FROM swaps ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids, vec![7, 9]);
+        conn.execute(
+            "INSERT INTO swaps (pool_id, amount_in, amount_out, timestamp, tx_digest, event_seq)
+             VALUES ('0xp1', 1.0, 1.0, 3000, 'tx1', 1)",
+            [],
+        )
+        .unwrap();
+
+        let stats: (f64, f64) = conn
+            .query_row("SELECT volume_a, volume_b FROM pool_stats", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert_eq!(stats, (14.0, 12.0));
+        assert!(!table_columns(&conn, "pool_stats").unwrap().iter().any(|c| c == "volume"));
+        assert!(table_columns(&conn, "candles").unwrap().iter().any(|c| c == "volume_b"));
+        let progress: i64 = conn.query_row("SELECT COUNT(*) FROM candle_progress", [], |row| row.get(0)).unwrap();
+        assert_eq!(progress, 0);
+
+        // Nothing is left to apply
+        run_online_migrations(&conn, "a", true).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_schema_version());
+    }
+
+    #[test]
+    fn versions_are_consecutive() {
+        for (i, migration) in ONLINE_MIGRATIONS.iter().enumerate() {
+            assert_eq!(migration.version as usize, i + 1, "{}", migration.name);
+        }
+        assert_eq!(latest_schema_version() as usize, ONLINE_MIGRATIONS.len());
+    }
+
+    #[test]
+    fn fresh_database_is_baselined() {
+        let conn = Connection::open_in_memory().unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), 0);
+        crate::db::init_schema(&conn).unwrap();
+        assert_eq!(schema_version(&conn).unwrap(), latest_schema_version());
+    }
+
+    #[test]
+    fn newer_schema_is_refused() {
+        let conn = legacy_db();
+        conn.execute(
+            "INSERT INTO schema_version VALUES (?1, 'from_the_future', 0)",
+            [latest_schema_version() + 1],
+        )
+        .unwrap();
+        assert!(check_schema_version(&conn).is_err());
+        assert!(run_online_migrations(&conn, "a", true).is_err());
+        assert_eq!(lock_holder(&conn), None);
+        assert!(crate::db::init_schema(&conn).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use rusqlite::{params_from_iter, Connection, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, RwLock};
+use tokio::sync::watch;
+
+use crate::config::config;
+
+/// Current state of a pool as held in memory
+#[derive(Clone)]
+pub struct PoolState {
+    pub pool_id: String,
+    pub token_a: String,
+    pub token_b: String,
+    pub reserve_a: f64,
+    pub reserve_b: f64,
+    pub last_updated: i64,
+    pub version: i64,
+}
+
+/// In-memory copy of the `pools` table, keyed by pool ID.
+/// 
+/// The indexer refreshes the pools it writes right after each committed page,
+/// so quote and price endpoints can read pool state without touching SQLite.
+pub type PoolCache = Arc<RwLock<HashMap<String, PoolState>>>;
+
+/// Counter bumped whenever pool rows may have changed, so requests waiting
+/// for a price update re-read it only then. Signaled by the indexer after
+/// each committed page, and on replicas by cache invalidations and by the
+/// indexer progress they follow.
+static POOL_UPDATES: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::channel(0).0);
+
+/// Wakes every request waiting for a pool update.
+pub fn notify_pool_updates() {
+    POOL_UPDATES.send_modify(|count| *count += 1);
+}
+
+/// Subscribes to pool updates. The receiver only sees updates signaled
+/// after this call.
+pub fn pool_updates() -> watch::Receiver<u64> {
+    POOL_UPDATES.subscribe()
+}
+
+/// Whether quotes and prices are served from the pool cache (`POOL_CACHE=1` or `true`).
+pub fn pool_cache_enabled() -> bool {
+    config().pool_cache
+}
+
+/// Reads pools from the database, all of them or only the given IDs.
+pub fn read_pools(conn: &Connection, pool_ids: Option<&[String]>) -> Result<Vec<PoolState>> {
+    let mut sql = String::from(
+        "SELECT pool_id, token_a, token_b, reserve_a, reserve_b, last_updated, version FROM pools",
+    );
+    if let Some(ids) = pool_ids {
+        sql.push_str(&format!(" WHERE pool_id IN ({})", vec!["?"; ids.len()].join(",")));
+    }
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(pool_ids.unwrap_or_default()), |row| {
+        Ok(PoolState {
+            pool_id: row.get(0)?,
+            token_a: row.get(1)?,
+            token_b: row.get(2)?,
+            reserve_a: row.get(3)?,
+            reserve_b: row.get(4)?,
+            last_updated: row.get(5)?,
+            version: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+/// Builds the cache from every pool in the database.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// 
+/// # Returns
+/// * `Result<PoolCache>` - The populated cache
+pub fn load_pool_cache(conn: &Connection) -> Result<PoolCache> {
+    let pools = read_pools(conn, None)?;
+    Ok(Arc::new(RwLock::new(pools.into_iter().map(|p| (p.pool_id.clone(), p)).collect())))
+}
+
+/// Lists the pools a page of events touches, sorted and deduplicated.
+pub fn touched_pools(events: &[Value]) -> Vec<String> {
+    let mut pool_ids: Vec<String> = events
+        .iter()
+        .filter_map(|evt| evt["parsedJson"]["pool_id"].as_str())
+        .map(str::to_string)
+        .collect();
+    pool_ids.sort();
+    pool_ids.dedup();
+    pool_ids
+}
+
+/// Reloads pools into the cache after their rows changed, e.g. right after
+/// the indexer committed a page touching them.
+/// 
+/// Reading the rows back, rather than applying the events to the cache
+/// directly, keeps the cache identical to what SQLite stored, including
+/// updates the database ignored as out of date.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `cache` - Pool cache to update
+/// * `pool_ids` - Pools to reload
+/// 
+/// # Returns
+/// * `Result<()>` - Success or error
+pub fn reload_pools(conn: &Connection, cache: &PoolCache, pool_ids: &[String]) -> Result<()> {
+    if pool_ids.is_empty() {
+        return Ok(());
+    }
+
+    let pools = read_pools(conn, Some(pool_ids))?;
+    if let Ok(mut guard) = cache.write() {
+        for pool in pools {
+            guard.insert(pool.pool_id.clone(), pool);
+        }
+    }
+    Ok(())
+}
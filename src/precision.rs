@@ -0,0 +1,173 @@
+use std::sync::atomic::Ordering;
+use tracing::warn;
+
+use crate::config::config;
+use crate::metrics::PRECISION_DISCREPANCIES;
+
+/// Default relative error tolerated before a discrepancy is reported
+pub const DEFAULT_TOLERANCE: f64 = 1e-12;
+
+/// Fractional digits computed for exact quotients
+const FRACTION_DIGITS: usize = 18;
+
+/// Whether precision auditing is enabled (`PRECISION_AUDIT=1` or `true`).
+/// 
+/// Auditing repeats every audited computation in exact decimal arithmetic, so
+/// it is meant for debugging rather than production traffic.
+pub fn audit_enabled() -> bool {
+    config().precision_audit
+}
+
+/// Returns the configured relative error tolerated (`PRECISION_AUDIT_TOLERANCE`).
+fn tolerance() -> f64 {
+    config().precision_audit_tolerance
+}
+
+/// An exact non-negative decimal, as an integer part and `FRACTION_DIGITS`
+/// fractional digits
+#[derive(PartialEq, PartialOrd)]
+struct Decimal {
+    int: u128,
+    frac: u128,
+}
+
+impl Decimal {
+    /// Divides exactly, truncating after `FRACTION_DIGITS` digits.
+    fn quotient(numerator: u128, denominator: u128) -> Option<Decimal> {
+        if denominator == 0 {
+            return None;
+        }
+        let mut rem = numerator % denominator;
+        let mut frac = 0u128;
+        for _ in 0..FRACTION_DIGITS {
+            rem = rem.checked_mul(10)?;
+            frac = frac * 10 + rem / denominator;
+            rem %= denominator;
+        }
+        Some(Decimal { int: numerator / denominator, frac })
+    }
+
+    /// Exact decimal expansion of a float, truncated after `FRACTION_DIGITS` digits.
+    fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() || value < 0.0 {
+            return None;
+        }
+        let text = format!("{:.*}", FRACTION_DIGITS + 1, value);
+        let (int, frac) = text.split_once('.')?;
+        Some(Decimal {
+            int: int.parse().ok()?,
+            frac: frac[..FRACTION_DIGITS].parse().ok()?,
+        })
+    }
+
+    /// Converts an integral float to an integer, if it is one.
+    fn integer(value: f64) -> Option<u128> {
+        (value.is_finite() && value >= 0.0 && value.fract() == 0.0 && value < u128::MAX as f64)
+            .then_some(value as u128)
+    }
+
+    fn to_f64(&self) -> f64 {
+        self.int as f64 + self.frac as f64 / 10f64.powi(FRACTION_DIGITS as i32)
+    }
+}
+
+impl std::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let frac = format!("{:0width$}", self.frac, width = FRACTION_DIGITS);
+        let frac = frac.trim_end_matches('0');
+        if frac.is_empty() {
+            write!(f, "{}", self.int)
+        } else {
+            write!(f, "{}.{}", self.int, frac)
+        }
+    }
+}
+
+/// Compares a float with its exact value and reports it if the relative error
+/// exceeds `tolerance`.
+fn check(context: &str, exact: Decimal, value: f64, tolerance: f64) {
+    let Some(actual) = Decimal::from_f64(value) else {
+        report(context, &exact, value, f64::INFINITY);
+        return;
+    };
+    if actual == exact {
+        return;
+    }
+    let (high, low) = if actual > exact { (&actual, &exact) } else { (&exact, &actual) };
+    let diff = (high.int - low.int) as f64
+        + (high.frac as f64 - low.frac as f64) / 10f64.powi(FRACTION_DIGITS as i32);
+    let magnitude = exact.to_f64();
+    let relative = if magnitude > 0.0 { diff / magnitude } else { diff };
+    if relative > tolerance {
+        report(context, &exact, value, relative);
+    }
+}
+
+fn report(context: &str, exact: &Decimal, value: f64, relative: f64) {
+    PRECISION_DISCREPANCIES.fetch_add(1, Ordering::Relaxed);
+    warn!(
+        "Precision audit: {} is {} as f64 but {} exactly (relative error {:e})",
+        context, value, exact, relative
+    );
+}
+
+/// Audits an on-chain integer amount after it has been parsed into a float.
+/// 
+/// Amounts above 2^53 cannot be represented exactly as `f64`. Any lost unit
+/// is reported, regardless of the configured tolerance.
+/// 
+/// # Arguments
+/// * `context` - What the amount is, for the log line
+/// * `raw` - The amount as received from the RPC
+/// * `value` - The parsed float
+pub fn audit_amount(context: &str, raw: &str, value: f64) {
+    if !audit_enabled() {
+        return;
+    }
+    if let Ok(exact) = raw.parse::<u128>() {
+        check(context, Decimal { int: exact, frac: 0 }, value, 0.0);
+    }
+}
+
+/// Audits a float quotient of two integral amounts, such as a price derived
+/// from reserves.
+/// 
+/// # Arguments
+/// * `context` - What the quotient is, for the log line
+/// * `numerator` - Integral dividend as stored
+/// * `denominator` - Integral divisor as stored
+/// * `value` - The float result being served
+pub fn audit_quotient(context: &str, numerator: f64, denominator: f64, value: f64) {
+    if !audit_enabled() {
+        return;
+    }
+    if let (Some(n), Some(d)) = (Decimal::integer(numerator), Decimal::integer(denominator)) {
+        if let Some(exact) = Decimal::quotient(n, d) {
+            check(context, exact, value, tolerance());
+        }
+    }
+}
+
+/// Audits a constant-product output amount, `reserve_out * amount_in / (reserve_in + amount_in)`.
+/// 
+/// # Arguments
+/// * `context` - What the amount is, for the log line
+/// * `amount_in` - Integral input amount
+/// * `reserve_in` - Integral input-side reserve
+/// * `reserve_out` - Integral output-side reserve
+/// * `value` - The float result being served
+pub fn audit_amount_out(context: &str, amount_in: f64, reserve_in: f64, reserve_out: f64, value: f64) {
+    if !audit_enabled() {
+        return;
+    }
+    let inputs = (Decimal::integer(amount_in), Decimal::integer(reserve_in), Decimal::integer(reserve_out));
+    if let (Some(a), Some(r_in), Some(r_out)) = inputs {
+        let exact = r_out
+            .checked_mul(a)
+            .zip(r_in.checked_add(a))
+            .and_then(|(n, d)| Decimal::quotient(n, d));
+        if let Some(exact) = exact {
+            check(context, exact, value, tolerance());
+        }
+    }
+}
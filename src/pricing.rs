@@ -0,0 +1,231 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::config::config;
+use crate::db::{set_swap_value, swaps_missing_value};
+use crate::features::{is_enabled, FeatureFlags};
+use crate::risk::{score_tokens, TokenRisk};
+use crate::routing::{PoolEdge, PoolGraph};
+
+/// Interval between token price recomputations (in seconds)
+const PRICE_REFRESH_INTERVAL_SECS: u64 = 30;
+
+/// Number of swaps valued per batch
+const SWAP_VALUE_BATCH: usize = 500;
+
+/// Maximum number of swap batches valued per refresh, bounding how long the
+/// connection is held while a large history is caught up on
+const SWAP_VALUE_BATCHES_PER_REFRESH: usize = 10;
+
+/// Default coin type treated as SUI when pricing tokens (overridable via `SUI_TOKEN`)
+pub const DEFAULT_SUI_TOKEN: &str = "0x2::sui::SUI";
+
+/// Derived price of a single token
+#[derive(Serialize, Clone)]
+pub struct TokenPrice {
+    pub token: String,
+    pub price_sui: Option<f64>,
+    /// Price in the deployment's canonical quote token (`QUOTE_TOKEN`)
+    pub price_quote: Option<f64>,
+    pub price_usd: Option<f64>,
+    /// Pools used to derive `price_sui`, in hop order
+    pub route: Vec<String>,
+    pub updated_at: i64,
+    /// Heuristic risk flags, see `risk::score_tokens`
+    #[serde(flatten)]
+    pub risk: TokenRisk,
+}
+
+/// Shared cache of token prices, keyed by token, maintained by `run_price_refresher`
+pub type PriceCache = Arc<RwLock<HashMap<String, TokenPrice>>>;
+
+/// Returns the token treated as SUI (`SUI_TOKEN`).
+pub fn sui_token() -> String {
+    config().sui_token.clone()
+}
+
+/// Returns the USD-pegged reference token (`USD_TOKEN`), if configured.
+/// 
+/// The reference token is assumed to trade at exactly 1 USD and acts as the
+/// oracle for `price_usd`.
+pub fn usd_token() -> Option<String> {
+    config().usd_token.clone()
+}
+
+/// Returns the deployment's canonical quote token (`QUOTE_TOKEN`).
+/// 
+/// This is the default denomination wherever a price is requested without
+/// an explicit counter token. Unset, it is `USD_TOKEN` when configured (e.g.
+/// USDC on mainnet) and otherwise the SUI token, so devnet deployments can
+/// point it at a faucet coin.
+pub fn quote_token() -> String {
+    config().quote_token.clone()
+}
+
+/// Prices `token` in units of `target` via the deepest direct pool, or failing
+/// that the two-hop route whose SUI-side pool is deepest.
+/// 
+/// # Returns
+/// * `Option<(f64, Vec<String>)>` - Price and the pools used, or `None` if unreachable
+fn price_in(graph: &PoolGraph, token: &str, target: &str) -> Option<(f64, Vec<String>)> {
+    if token == target {
+        return Some((1.0, Vec::new()));
+    }
+    let rate = |e: &PoolEdge| e.reserve_out / e.reserve_in;
+
+    // Prefer a direct pool with the largest target-side reserve
+    let routes = graph.find_routes(token, target, 2);
+    let direct = routes
+        .iter()
+        .filter(|r| r.len() == 1)
+        .max_by(|a, b| a[0].reserve_out.total_cmp(&b[0].reserve_out));
+    if let Some(r) = direct {
+        return Some((rate(r[0]), vec![r[0].pool_id.clone()]));
+    }
+
+    // Otherwise route through the intermediate token whose target-side pool is deepest
+    routes
+        .iter()
+        .max_by(|a, b| a[1].reserve_out.total_cmp(&b[1].reserve_out))
+        .map(|r| (rate(r[0]) * rate(r[1]), vec![r[0].pool_id.clone(), r[1].pool_id.clone()]))
+}
+
+/// Recomputes the SUI, quote token and USD price and the risk score of every
+/// token that appears in a pool.
+/// 
+/// `price_usd` is derived through SUI, or through the quote token when the
+/// USD token has no route to SUI.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Timestamp recorded as `updated_at`
+/// 
+/// # Returns
+/// * `rusqlite::Result<HashMap<String, TokenPrice>>` - Prices keyed by token
+pub fn compute_token_prices(conn: &Connection, now: i64) -> rusqlite::Result<HashMap<String, TokenPrice>> {
+    let graph = PoolGraph::load(conn)?;
+    let sui = sui_token();
+    let quote = quote_token();
+    let usd = usd_token();
+    let usd_in = |target: &str| {
+        usd.as_deref()
+            .and_then(|usd| price_in(&graph, usd, target))
+            .map(|(price, _)| price)
+            .filter(|price| *price > 0.0)
+    };
+    let (usd_in_sui, usd_in_quote) = (usd_in(&sui), usd_in(&quote));
+    let mut risks = score_tokens(conn, now)?;
+
+    let mut prices = HashMap::new();
+    for token in graph.edges.keys() {
+        let (price_sui, route) = match price_in(&graph, token, &sui) {
+            Some((price, route)) => (Some(price), route),
+            None => (None, Vec::new()),
+        };
+        let price_quote = price_in(&graph, token, &quote).map(|(price, _)| price);
+        let price_usd = price_sui
+            .zip(usd_in_sui)
+            .or(price_quote.zip(usd_in_quote))
+            .map(|(p, usd)| p / usd);
+        prices.insert(
+            token.clone(),
+            TokenPrice {
+                token: token.clone(),
+                price_sui,
+                price_quote,
+                price_usd,
+                route,
+                updated_at: now,
+                risk: risks.remove(token).unwrap_or_default(),
+            },
+        );
+    }
+    Ok(prices)
+}
+
+/// Records the notional value of swaps indexed since the last refresh.
+/// 
+/// Each swap's `amount_in` is valued at the sold token's freshly derived
+/// price, so swaps are valued within one refresh of being indexed; a
+/// history indexed before this ran is valued at the price when first seen.
+/// Swaps whose token has no price are left unvalued. `after_id` tracks how
+/// far the scan got, so they are not revisited until the next restart.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `prices` - Prices just computed, keyed by token
+/// * `after_id` - Row ID of the last swap valued
+fn value_new_swaps(conn: &Connection, prices: &HashMap<String, TokenPrice>, after_id: &mut i64) {
+    for _ in 0..SWAP_VALUE_BATCHES_PER_REFRESH {
+        let pending = match swaps_missing_value(conn, *after_id, SWAP_VALUE_BATCH) {
+            Ok(pending) if !pending.is_empty() => pending,
+            Ok(_) => return,
+            Err(e) => {
+                warn!(error = %e, "failed to list swaps missing a value");
+                return;
+            }
+        };
+        for (id, token, amount_in) in &pending {
+            let Some(price) = prices.get(token) else { continue };
+            let value_quote = price.price_quote.map(|p| amount_in * p);
+            let value_usd = price.price_usd.map(|p| amount_in * p);
+            if value_quote.is_none() && value_usd.is_none() {
+                continue;
+            }
+            if let Err(e) = set_swap_value(conn, *id, value_quote, value_usd) {
+                warn!(swap = id, error = %e, "failed to record value of swap");
+            }
+        }
+        *after_id = pending.last().map_or(*after_id, |(id, _, _)| *id);
+    }
+}
+
+/// Periodically recomputes token prices into the shared cache.
+/// 
+/// Runs indefinitely, refreshing every `PRICE_REFRESH_INTERVAL_SECS` seconds so
+/// the tokens endpoint never has to walk the pool graph per request. Each
+/// refresh also values newly indexed swaps at the new prices.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `cache` - Price cache served by the API
+/// * `flags` - Feature flags; refreshes are skipped while `price_refresher` is disabled
+pub async fn run_price_refresher(conn_arc: Arc<Mutex<Connection>>, cache: PriceCache, flags: FeatureFlags) {
+    let mut value_scan_id = 0;
+    loop {
+        if !is_enabled(&flags, "price_refresher") {
+            sleep(Duration::from_secs(PRICE_REFRESH_INTERVAL_SECS)).await;
+            continue;
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        let computed = match conn_arc.lock() {
+            Ok(conn) => {
+                let computed = compute_token_prices(&conn, now);
+                if let Ok(prices) = &computed {
+                    value_new_swaps(&conn, prices, &mut value_scan_id);
+                }
+                computed
+            }
+            Err(_) => return,
+        };
+        match computed {
+            Ok(prices) => {
+                if let Ok(mut guard) = cache.write() {
+                    *guard = prices;
+                }
+            }
+            Err(e) => warn!(error = %e, "failed to compute token prices"),
+        }
+
+        sleep(Duration::from_secs(PRICE_REFRESH_INTERVAL_SECS)).await;
+    }
+}
@@ -0,0 +1,127 @@
+use rusqlite::types::{ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{Connection, OptionalExtension, Result, Row};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+use crate::config::config;
+use crate::debug::record_query;
+use crate::metrics::DB_QUERIES;
+
+/// Default duration above which a query is logged as slow (in milliseconds)
+pub const DEFAULT_SLOW_QUERY_MS: u64 = 100;
+
+/// Longest text parameter shown in full in the slow-query log
+const MAX_LOGGED_TEXT: usize = 32;
+
+/// Returns the configured slow-query threshold (`SLOW_QUERY_MS`, `0` disables the log).
+fn slow_query_threshold() -> Option<Duration> {
+    let ms = config().slow_query_ms;
+    (ms > 0).then(|| Duration::from_millis(ms))
+}
+
+/// Summarizes bound parameters for the log: numbers in full, long text
+/// truncated and blobs by size only.
+fn summarize(params: &[&dyn ToSql]) -> String {
+    let values: Vec<String> = params
+        .iter()
+        .map(|param| {
+            let value = match param.to_sql() {
+                Ok(ToSqlOutput::Borrowed(value)) => value,
+                Ok(ToSqlOutput::Owned(ref value)) => return describe(value.into()),
+                _ => return "?".to_string(),
+            };
+            describe(value)
+        })
+        .collect();
+    format!("[{}]", values.join(", "))
+}
+
+fn describe(value: ValueRef) -> String {
+    match value {
+        ValueRef::Null => "NULL".to_string(),
+        ValueRef::Integer(n) => n.to_string(),
+        ValueRef::Real(n) => n.to_string(),
+        ValueRef::Text(t) => {
+            let text = String::from_utf8_lossy(t);
+            match text.char_indices().nth(MAX_LOGGED_TEXT) {
+                Some((end, _)) => format!("'{}...' ({} bytes)", &text[..end], t.len()),
+                None => format!("'{}'", text),
+            }
+        }
+        ValueRef::Blob(b) => format!("<{} byte blob>", b.len()),
+    }
+}
+
+/// Runs a database call, counting it under `name` in `fooswap_db_queries_*`.
+/// 
+/// Calls slower than `SLOW_QUERY_MS` are logged with their SQL and a summary
+/// of the bound parameters, so a query that regresses as tables grow can be
+/// traced to the code issuing it. Calls made while answering a
+/// `?debug=true` request are also listed in its `_debug` field.
+/// 
+/// # Arguments
+/// * `name` - Stable name the query is counted and logged under
+/// * `sql` - Statement the call runs, for the log
+/// * `params` - Parameters bound to it, for the log
+/// * `call` - The database call
+pub fn timed<T>(name: &'static str, sql: &str, params: &[&dyn ToSql], call: impl FnOnce() -> T) -> T {
+    let started = Instant::now();
+    let result = call();
+    let elapsed = started.elapsed();
+
+    let slow = slow_query_threshold().is_some_and(|threshold| elapsed >= threshold);
+    DB_QUERIES.observe(name, elapsed, slow);
+    record_query(name, sql, || summarize(params), elapsed);
+    if slow {
+        warn!(
+            query = name,
+            elapsed_ms = elapsed.as_millis() as u64,
+            "slow query: {} with {}",
+            sql.split_whitespace().collect::<Vec<_>>().join(" "),
+            summarize(params)
+        );
+    }
+    result
+}
+
+/// `Connection::execute`, timed under `name`.
+pub fn execute(conn: &Connection, name: &'static str, sql: &str, params: &[&dyn ToSql]) -> Result<usize> {
+    timed(name, sql, params, || conn.execute(sql, params))
+}
+
+/// `Connection::query_row`, timed under `name`.
+pub fn query_row<T>(
+    conn: &Connection,
+    name: &'static str,
+    sql: &str,
+    params: &[&dyn ToSql],
+    map: impl FnOnce(&Row<'_>) -> Result<T>,
+) -> Result<T> {
+    timed(name, sql, params, || conn.query_row(sql, params, map))
+}
+
+/// `Connection::query_row` for a row that may not exist, timed under `name`.
+pub fn query_opt<T>(
+    conn: &Connection,
+    name: &'static str,
+    sql: &str,
+    params: &[&dyn ToSql],
+    map: impl FnOnce(&Row<'_>) -> Result<T>,
+) -> Result<Option<T>> {
+    timed(name, sql, params, || conn.query_row(sql, params, map).optional())
+}
+
+/// Runs a query and collects every row, timed under `name`.
+pub fn query_all<T>(
+    conn: &Connection,
+    name: &'static str,
+    sql: &str,
+    params: &[&dyn ToSql],
+    map: impl FnMut(&Row<'_>) -> Result<T>,
+) -> Result<Vec<T>> {
+    timed(name, sql, params, || {
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params, map)?;
+        rows.collect()
+    })
+}
@@ -0,0 +1,238 @@
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::config::config;
+use crate::features::{is_enabled, FeatureFlags};
+use crate::metrics::{PRICE_DEVIATION, REFERENCE_PRICE_FAILURES};
+
+/// Default interval between reference price checks (in seconds)
+pub const DEFAULT_REFERENCE_INTERVAL_SECS: u64 = 60;
+
+/// Default relative deviation from the reference beyond which price
+/// responses carry a warning
+pub const DEFAULT_DEVIATION_THRESHOLD: f64 = 0.02;
+
+/// Number of check intervals after which a reference price is too old to
+/// compare against
+const REFERENCE_MAX_AGE_INTERVALS: u64 = 3;
+
+/// How long a reference source may take to answer
+const REFERENCE_TIMEOUT_SECS: u64 = 10;
+
+/// An external price source for one pair
+pub struct ReferenceSource {
+    /// Pair as `TOKENA/TOKENB`, priced like `/api/price` (B per A)
+    pub pair: String,
+    pub token_a: String,
+    pub token_b: String,
+    /// URL answering with JSON, without the fragment
+    pub url: String,
+    /// JSON pointer to the price in the response (the URL's fragment)
+    pub pointer: String,
+}
+
+/// Latest reference price fetched for a pair
+#[derive(Clone)]
+pub struct ReferencePrice {
+    pub price: f64,
+    pub checked_at: i64,
+}
+
+/// Reference prices keyed by pair, maintained by `run_reference_checker`
+pub type ReferencePrices = Arc<RwLock<HashMap<String, ReferencePrice>>>;
+
+/// Comparison of a served pool price with the pair's reference price
+#[derive(Serialize)]
+pub struct PriceCheck {
+    pub reference_price: f64,
+    /// Relative difference between the pool price and the reference
+    pub deviation: f64,
+    /// Whether `deviation` exceeds `PRICE_DEVIATION_THRESHOLD`
+    pub diverged: bool,
+    pub checked_at: i64,
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Returns the configured check interval (`REFERENCE_PRICE_INTERVAL_SECS`).
+fn reference_interval_secs() -> u64 {
+    config().reference_price_interval_secs
+}
+
+/// Returns the configured warning threshold (`PRICE_DEVIATION_THRESHOLD`).
+fn deviation_threshold() -> f64 {
+    config().price_deviation_threshold
+}
+
+/// Returns the configured pairs to check (`REFERENCE_PRICES`).
+pub fn reference_sources() -> &'static [ReferenceSource] {
+    &config().reference_prices
+}
+
+/// Parses one `REFERENCE_PRICES` entry.
+/// 
+/// Entries are `TOKENA/TOKENB=URL`. The URL's fragment is a JSON pointer to
+/// the price in the response, e.g.
+/// `0x2::sui::SUI/0x...::usdc::USDC=https://api.example.com/ticker?symbol=SUIUSDC#/price`;
+/// without one the whole response must be the price. Prices may be JSON
+/// numbers or numeric strings.
+/// 
+/// # Returns
+/// * `Option<ReferenceSource>` - The source, or `None` if the entry is invalid
+pub fn parse_reference_source(entry: &str) -> Option<ReferenceSource> {
+    let (pair, url) = entry.trim().split_once('=')?;
+    let (token_a, token_b) = pair.trim().split_once('/')?;
+    let (token_a, token_b, url) = (token_a.trim(), token_b.trim(), url.trim());
+    if token_a.is_empty() || token_b.is_empty() || !url.starts_with("http") {
+        return None;
+    }
+    let (url, pointer) = url.split_once('#').unwrap_or((url, ""));
+    Some(ReferenceSource {
+        pair: format!("{}/{}", token_a, token_b),
+        token_a: token_a.to_string(),
+        token_b: token_b.to_string(),
+        url: url.to_string(),
+        pointer: pointer.to_string(),
+    })
+}
+
+/// Relative difference between a pool price and a reference price.
+fn deviation(pool_price: f64, reference_price: f64) -> f64 {
+    (pool_price - reference_price).abs() / reference_price
+}
+
+/// Compares a pool price being served with the pair's reference price.
+/// 
+/// # Returns
+/// * `Option<PriceCheck>` - `None` if the pair is not checked or its reference price is out of date
+pub fn check_price(cache: &ReferencePrices, pair: &str, pool_price: f64) -> Option<PriceCheck> {
+    let reference = cache.read().ok()?.get(pair).cloned()?;
+    let max_age_ms = (reference_interval_secs() * REFERENCE_MAX_AGE_INTERVALS * 1000) as i64;
+    if now_ms() - reference.checked_at > max_age_ms {
+        return None;
+    }
+    let deviation = deviation(pool_price, reference.price);
+    Some(PriceCheck {
+        reference_price: reference.price,
+        deviation,
+        diverged: deviation > deviation_threshold(),
+        checked_at: reference.checked_at,
+    })
+}
+
+/// Fetches a reference price from its source.
+async fn fetch_reference(client: &reqwest::Client, source: &ReferenceSource) -> Result<f64, String> {
+    let body: Value = client
+        .get(&source.url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| e.to_string())?
+        .json()
+        .await
+        .map_err(|e| e.to_string())?;
+    let value = body
+        .pointer(&source.pointer)
+        .ok_or_else(|| format!("response has no value at `{}`", source.pointer))?;
+    let price = match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    price
+        .filter(|p: &f64| p.is_finite() && *p > 0.0)
+        .ok_or_else(|| format!("`{}` is not a positive price", value))
+}
+
+/// Price of a pair from its pool, as `/api/price` derives it.
+fn pool_price(conn: &Connection, token_a: &str, token_b: &str) -> rusqlite::Result<Option<f64>> {
+    conn.query_row(
+        "SELECT reserve_a, reserve_b FROM pools
+         WHERE token_a = ?1 AND token_b = ?2 AND reserve_a > 0 AND reserve_b > 0
+         ORDER BY pool_id LIMIT 1",
+        [token_a, token_b],
+        |row| Ok(row.get::<_, f64>(1)? / row.get::<_, f64>(0)?),
+    )
+    .optional()
+}
+
+/// Periodically fetches reference prices and compares them with pool prices.
+/// 
+/// Runs indefinitely, every `REFERENCE_PRICE_INTERVAL_SECS`. Each pair's
+/// deviation is published as `fooswap_price_deviation_ratio` and logged when
+/// it exceeds `PRICE_DEVIATION_THRESHOLD`, which can point to a depeg or a
+/// manipulated pool. Price responses compare against the cached reference.
+/// 
+/// # Arguments
+/// * `conn_arc` - Thread-safe SQLite connection
+/// * `cache` - Reference prices served to the price endpoints
+/// * `sources` - Pairs to check, from `reference_sources`
+/// * `flags` - Feature flags; checks are skipped while `price_reference` is disabled
+pub async fn run_reference_checker(
+    conn_arc: Arc<Mutex<Connection>>,
+    cache: ReferencePrices,
+    sources: &'static [ReferenceSource],
+    flags: FeatureFlags,
+) {
+    let interval = Duration::from_secs(reference_interval_secs());
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(REFERENCE_TIMEOUT_SECS))
+        .build()
+        .unwrap_or_default();
+    loop {
+        if !is_enabled(&flags, "price_reference") {
+            sleep(interval).await;
+            continue;
+        }
+
+        for source in sources {
+            let reference = match fetch_reference(&client, source).await {
+                Ok(price) => price,
+                Err(e) => {
+                    REFERENCE_PRICE_FAILURES.fetch_add(1, Ordering::Relaxed);
+                    warn!(pair = %source.pair, error = %e, "failed to fetch reference price");
+                    continue;
+                }
+            };
+            if let Ok(mut guard) = cache.write() {
+                guard.insert(source.pair.clone(), ReferencePrice { price: reference, checked_at: now_ms() });
+            }
+
+            let pool = match conn_arc.lock() {
+                Ok(conn) => pool_price(&conn, &source.token_a, &source.token_b),
+                Err(_) => return,
+            };
+            match pool {
+                Ok(Some(pool)) => {
+                    let deviation = deviation(pool, reference);
+                    PRICE_DEVIATION.set(&source.pair, deviation);
+                    if deviation > deviation_threshold() {
+                        warn!(
+                            pair = %source.pair,
+                            pool_price = pool,
+                            reference_price = reference,
+                            "pool price deviates {:.2}% from reference price",
+                            deviation * 100.0
+                        );
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => warn!(pair = %source.pair, error = %e, "failed to read pool price"),
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
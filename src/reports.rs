@@ -0,0 +1,208 @@
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params_from_iter, Connection, Result};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+
+/// Kind of value a report filter takes
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterKind {
+    Number,
+    Text,
+}
+
+/// A query parameter that narrows a report
+#[derive(Serialize)]
+pub struct ReportFilter {
+    pub param: &'static str,
+    pub kind: FilterKind,
+    pub description: &'static str,
+    /// Condition on the view's columns; each `{}` is bound to the value
+    #[serde(skip)]
+    pub condition: &'static str,
+}
+
+/// A named report backed by a SQL view
+#[derive(Serialize)]
+pub struct Report {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub filters: &'static [ReportFilter],
+    /// View the report reads from, created by `create_views`
+    #[serde(skip)]
+    pub view: &'static str,
+    #[serde(skip)]
+    pub definition: &'static str,
+    /// `ORDER BY` clause; must give a stable order for pagination
+    #[serde(skip)]
+    pub order_by: &'static str,
+}
+
+/// Filter on either token of a pool, shared by the pool reports
+const TOKEN_FILTER: ReportFilter = ReportFilter {
+    param: "token",
+    kind: FilterKind::Text,
+    description: "Only pools trading this token",
+    condition: "(token_a = {} OR token_b = {})",
+};
+
+/// Reports served by `GET /api/reports/:name`.
+/// 
+/// Adding a report only takes an entry here: its view is (re)created at
+/// startup and the endpoint, filters and pagination are generic.
+pub const REPORTS: [Report; 3] = [
+    Report {
+        name: "fee_efficiency",
+        description: "Pools with liquidity ranked by 24h volume relative to their reserves, which fees earned per unit of liquidity scale with",
+        filters: &[
+            TOKEN_FILTER,
+            ReportFilter {
+                param: "min_swaps",
+                kind: FilterKind::Number,
+                description: "Only pools with at least this many swaps in the last 24h",
+                condition: "swaps_24h >= {}",
+            },
+            ReportFilter {
+                param: "min_reserve",
+                kind: FilterKind::Number,
+                description: "Only pools whose reserve A is at least this large",
+                condition: "reserve_a >= {}",
+            },
+        ],
+        view: "report_fee_efficiency",
+        definition: "SELECT p.pool_id, p.token_a, p.token_b, p.reserve_a, p.reserve_b,
+                            COUNT(s.id) AS swaps_24h,
+                            COALESCE(SUM(s.amount_in), 0.0) AS volume_24h,
+                            COALESCE(SUM(s.amount_in), 0.0) / p.reserve_a AS turnover_24h
+                     FROM pools p
+                     LEFT JOIN swaps s ON s.pool_id = p.pool_id
+                         AND s.timestamp >= CAST(strftime('%s', 'now') AS INTEGER) * 1000 - 86400000
+                     WHERE p.reserve_a > 0 AND p.reserve_b > 0
+                     GROUP BY p.pool_id",
+        order_by: "turnover_24h DESC, pool_id",
+    },
+    Report {
+        name: "stale_pools",
+        description: "Pools ordered by how long ago their state last changed",
+        filters: &[
+            TOKEN_FILTER,
+            ReportFilter {
+                param: "min_idle_secs",
+                kind: FilterKind::Number,
+                description: "Only pools unchanged for at least this many seconds",
+                condition: "idle_secs >= {}",
+            },
+        ],
+        view: "report_stale_pools",
+        definition: "SELECT p.pool_id, p.token_a, p.token_b, p.reserve_a, p.reserve_b, p.last_updated,
+                            (SELECT MAX(s.timestamp) FROM swaps s WHERE s.pool_id = p.pool_id) AS last_swap,
+                            (CAST(strftime('%s', 'now') AS INTEGER) * 1000 - p.last_updated) / 1000 AS idle_secs
+                     FROM pools p",
+        order_by: "last_updated ASC, pool_id",
+    },
+    Report {
+        name: "retention",
+        description: "Wallets grouped by the week of their first swap, with the share of each cohort trading in every later week",
+        filters: &[
+            ReportFilter {
+                param: "since",
+                kind: FilterKind::Number,
+                description: "Only cohorts whose first week starts at or after this timestamp (ms)",
+                condition: "cohort_start >= {}",
+            },
+            ReportFilter {
+                param: "max_week",
+                kind: FilterKind::Number,
+                description: "Only the first weeks of each cohort, up to this many weeks after its first",
+                condition: "week <= {}",
+            },
+        ],
+        view: "report_retention",
+        definition: "SELECT cohort_start, week, wallets, cohort_size,
+                            CAST(wallets AS REAL) / cohort_size AS retention, computed_at
+                     FROM wallet_retention",
+        order_by: "cohort_start, week",
+    },
+];
+
+/// Looks up a report by name.
+pub fn report(name: &str) -> Option<&'static Report> {
+    REPORTS.iter().find(|r| r.name == name)
+}
+
+/// Recreates every report view from its current definition.
+/// 
+/// Views hold no data, so replacing them on every startup keeps them in step
+/// with the code without a migration.
+pub fn create_views(conn: &Connection) -> Result<()> {
+    for report in &REPORTS {
+        conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS {view}; CREATE VIEW {view} AS {definition};",
+            view = report.view,
+            definition = report.definition
+        ))?;
+    }
+    Ok(())
+}
+
+/// Builds the query for a report from the filters given in `params`.
+/// 
+/// Filter values are bound as parameters, never spliced into the SQL.
+/// Parameters the report does not declare are rejected, except `cursor`,
+/// which selects the page.
+/// 
+/// # Returns
+/// * `Result<(String, Vec<SqlValue>), String>` - SQL without a `LIMIT`, and its bound values; or a description of the invalid parameter
+pub fn report_query(report: &Report, params: &HashMap<String, String>) -> Result<(String, Vec<SqlValue>), String> {
+    if let Some(unknown) = params
+        .keys()
+        .find(|k| *k != "cursor" && !report.filters.iter().any(|f| f.param == k.as_str()))
+    {
+        return Err(format!("Report `{}` has no filter `{}`", report.name, unknown));
+    }
+
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    for filter in report.filters {
+        let Some(raw) = params.get(filter.param) else { continue };
+        let value = match filter.kind {
+            FilterKind::Number => match raw.parse::<f64>() {
+                Ok(n) if n.is_finite() => SqlValue::Real(n),
+                _ => return Err(format!("Filter `{}` must be a number", filter.param)),
+            },
+            FilterKind::Text => SqlValue::Text(raw.clone()),
+        };
+        values.push(value);
+        conditions.push(filter.condition.replace("{}", &format!("?{}", values.len())));
+    }
+
+    let mut sql = format!("SELECT * FROM {}", report.view);
+    if !conditions.is_empty() {
+        sql.push_str(" WHERE ");
+        sql.push_str(&conditions.join(" AND "));
+    }
+    sql.push_str(&format!(" ORDER BY {}", report.order_by));
+    Ok((sql, values))
+}
+
+/// Runs a report query, returning each row as a JSON object keyed by column.
+pub fn run_report(conn: &Connection, sql: &str, values: &[SqlValue]) -> Result<Vec<Value>> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let rows = stmt.query_map(params_from_iter(values), |row| {
+        let mut object = Map::new();
+        for (i, column) in columns.iter().enumerate() {
+            let value = match row.get::<_, SqlValue>(i)? {
+                SqlValue::Null => Value::Null,
+                SqlValue::Integer(n) => json!(n),
+                SqlValue::Real(n) => json!(n),
+                SqlValue::Text(s) => json!(s),
+                SqlValue::Blob(b) => json!(b.iter().map(|byte| format!("{:02x}", byte)).collect::<String>()),
+            };
+            object.insert(column.clone(), value);
+        }
+        Ok(Value::Object(object))
+    })?;
+    rows.collect()
+}
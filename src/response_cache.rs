@@ -0,0 +1,393 @@
+use axum::{
+    body::{to_bytes, Body, Bytes, HttpBody},
+    extract::{MatchedPath, Request},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::config::config;
+use crate::metrics::STALE_RESPONSES_SERVED;
+
+/// Largest response body that will be cached
+const MAX_CACHED_BODY_BYTES: usize = 4 * 1024 * 1024;
+
+/// Most responses kept in the cache at once
+const MAX_CACHE_ENTRIES: usize = 10_000;
+
+/// Response header telling clients whether a response came from the cache
+pub const CACHE_STATUS_HEADER: &str = "x-cache";
+
+/// Key template of policies that don't set one
+pub const DEFAULT_CACHE_KEY: &str = "{path}";
+
+/// Caching policy for one route
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachePolicy {
+    /// Matched route path, as registered with the router
+    pub route: String,
+    /// How long a cached response is served, in seconds
+    pub ttl_secs: u64,
+    /// Query parameters that select a different response; all others are
+    /// ignored when building the cache key
+    pub vary_by: Vec<String>,
+    /// Template of the cache key, before the vary-by parameters: `{path}` is
+    /// the request path, `{route}` the matched route and `{name}` the value
+    /// of the route's `:name` path parameter
+    pub key: String,
+    /// Whether the last good response is served when the database is busy,
+    /// whatever its age. With a TTL of 0 it is kept for this alone.
+    pub stale_fallback: bool,
+}
+
+/// Routes whose responses are cached by default, as `(route, ttl_secs, vary_by)`.
+/// 
+/// Routes with a TTL of 0 are never served from the cache, but keep their
+/// last good response for when the database is busy. The `[[cache]]`
+/// entries of the config file and `CACHE_TTL_SECS` are applied on top of
+/// these (see `config::Config::cache_policies`).
+pub const DEFAULT_CACHE_POLICIES: [(&str, u64, &[&str]); 13] = [
+    ("/api/pools", 0, &["verified_only", "cursor"]),
+    ("/api/price", 0, &["pair"]),
+    ("/api/swaps/:pool_id", 0, &["epoch", "min_value", "max_value", "value_in", "limit", "before", "after"]),
+    ("/api/candles", 0, &["pool_id", "interval", "from", "to", "filtered", "cursor"]),
+    ("/api/pools/:pool_id/velocity", 10, &[]),
+    ("/api/pools/:pool_id/volume", 10, &[]),
+    ("/api/pools/:pool_id/size-histogram", 30, &["window", "epoch"]),
+    ("/api/pools/:pool_id/failures", 30, &[]),
+    ("/api/graph", 10, &[]),
+    ("/api/routing/accuracy", 60, &[]),
+    ("/api/quotes/conversion", 60, &[]),
+    ("/api/ticker", 5, &["pair"]),
+    ("/api/movers", 30, &["window", "by", "limit"]),
+];
+
+/// Returns the default policies as `CachePolicy` values.
+pub fn default_cache_policies() -> Vec<CachePolicy> {
+    DEFAULT_CACHE_POLICIES
+        .iter()
+        .map(|(route, ttl_secs, vary_by)| CachePolicy {
+            route: route.to_string(),
+            ttl_secs: *ttl_secs,
+            vary_by: vary_by.iter().map(|p| p.to_string()).collect(),
+            key: DEFAULT_CACHE_KEY.to_string(),
+            stale_fallback: true,
+        })
+        .collect()
+}
+
+impl CachePolicy {
+    /// Names of the route's path parameters, in order.
+    fn path_params(&self) -> impl Iterator<Item = &str> {
+        self.route.split('/').filter_map(|segment| segment.strip_prefix(':'))
+    }
+
+    /// Splits the key template into literal text and placeholder names,
+    /// flagged `true`, or fails if a brace is unbalanced.
+    fn key_parts(&self) -> Result<Vec<(&str, bool)>, String> {
+        let unbalanced = || format!("cache key `{}` of {} has an unbalanced brace", self.key, self.route);
+        let mut parts = Vec::new();
+        let mut rest = self.key.as_str();
+        while let Some(start) = rest.find(['{', '}']) {
+            if !rest[start..].starts_with('{') {
+                return Err(unbalanced());
+            }
+            let end = rest[start + 1..]
+                .find(['{', '}'])
+                .map(|end| start + 1 + end)
+                .filter(|end| rest[*end..].starts_with('}'))
+                .ok_or_else(unbalanced)?;
+            parts.push((&rest[..start], false));
+            parts.push((&rest[start + 1..end], true));
+            rest = &rest[end + 1..];
+        }
+        parts.push((rest, false));
+        Ok(parts)
+    }
+
+    /// Checks that the policy can be applied.
+    /// 
+    /// The route must be a plain path without wildcards, vary-by parameters
+    /// must be named, and the key template may only use `{path}`, `{route}`
+    /// and the route's path parameters. A key that leaves out a path
+    /// parameter would serve one pool's response for another, so it must
+    /// contain `{path}` or every path parameter.
+    /// 
+    /// # Returns
+    /// * `Result<(), String>` - A description of the first problem found
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.route.starts_with('/') || self.route.contains('*') {
+            return Err(format!("cache route `{}` is not a plain route path", self.route));
+        }
+        if self.vary_by.iter().any(|param| param.trim().is_empty()) {
+            return Err(format!("cache policy for {} varies by an empty parameter name", self.route));
+        }
+        let parts = self.key_parts()?;
+        let placeholders: Vec<&str> = parts.iter().filter(|(_, placeholder)| *placeholder).map(|(p, _)| *p).collect();
+        let params: Vec<&str> = self.path_params().collect();
+        if let Some(unknown) = placeholders
+            .iter()
+            .find(|p| !matches!(**p, "path" | "route") && !params.contains(*p))
+        {
+            return Err(format!("cache key `{}` of {} uses unknown placeholder `{{{}}}`", self.key, self.route, unknown));
+        }
+        if !placeholders.contains(&"path") {
+            if let Some(missing) = params.iter().find(|p| !placeholders.contains(*p)) {
+                return Err(format!(
+                    "cache key `{}` of {} leaves out path parameter `{}`",
+                    self.key, self.route, missing
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A cached successful response
+#[derive(Clone)]
+pub struct CachedResponse {
+    pub content_type: Option<HeaderValue>,
+    pub body: Bytes,
+    pub stored_at: Instant,
+}
+
+/// Marker a handler attaches to its response when a read failed because the
+/// database was locked, asking the cache to fall back to the last good response
+#[derive(Clone, Copy)]
+pub struct DatabaseBusy;
+
+/// Cached responses keyed by route, path and varying query parameters
+pub type ResponseCache = Arc<RwLock<HashMap<String, CachedResponse>>>;
+
+/// Returns the configured policy for a route, if it is cached.
+pub fn cache_policy(route: &str) -> Option<&'static CachePolicy> {
+    config().cache_policies.iter().find(|p| p.route == route)
+}
+
+/// Builds the cache key from the policy's key template and its vary-by
+/// query parameters, in the policy's order.
+fn cache_key(policy: &CachePolicy, request: &Request) -> String {
+    let path = request.uri().path();
+    let params: HashMap<&str, &str> = policy
+        .route
+        .split('/')
+        .zip(path.split('/'))
+        .filter_map(|(segment, value)| Some((segment.strip_prefix(':')?, value)))
+        .collect();
+
+    let query: HashMap<String, String> = request
+        .uri()
+        .query()
+        .map(|q| {
+            q.split('&')
+                .filter_map(|pair| pair.split_once('=').or(Some((pair, ""))))
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    // Policies are validated when the configuration is loaded
+    let parts = policy.key_parts().unwrap_or_else(|_| vec![("path", true)]);
+    let mut key = String::new();
+    for (part, placeholder) in parts {
+        key.push_str(match (part, placeholder) {
+            (text, false) => text,
+            ("path", true) => path,
+            ("route", true) => &policy.route,
+            (name, true) => params.get(name).copied().unwrap_or(""),
+        });
+    }
+    for param in &policy.vary_by {
+        key.push_str(&format!("|{}={}", param, query.get(param).map(String::as_str).unwrap_or("")));
+    }
+    key
+}
+
+/// Builds a response from a cache entry.
+fn cached_response(entry: &CachedResponse, cache_status: &'static str) -> Response {
+    cached_body_response(entry, entry.body.clone(), cache_status)
+}
+
+/// Builds a response from an expired cache entry, with `"stale": true` and
+/// the entry's age in seconds added to its JSON body.
+fn stale_response(entry: &CachedResponse) -> Response {
+    let body = match serde_json::from_slice::<serde_json::Value>(&entry.body) {
+        Ok(serde_json::Value::Object(mut map)) => {
+            map.insert("stale".into(), json!(true));
+            map.insert("age_secs".into(), json!(entry.stored_at.elapsed().as_secs()));
+            serde_json::to_vec(&map).map(Bytes::from).unwrap_or_else(|_| entry.body.clone())
+        }
+        _ => entry.body.clone(),
+    };
+    cached_body_response(entry, body, "stale")
+}
+
+fn cached_body_response(entry: &CachedResponse, body: Bytes, cache_status: &'static str) -> Response {
+    let mut response = (StatusCode::OK, Body::from(body)).into_response();
+    if let Some(content_type) = &entry.content_type {
+        response.headers_mut().insert(header::CONTENT_TYPE, content_type.clone());
+    }
+    response.headers_mut().insert(CACHE_STATUS_HEADER, HeaderValue::from_static(cache_status));
+    response
+}
+
+/// Middleware serving cached responses for routes with a configured
+/// `CachePolicy`, and the last good response when the database is busy.
+/// 
+/// Only GET requests are cached, and only responses that are HTTP 200 with a
+/// JSON body whose `status` is `"ok"`. On market-data routes the stale-data
+/// guard wraps this middleware, so it flags hits as well as misses and the
+/// cache only ever holds unflagged bodies. Responses carry `X-Cache: hit` or
+/// `X-Cache: miss`. Routes without a policy, or without a `ResponseCache`
+/// extension, pass through untouched.
+/// 
+/// When the handler fails because the database is locked (its response is
+/// marked `DatabaseBusy`), the last cached response is served regardless of
+/// its age, with `"stale": true`, `"age_secs"` and `X-Cache: stale`, unless
+/// the policy turns `stale_fallback` off. Routes with a TTL of 0 are only
+/// stored for this fallback and answer with `X-Cache: miss`.
+pub async fn cache_response(request: Request, next: Next) -> Response {
+    let policy = request
+        .extensions()
+        .get::<MatchedPath>()
+        .and_then(|p| cache_policy(p.as_str()))
+        .filter(|p| p.ttl_secs > 0 || p.stale_fallback);
+    let cache = request.extensions().get::<ResponseCache>().cloned();
+    let (Some(policy), Some(cache)) = (policy, cache) else {
+        return next.run(request).await;
+    };
+    if request.method() != Method::GET {
+        return next.run(request).await;
+    }
+
+    let key = cache_key(policy, &request);
+    let ttl = Duration::from_secs(policy.ttl_secs);
+    if let Some(entry) = cache.read().ok().and_then(|c| c.get(&key).cloned()) {
+        if entry.stored_at.elapsed() < ttl {
+            return cached_response(&entry, "hit");
+        }
+    }
+
+    let response = next.run(request).await;
+    if response.extensions().get::<DatabaseBusy>().is_some() && policy.stale_fallback {
+        return match cache.read().ok().and_then(|c| c.get(&key).cloned()) {
+            Some(entry) => {
+                STALE_RESPONSES_SERVED.fetch_add(1, Ordering::Relaxed);
+                stale_response(&entry)
+            }
+            None => response,
+        };
+    }
+    let size = response.body().size_hint().upper();
+    if response.status() != StatusCode::OK || size.is_none_or(|n| n > MAX_CACHED_BODY_BYTES as u64) {
+        return response;
+    }
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_CACHED_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    };
+    let ok = serde_json::from_slice::<serde_json::Value>(&bytes)
+        .map(|v| v["status"] == "ok")
+        .unwrap_or(false);
+    if ok {
+        if let Ok(mut guard) = cache.write() {
+            if guard.len() >= MAX_CACHE_ENTRIES {
+                guard.retain(|_, e| e.stored_at.elapsed() < ttl);
+            }
+            // Entries kept only for the busy fallback never expire, so the
+            // oldest one makes room once nothing else has
+            if guard.len() >= MAX_CACHE_ENTRIES && !guard.contains_key(&key) {
+                if let Some(oldest) = guard.iter().min_by_key(|(_, e)| e.stored_at).map(|(k, _)| k.clone()) {
+                    guard.remove(&oldest);
+                }
+            }
+            guard.insert(
+                key,
+                CachedResponse {
+                    content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+                    body: bytes.clone(),
+                    stored_at: Instant::now(),
+                },
+            );
+        }
+    }
+    parts.headers.insert(CACHE_STATUS_HEADER, HeaderValue::from_static("miss"));
+    Response::from_parts(parts, Body::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(route: &str, key: &str, vary_by: &[&str]) -> CachePolicy {
+        CachePolicy {
+            route: route.to_string(),
+            ttl_secs: 10,
+            vary_by: vary_by.iter().map(|p| p.to_string()).collect(),
+            key: key.to_string(),
+            stale_fallback: true,
+        }
+    }
+
+    fn request(uri: &str) -> Request {
+        Request::builder().uri(uri).body(Body::empty()).unwrap()
+    }
+
+    #[test]
+    fn default_policies_are_valid() {
+        for policy in default_cache_policies() {
+            assert_eq!(policy.validate(), Ok(()), "{}", policy.route);
+        }
+    }
+
+    #[test]
+    fn policies_that_could_mix_up_responses_are_rejected() {
+        let invalid = [
+            policy("/api/swaps/:pool_id", "{route}", &[]),
+            policy("/api/swaps/:pool_id", "{path", &[]),
+            policy("/api/swaps/:pool_id", "path}", &[]),
+            policy("/api/swaps/:pool_id", "{pool}", &[]),
+            policy("/api/*rest", "{path}", &[]),
+            policy("/api/graph", "{path}", &[" "]),
+        ];
+        for policy in invalid {
+            assert!(policy.validate().is_err(), "{} {}", policy.route, policy.key);
+        }
+        assert_eq!(policy("/api/swaps/:pool_id", "swaps:{pool_id}", &[]).validate(), Ok(()));
+    }
+
+    #[test]
+    fn cache_key_varies_only_by_the_policy_parameters() {
+        let swaps = policy("/api/swaps/:pool_id", DEFAULT_CACHE_KEY, &["limit", "before"]);
+        let key = cache_key(&swaps, &request("/api/swaps/0xp?before=1_2&utm=x&limit=5"));
+        assert_eq!(key, "/api/swaps/0xp|limit=5|before=1_2");
+        assert_eq!(cache_key(&swaps, &request("/api/swaps/0xp?limit=5&before=1_2")), key);
+        assert_eq!(cache_key(&swaps, &request("/api/swaps/0xp")), "/api/swaps/0xp|limit=|before=");
+
+        let shared = policy("/api/swaps/:pool_id", "{route}@{pool_id}", &[]);
+        assert_eq!(cache_key(&shared, &request("/api/swaps/0xp?limit=5")), "/api/swaps/:pool_id@0xp");
+    }
+
+    #[tokio::test]
+    async fn stale_response_is_flagged_with_its_age() {
+        let entry = CachedResponse {
+            content_type: Some(HeaderValue::from_static("application/json")),
+            body: Bytes::from_static(br#"{"status":"ok","data":[]}"#),
+            stored_at: Instant::now() - Duration::from_secs(90),
+        };
+        let response = stale_response(&entry);
+        assert_eq!(response.headers()[CACHE_STATUS_HEADER], "stale");
+        assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["stale"], true);
+        assert_eq!(body["age_secs"], 90);
+        assert_eq!(body["status"], "ok");
+    }
+}
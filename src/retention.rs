@@ -0,0 +1,79 @@
+use rusqlite::{params, Connection, Result};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::features::{is_enabled, FeatureFlags};
+use crate::query_log;
+
+/// Interval between recomputations of the wallet retention cohorts (in seconds)
+const RETENTION_INTERVAL_SECS: u64 = 60 * 60;
+
+/// Length of a cohort week (in milliseconds)
+const WEEK_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Offset of the first Monday after the Unix epoch (in milliseconds), so
+/// weeks run Monday to Sunday in UTC
+const WEEK_START_MS: i64 = 4 * 24 * 60 * 60 * 1000;
+
+/// Recomputes wallet retention from the full swap history.
+/// 
+/// Each wallet that traded belongs to the cohort of the week of its first
+/// swap, and is retained in every later week in which it swapped at least
+/// once. Swaps without a sender are left out. The previous results are
+/// replaced in one transaction, so the report never shows a partial
+/// computation.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<usize>` - Number of (cohort, week) rows stored
+pub fn compute_retention(conn: &Connection, now: i64) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    query_log::execute(&tx, "compute_retention:clear", "DELETE FROM wallet_retention", params![])?;
+    let rows = query_log::execute(
+        &tx,
+        "compute_retention:insert",
+        "WITH activity AS (
+             SELECT DISTINCT sender, (timestamp - ?1) / ?2 AS week
+             FROM swaps WHERE sender IS NOT NULL AND timestamp >= ?1
+         ),
+         cohorts AS (SELECT sender, MIN(week) AS cohort FROM activity GROUP BY sender),
+         sizes AS (SELECT cohort, COUNT(*) AS wallets FROM cohorts GROUP BY cohort)
+         INSERT INTO wallet_retention (cohort_start, week, wallets, cohort_size, computed_at)
+         SELECT c.cohort * ?2 + ?1, a.week - c.cohort, COUNT(*), s.wallets, ?3
+         FROM activity a
+         JOIN cohorts c ON c.sender = a.sender
+         JOIN sizes s ON s.cohort = c.cohort
+         GROUP BY c.cohort, a.week",
+        params![WEEK_START_MS, WEEK_MS, now],
+    )?;
+    tx.commit()?;
+    Ok(rows)
+}
+
+/// Recomputes the wallet retention cohorts once an hour while the `reports`
+/// feature is enabled.
+pub async fn run_retention(conn_arc: Arc<Mutex<Connection>>, flags: FeatureFlags) {
+    let interval = Duration::from_secs(RETENTION_INTERVAL_SECS);
+    loop {
+        if is_enabled(&flags, "reports") {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            let computed = match conn_arc.lock() {
+                Ok(conn) => compute_retention(&conn, now),
+                Err(_) => return,
+            };
+            if let Err(e) = computed {
+                warn!(error = %e, "failed to compute wallet retention");
+            }
+        }
+
+        sleep(interval).await;
+    }
+}
@@ -0,0 +1,256 @@
+use rusqlite::{params, Connection, Result};
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::query_log;
+
+/// Share of a token's liquidity in its largest pool above which the token is
+/// flagged as concentrated
+const CONCENTRATION_THRESHOLD: f64 = 0.9;
+
+/// Age below which a token is flagged as new (in milliseconds)
+const NEW_TOKEN_AGE_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Relative range between the highest and lowest 1h candle price of the last
+/// 24 hours above which a token is flagged for an abnormal price move
+const PRICE_MOVE_THRESHOLD: f64 = 0.5;
+
+/// Window price moves are measured over (in milliseconds)
+const PRICE_MOVE_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Heuristics a token is scored on, as (code, weight). Weights add up to 100.
+const HEURISTICS: [(&str, u32); 4] = [
+    ("no_metadata", 20),
+    ("concentrated_liquidity", 20),
+    ("new_token", 30),
+    ("abnormal_price_move", 30),
+];
+
+/// Why a token was flagged
+#[derive(Serialize, Clone)]
+pub struct RiskReason {
+    pub token: String,
+    /// One of the heuristic codes, e.g. `new_token`
+    pub code: &'static str,
+    pub message: String,
+}
+
+/// Risk assessment of a token, for frontends to warn on
+#[derive(Serialize, Clone, Default)]
+pub struct TokenRisk {
+    /// Sum of the weights of the flagged heuristics, from 0 (none) to 100 (all)
+    pub risk_score: u32,
+    pub risk_reasons: Vec<RiskReason>,
+}
+
+impl TokenRisk {
+    fn flag(&mut self, token: &str, code: &'static str, message: String) {
+        let weight = HEURISTICS.iter().find(|(c, _)| *c == code).map_or(0, |(_, w)| *w);
+        self.risk_score += weight;
+        self.risk_reasons.push(RiskReason { token: token.to_string(), code, message });
+    }
+}
+
+/// What a pool says about its tokens
+struct PoolFacts {
+    /// Each token with its reserve
+    tokens: [(String, f64); 2],
+    /// Whether the pool is verified or has a display name
+    curated: bool,
+    /// Timestamp of the pool's first snapshot
+    first_seen: Option<i64>,
+    /// Relative price range of the pool's recent 1h candles
+    price_move: Option<f64>,
+}
+
+/// What the pools trading a token say about it
+#[derive(Default)]
+struct TokenFacts {
+    /// Whether any pool trading the token has curated metadata
+    curated: bool,
+    /// The token's reserve in each pool
+    reserves: Vec<f64>,
+    /// Earliest snapshot of any pool trading the token
+    first_seen: Option<i64>,
+    /// Largest relative price range of its pools' recent 1h candles
+    price_move: Option<f64>,
+}
+
+/// Scores every token that appears in a pool.
+/// 
+/// Tokens are flagged when no pool trading them has a display name or is
+/// verified in `pool_overrides` (`no_metadata`), when one pool holds more
+/// than 90% of their reserves (`concentrated_liquidity`; the contract emits
+/// no liquidity events, so holdings of individual LPs are not known), when
+/// their first pool was created less than 24 hours ago (`new_token`), and
+/// when a pool's 1h candles of the last 24 hours range more than 50%
+/// (`abnormal_price_move`).
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `now` - Current timestamp in milliseconds
+/// 
+/// # Returns
+/// * `Result<HashMap<String, TokenRisk>>` - Risk keyed by token
+pub fn score_tokens(conn: &Connection, now: i64) -> Result<HashMap<String, TokenRisk>> {
+    let pools = query_log::query_all(
+        conn,
+        "score_tokens",
+        "SELECT p.token_a, p.token_b, p.reserve_a, p.reserve_b,
+                COALESCE(o.verified, 0) = 1 OR o.display_name IS NOT NULL,
+                (SELECT MIN(s.timestamp) FROM pool_snapshots s WHERE s.pool_id = p.pool_id),
+                (SELECT MAX(c.high) / MIN(c.low) - 1 FROM candles c
+                 WHERE c.pool_id = p.pool_id AND c.interval = '1h' AND c.bucket_start >= ?1 AND c.low > 0)
+         FROM pools p
+         LEFT JOIN pool_overrides o ON o.pool_id = p.pool_id",
+        params![now - PRICE_MOVE_WINDOW_MS],
+        |row| {
+            Ok(PoolFacts {
+                tokens: [(row.get(0)?, row.get(2)?), (row.get(1)?, row.get(3)?)],
+                curated: row.get(4)?,
+                first_seen: row.get(5)?,
+                price_move: row.get(6)?,
+            })
+        },
+    )?;
+
+    let mut facts: HashMap<String, TokenFacts> = HashMap::new();
+    for pool in pools {
+        for (token, reserve) in pool.tokens {
+            let entry = facts.entry(token).or_default();
+            entry.curated |= pool.curated;
+            entry.reserves.push(reserve);
+            entry.first_seen = entry.first_seen.into_iter().chain(pool.first_seen).min();
+            entry.price_move = entry.price_move.into_iter().chain(pool.price_move).reduce(f64::max);
+        }
+    }
+
+    Ok(facts
+        .into_iter()
+        .map(|(token, facts)| {
+            let mut risk = TokenRisk::default();
+            if !facts.curated {
+                risk.flag(&token, "no_metadata", "No pool trading this token has curated metadata".to_string());
+            }
+            let total: f64 = facts.reserves.iter().sum();
+            let largest = facts.reserves.iter().copied().fold(0.0, f64::max);
+            if total > 0.0 && largest / total > CONCENTRATION_THRESHOLD {
+                risk.flag(
+                    &token,
+                    "concentrated_liquidity",
+                    format!("{:.0}% of liquidity is in a single pool", largest / total * 100.0),
+                );
+            }
+            if let Some(first_seen) = facts.first_seen.filter(|ts| now - ts < NEW_TOKEN_AGE_MS) {
+                risk.flag(
+                    &token,
+                    "new_token",
+                    format!("First traded {} hours ago", (now - first_seen) / (60 * 60 * 1000)),
+                );
+            }
+            if let Some(price_move) = facts.price_move.filter(|m| *m > PRICE_MOVE_THRESHOLD) {
+                risk.flag(
+                    &token,
+                    "abnormal_price_move",
+                    format!("Price ranged {:.0}% in the last 24 hours", price_move * 100.0),
+                );
+            }
+            (token, risk)
+        })
+        .collect())
+}
+
+/// Combines the risk of a pool's two tokens: the higher score, with the
+/// reasons of both.
+pub fn pool_risk(token_a: Option<&TokenRisk>, token_b: Option<&TokenRisk>) -> Option<TokenRisk> {
+    let risks: Vec<&TokenRisk> = token_a.into_iter().chain(token_b).collect();
+    if risks.is_empty() {
+        return None;
+    }
+    Some(TokenRisk {
+        risk_score: risks.iter().map(|r| r.risk_score).max().unwrap_or(0),
+        risk_reasons: risks.iter().flat_map(|r| r.risk_reasons.iter().cloned()).collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 100 * 24 * 60 * 60 * 1000;
+    const HOUR: i64 = 60 * 60 * 1000;
+
+    fn codes(risk: &TokenRisk) -> Vec<&'static str> {
+        risk.risk_reasons.iter().map(|reason| reason.code).collect()
+    }
+
+    /// A verified SUI/USDC pool trading for days, and a new, volatile pool
+    /// pairing SUI with a new token.
+    fn risk_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO pools (pool_id, token_a, token_b, reserve_a, reserve_b) VALUES
+                 ('0x1', 'SUI', 'USDC', 1000, 1000),
+                 ('0x2', 'SUI', 'NEW', 50, 5000);
+             INSERT INTO pool_overrides (pool_id, verified, updated_at) VALUES ('0x1', 1, 0);",
+        )
+        .unwrap();
+        for (pool_id, timestamp) in [("0x1", NOW - 10 * 24 * HOUR), ("0x2", NOW - 2 * HOUR)] {
+            conn.execute(
+                "INSERT INTO pool_snapshots (pool_id, reserve_a, reserve_b, timestamp) VALUES (?1, 1, 1, ?2)",
+                params![pool_id, timestamp],
+            )
+            .unwrap();
+        }
+        // The 0x1 candle that ranged 900% is older than the window
+        for (pool_id, bucket_start, high, low) in
+            [("0x1", NOW - HOUR, 1.1, 1.0), ("0x1", NOW - 48 * HOUR, 10.0, 1.0), ("0x2", NOW - HOUR, 3.0, 1.0)]
+        {
+            conn.execute(
+                "INSERT INTO candles VALUES (?1, '1h', ?2, ?4, ?3, ?4, ?4, 1, 1, 1)",
+                params![pool_id, bucket_start, high, low],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    #[test]
+    fn tokens_are_scored_on_every_pool_trading_them() {
+        let risks = score_tokens(&risk_db(), NOW).unwrap();
+
+        // Curated and old through 0x1, but 95% of its liquidity is in 0x1 and 0x2 moved
+        assert_eq!(codes(&risks["SUI"]), vec!["concentrated_liquidity", "abnormal_price_move"]);
+        assert_eq!(risks["SUI"].risk_score, 50);
+
+        assert_eq!(codes(&risks["USDC"]), vec!["concentrated_liquidity"]);
+        assert_eq!(risks["USDC"].risk_score, 20);
+
+        assert_eq!(
+            codes(&risks["NEW"]),
+            vec!["no_metadata", "concentrated_liquidity", "new_token", "abnormal_price_move"]
+        );
+        assert_eq!(risks["NEW"].risk_score, 100);
+        assert_eq!(risks["NEW"].risk_reasons[2].message, "First traded 2 hours ago");
+    }
+
+    #[test]
+    fn tokens_age_out_of_the_new_and_moving_flags() {
+        let risks = score_tokens(&risk_db(), NOW + 24 * HOUR).unwrap();
+        assert_eq!(codes(&risks["NEW"]), vec!["no_metadata", "concentrated_liquidity"]);
+        assert_eq!(risks["NEW"].risk_score, 40);
+    }
+
+    #[test]
+    fn pool_risk_is_the_riskier_token_with_both_reasons() {
+        let risks = score_tokens(&risk_db(), NOW).unwrap();
+        let pool = pool_risk(risks.get("SUI"), risks.get("NEW")).unwrap();
+        assert_eq!(pool.risk_score, 100);
+        assert_eq!(pool.risk_reasons.len(), 6);
+        assert_eq!(pool.risk_reasons[0].token, "SUI");
+
+        assert_eq!(pool_risk(None, risks.get("USDC")).unwrap().risk_score, 20);
+        assert!(pool_risk(None, None).is_none());
+    }
+}
@@ -1,14 +1,64 @@
 use axum::{
+    body::Body,
     extract::{Path, Query, Extension},
-    routing::get,
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware,
+    routing::{delete, get, post, put},
     Router,
-    response::Json,
+    response::{IntoResponse, Json, Response},
 };
-use rusqlite::Connection;
-use serde::Serialize;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
+use sha2::{Digest, Sha256};
 use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
 use std::collections::HashMap;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::candles::{
+    backfill_candles, download_candles, filtered_candles, interval_ms, rolled_up_candles, CandleDownload,
+    DEFAULT_DOWNLOAD_MAX_POINTS, MAX_DOWNLOAD_MAX_POINTS,
+};
+use crate::config::config;
+use crate::custom_metrics::{compute_custom_metrics, Expr, METRIC_COLUMNS};
+use crate::db::{
+    add_api_key_address, alias_for_pair, api_key_addresses, api_key_hash, clear_custom_metric, clear_feature_override,
+    clear_pool_override, compact, custom_metric, delete_label, delete_pair_alias, find_pair_alias, import_labels,
+    insert_quote, insert_route_recommendation, list_custom_metrics, list_labels, list_pair_aliases, list_pool_overrides,
+    pool_volumes, pools_checksum, remove_api_key_address, set_custom_metric, set_feature_override, set_pair_alias,
+    set_pool_override, set_pool_verified, token_metadata, AddressLabel, PairAlias, PoolOverride, PoolVolume,
+    QuoteRecord, TokenMetadata,
+};
+use crate::error::ApiError;
+use crate::exports::{
+    create_export_job, export_path, get_export_job, run_export_job, ExportJob, ExportRequest, FileBody,
+    MAX_ACTIVE_EXPORTS,
+};
+use crate::features::{configured_state, feature, require_feature, FeatureFlags, FeatureState, FlagSource};
+use crate::freshness::{degrade_when_stale, indexer_lag_secs};
+use crate::indexer::{fetch_checkpoint, rpc_client, rpc_url};
+use crate::latency::QuoteTimings;
+use crate::maintenance::record_db_size;
+use crate::metrics::{
+    CACHE_INVALIDATIONS_RECEIVED, INDEXER_EVENTS_PROCESSED, INDEXER_GAPS_DETECTED, INDEXER_LAST_SYNC_MS, INGEST_LATENCY,
+    track_http_metrics,
+};
+use crate::graph_cache::{current_graph, graph_stats, pools_changed, GraphCache};
+use crate::growth;
+use crate::idempotency::idempotent;
+use crate::pool_cache::{notify_pool_updates, pool_updates, read_pools, reload_pools, PoolCache, PoolState};
+use crate::precision::audit_quotient;
+use crate::pricing::{quote_token, PriceCache, TokenPrice};
+use crate::reference::{check_price, ReferencePrices};
+use crate::reports::{report, report_query, run_report, REPORTS};
+use crate::risk::{pool_risk, RiskReason};
+use crate::response_cache::{cache_response, ResponseCache};
+use crate::routing::{
+    pool_fee_bps, quote_swap, simulate_route, spot_price, RouteQuote, MAX_HOPS, ZERO_LIQUIDITY,
+};
+use crate::debug::debug_response;
+use crate::slo::{self, track_availability, SLO_TRACKED_SINCE_MS};
+use crate::usage::{track_usage, API_KEY_HEADER};
 
 /// Represents a liquidity pool in the DEX
 #[derive(Serialize)]
@@ -19,6 +69,100 @@ struct PoolInfo {
     reserve_a: f64,
     reserve_b: f64,
     last_updated: i64,
+    version: i64,
+    /// Curated metadata from `pool_overrides`; `null` and `false` when not set
+    display_name: Option<String>,
+    logo_url: Option<String>,
+    verified: bool,
+    warning: Option<String>,
+    /// Higher risk score of the two tokens, with the reasons of both; `null`
+    /// until the price refresher has scored them
+    risk_score: Option<u32>,
+    risk_reasons: Vec<RiskReason>,
+    /// Token A and token B moved by the pool's swaps over the last 24 hours
+    /// and 7 days, in whole hours (see `pool_volumes`)
+    volume_a_24h: f64,
+    volume_b_24h: f64,
+    volume_a_7d: f64,
+    volume_b_7d: f64,
+    /// Symbol, name and decimals of each token, once the indexer fetched
+    /// them; `null` for coins without metadata
+    token_a_metadata: Option<TokenMetadata>,
+    token_b_metadata: Option<TokenMetadata>,
+}
+
+/// Columns `PoolInfo::from_row` reads, from `pools p` joined with `pool_overrides o`
+const POOL_INFO_COLUMNS: &str = "p.pool_id, p.token_a, p.token_b, p.reserve_a, p.reserve_b, p.last_updated, p.version,
+                    o.display_name, o.logo_url, COALESCE(o.verified, 0), o.warning";
+
+impl PoolInfo {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<PoolInfo> {
+        Ok(PoolInfo {
+            pool_id: row.get(0)?,
+            token_a: row.get(1)?,
+            token_b: row.get(2)?,
+            reserve_a: row.get(3)?,
+            reserve_b: row.get(4)?,
+            last_updated: row.get(5)?,
+            version: row.get(6)?,
+            display_name: row.get(7)?,
+            logo_url: row.get(8)?,
+            verified: row.get(9)?,
+            warning: row.get(10)?,
+            risk_score: None,
+            risk_reasons: Vec::new(),
+            volume_a_24h: 0.0,
+            volume_b_24h: 0.0,
+            volume_a_7d: 0.0,
+            volume_b_7d: 0.0,
+            token_a_metadata: None,
+            token_b_metadata: None,
+        })
+    }
+
+    /// Fills in the metadata of the pool's tokens.
+    fn with_metadata(mut self, metadata: &HashMap<String, TokenMetadata>) -> PoolInfo {
+        self.token_a_metadata = metadata.get(&self.token_a).cloned();
+        self.token_b_metadata = metadata.get(&self.token_b).cloned();
+        self
+    }
+
+    /// Fills in the pool's volume; pools missing from `volumes` had no swaps.
+    fn with_volume(mut self, volumes: &HashMap<String, PoolVolume>) -> PoolInfo {
+        if let Some(volume) = volumes.get(&self.pool_id) {
+            self.volume_a_24h = volume.volume_a_24h;
+            self.volume_b_24h = volume.volume_b_24h;
+            self.volume_a_7d = volume.volume_a_7d;
+            self.volume_b_7d = volume.volume_b_7d;
+        }
+        self
+    }
+
+    /// Fills in the risk of the pool's tokens from the price cache.
+    fn with_risk(mut self, prices: &HashMap<String, TokenPrice>) -> PoolInfo {
+        let risk = |token: &String| prices.get(token).map(|price| &price.risk);
+        if let Some(risk) = pool_risk(risk(&self.token_a), risk(&self.token_b)) {
+            self.risk_score = Some(risk.risk_score);
+            self.risk_reasons = risk.risk_reasons;
+        }
+        self
+    }
+}
+
+/// A pool with stats derived from its swaps
+#[derive(Serialize)]
+struct PoolDetail {
+    #[serde(flatten)]
+    pool: PoolInfo,
+    /// Price of token A in token B; `null` if a reserve is zero
+    price: Option<f64>,
+    /// Why `price` is `null`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reason: Option<&'static str>,
+    swap_count: i64,
+    swap_count_24h: i64,
+    /// Timestamp of the most recent swap, if any
+    last_swap: Option<i64>,
 }
 
 /// Represents a swap transaction in the DEX
@@ -28,15 +172,88 @@ struct SwapInfo {
     amount_in: f64,
     amount_out: f64,
     timestamp: i64,
+    /// Sui epoch and checkpoint of the swap, once the indexer looked them up
+    epoch: Option<i64>,
+    checkpoint: Option<i64>,
+    /// Notional of `amount_in` in the quote token and in USD, once priced
+    value_quote: Option<f64>,
+    value_usd: Option<f64>,
+    /// Whether the swap's reserve deltas didn't match its amounts
+    inconsistent: bool,
+    /// Address of the trader, if the swap event carried one
+    sender: Option<String>,
+    /// Label imported for `sender`, `null` if it has none
+    sender_label: Option<SenderLabel>,
+    /// `a_to_b` if token A was sold for token B, `b_to_a` otherwise
+    direction: String,
+}
+
+/// Label an admin imported for a swap's sender
+#[derive(Serialize)]
+struct SenderLabel {
+    label: String,
+    category: Option<String>,
+}
+
+/// Default maximum number of rows a list endpoint returns per request
+pub const DEFAULT_MAX_RESPONSE_ROWS: usize = 1000;
+
+/// Returns the configured per-request row limit (`MAX_RESPONSE_ROWS`).
+fn max_response_rows() -> usize {
+    config().max_response_rows
+}
+
+/// One page of a list endpoint.
+/// 
+/// The continuation cursor is the offset of the page's first row, so lists
+/// must be read in a stable order.
+struct Page {
+    offset: usize,
+    limit: usize,
+}
+
+impl Page {
+    /// Reads the page requested by the `cursor` query parameter.
+    fn from_params(params: &HashMap<String, String>) -> Result<Page, ApiError> {
+        let offset = match params.get("cursor").map(|c| c.parse::<usize>()) {
+            None => 0,
+            Some(Ok(offset)) => offset,
+            Some(Err(_)) => return Err(ApiError::BadRequest("Query parameter `cursor` is not a valid cursor".to_string())),
+        };
+        Ok(Page { offset, limit: max_response_rows() })
+    }
+
+    /// SQL clause reading the page plus one row, which shows whether the list
+    /// continues past it.
+    fn sql(&self) -> String {
+        format!(" LIMIT {} OFFSET {}", self.limit + 1, self.offset)
+    }
+
+    /// Trims the extra row read by `sql` and, when the list continues, adds
+    /// `"truncated": true` and the `next_cursor` to the response.
+    fn finish<T>(&self, rows: &mut Vec<T>, response: &mut serde_json::Value) {
+        if rows.len() > self.limit {
+            rows.truncate(self.limit);
+            response["truncated"] = json!(true);
+            response["next_cursor"] = json!((self.offset + self.limit).to_string());
+        }
+    }
 }
 
 /// Retrieves all liquidity pools from the database.
 /// 
 /// Returns a JSON response containing an array of pool information including
-/// current reserves, token addresses, and last update timestamp.
+/// current reserves, token addresses, and last update timestamp, ordered by
+/// pool ID, merged with any display metadata admins have set for the pool
+/// (see `set_pool_override_handler`), the risk of its tokens (see
+/// `tokens_handler`), its 24h and 7d volume (see `pool_volume_handler`) and
+/// the coin metadata of its tokens. With `verified_only=true`, only pools
+/// an admin has marked verified are listed. At most `MAX_RESPONSE_ROWS`
+/// pools are returned per request; when more exist the response carries
+/// `"truncated": true` and a `next_cursor` to pass back as `cursor`.
 /// 
 /// # Endpoint
-/// `GET /api/pools`
+/// `GET /api/pools?verified_only=true&cursor=...`
 /// 
 /// # Response Format
 /// ```json
@@ -49,205 +266,4570 @@ struct SwapInfo {
 ///       "token_b": "0x...",
 ///       "reserve_a": 1000.0,
 ///       "reserve_b": 500.0,
-///       "last_updated": 1751104133893
+///       "last_updated": 1751104133893,
+///       "version": 3,
+///       "display_name": "SUI/USDC",
+///       "logo_url": "https://example.com/sui-usdc.png",
+///       "verified": true,
+///       "warning": null,
+///       "risk_score": 0,
+///       "risk_reasons": [],
+///       "volume_a_24h": 1200.0,
+///       "volume_b_24h": 600.0,
+///       "volume_a_7d": 8400.0,
+///       "volume_b_7d": 4200.0,
+///       "token_a_metadata": { "symbol": "SUI", "name": "Sui", "decimals": 9 },
+///       "token_b_metadata": { "symbol": "USDC", "name": "USD Coin", "decimals": 6 }
 ///     }
 ///   ]
 /// }
 /// ```
 async fn pools_handler(
+    Query(params): Query<HashMap<String, String>>,
     Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
-) -> Json<serde_json::Value> {
+    Extension(price_cache): Extension<PriceCache>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let page = Page::from_params(&params)?;
+    let verified_only = match params.get("verified_only").map(String::as_str) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `verified_only` must be true or false".to_string(),
+            ));
+        }
+    };
+
     // Acquire database connection lock
-    let conn = conn_arc.lock().unwrap();
+    let conn = conn_arc.lock()?;
 
-    // Prepare SQL query to fetch all pools
-    let mut stmt = conn
-        .prepare(
-            "SELECT pool_id, token_a, token_b, reserve_a, reserve_b, last_updated
-             FROM pools",
-        )
-        .unwrap();
+    // Prepare SQL query to fetch one page of pools
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}
+         FROM pools p
+         LEFT JOIN pool_overrides o ON o.pool_id = p.pool_id
+         {}
+         ORDER BY p.pool_id{}",
+        POOL_INFO_COLUMNS,
+        if verified_only { "WHERE o.verified = 1" } else { "" },
+        page.sql()
+    ))?;
 
     // Execute query and map results to PoolInfo structs
-    let rows = stmt
-        .query_map([], |row| {
-            Ok(PoolInfo {
-                pool_id: row.get(0)?,
-                token_a: row.get(1)?,
-                token_b: row.get(2)?,
-                reserve_a: row.get(3)?,
-                reserve_b: row.get(4)?,
-                last_updated: row.get(5)?,
-            })
-        })
-        .unwrap();
+    let rows = stmt.query_map([], PoolInfo::from_row)?;
 
-    // Collect all pool data into a vector
+    // Collect all pool data into a vector, with the risk and metadata of their tokens and their volume
+    let prices = price_cache.read()?;
+    let volumes = pool_volumes(&conn, None, now_ms())?;
+    let metadata = token_metadata(&conn, None)?;
     let mut pools = Vec::new();
     for r in rows {
-        pools.push(r.unwrap());
+        pools.push(r?.with_risk(&prices).with_volume(&volumes).with_metadata(&metadata));
+    }
+
+    let mut response = json!({ "status": "ok" });
+    page.finish(&mut pools, &mut response);
+    response["data"] = json!(pools);
+    Ok(Json(response))
+}
+
+/// Fingerprints the pools table, so syncing clients can tell whether their
+/// copy is current without downloading every pool.
+/// 
+/// `checksum` covers each pool's ID, tokens, reserves, `last_updated` and
+/// `version`; `versions` lets a client find which of its pools are behind.
+/// `version` is the table version to pass to `pools_changed_handler`.
+/// 
+/// # Endpoint
+/// `GET /api/pools/checksum`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "checksum": "5d41402abc4b2a76...",
+///     "version": 42,
+///     "pool_count": 2,
+///     "versions": { "0x...a": 3, "0x...b": 1 }
+///   }
+/// }
+/// ```
+async fn pools_checksum_handler(
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let checksum = pools_checksum(&*conn_arc.lock()?)?;
+    Ok(Json(json!({ "status": "ok", "data": checksum })))
+}
+
+/// Lists the pools created or updated after a table version, in the order
+/// they changed, with the same fields as `/api/pools`.
+/// 
+/// Every pool change takes the next table version, so a client keeps the
+/// returned `version` and passes it as `since_version` on its next sync;
+/// `since_version=0` returns every pool. At most `MAX_RESPONSE_ROWS` pools
+/// are returned; when more changed, the response carries `"truncated": true`
+/// and `version` is that of the last pool returned.
+/// 
+/// # Endpoint
+/// `GET /api/pools/changed?since_version=40`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "since_version": 40,
+///   "version": 42,
+///   "data": [
+///     {
+///       "pool_id": "0x...",
+///       "token_a": "0x...",
+///       "token_b": "0x...",
+///       "reserve_a": 1000.0,
+///       "reserve_b": 500.0,
+///       "last_updated": 1751104133893,
+///       "version": 3,
+///       ...
+///     }
+///   ]
+/// }
+/// ```
+async fn pools_changed_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Extension(price_cache): Extension<PriceCache>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let since_version: i64 = match params.get("since_version").map(|v| v.parse::<i64>()) {
+        None => return Err(ApiError::BadRequest("Missing `since_version` query parameter".to_string())),
+        Some(Ok(version)) if version >= 0 => version,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `since_version` must be a non-negative integer".to_string(),
+            ));
+        }
+    };
+    let limit = max_response_rows();
+
+    let conn = conn_arc.lock()?;
+    let table_version: i64 =
+        conn.query_row("SELECT COALESCE(MAX(sync_version), 0) FROM pools", [], |row| row.get(0))?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {}, p.sync_version
+         FROM pools p
+         LEFT JOIN pool_overrides o ON o.pool_id = p.pool_id
+         WHERE p.sync_version > ?1
+         ORDER BY p.sync_version
+         LIMIT {}",
+        POOL_INFO_COLUMNS,
+        limit + 1
+    ))?;
+    let mut rows = stmt
+        .query_map([since_version], |row| Ok((PoolInfo::from_row(row)?, row.get::<_, i64>(11)?)))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let truncated = rows.len() > limit;
+    rows.truncate(limit);
+    let version = match rows.last() {
+        Some((_, sync_version)) if truncated => *sync_version,
+        _ => table_version.max(since_version),
+    };
+
+    let prices = price_cache.read()?;
+    let volumes = pool_volumes(&conn, None, now_ms())?;
+    let metadata = token_metadata(&conn, None)?;
+    let pools: Vec<PoolInfo> = rows
+        .into_iter()
+        .map(|(pool, _)| pool.with_risk(&prices).with_volume(&volumes).with_metadata(&metadata))
+        .collect();
+
+    let mut response = json!({ "status": "ok", "since_version": since_version, "version": version });
+    if truncated {
+        response["truncated"] = json!(true);
+    }
+    response["data"] = json!(pools);
+    Ok(Json(response))
+}
+
+/// Window the pool detail endpoint's 24h stats cover (in milliseconds)
+const POOL_STATS_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Retrieves a single pool with stats derived from its swaps.
+/// 
+/// Returns the same fields as an entry of `/api/pools`, plus the current
+/// price (`null` with a `reason` if a reserve is zero), the last 24 hours'
+/// swap count, the all-time swap count and the time of the most recent swap.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "pool_id": "0x...",
+///     "token_a": "0x...",
+///     "token_b": "0x...",
+///     "reserve_a": 1000.0,
+///     "reserve_b": 500.0,
+///     "last_updated": 1751104133893,
+///     "version": 3,
+///     "display_name": null,
+///     "logo_url": null,
+///     "verified": false,
+///     "warning": null,
+///     "risk_score": 50,
+///     "risk_reasons": [
+///       {
+///         "token": "0x...",
+///         "code": "no_metadata",
+///         "message": "No pool trading this token has curated metadata"
+///       },
+///       {
+///         "token": "0x...",
+///         "code": "new_token",
+///         "message": "First traded 3 hours ago"
+///       }
+///     ],
+///     "volume_a_24h": 1200.0,
+///     "volume_b_24h": 600.0,
+///     "volume_a_7d": 8400.0,
+///     "volume_b_7d": 4200.0,
+///     "token_a_metadata": null,
+///     "token_b_metadata": { "symbol": "SUI", "name": "Sui", "decimals": 9 },
+///     "price": 0.5,
+///     "swap_count": 87,
+///     "swap_count_24h": 12,
+///     "last_swap": 1751104133893
+///   }
+/// }
+/// ```
+async fn pool_detail_handler(
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Extension(price_cache): Extension<PriceCache>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+
+    let pool = conn
+        .query_row(
+            &format!(
+                "SELECT {}
+                 FROM pools p
+                 LEFT JOIN pool_overrides o ON o.pool_id = p.pool_id
+                 WHERE p.pool_id = ?1",
+                POOL_INFO_COLUMNS
+            ),
+            [&pool_id],
+            PoolInfo::from_row,
+        )
+        .optional()?
+        .ok_or_else(|| unknown_pool(&pool_id))?
+        .with_risk(&*price_cache.read()?)
+        .with_volume(&pool_volumes(&conn, Some(&pool_id), now_ms())?);
+    let metadata = token_metadata(&conn, Some(&[&pool.token_a, &pool.token_b]))?;
+    let pool = pool.with_metadata(&metadata);
+
+    let (swap_count, swap_count_24h, last_swap) = conn.query_row(
+        "SELECT COUNT(*),
+                COUNT(CASE WHEN timestamp >= ?2 THEN 1 END),
+                MAX(timestamp)
+         FROM swaps
+         WHERE pool_id = ?1",
+        params![pool_id, now_ms() - POOL_STATS_WINDOW_MS],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let price = spot_price(pool.reserve_a, pool.reserve_b);
+    let detail = PoolDetail {
+        pool,
+        price,
+        reason: price.is_none().then_some(ZERO_LIQUIDITY),
+        swap_count,
+        swap_count_24h,
+        last_swap,
+    };
+    Ok(Json(json!({ "status": "ok", "data": detail })))
+}
+
+/// Reports a pool's swap volume and swap count over the last 24 hours and 7 days.
+/// 
+/// Volume is reported per token: `volume_a_*` sums the token A each swap
+/// moved, sold into the pool or bought out of it, and `volume_b_*` the token
+/// B, so amounts of different tokens are never added up. Swaps of unknown
+/// direction count towards neither. Volume is kept per hour in `pool_stats`
+/// as swaps are indexed, so windows are made of whole hours: the 24h window
+/// is the current hour and the 23 before it.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}/volume`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "data": {
+///     "volume_a_24h": 1200.0,
+///     "volume_b_24h": 600.0,
+///     "volume_a_7d": 8400.0,
+///     "volume_b_7d": 4200.0,
+///     "swaps_24h": 12,
+///     "swaps_7d": 87
+///   }
+/// }
+/// ```
+async fn pool_volume_handler(
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+
+    let volume = pool_volumes(&conn, Some(&pool_id), now_ms())?
+        .remove(&pool_id)
+        .unwrap_or_default();
+    Ok(Json(json!({ "status": "ok", "pool_id": pool_id, "data": volume })))
+}
+
+/// Swaps returned by the swaps endpoint when no `limit` is given
+const SWAPS_DEFAULT_LIMIT: usize = 20;
+
+/// Position in a pool's swap history, encoded in cursors as `{timestamp}_{id}`
+struct SwapCursor {
+    timestamp: i64,
+    id: i64,
+}
+
+impl SwapCursor {
+    fn parse(raw: &str) -> Option<SwapCursor> {
+        let (timestamp, id) = raw.split_once('_')?;
+        Some(SwapCursor { timestamp: timestamp.parse().ok()?, id: id.parse().ok()? })
     }
 
-    Json(json!({ "status": "ok", "data": pools }))
+    fn encode(&self) -> String {
+        format!("{}_{}", self.timestamp, self.id)
+    }
 }
 
-/// Retrieves recent swap history for a specific pool.
+/// Retrieves swap history for a specific pool, one page at a time.
+/// 
+/// Swaps are ordered by timestamp in descending order (most recent first).
+/// Without a cursor the most recent `limit` swaps are returned. Pass
+/// `next_cursor` back as `before` to page towards older swaps, or
+/// `prev_cursor` as `after` to fetch swaps indexed since the page was read.
+/// Cursors are keyed on the swap itself rather than an offset, so swaps
+/// arriving between requests never shift a page. `total` counts every swap
+/// matching the filters.
 /// 
-/// Returns the last 20 swap transactions for the specified pool, ordered by
-/// timestamp in descending order (most recent first).
+/// `min_value`/`max_value` keep only swaps whose notional value lies in the
+/// range, e.g. to pull institutional-size trades without the full history.
+/// Swaps are valued when the price refresher next runs after they are
+/// indexed; swaps it has not valued yet never match a value filter.
+/// 
+/// `inconsistent` marks swaps whose reserves before the swap match no
+/// snapshot of the pool; their pool was then reconciled with its on-chain
+/// state.
+/// 
+/// `direction` tells which token the trader sold (`a_to_b` sold token A for
+/// token B), worked out from the pool's snapshots before the swap, or is
+/// `unknown` if none tells. `sender` is the trader's address when the swap
+/// event carried one. `sender_label` is the label admins imported for that
+/// address (see `import_labels_handler`), `null` for unlabeled senders.
+/// 
+/// An unknown pool answers 404.
 /// 
 /// # Endpoint
-/// `GET /api/swaps/{pool_id}`
+/// `GET /api/swaps/{pool_id}?epoch=412&limit=20&before=1751104259632_812`
 /// 
 /// # Parameters
 /// * `pool_id` - The unique identifier of the liquidity pool
+/// * `epoch` - Only swaps executed in this Sui epoch (optional)
+/// * `min_value` - Only swaps worth at least this much (optional)
+/// * `max_value` - Only swaps worth at most this much (optional)
+/// * `value_in` - Denomination of the value filters: `quote` (default) or `usd`
+/// * `limit` - Swaps per page (default 20, at most `MAX_RESPONSE_ROWS`)
+/// * `before` - Only swaps older than this cursor
+/// * `after` - Only swaps newer than this cursor, the oldest of them first
+///   to fill the page; cannot be combined with `before`
 /// 
 /// # Response Format
 /// ```json
 /// {
 ///   "status": "ok",
+///   "total": 1342,
+///   "next_cursor": "1751104259632_812",
+///   "prev_cursor": "1751104533201_831",
 ///   "data": [
 ///     {
 ///       "pool_id": "0x...",
 ///       "amount_in": 100.0,
 ///       "amount_out": 50.0,
-///       "timestamp": 1751104259632
+///       "timestamp": 1751104259632,
+///       "epoch": 412,
+///       "checkpoint": 10834211,
+///       "value_quote": 312.5,
+///       "value_usd": 312.5,
+///       "inconsistent": false,
+///       "sender": "0x...",
+///       "sender_label": { "label": "Acme Market Making", "category": "market_maker" },
+///       "direction": "a_to_b"
 ///     }
 ///   ]
 /// }
 /// ```
 async fn swaps_handler(
     Path(pool_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
     Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
-) -> Json<serde_json::Value> {
-    let conn = conn_arc.lock().unwrap();
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let epoch = match params.get("epoch").map(|e| e.parse::<i64>()) {
+        None => None,
+        Some(Ok(epoch)) => Some(epoch),
+        Some(Err(_)) => {
+            return Err(ApiError::BadRequest("Query parameter `epoch` must be an integer".to_string()));
+        }
+    };
+    let value = |name: &str| match params.get(name).map(|v| v.parse::<f64>()) {
+        None => Ok(None),
+        Some(Ok(value)) if value.is_finite() && value >= 0.0 => Ok(Some(value)),
+        Some(_) => Err(format!("Query parameter `{}` must be a non-negative number", name)),
+    };
+    let (min_value, max_value) = match (value("min_value"), value("max_value")) {
+        (Ok(Some(min)), Ok(Some(max))) if min > max => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `min_value` cannot exceed `max_value`".to_string(),
+            ));
+        }
+        (Ok(min), Ok(max)) => (min, max),
+        (Err(message), _) | (_, Err(message)) => return Err(ApiError::BadRequest(message)),
+    };
+    let value_column = match params.get("value_in").map(String::as_str) {
+        None | Some("quote") => "value_quote",
+        Some("usd") => "value_usd",
+        Some(_) => {
+            return Err(ApiError::BadRequest("Query parameter `value_in` must be `quote` or `usd`".to_string()));
+        }
+    };
+    let limit = match params.get("limit").map(|l| l.parse::<usize>()) {
+        None => SWAPS_DEFAULT_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(max_response_rows()),
+        Some(_) => {
+            return Err(ApiError::BadRequest("Query parameter `limit` must be a positive integer".to_string()));
+        }
+    };
+    let cursor = |name: &str| match params.get(name) {
+        None => Ok(None),
+        Some(raw) => SwapCursor::parse(raw)
+            .map(Some)
+            .ok_or_else(|| format!("Query parameter `{}` is not a valid cursor", name)),
+    };
+    let (before, after) = match (cursor("before"), cursor("after")) {
+        (Ok(Some(_)), Ok(Some(_))) => {
+            return Err(ApiError::BadRequest(
+                "Query parameters `before` and `after` cannot be combined".to_string(),
+            ));
+        }
+        (Ok(before), Ok(after)) => (before, after),
+        (Err(message), _) | (_, Err(message)) => return Err(ApiError::BadRequest(message)),
+    };
 
-    // Prepare SQL query to fetch recent swaps for the specified pool
-    let mut stmt = conn
-        .prepare(
-            "SELECT amount_in, amount_out, timestamp
-             FROM swaps
-             WHERE pool_id = ?1
-             ORDER BY timestamp DESC
-             LIMIT 20",
-        )
-        .unwrap();
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+
+    // Filters shared by the count and the page, on ?1 to ?4
+    let filters = format!(
+        "pool_id = ?1 AND (?2 IS NULL OR epoch = ?2)
+         AND (?3 IS NULL OR {value} >= ?3) AND (?4 IS NULL OR {value} <= ?4)",
+        value = value_column
+    );
+    let total: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) FROM swaps WHERE {}", filters),
+        params![pool_id, epoch, min_value, max_value],
+        |row| row.get(0),
+    )?;
+
+    // Read one row past the page to tell whether older swaps remain. Pages
+    // after a cursor are read upwards from it and reversed.
+    let (bound, order, position) = match (&before, &after) {
+        (_, Some(after)) => ("(timestamp > ?5 OR (timestamp = ?5 AND id > ?6))", "ASC", Some(after)),
+        (Some(before), None) => ("(timestamp < ?5 OR (timestamp = ?5 AND id < ?6))", "DESC", Some(before)),
+        (None, None) => ("1", "DESC", None),
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, amount_in, amount_out, timestamp, epoch, checkpoint, value_quote, value_usd, inconsistent,
+                sender, direction, l.label, l.category
+         FROM swaps
+         LEFT JOIN labels l ON l.address = swaps.sender
+         WHERE {filters} AND {bound}
+         ORDER BY timestamp {order}, id {order}
+         LIMIT ?7",
+        filters = filters,
+        bound = bound,
+        order = order
+    ))?;
 
     // Execute query and map results to SwapInfo structs
     let rows = stmt
-        .query_map([pool_id.clone()], |row| {
-            Ok(SwapInfo {
-                pool_id: pool_id.clone(),
-                amount_in: row.get(0)?,
-                amount_out: row.get(1)?,
-                timestamp: row.get(2)?,
-            })
-        })
-        .unwrap();
+        .query_map(
+            params![
+                pool_id,
+                epoch,
+                min_value,
+                max_value,
+                position.map(|p| p.timestamp),
+                position.map(|p| p.id),
+                limit as i64 + 1
+            ],
+            |row| {
+                let cursor = SwapCursor { timestamp: row.get(3)?, id: row.get(0)? };
+                let sender_label = match row.get::<_, Option<String>>(11)? {
+                    Some(label) => Some(SenderLabel { label, category: row.get(12)? }),
+                    None => None,
+                };
+                let swap = SwapInfo {
+                    pool_id: pool_id.clone(),
+                    amount_in: row.get(1)?,
+                    amount_out: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    epoch: row.get(4)?,
+                    checkpoint: row.get(5)?,
+                    value_quote: row.get(6)?,
+                    value_usd: row.get(7)?,
+                    inconsistent: row.get(8)?,
+                    sender: row.get(9)?,
+                    sender_label,
+                    direction: row.get(10)?,
+                };
+                Ok((cursor, swap))
+            },
+        )?;
 
     // Collect all swap data into a vector
     let mut swaps = Vec::new();
     for s in rows {
-        swaps.push(s.unwrap());
+        swaps.push(s?);
     }
+    let more = swaps.len() > limit;
+    swaps.truncate(limit);
+    if after.is_some() {
+        swaps.reverse();
+    }
+
+    // Older swaps remain past the page when it was cut short, and always
+    // when it was read after a cursor, which is itself older
+    let next_cursor = match swaps.last() {
+        Some((cursor, _)) if more || after.is_some() => Some(cursor.encode()),
+        _ => None,
+    };
+    let prev_cursor = match (swaps.first(), &after) {
+        (Some((cursor, _)), _) => Some(cursor.encode()),
+        (None, Some(after)) => Some(after.encode()),
+        (None, None) => None,
+    };
+    let data: Vec<SwapInfo> = swaps.into_iter().map(|(_, swap)| swap).collect();
 
-    Json(json!({ "status": "ok", "data": swaps }))
+    Ok(Json(json!({
+        "status": "ok",
+        "total": total,
+        "next_cursor": next_cursor,
+        "prev_cursor": prev_cursor,
+        "data": data
+    })))
 }
 
-/// Calculates the current price for a token pair based on pool reserves.
+/// Pool, interval and time range of a candle query
+struct CandleRange<'a> {
+    pool_id: &'a str,
+    interval: &'a str,
+    /// Width of a bucket in milliseconds
+    width: i64,
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Reads the `pool_id`, `interval`, `from` and `to` query parameters of the
+/// candle endpoints.
+fn candle_range(params: &HashMap<String, String>) -> Result<CandleRange<'_>, ApiError> {
+    let Some(pool_id) = params.get("pool_id") else {
+        return Err(ApiError::BadRequest("Missing `pool_id` query parameter".to_string()));
+    };
+    let interval = params.get("interval").map(String::as_str).unwrap_or_default();
+    let Some(width) = interval_ms(interval) else {
+        return Err(ApiError::BadRequest(
+            "Query parameter `interval` must be one of 1m, 5m, 1h, 1d".to_string(),
+        ));
+    };
+    let time = |name: &str| match params.get(name).map(|t| t.parse::<i64>()) {
+        None => Ok(None),
+        Some(Ok(t)) => Ok(Some(t)),
+        Some(Err(_)) => Err(format!("Query parameter `{}` must be a timestamp in milliseconds", name)),
+    };
+    match (time("from"), time("to")) {
+        (Ok(from), Ok(to)) => Ok(CandleRange { pool_id, interval, width, from, to }),
+        (Err(message), _) | (_, Err(message)) => Err(ApiError::BadRequest(message)),
+    }
+}
+
+/// Retrieves OHLCV candles for a pool, oldest first.
 /// 
-/// Uses the constant product formula (x * y = k) to calculate the price
-/// of token B in terms of token A from the current pool reserves.
+/// Candles are rolled up from swaps by the candle aggregator every
+/// `CANDLE_AGGREGATION_INTERVAL_SECS`. Buckets in the range holding swaps
+/// it hasn't reached yet are rolled up on request first (see
+/// `candles::backfill_candles`), so the series is complete up to the last
+/// indexed swap. Buckets without swaps have no candle. At most
+/// `MAX_RESPONSE_ROWS` candles are returned per request; when more exist the
+/// response carries `"truncated": true` and a `next_cursor` to pass back as
+/// `cursor`.
+/// 
+/// With `filtered=true`, candles are computed from the swaps directly,
+/// leaving out wash trades (see `wash::wash_trade_condition`), and the
+/// response carries `"filtered": true`. The raw series stays the default.
 /// 
 /// # Endpoint
-/// `GET /api/price?pair=TOKENA/TOKENB`
+/// `GET /api/candles?pool_id=0x...&interval=1h&from=1751068800000&to=1751155200000&filtered=true`
 /// 
-/// # Query Parameters
-/// * `pair` - Token pair in format "TOKENA/TOKENB" (e.g., "USDC/SUI")
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// * `interval` - `1m`, `5m`, `1h` or `1d`
+/// * `from` - Only buckets starting at or after this time (ms, optional)
+/// * `to` - Only buckets starting before this time (ms, optional)
+/// * `filtered` - `true` to leave out wash trades (optional, default `false`)
 /// 
 /// # Response Format
 /// ```json
 /// {
 ///   "status": "ok",
-///   "pair": "USDC/SUI",
 ///   "pool_id": "0x...",
-///   "price": 0.5
+///   "interval": "1h",
+///   "data": [
+///     {
+///       "bucket_start": 1751101200000,
+///       "open": 0.5,
+///       "high": 0.52,
+///       "low": 0.49,
+///       "close": 0.51,
+///       "volume_a": 1200.0,
+///       "volume_b": 604.0,
+///       "trades": 14
+///     }
+///   ]
 /// }
 /// ```
-async fn price_handler(
+async fn candles_handler(
     Query(params): Query<HashMap<String, String>>,
     Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
-) -> Json<serde_json::Value> {
-    let conn = conn_arc.lock().unwrap();
-
-    // Extract and validate the pair parameter
-    let pair = match params.get("pair") {
-        Some(p) => p.clone(),
-        None => {
-            return Json(json!({
-                "status": "error",
-                "message": "Missing `pair` query parameter"
-            }));
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let CandleRange { pool_id, interval, width, from, to } = candle_range(&params)?;
+    let filtered = match params.get("filtered").map(String::as_str) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `filtered` must be true or false".to_string(),
+            ));
         }
     };
+    let page = Page::from_params(&params)?;
 
-    // Parse token symbols from the pair string
-    let tokens: Vec<&str> = pair.split('/').collect();
-    if tokens.len() != 2 {
-        return Json(json!({
-            "status": "error",
-            "message": "Query parameter `pair` must be in the form TOKENA/TOKENB"
-        }));
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, pool_id)?;
+
+    if filtered {
+        let mut candles = filtered_candles(&conn, pool_id, width, from, to, &page.sql())?;
+        let mut response = json!({ "status": "ok", "pool_id": pool_id, "interval": interval, "filtered": true });
+        page.finish(&mut candles, &mut response);
+        response["data"] = json!(candles);
+        return Ok(Json(response));
     }
-    let (token_a, token_b) = (tokens[0], tokens[1]);
 
-    // Query database for the specified token pair
-    let mut stmt = conn
-        .prepare(
-            "SELECT pool_id, reserve_a, reserve_b
-             FROM pools
-             WHERE token_a = ?1 AND token_b = ?2
-             LIMIT 1",
-        )
-        .unwrap();
+    backfill_candles(&conn, pool_id, interval, from, to)?;
+    let mut candles = rolled_up_candles(&conn, pool_id, interval, from, to, &page.sql())?;
 
-    let mut rows = stmt
-        .query_map([token_a, token_b], |row| {
-            Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?, row.get::<_, f64>(2)?))
-        })
-        .unwrap();
+    let mut response = json!({ "status": "ok", "pool_id": pool_id, "interval": interval });
+    page.finish(&mut candles, &mut response);
+    response["data"] = json!(candles);
+    Ok(Json(response))
+}
 
-    // Calculate price from reserves if pool exists
-    if let Some(Ok((pool_id, reserve_a, reserve_b))) = rows.next() {
-        let price = if reserve_a > 0.0 {
-            reserve_b / reserve_a
-        } else {
-            0.0
-        };
-        Json(json!({
-            "status": "ok",
-            "pair": pair,
-            "pool_id": pool_id,
-            "price": price
-        }))
-    } else {
-        Json(json!({
-            "status": "error",
-            "message": format!("No pool found for {}", pair)
-        }))
+/// Most series a candle batch request may ask for
+const MAX_CANDLE_BATCH_SERIES: usize = 50;
+
+/// One series of a candle batch request
+#[derive(Deserialize)]
+struct CandleSeries {
+    pool_id: String,
+    interval: String,
+    from: Option<i64>,
+    to: Option<i64>,
+}
+
+/// Retrieves several candle series in one request, for dashboards with many
+/// charts.
+/// 
+/// Each series is read like `/api/candles` without `filtered` or `cursor`:
+/// at most `MAX_RESPONSE_ROWS` candles, and when more exist the series
+/// carries `"truncated": true` and the `next_from` to pass as its `from` in
+/// a later request. Series are returned in the order requested. At most
+/// `MAX_CANDLE_BATCH_SERIES` series may be requested; an invalid series
+/// fails the whole request with 400, and one for an unknown pool with 404.
+/// 
+/// # Endpoint
+/// `POST /api/candles/batch` with body
+/// `[{"pool_id": "0x...", "interval": "1h", "from": 1751068800000, "to": 1751155200000}, {"pool_id": "0x...", "interval": "1d"}]`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "pool_id": "0x...",
+///       "interval": "1h",
+///       "data": [
+///         {
+///           "bucket_start": 1751101200000,
+///           "open": 0.5,
+///           "high": 0.52,
+///           "low": 0.49,
+///           "close": 0.51,
+///           "volume_a": 1200.0,
+///           "volume_b": 604.0,
+///           "trades": 14
+///         }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+async fn candles_batch_handler(
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(series): Json<Vec<CandleSeries>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if series.is_empty() || series.len() > MAX_CANDLE_BATCH_SERIES {
+        return Err(ApiError::BadRequest(format!(
+            "Request body must list between 1 and {} series",
+            MAX_CANDLE_BATCH_SERIES
+        )));
+    }
+    if let Some(index) = series.iter().position(|s| interval_ms(&s.interval).is_none()) {
+        return Err(ApiError::BadRequest(format!(
+            "Series {}: `interval` must be one of 1m, 5m, 1h, 1d",
+            index
+        )));
+    }
+
+    let limit = max_response_rows();
+    let conn = conn_arc.lock()?;
+    let mut data = Vec::with_capacity(series.len());
+    for CandleSeries { pool_id, interval, from, to } in series {
+        require_pool(&conn, &pool_id)?;
+        backfill_candles(&conn, &pool_id, &interval, from, to)?;
+        let mut candles = rolled_up_candles(&conn, &pool_id, &interval, from, to, &format!(" LIMIT {}", limit + 1))?;
+        let mut entry = json!({ "pool_id": pool_id, "interval": interval });
+        if candles.len() > limit {
+            entry["truncated"] = json!(true);
+            entry["next_from"] = json!(candles[limit].bucket_start);
+            candles.truncate(limit);
+        }
+        entry["data"] = json!(candles);
+        data.push(entry);
     }
+    Ok(Json(json!({ "status": "ok", "data": data })))
 }
 
-/// Creates and returns the API router with all DEX endpoints.
+/// Downloads a pool's candles over an arbitrarily large range as NDJSON.
 /// 
-/// This function configures all the HTTP routes for the DEX API,
-/// including pools, swaps, and price calculation endpoints.
+/// Unlike `/api/candles`, the range isn't row-limited: it is read in
+/// chunks and streamed as it is read (see `candles::download_candles`),
+/// one candle per line, oldest first. At most `max_points` candles are
+/// written; the last line summarizes the download, and when the range held
+/// more candles than that carries `"truncated": true` and the `next_from`
+/// to pass back as `from`.
 /// 
-/// # Returns
-/// * `Router` - Axum router configured with all API routes
-pub fn api_routes() -> Router {
-    Router::new()
-        .route("/pools", get(pools_handler))
-        .route("/swaps/:pool_id", get(swaps_handler))
-        .route("/price", get(price_handler))
+/// # Endpoint
+/// `GET /api/candles/download?pool_id=0x...&interval=1m&from=1735689600000&to=1751155200000&max_points=500000`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// * `interval` - `1m`, `5m`, `1h` or `1d`
+/// * `from` - Only buckets starting at or after this time (ms, optional)
+/// * `to` - Only buckets starting before this time (ms, optional)
+/// * `max_points` - Most candles to write (optional, default 100000, at most 1000000)
+/// 
+/// # Response Format
+/// `application/x-ndjson`:
+/// ```text
+/// {"bucket_start":1735689600000,"open":0.5,"high":0.52,"low":0.49,"close":0.51,"volume_a":1200.0,"volume_b":604.0,"trades":14}
+/// {"done":true,"points":1,"truncated":false,"next_from":null}
+/// ```
+async fn candles_download_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Response, ApiError> {
+    let CandleRange { pool_id, interval, from, to, .. } = candle_range(&params)?;
+    let max_points = match params.get("max_points").map(|n| n.parse::<usize>()) {
+        None => DEFAULT_DOWNLOAD_MAX_POINTS,
+        Some(Ok(n)) if (1..=MAX_DOWNLOAD_MAX_POINTS).contains(&n) => n,
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "Query parameter `max_points` must be between 1 and {}",
+                MAX_DOWNLOAD_MAX_POINTS
+            )));
+        }
+    };
+    require_pool(&*conn_arc.lock()?, pool_id)?;
+
+    let download = CandleDownload {
+        pool_id: pool_id.to_string(),
+        interval: interval.to_string(),
+        from,
+        to,
+        max_points,
+    };
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::new(download_candles(conn_arc, download)),
+    )
+        .into_response())
+}
+
+/// Window the ticker's change, range and volume cover (in milliseconds)
+const TICKER_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Candle interval the ticker is computed from
+const TICKER_CANDLE_INTERVAL: &str = "5m";
+
+/// Compact 24h summary of a pair, as served by the ticker endpoint
+#[derive(Serialize)]
+struct Ticker {
+    pair: String,
+    /// Alias registered for the pair, `null` if none
+    alias: Option<String>,
+    pool_id: String,
+    /// Price of the latest trade, `null` if the pool never traded
+    price: Option<f64>,
+    open_24h: Option<f64>,
+    high_24h: Option<f64>,
+    low_24h: Option<f64>,
+    /// Relative change from `open_24h` to `price`
+    change_24h: Option<f64>,
+    /// Amount of the pair's first token traded
+    volume_24h: f64,
+    trades_24h: i64,
+    last_trade: Option<i64>,
+}
+
+/// Returns a CEX-style 24h ticker for a token pair.
+/// 
+/// Computed from the pool's 5-minute candles rather than raw swaps, so it
+/// stays cheap for bots polling it frequently. Prices are trade prices in
+/// the pair's second token per its first; the window covers the 5-minute
+/// buckets of the last 24 hours, and `open_24h`, `high_24h`, `low_24h` and
+/// `change_24h` are `null` when the pool did not trade in it. For a pair
+/// given in the reverse of the pool's order, prices are inverted (the high
+/// being the inverse of the candles' low). `volume_24h` is in the pair's
+/// first token, so a reversed pair reads the candles' token B volume. Like
+/// candles, the ticker trails the swap history by up to
+/// `CANDLE_AGGREGATION_INTERVAL_SECS`.
+/// 
+/// # Endpoint
+/// `GET /api/ticker?pair=TOKENA/TOKENB` (or `pair=ALIAS`)
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "pair": "0x...::usdc::USDC/0x2::sui::SUI",
+///     "alias": "USDC-SUI",
+///     "pool_id": "0x...",
+///     "price": 0.51,
+///     "open_24h": 0.5,
+///     "high_24h": 0.52,
+///     "low_24h": 0.49,
+///     "change_24h": 0.02,
+///     "volume_24h": 18200.0,
+///     "trades_24h": 231,
+///     "last_trade": 1751104536847
+///   }
+/// }
+/// ```
+async fn ticker_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    pool_cache: Option<Extension<PoolCache>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let PairParam { pair, token_a, token_b, alias } = parse_pair(&*conn_arc.lock()?, &params)?;
+    let Some(pool) = lookup_pair_price(&conn_arc, pool_cache.as_deref(), &token_a, &token_b)? else {
+        return Err(unknown_pair(&pair));
+    };
+
+    let conn = conn_arc.lock()?;
+    let (open, high, low, volume_a, volume_b, trades_24h): (Option<f64>, Option<f64>, Option<f64>, f64, f64, i64) =
+        conn.query_row(
+            "SELECT (SELECT open FROM candles
+                     WHERE pool_id = ?1 AND interval = ?2 AND bucket_start >= ?3
+                     ORDER BY bucket_start LIMIT 1),
+                    MAX(high), MIN(low), COALESCE(SUM(volume_a), 0.0), COALESCE(SUM(volume_b), 0.0),
+                    COALESCE(SUM(trades), 0)
+             FROM candles
+             WHERE pool_id = ?1 AND interval = ?2 AND bucket_start >= ?3",
+            params![pool.pool_id, TICKER_CANDLE_INTERVAL, now_ms() - TICKER_WINDOW_MS],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?, row.get(5)?)),
+        )?;
+    let (close, last_trade): (Option<f64>, _) = conn.query_row(
+        "SELECT (SELECT close FROM candles WHERE pool_id = ?1 AND interval = ?2
+                 ORDER BY bucket_start DESC LIMIT 1),
+                (SELECT MAX(timestamp) FROM swaps WHERE pool_id = ?1)",
+        params![pool.pool_id, TICKER_CANDLE_INTERVAL],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+
+    // Candles are in the pool's own order; a reversed pair reads them upside down
+    let invert = |price: Option<f64>| price.filter(|p| *p > 0.0).map(|p| 1.0 / p);
+    let (price, open_24h, high_24h, low_24h, volume_24h) = if pool.inverted {
+        (invert(close), invert(open), invert(low), invert(high), volume_b)
+    } else {
+        (close, open, high, low, volume_a)
+    };
+
+    let ticker = Ticker {
+        pair,
+        alias,
+        pool_id: pool.pool_id,
+        price,
+        open_24h,
+        high_24h,
+        low_24h,
+        change_24h: price.zip(open_24h).filter(|(_, open)| *open > 0.0).map(|(price, open)| price / open - 1.0),
+        volume_24h,
+        trades_24h,
+        last_trade,
+    };
+    Ok(Json(json!({ "status": "ok", "data": ticker })))
+}
+
+/// Windows the movers endpoint diffs over, as (name, length in milliseconds)
+const MOVER_WINDOWS: [(&str, i64); 2] = [("1h", 60 * 60 * 1000), ("24h", 24 * 60 * 60 * 1000)];
+
+/// Default number of gainers and of losers the movers endpoint returns
+const DEFAULT_MOVERS_LIMIT: usize = 10;
+
+/// A pool's price and TVL now and at the start of a window
+#[derive(Serialize)]
+struct Mover {
+    pool_id: String,
+    token_a: String,
+    token_b: String,
+    /// Spot price in token B per token A
+    price: Option<f64>,
+    price_before: Option<f64>,
+    /// Relative change from `price_before` to `price`
+    price_change: Option<f64>,
+    /// Total value locked, in token B (twice reserve B)
+    tvl: f64,
+    tvl_before: f64,
+    /// Relative change from `tvl_before` to `tvl`
+    tvl_change: Option<f64>,
+}
+
+/// Relative change from `before` to `now`, `None` unless both are positive.
+fn relative_change(now: Option<f64>, before: Option<f64>) -> Option<f64> {
+    now.zip(before)
+        .filter(|(now, before)| *now > 0.0 && *before > 0.0)
+        .map(|(now, before)| now / before - 1.0)
+}
+
+/// Returns the pools whose price or TVL moved most over a window.
+/// 
+/// Each pool's current reserves are diffed against its latest snapshot
+/// taken before the window started, so pools created during the window are
+/// left out. Pools are ranked by `price_change`, or by `tvl_change` with
+/// `by=tvl`: `gainers` lists the largest rises first and `losers` the
+/// largest falls first; pools that didn't move are in neither. TVL is
+/// measured in token B, so it moves with the pool's reserves only.
+/// 
+/// # Endpoint
+/// `GET /api/movers?window=24h&by=price&limit=10`
+/// 
+/// # Query Parameters
+/// * `window` (optional) - `1h` or `24h` (default)
+/// * `by` (optional) - Rank by `price` (default) or `tvl` change
+/// * `limit` (optional) - Number of gainers and of losers (default 10)
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "window": "24h",
+///   "by": "price",
+///   "data": {
+///     "gainers": [
+///       {
+///         "pool_id": "0x...",
+///         "token_a": "0x...",
+///         "token_b": "0x2::sui::SUI",
+///         "price": 0.55,
+///         "price_before": 0.5,
+///         "price_change": 0.1,
+///         "tvl": 2100.0,
+///         "tvl_before": 2000.0,
+///         "tvl_change": 0.05
+///       }
+///     ],
+///     "losers": []
+///   }
+/// }
+/// ```
+async fn movers_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let window = params.get("window").map_or("24h", String::as_str);
+    let Some(&(window, window_ms)) = MOVER_WINDOWS.iter().find(|(name, _)| *name == window) else {
+        return Err(ApiError::BadRequest("Query parameter `window` must be `1h` or `24h`".to_string()));
+    };
+    let by_tvl = match params.get("by").map(String::as_str) {
+        None | Some("price") => false,
+        Some("tvl") => true,
+        Some(_) => return Err(ApiError::BadRequest("Query parameter `by` must be `price` or `tvl`".to_string())),
+    };
+    let limit = match params.get("limit").map(|l| l.parse::<usize>()) {
+        None => DEFAULT_MOVERS_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(max_response_rows()),
+        Some(_) => {
+            return Err(ApiError::BadRequest("Query parameter `limit` must be a positive integer".to_string()));
+        }
+    };
+
+    let conn = conn_arc.lock()?;
+    let movers = conn
+        .prepare(
+            "SELECT p.pool_id, p.token_a, p.token_b, p.reserve_a, p.reserve_b, s.reserve_a, s.reserve_b
+             FROM pools p
+             JOIN pool_snapshots s ON s.id = (
+                 SELECT id FROM pool_snapshots
+                 WHERE pool_id = p.pool_id AND timestamp <= ?1
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT 1
+             )",
+        )
+        .and_then(|mut stmt| {
+            stmt.query_map(params![now_ms() - window_ms], |row| {
+                let (reserve_a, reserve_b): (f64, f64) = (row.get(3)?, row.get(4)?);
+                let (reserve_a_before, reserve_b_before): (f64, f64) = (row.get(5)?, row.get(6)?);
+                let price = spot_price(reserve_a, reserve_b);
+                let price_before = spot_price(reserve_a_before, reserve_b_before);
+                Ok(Mover {
+                    pool_id: row.get(0)?,
+                    token_a: row.get(1)?,
+                    token_b: row.get(2)?,
+                    price,
+                    price_before,
+                    price_change: relative_change(price, price_before),
+                    tvl: 2.0 * reserve_b,
+                    tvl_before: 2.0 * reserve_b_before,
+                    tvl_change: relative_change(Some(reserve_b), Some(reserve_b_before)),
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()
+        })?;
+    drop(conn);
+
+    let ranked: Vec<(f64, Mover)> = movers
+        .into_iter()
+        .filter_map(|mover| {
+            let change = if by_tvl { mover.tvl_change } else { mover.price_change };
+            change.map(|change| (change, mover))
+        })
+        .collect();
+    let (mut gainers, mut losers): (Vec<_>, Vec<_>) =
+        ranked.into_iter().filter(|(change, _)| *change != 0.0).partition(|(change, _)| *change > 0.0);
+    gainers.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    losers.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    let top = |movers: Vec<(f64, Mover)>| movers.into_iter().take(limit).map(|(_, mover)| mover).collect::<Vec<_>>();
+    let (gainers, losers) = (top(gainers), top(losers));
+
+    Ok(Json(json!({
+        "status": "ok",
+        "window": window,
+        "by": if by_tvl { "tvl" } else { "price" },
+        "data": { "gainers": gainers, "losers": losers }
+    })))
+}
+
+/// A token pair named by a `pair` query parameter
+struct PairParam {
+    /// The full pair, `TOKENA/TOKENB`
+    pair: String,
+    token_a: String,
+    token_b: String,
+    /// Alias registered for the pair in this order, if any
+    alias: Option<String>,
+}
+
+/// Reads the `pair` query parameter and splits it into its two tokens.
+/// 
+/// The parameter may also be a pair alias registered by an admin (e.g.
+/// `SUI-USDC`, matched case-insensitively), which takes precedence over
+/// reading it as a single token. A single token is priced against the
+/// canonical quote token.
+/// 
+/// # Returns
+/// * `Result<PairParam, ApiError>` - The full pair, its two tokens and its alias, or a bad request if missing or malformed
+fn parse_pair(conn: &Connection, params: &HashMap<String, String>) -> Result<PairParam, ApiError> {
+    let Some(pair) = params.get("pair") else {
+        return Err(ApiError::BadRequest("Missing `pair` query parameter".to_string()));
+    };
+    if !pair.contains('/') {
+        if let Some(entry) = find_pair_alias(conn, pair)? {
+            return Ok(PairParam {
+                pair: format!("{}/{}", entry.token_a, entry.token_b),
+                token_a: entry.token_a,
+                token_b: entry.token_b,
+                alias: Some(entry.alias),
+            });
+        }
+    }
+    let tokens: Vec<&str> = pair.split('/').collect();
+    let (pair, token_a, token_b) = match tokens[..] {
+        [token_a, token_b] if !token_a.is_empty() && !token_b.is_empty() => {
+            (pair.to_string(), token_a.to_string(), token_b.to_string())
+        }
+        [token] if !token.is_empty() => {
+            let quote = quote_token();
+            (format!("{}/{}", token, quote), token.to_string(), quote)
+        }
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `pair` must be in the form TOKENA/TOKENB or TOKEN, or be a pair alias".to_string(),
+            ))
+        }
+    };
+    let alias = alias_for_pair(conn, &token_a, &token_b)?;
+    Ok(PairParam { pair, token_a, token_b, alias })
+}
+
+/// Error for a token pair no pool trades.
+fn unknown_pair(pair: &str) -> ApiError {
+    ApiError::NotFound(format!("No pool found for {}", pair))
+}
+
+/// Calculates the current price for a token pair based on pool reserves.
+/// 
+/// Uses the constant product formula (x * y = k) to calculate the price
+/// of token B in terms of token A from the current pool reserves. A pool
+/// with a zero reserve has no price: `price` is `null` and `reason` is
+/// `zero_liquidity`. Pools with liquidity are preferred when several trade
+/// the pair.
+/// 
+/// The pair may be given in either order. If the pool stores it the other
+/// way round, its price is inverted so `price` is always in the requested
+/// order; `inverted` is then `true` and `canonical_pair` gives the pool's
+/// own order.
+/// 
+/// `price` is in raw units of the coins. Once the indexer has fetched both
+/// tokens' metadata, `normalized_price` scales it by their decimals to whole
+/// units; it is `null` until then.
+/// 
+/// # Endpoint
+/// `GET /api/price?pair=TOKENA/TOKENB`
+/// 
+/// # Query Parameters
+/// * `pair` - Token pair in format "TOKENA/TOKENB" (e.g., "USDC/SUI"), a
+///   single token to price it against the canonical quote token, or a pair
+///   alias registered by an admin (e.g., "USDC-SUI")
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pair": "USDC/SUI",
+///   "alias": "USDC-SUI",
+///   "pool_id": "0x...",
+///   "canonical_pair": "USDC/SUI",
+///   "inverted": false,
+///   "price": 0.5,
+///   "version": 3,
+///   "normalized_price": 0.0005,
+///   "token_a_metadata": { "symbol": "USDC", "name": "USD Coin", "decimals": 6 },
+///   "token_b_metadata": { "symbol": "SUI", "name": "Sui", "decimals": 9 }
+/// }
+/// ```
+async fn price_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    pool_cache: Option<Extension<PoolCache>>,
+    reference_prices: Option<Extension<ReferencePrices>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    // Extract and parse the token symbols of the pair parameter
+    let PairParam { pair, token_a, token_b, alias } = parse_pair(&*conn_arc.lock()?, &params)?;
+
+    // Calculate price from reserves if pool exists
+    let Some(quote) = lookup_pair_price(&conn_arc, pool_cache.as_deref(), &token_a, &token_b)? else {
+        return Err(unknown_pair(&pair));
+    };
+    let mut response = json!({
+        "status": "ok",
+        "pair": pair,
+        "alias": alias,
+        "pool_id": quote.pool_id,
+        "canonical_pair": quote.canonical_pair,
+        "inverted": quote.inverted,
+        "price": quote.price,
+        "version": quote.version
+    });
+    add_price_context(&mut response, reference_prices.as_deref(), &pair, quote.price);
+
+    // Scale the price by the tokens' decimals, once both are known
+    let metadata = token_metadata(&*conn_arc.lock()?, Some(&[&token_a, &token_b]))?;
+    let (metadata_a, metadata_b) = (metadata.get(&token_a), metadata.get(&token_b));
+    response["normalized_price"] = match (quote.price, metadata_a, metadata_b) {
+        (Some(price), Some(a), Some(b)) => json!(price * 10f64.powi(a.decimals as i32 - b.decimals as i32)),
+        _ => json!(null),
+    };
+    response["token_a_metadata"] = json!(metadata_a);
+    response["token_b_metadata"] = json!(metadata_b);
+    Ok(Json(response))
+}
+
+/// Adds the comparison with the pair's external reference price to a price
+/// response, when the pair is checked (see `reference::run_reference_checker`),
+/// or the reason the pool has no price.
+/// 
+/// A pool price further from the reference than `PRICE_DEVIATION_THRESHOLD`
+/// also gets a `warning`, since it may be depegged or manipulated.
+fn add_price_context(
+    response: &mut serde_json::Value,
+    reference_prices: Option<&ReferencePrices>,
+    pair: &str,
+    price: Option<f64>,
+) {
+    let Some(price) = price else {
+        response["reason"] = json!(ZERO_LIQUIDITY);
+        return;
+    };
+    let Some(check) = reference_prices.and_then(|cache| check_price(cache, pair, price)) else {
+        return;
+    };
+    if check.diverged {
+        response["warning"] = json!(format!(
+            "Pool price deviates {:.2}% from the external reference price",
+            check.deviation * 100.0
+        ));
+    }
+    response["reference"] = json!(check);
+}
+
+/// Current price of a pool matched by token pair
+struct PairPrice {
+    pool_id: String,
+    /// The pool's own ordering of the pair, `TOKENA/TOKENB`
+    canonical_pair: String,
+    /// Whether the pool stores the pair in the reverse of the requested order
+    inverted: bool,
+    /// In the requested order; `None` if the pool has a zero reserve
+    price: Option<f64>,
+    last_updated: i64,
+    version: i64,
+}
+
+impl PairPrice {
+    /// Prices a pool matched for the pair `token_a`/`token_b`, inverting the
+    /// pool's price if it stores the pair the other way round.
+    fn new(pool: PoolState, token_a: &str) -> PairPrice {
+        let inverted = pool.token_a != token_a;
+        let (reserve_base, reserve_quote) = if inverted {
+            (pool.reserve_b, pool.reserve_a)
+        } else {
+            (pool.reserve_a, pool.reserve_b)
+        };
+        let price = spot_price(reserve_base, reserve_quote);
+        if let Some(price) = price {
+            audit_quotient("pair price", reserve_quote, reserve_base, price);
+        }
+        PairPrice {
+            pool_id: pool.pool_id,
+            canonical_pair: format!("{}/{}", pool.token_a, pool.token_b),
+            inverted,
+            price,
+            last_updated: pool.last_updated,
+            version: pool.version,
+        }
+    }
+}
+
+/// Looks up the pool for a token pair and derives its price from reserves.
+/// 
+/// Pools storing the pair in either order match. With several, a pool in
+/// the requested order is picked over a reversed one, then the lowest pool
+/// ID with liquidity on both sides, or failing that the lowest pool ID.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `token_a` - First token of the pair
+/// * `token_b` - Second token of the pair
+/// 
+/// # Returns
+/// * `Option<PairPrice>` - The matched pool's price, or `None` if no pool exists
+fn find_pair_price(conn: &Connection, token_a: &str, token_b: &str) -> Option<PairPrice> {
+    conn.query_row(
+        "SELECT pool_id, token_a, token_b, reserve_a, reserve_b, last_updated, version
+         FROM pools
+         WHERE (token_a = ?1 AND token_b = ?2) OR (token_a = ?2 AND token_b = ?1)
+         ORDER BY token_a <> ?1, reserve_a > 0 AND reserve_b > 0 DESC, pool_id
+         LIMIT 1",
+        [token_a, token_b],
+        |row| {
+            Ok(PoolState {
+                pool_id: row.get(0)?,
+                token_a: row.get(1)?,
+                token_b: row.get(2)?,
+                reserve_a: row.get(3)?,
+                reserve_b: row.get(4)?,
+                last_updated: row.get(5)?,
+                version: row.get(6)?,
+            })
+        },
+    )
+    .ok()
+    .map(|pool| PairPrice::new(pool, token_a))
+}
+
+/// Looks up a pair's price in the pool cache when it is enabled, and in
+/// SQLite otherwise.
+/// 
+/// Both pick the same pool when several trade the pair.
+/// 
+/// # Returns
+/// * `Result<Option<PairPrice>, ApiError>` - The price, or `None` if no pool trades the pair
+fn lookup_pair_price(
+    conn_arc: &Arc<Mutex<Connection>>,
+    pool_cache: Option<&PoolCache>,
+    token_a: &str,
+    token_b: &str,
+) -> Result<Option<PairPrice>, ApiError> {
+    let Some(cache) = pool_cache else {
+        let conn = conn_arc.lock()?;
+        return Ok(find_pair_price(&conn, token_a, token_b));
+    };
+    let guard = cache.read()?;
+    let pool = guard
+        .values()
+        .filter(|p| (p.token_a == token_a && p.token_b == token_b) || (p.token_a == token_b && p.token_b == token_a))
+        .min_by_key(|p| (p.token_a != token_a, spot_price(p.reserve_a, p.reserve_b).is_none(), &p.pool_id));
+    Ok(pool.map(|pool| PairPrice::new(pool.clone(), token_a)))
+}
+
+/// Default time a long-poll price request is held open (in milliseconds)
+const PRICE_WAIT_DEFAULT_TIMEOUT_MS: u64 = 30_000;
+
+/// Upper bound on the `timeout_ms` a client may request (in milliseconds)
+const PRICE_WAIT_MAX_TIMEOUT_MS: u64 = 60_000;
+
+/// Long-polls for a price update on a token pair.
+/// 
+/// Holds the request open until the pool's `version` exceeds `after`,
+/// then returns the new price (`null` with a `reason` while the pool has a
+/// zero reserve, as for `/api/price`). As there, the pair may be given in
+/// either order. If nothing changes before the timeout the current price
+/// is returned with `"timed_out": true`, and the client should simply
+/// re-issue the request with the same `after` value.
+/// 
+/// The held request doesn't poll: it re-reads the price only when woken by
+/// `pool_cache::notify_pool_updates`, and once more at the timeout.
+/// 
+/// # Endpoint
+/// `GET /api/price/wait?pair=TOKENA/TOKENB&after=3`
+/// 
+/// # Query Parameters
+/// * `pair` - Token pair in format "TOKENA/TOKENB", a single token to price
+///   it against the canonical quote token, or a pair alias
+/// * `after` - Last pool `version` seen by the client (defaults to 0)
+/// * `timeout_ms` - Maximum time to hold the request (default 30000, max 60000)
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pair": "USDC/SUI",
+///   "alias": "USDC-SUI",
+///   "pool_id": "0x...",
+///   "canonical_pair": "USDC/SUI",
+///   "inverted": false,
+///   "price": 0.5,
+///   "last_updated": 1751104259632,
+///   "version": 4,
+///   "timed_out": false
+/// }
+/// ```
+async fn price_wait_handler(
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    pool_cache: Option<Extension<PoolCache>>,
+    reference_prices: Option<Extension<ReferencePrices>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let PairParam { pair, token_a, token_b, alias } = parse_pair(&*conn_arc.lock()?, &params)?;
+    let (token_a, token_b) = (token_a.as_str(), token_b.as_str());
+
+    let after = match params.get("after").map(|v| v.parse::<i64>()) {
+        None => 0,
+        Some(Ok(v)) => v,
+        Some(Err(_)) => return Err(ApiError::BadRequest("Query parameter `after` must be an integer".to_string())),
+    };
+    let timeout_ms = params
+        .get("timeout_ms")
+        .and_then(|v| v.parse::<u64>().ok())
+        .unwrap_or(PRICE_WAIT_DEFAULT_TIMEOUT_MS)
+        .min(PRICE_WAIT_MAX_TIMEOUT_MS);
+    let deadline = Instant::now() + Duration::from_millis(timeout_ms);
+    // Subscribed before the first read, so an update landing between a
+    // read and the wait still wakes it
+    let mut updates = pool_updates();
+
+    loop {
+        // Only hold the connection lock for the lookup itself, never across a sleep
+        let quote = lookup_pair_price(&conn_arc, pool_cache.as_deref(), token_a, token_b)?;
+        let Some(quote) = quote else {
+            return Err(unknown_pair(&pair));
+        };
+
+        let timed_out = Instant::now() >= deadline;
+        if quote.version > after || timed_out {
+            let mut response = json!({
+                "status": "ok",
+                "pair": pair,
+                "alias": alias,
+                "pool_id": quote.pool_id,
+                "canonical_pair": quote.canonical_pair,
+                "inverted": quote.inverted,
+                "price": quote.price,
+                "last_updated": quote.last_updated,
+                "version": quote.version,
+                "timed_out": quote.version <= after
+            });
+            add_price_context(&mut response, reference_prices.as_deref(), &pair, quote.price);
+            return Ok(Json(response));
+        }
+
+        // Woken by a pool update, or at the deadline to answer with `timed_out`
+        let _ = tokio::time::timeout_at(deadline.into(), updates.changed()).await;
+    }
+}
+
+/// Change of reserve A from a swap: selling token A adds `amount_in` to it,
+/// selling token B removes `amount_out` from it. A swap of unknown
+/// direction moves neither reserve.
+const SWAP_DELTA_A: &str = "CASE direction WHEN 'a_to_b' THEN amount_in WHEN 'b_to_a' THEN -amount_out ELSE 0.0 END";
+
+/// Change of reserve B from a swap, the mirror of `SWAP_DELTA_A`
+const SWAP_DELTA_B: &str = "CASE direction WHEN 'a_to_b' THEN -amount_out WHEN 'b_to_a' THEN amount_in ELSE 0.0 END";
+
+/// Sliding windows used for pool velocity analytics, as (label, milliseconds)
+const VELOCITY_WINDOWS: [(&str, i64); 3] = [
+    ("1h", 60 * 60 * 1000),
+    ("24h", 24 * 60 * 60 * 1000),
+    ("7d", 7 * 24 * 60 * 60 * 1000),
+];
+
+/// Reserve change rates and trade frequency for a pool over one window
+#[derive(Serialize)]
+struct VelocityWindow {
+    window: String,
+    swap_count: i64,
+    trades_per_hour: f64,
+    reserve_a_change_per_hour: f64,
+    reserve_b_change_per_hour: f64,
+    turnover_a: f64,
+    turnover_b: f64,
+}
+
+/// Returns the current time in milliseconds since the Unix epoch.
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as i64
+}
+
+/// Computes how quickly a pool's inventory is moving over sliding windows.
+/// 
+/// For each window (1h, 24h, 7d) the handler aggregates the pool's swaps and
+/// reports trade frequency, the hourly rate at which each reserve changes,
+/// and turnover (volume through the pool relative to its current reserves).
+/// Each swap adds `amount_in` to the reserve of the token sold and removes
+/// `amount_out` from the other, according to its `direction`.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}/velocity`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "data": [
+///     {
+///       "window": "1h",
+///       "swap_count": 12,
+///       "trades_per_hour": 12.0,
+///       "reserve_a_change_per_hour": 1200.0,
+///       "reserve_b_change_per_hour": -600.0,
+///       "turnover_a": 1.2,
+///       "turnover_b": 1.2
+///     }
+///   ]
+/// }
+/// ```
+async fn velocity_handler(
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+
+    // Current reserves are the baseline for turnover ratios
+    let (reserve_a, reserve_b) = conn
+        .query_row(
+            "SELECT reserve_a, reserve_b FROM pools WHERE pool_id = ?1",
+            [&pool_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+        )
+        .optional()?
+        .ok_or_else(|| unknown_pool(&pool_id))?;
+
+    let now = now_ms();
+    let mut windows = Vec::new();
+    for (label, window_ms) in VELOCITY_WINDOWS {
+        let (swap_count, change_a, change_b, volume_a, volume_b) = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM({a}), 0.0), COALESCE(SUM({b}), 0.0),
+                        COALESCE(SUM(ABS({a})), 0.0), COALESCE(SUM(ABS({b})), 0.0)
+                 FROM swaps
+                 WHERE pool_id = ?1 AND timestamp >= ?2",
+                a = SWAP_DELTA_A,
+                b = SWAP_DELTA_B,
+            ),
+            params![pool_id, now - window_ms],
+            |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, f64>(2)?,
+                    row.get::<_, f64>(3)?,
+                    row.get::<_, f64>(4)?,
+                ))
+            },
+        )?;
+
+        let hours = window_ms as f64 / (60.0 * 60.0 * 1000.0);
+        windows.push(VelocityWindow {
+            window: label.to_string(),
+            swap_count,
+            trades_per_hour: swap_count as f64 / hours,
+            reserve_a_change_per_hour: change_a / hours,
+            reserve_b_change_per_hour: change_b / hours,
+            turnover_a: if reserve_a > 0.0 { volume_a / reserve_a } else { 0.0 },
+            turnover_b: if reserve_b > 0.0 { volume_b / reserve_b } else { 0.0 },
+        });
+    }
+
+    Ok(Json(json!({ "status": "ok", "pool_id": pool_id, "data": windows })))
+}
+
+/// Windows supported by the swap size histogram, as (label, length in milliseconds)
+const SIZE_HISTOGRAM_WINDOWS: [(&str, i64); 4] = [
+    ("1h", 60 * 60 * 1000),
+    ("24h", 24 * 60 * 60 * 1000),
+    ("7d", 7 * 24 * 60 * 60 * 1000),
+    ("30d", 30 * 24 * 60 * 60 * 1000),
+];
+
+/// Swaps whose input amount falls in `[min, max)`
+#[derive(Serialize)]
+struct SizeBucket {
+    min: f64,
+    max: f64,
+    count: usize,
+    volume: f64,
+}
+
+/// Power of ten an amount falls in; everything below 1 lands in decade -1, i.e. `[0, 1)`.
+fn size_decade(amount: f64) -> i32 {
+    if amount < 1.0 {
+        -1
+    } else {
+        amount.log10().floor() as i32
+    }
+}
+
+/// Bounds `[min, max)` of a size decade.
+fn decade_bounds(decade: i32) -> (f64, f64) {
+    if decade < 0 {
+        (0.0, 1.0)
+    } else {
+        (10f64.powi(decade), 10f64.powi(decade + 1))
+    }
+}
+
+/// Buckets swaps of a pool by input size.
+/// 
+/// Buckets are powers of ten of `amount_in` (`[1, 10)`, `[10, 100)`, ...);
+/// inputs below 1 share a `[0, 1)` bucket. Empty buckets between the smallest
+/// and largest swap are included so the histogram can be plotted directly.
+/// Percentiles of the input size are returned alongside, as a starting point
+/// for default trade-size presets.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}/size-histogram?window=7d`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// * `window` - `1h`, `24h`, `7d` (default) or `30d`
+/// * `epoch` - Cover the swaps of this Sui epoch instead of a window
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "window": "7d",
+///   "epoch": null,
+///   "swap_count": 42,
+///   "percentiles": { "p50": 120.0, "p90": 2500.0, "p99": 18000.0 },
+///   "data": [
+///     { "min": 10.0, "max": 100.0, "count": 12, "volume": 540.0 },
+///     { "min": 100.0, "max": 1000.0, "count": 20, "volume": 8100.0 }
+///   ]
+/// }
+/// ```
+async fn size_histogram_handler(
+    Path(pool_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let label = params.get("window").map(String::as_str).unwrap_or("7d");
+    let Some(&(label, window_ms)) = SIZE_HISTOGRAM_WINDOWS.iter().find(|(l, _)| *l == label) else {
+        return Err(ApiError::BadRequest(
+            "Query parameter `window` must be one of 1h, 24h, 7d, 30d".to_string(),
+        ));
+    };
+    let epoch = match params.get("epoch").map(|e| e.parse::<i64>()) {
+        None => None,
+        Some(Ok(_)) if params.contains_key("window") => {
+            return Err(ApiError::BadRequest(
+                "Query parameters `window` and `epoch` can't be combined".to_string(),
+            ));
+        }
+        Some(Ok(epoch)) => Some(epoch),
+        Some(Err(_)) => {
+            return Err(ApiError::BadRequest("Query parameter `epoch` must be an integer".to_string()));
+        }
+    };
+
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+
+    // Swaps are streamed in size order rather than collected, so the window
+    // can hold any number of them; the count up front fixes percentile ranks.
+    // An epoch replaces the window.
+    let since = if epoch.is_some() { i64::MIN } else { now_ms() - window_ms };
+    let swap_count = conn.query_row(
+        "SELECT COUNT(*) FROM swaps WHERE pool_id = ?1 AND timestamp >= ?2 AND (?3 IS NULL OR epoch = ?3)",
+        params![pool_id, since, epoch],
+        |row| row.get::<_, i64>(0),
+    )? as usize;
+    // Nearest-rank percentiles over the sorted amounts
+    let rank = |p: f64| ((p * swap_count as f64).ceil() as usize).max(1);
+    let percentile_ranks = [rank(0.5), rank(0.9), rank(0.99)];
+    let mut percentiles = [None; 3];
+
+    let bucket = |d: i32| {
+        let (min, max) = decade_bounds(d);
+        SizeBucket { min, max, count: 0, volume: 0.0 }
+    };
+    let mut buckets: Vec<SizeBucket> = Vec::new();
+    let mut last_decade = None;
+    let mut stmt = conn.prepare(
+        "SELECT amount_in FROM swaps
+         WHERE pool_id = ?1 AND timestamp >= ?2 AND (?3 IS NULL OR epoch = ?3)
+         ORDER BY amount_in",
+    )?;
+    let amounts = stmt.query_map(params![pool_id, since, epoch], |row| row.get::<_, f64>(0))?;
+    for (i, amount) in amounts.enumerate() {
+        let amount = amount?;
+        for (p, &r) in percentile_ranks.iter().enumerate() {
+            if i + 1 == r {
+                percentiles[p] = Some(amount);
+            }
+        }
+        // Empty buckets between the smallest and largest swap are filled in
+        let d = size_decade(amount);
+        for gap in last_decade.map_or(d, |last| last + 1)..=d {
+            buckets.push(bucket(gap));
+        }
+        last_decade = Some(d);
+        let current = buckets.last_mut().expect("bucket for the current decade");
+        current.count += 1;
+        current.volume += amount;
+    }
+
+    Ok(Json(json!({
+        "status": "ok",
+        "pool_id": pool_id,
+        "window": if epoch.is_some() { None } else { Some(label) },
+        "epoch": epoch,
+        "swap_count": swap_count,
+        "percentiles": { "p50": percentiles[0], "p90": percentiles[1], "p99": percentiles[2] },
+        "data": buckets
+    })))
+}
+
+/// Default number of most recent trades the trade tape covers
+const TAPE_DEFAULT_LIMIT: usize = 50;
+
+/// Most trades the trade tape covers per request
+const TAPE_MAX_LIMIT: usize = 500;
+
+/// One line of the trade tape: a single trade, or a run of consecutive small
+/// trades merged into one
+#[derive(Serialize)]
+struct TapeEntry {
+    /// Time of the latest trade in the entry
+    timestamp: i64,
+    /// Time of the earliest trade in the entry
+    first_timestamp: i64,
+    /// Digest of the trade, for entries holding a single trade
+    tx_digest: Option<String>,
+    /// Token sold into the pool, to token bought out of it
+    direction: &'static str,
+    trade_count: usize,
+    amount_in: f64,
+    amount_out: f64,
+    /// Execution price in token B per token A, volume-weighted for merged trades
+    price: f64,
+    /// Size decade of `amount_in`, as in the size histogram
+    size_bucket: SizeRange,
+}
+
+/// Bounds `[min, max)` of a size bucket
+#[derive(Serialize)]
+struct SizeRange {
+    min: f64,
+    max: f64,
+}
+
+impl TapeEntry {
+    fn new(tx_digest: String, direction: &str, amount_in: f64, amount_out: f64, timestamp: i64) -> TapeEntry {
+        let mut entry = TapeEntry {
+            timestamp,
+            first_timestamp: timestamp,
+            tx_digest: Some(tx_digest),
+            direction: if direction == "b_to_a" { "b_to_a" } else { "a_to_b" },
+            trade_count: 1,
+            amount_in,
+            amount_out,
+            price: 0.0,
+            size_bucket: SizeRange { min: 0.0, max: 0.0 },
+        };
+        entry.update_derived();
+        entry
+    }
+
+    /// Merges an earlier trade into this entry.
+    fn merge(&mut self, earlier: TapeEntry) {
+        self.first_timestamp = earlier.first_timestamp;
+        self.tx_digest = None;
+        self.trade_count += earlier.trade_count;
+        self.amount_in += earlier.amount_in;
+        self.amount_out += earlier.amount_out;
+        self.update_derived();
+    }
+
+    fn update_derived(&mut self) {
+        let (amount_a, amount_b) = match self.direction {
+            "b_to_a" => (self.amount_out, self.amount_in),
+            _ => (self.amount_in, self.amount_out),
+        };
+        self.price = if amount_a > 0.0 { amount_b / amount_a } else { 0.0 };
+        let (min, max) = decade_bounds(size_decade(self.amount_in));
+        self.size_bucket = SizeRange { min, max };
+    }
+}
+
+/// Returns a pool's recent trades as a trade tape, most recent first.
+/// 
+/// Each entry carries the trade's direction (`a_to_b` for trades selling
+/// token A for token B, `b_to_a` for the reverse), execution price in token
+/// B per token A and size bucket. With `aggregate_below`, consecutive trades
+/// in the same direction whose `amount_in` is below that amount are merged
+/// into one entry with summed amounts and a volume-weighted price, so bursts
+/// of dust trades take one line; larger trades are never merged.
+/// 
+/// # Endpoint
+/// `GET /api/tape/{pool_id}?limit=50&aggregate_below=1000`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// * `limit` - Number of most recent trades covered (default 50, at most 500)
+/// * `aggregate_below` - Merge runs of trades with `amount_in` below this amount
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "data": [
+///     {
+///       "timestamp": 1751104536847,
+///       "first_timestamp": 1751104536847,
+///       "tx_digest": "1X8U2S...",
+///       "direction": "a_to_b",
+///       "trade_count": 1,
+///       "amount_in": 150000000.0,
+///       "amount_out": 244603436.0,
+///       "price": 1.63,
+///       "size_bucket": { "min": 100000000.0, "max": 1000000000.0 }
+///     }
+///   ]
+/// }
+/// ```
+async fn tape_handler(
+    Path(pool_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let limit = match params.get("limit").map(|l| l.parse::<usize>()) {
+        None => TAPE_DEFAULT_LIMIT,
+        Some(Ok(limit)) if limit > 0 => limit.min(TAPE_MAX_LIMIT),
+        Some(_) => {
+            return Err(ApiError::BadRequest("Query parameter `limit` must be a positive integer".to_string()));
+        }
+    };
+    let aggregate_below = match params.get("aggregate_below").map(|a| a.parse::<f64>()) {
+        None => None,
+        Some(Ok(amount)) if amount.is_finite() && amount > 0.0 => Some(amount),
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `aggregate_below` must be a positive amount".to_string(),
+            ));
+        }
+    };
+
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+
+    let mut entries: Vec<TapeEntry> = Vec::new();
+    // Whether the last entry holds only small trades, so the next one may join it
+    let mut last_small = false;
+    let mut stmt = conn.prepare(
+        "SELECT tx_digest, direction, amount_in, amount_out, timestamp FROM swaps
+         WHERE pool_id = ?1 AND direction <> 'unknown'
+         ORDER BY timestamp DESC, id DESC
+         LIMIT ?2",
+    )?;
+    let trades = stmt.query_map(params![pool_id, limit as i64], |row| {
+        Ok(TapeEntry::new(row.get(0)?, &row.get::<_, String>(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+    })?;
+    for trade in trades {
+        let trade = trade?;
+        let small = aggregate_below.is_some_and(|below| trade.amount_in < below);
+        match entries.last_mut() {
+            Some(last) if small && last_small && last.direction == trade.direction => last.merge(trade),
+            _ => entries.push(trade),
+        }
+        last_small = small;
+    }
+
+    Ok(Json(json!({ "status": "ok", "pool_id": pool_id, "data": entries })))
+}
+
+/// Represents a failed transaction against a pool
+#[derive(Serialize)]
+struct FailedSwapInfo {
+    tx_digest: String,
+    error: String,
+    timestamp: i64,
+}
+
+/// Window used for the recent failure rate (in milliseconds)
+const FAILURE_RATE_WINDOW_MS: i64 = 24 * 60 * 60 * 1000;
+
+/// Reports how often swaps against a pool fail on chain.
+/// 
+/// Failure rate is `failed / (failed + successful)` both over all indexed
+/// history and over the last 24 hours, alongside the 20 most recent failures
+/// and their execution errors. An unknown pool answers 404.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}/failures`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "failed_swaps": 3,
+///   "successful_swaps": 97,
+///   "failure_rate": 0.03,
+///   "failure_rate_24h": 0.05,
+///   "recent": [
+///     {
+///       "tx_digest": "...",
+///       "error": "MoveAbort(...)",
+///       "timestamp": 1751104259632
+///     }
+///   ]
+/// }
+/// ```
+async fn failures_handler(
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+
+    let since = now_ms() - FAILURE_RATE_WINDOW_MS;
+    let count = |table: &str, since: i64| -> rusqlite::Result<i64> {
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE pool_id = ?1 AND timestamp >= ?2", table),
+            params![pool_id, since],
+            |row| row.get(0),
+        )
+    };
+    let rate = |failed: i64, succeeded: i64| -> f64 {
+        if failed + succeeded > 0 {
+            failed as f64 / (failed + succeeded) as f64
+        } else {
+            0.0
+        }
+    };
+
+    let (failed, succeeded) = (count("failed_swaps", 0)?, count("swaps", 0)?);
+    let (failed_24h, succeeded_24h) = (count("failed_swaps", since)?, count("swaps", since)?);
+
+    // Most recent failures with their execution errors
+    let recent: Vec<FailedSwapInfo> = conn
+        .prepare(
+            "SELECT tx_digest, error, timestamp
+             FROM failed_swaps
+             WHERE pool_id = ?1
+             ORDER BY timestamp DESC
+             LIMIT 20",
+        )?
+        .query_map([&pool_id], |row| {
+            Ok(FailedSwapInfo {
+                tx_digest: row.get(0)?,
+                error: row.get(1)?,
+                timestamp: row.get(2)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(Json(json!({
+        "status": "ok",
+        "pool_id": pool_id,
+        "failed_swaps": failed,
+        "successful_swaps": succeeded,
+        "failure_rate": rate(failed, succeeded),
+        "failure_rate_24h": rate(failed_24h, succeeded_24h),
+        "recent": recent
+    })))
+}
+
+/// Reconstructs a pool's reserves and price at a past timestamp.
+/// 
+/// Starts from the latest snapshot at or before `ts` and replays any swaps
+/// recorded after it (each swap adds `amount_in` to the reserve of the token
+/// sold and removes `amount_out` from the other, according to its
+/// `direction`). Pools indexed before snapshots existed have
+/// no such snapshot, so their state is instead rewound from the current
+/// reserves by undoing every swap after `ts`. If a reserve was zero at `ts`,
+/// `price` is `null` with `reason` `zero_liquidity`.
+/// 
+/// # Endpoint
+/// `GET /api/pools/{pool_id}/at?ts=1751104133893`
+/// 
+/// # Parameters
+/// * `pool_id` - The unique identifier of the liquidity pool
+/// * `ts` - Timestamp in milliseconds since epoch
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "pool_id": "0x...",
+///   "ts": 1751104133893,
+///   "reserve_a": 1000.0,
+///   "reserve_b": 500.0,
+///   "price": 0.5,
+///   "source": "snapshot",
+///   "base_ts": 1751104100000,
+///   "replayed_swaps": 2
+/// }
+/// ```
+async fn pool_at_handler(
+    Path(pool_id): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let ts = match params.get("ts").map(|v| v.parse::<i64>()) {
+        Some(Ok(ts)) => ts,
+        _ => return Err(ApiError::BadRequest("Query parameter `ts` must be a millisecond timestamp".to_string())),
+    };
+
+    let conn = conn_arc.lock()?;
+
+    let current = conn
+        .query_row(
+            "SELECT reserve_a, reserve_b, last_updated FROM pools WHERE pool_id = ?1",
+            [&pool_id],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)?)),
+        )
+        .optional()?
+        .ok_or_else(|| unknown_pool(&pool_id))?;
+
+    // Net reserve changes of the swaps in (from, to], used to replay forwards
+    // or rewind backwards
+    let swap_flows = |from: i64, to: i64| -> rusqlite::Result<(i64, f64, f64)> {
+        conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM({}), 0.0), COALESCE(SUM({}), 0.0)
+                 FROM swaps
+                 WHERE pool_id = ?1 AND timestamp > ?2 AND timestamp <= ?3",
+                SWAP_DELTA_A, SWAP_DELTA_B
+            ),
+            params![pool_id, from, to],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+    };
+
+    let snapshot = conn
+        .query_row(
+            "SELECT reserve_a, reserve_b, timestamp
+             FROM pool_snapshots
+             WHERE pool_id = ?1 AND timestamp <= ?2
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1",
+            params![pool_id, ts],
+            |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?, row.get::<_, i64>(2)?)),
+        )
+        .optional()?;
+
+    let (reserve_a, reserve_b, source, base_ts, replayed) = match snapshot {
+        Some((snap_a, snap_b, snap_ts)) => {
+            let (count, change_a, change_b) = swap_flows(snap_ts, ts)?;
+            (snap_a + change_a, snap_b + change_b, "snapshot", snap_ts, count)
+        }
+        None => {
+            let (cur_a, cur_b, cur_ts) = current;
+            let (count, change_a, change_b) = swap_flows(ts, i64::MAX)?;
+            (cur_a - change_a, cur_b - change_b, "replay", cur_ts, count)
+        }
+    };
+    let price = spot_price(reserve_a, reserve_b);
+    if let Some(price) = price {
+        audit_quotient("historical price", reserve_b, reserve_a, price);
+    }
+
+    let mut response = json!({
+        "status": "ok",
+        "pool_id": pool_id,
+        "ts": ts,
+        "reserve_a": reserve_a,
+        "reserve_b": reserve_b,
+        "price": price,
+        "source": source,
+        "base_ts": base_ts,
+        "replayed_swaps": replayed
+    });
+    if price.is_none() {
+        response["reason"] = json!(ZERO_LIQUIDITY);
+    }
+    Ok(Json(response))
+}
+
+/// Lists every token that appears in a pool along with its derived price.
+/// 
+/// Prices come from the cache maintained by the background price refresher,
+/// so this endpoint never touches the database. `price_sui` is taken from the
+/// deepest direct SUI pool, or a two-hop route when no direct pool exists;
+/// `price_quote` is the price in the canonical quote token named by
+/// `quote_token`. `price_usd` is derived through the configured `USD_TOKEN`
+/// and is `null` when no reference token is configured or reachable.
+/// 
+/// Each token also carries a `risk_score` from 0 to 100 and the
+/// `risk_reasons` behind it, for frontends to warn on: tokens without
+/// curated metadata, with over 90% of their liquidity in one pool, first
+/// traded less than 24 hours ago, or whose price ranged over 50% in the last
+/// 24 hours are flagged (see `risk::score_tokens`).
+/// 
+/// # Endpoint
+/// `GET /api/tokens`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "quote_token": "0x...::usdc::USDC",
+///   "data": [
+///     {
+///       "token": "0x...",
+///       "price_sui": 0.5,
+///       "price_quote": 1.75,
+///       "price_usd": 1.75,
+///       "route": ["0x..."],
+///       "updated_at": 1751104259632,
+///       "risk_score": 20,
+///       "risk_reasons": [
+///         {
+///           "token": "0x...",
+///           "code": "concentrated_liquidity",
+///           "message": "100% of liquidity is in a single pool"
+///         }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+async fn tokens_handler(
+    Extension(cache): Extension<PriceCache>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let mut tokens: Vec<TokenPrice> = cache.read()?.values().cloned().collect();
+    tokens.sort_by(|a, b| a.token.cmp(&b.token));
+
+    Ok(Json(json!({ "status": "ok", "quote_token": quote_token(), "data": tokens })))
+}
+
+/// Returns the token connectivity graph used by the router.
+/// 
+/// Nodes are tokens; edges are pools with liquidity on both sides, weighted
+/// by `sqrt(reserve_a * reserve_b)`. This is the memoized graph the routing
+/// engine searches, so clients and the router agree on which paths exist.
+/// 
+/// # Endpoint
+/// `GET /api/graph`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "nodes": [
+///       { "token": "0x...", "pools": 2 }
+///     ],
+///     "edges": [
+///       {
+///         "pool_id": "0x...",
+///         "token_a": "0x...",
+///         "token_b": "0x...",
+///         "reserve_a": 1000.0,
+///         "reserve_b": 500.0,
+///         "liquidity": 707.1
+///       }
+///     ]
+///   }
+/// }
+/// ```
+async fn graph_handler(Extension(graph_cache): Extension<GraphCache>) -> Result<Response, ApiError> {
+    let graph = current_graph(&graph_cache)?;
+    Ok(Json(json!({ "status": "ok", "data": graph.topology() })).into_response())
+}
+
+/// Slippage tolerance applied to a quote when `slippage_bps` is not given (in basis points)
+const DEFAULT_QUOTE_SLIPPAGE_BPS: u32 = 50;
+
+/// How long a quote stays valid, and may be matched with a swap (in milliseconds)
+const QUOTE_TTL_MS: i64 = 5 * 60 * 1000;
+
+/// Quotes a swap against a single pool.
+/// 
+/// The pool's fee (`POOL_FEE_BPS`) is taken from `amount_in` and the rest is
+/// swapped with the constant product formula against the current reserves.
+/// `min_amount_out` is the least the swap should accept as output, i.e. the
+/// expected output less the slippage tolerance. If the pool has a zero
+/// reserve, the request fails with 422 and `reason` `zero_liquidity`.
+/// 
+/// Every quote is recorded with the caller's API key hash and user agent.
+/// `quote_id` identifies it; the indexer matches it with a later swap on
+/// the same pool and direction with a similar `amount_in` made before
+/// `expires_at` (see `GET /api/quotes/conversion`). It is `null` if the
+/// quote couldn't be recorded.
+/// 
+/// # Endpoint
+/// `GET /api/quote?pool_id=0x...&amount_in=100&direction=a_to_b`
+/// 
+/// # Query Parameters
+/// * `pool_id` - Pool to swap in
+/// * `amount_in` - Amount of the input token
+/// * `direction` - `a_to_b` to sell token A for token B, or `b_to_a`
+/// * `slippage_bps` - Slippage tolerance in basis points (default 50, at most 10000)
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "pool_id": "0x...",
+///     "direction": "a_to_b",
+///     "token_in": "0x...",
+///     "token_out": "0x...",
+///     "amount_in": 100.0,
+///     "fee_bps": 0,
+///     "fee": 0.0,
+///     "amount_out": 45.45,
+///     "price_impact_pct": 9.09,
+///     "slippage_bps": 50,
+///     "min_amount_out": 45.22,
+///     "quote_id": 42,
+///     "expires_at": 1700000300000
+///   }
+/// }
+/// ```
+async fn quote_handler(
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    pool_cache: Option<Extension<PoolCache>>,
+) -> Result<Response, ApiError> {
+    let Some(pool_id) = params.get("pool_id") else {
+        return Err(ApiError::BadRequest("Missing `pool_id` query parameter".to_string()));
+    };
+    let amount_in = match params.get("amount_in").map(|v| v.parse::<f64>()) {
+        Some(Ok(a)) if a > 0.0 && a.is_finite() => a,
+        _ => return Err(ApiError::BadRequest("Query parameter `amount_in` must be a positive number".to_string())),
+    };
+    let direction = match params.get("direction").map(String::as_str) {
+        Some(d @ ("a_to_b" | "b_to_a")) => d,
+        _ => return Err(ApiError::BadRequest("Query parameter `direction` must be `a_to_b` or `b_to_a`".to_string())),
+    };
+    let slippage_bps = match params.get("slippage_bps").map(|v| v.parse::<u32>()) {
+        None => DEFAULT_QUOTE_SLIPPAGE_BPS,
+        Some(Ok(bps)) if bps <= 10_000 => bps,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `slippage_bps` must be an integer from 0 to 10000".to_string(),
+            ));
+        }
+    };
+
+    let pool: Option<PoolState> = match pool_cache {
+        Some(Extension(cache)) => cache.read()?.get(pool_id.as_str()).cloned(),
+        None => read_pools(&*conn_arc.lock()?, Some(std::slice::from_ref(pool_id)))?.pop(),
+    };
+    let pool = pool.ok_or_else(|| unknown_pool(pool_id))?;
+    let (token_in, token_out, reserve_in, reserve_out) = match direction {
+        "a_to_b" => (&pool.token_a, &pool.token_b, pool.reserve_a, pool.reserve_b),
+        _ => (&pool.token_b, &pool.token_a, pool.reserve_b, pool.reserve_a),
+    };
+    if spot_price(reserve_in, reserve_out).is_none() {
+        return Ok((
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({
+                "status": "error",
+                "reason": ZERO_LIQUIDITY,
+                "message": format!("Pool {} has no liquidity", pool.pool_id)
+            })),
+        )
+            .into_response());
+    }
+
+    let fee_bps = pool_fee_bps();
+    let quote = quote_swap(amount_in, reserve_in, reserve_out, fee_bps);
+    let min_amount_out = quote.amount_out * (10_000 - slippage_bps) as f64 / 10_000.0;
+
+    // Record the quote so it can be matched with the swap it leads to
+    let created_at = now_ms();
+    let expires_at = created_at + QUOTE_TTL_MS;
+    let header = |name| headers.get(name).and_then(|v| v.to_str().ok()).filter(|v| !v.is_empty());
+    let key_hash = header(API_KEY_HEADER).map(api_key_hash);
+    let record = QuoteRecord {
+        pool_id: &pool.pool_id,
+        direction,
+        amount_in: quote.amount_in,
+        quoted_out: quote.amount_out,
+        min_amount_out,
+        key_hash: key_hash.as_deref(),
+        user_agent: header(header::USER_AGENT.as_str()),
+        created_at,
+        expires_at,
+    };
+    let quote_id = insert_quote(&*conn_arc.lock()?, &record).ok();
+
+    Ok(Json(json!({
+        "status": "ok",
+        "data": {
+            "pool_id": pool.pool_id,
+            "direction": direction,
+            "token_in": token_in,
+            "token_out": token_out,
+            "amount_in": quote.amount_in,
+            "fee_bps": fee_bps,
+            "fee": quote.fee,
+            "amount_out": quote.amount_out,
+            "price_impact_pct": quote.price_impact_pct,
+            "slippage_bps": slippage_bps,
+            "min_amount_out": min_amount_out,
+            "quote_id": quote_id,
+            "expires_at": expires_at
+        }
+    }))
+    .into_response())
+}
+
+/// Maximum number of execution options returned by the best-price endpoint
+const BEST_PRICE_MAX_OPTIONS: usize = 10;
+
+/// Ranks every way of swapping `amount` of one token into another.
+/// 
+/// Evaluates all direct pools and routed paths of up to `MAX_HOPS` pools,
+/// simulating each hop like `/api/quote` (the pool's fee, then the constant
+/// product formula against current reserves), and returns the best options
+/// ordered by expected output. If
+/// the tokens are only connected through pools with a zero reserve, the
+/// request fails with 422 and `reason` `zero_liquidity`.
+/// 
+/// # Endpoint
+/// `GET /api/best-price?from=TOKEN&to=TOKEN&amount=100`
+/// 
+/// # Query Parameters
+/// * `from` - Token to sell
+/// * `to` - Token to buy
+/// * `amount` - Amount of `from` to sell
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "from": "0x...",
+///   "to": "0x...",
+///   "amount_in": 100.0,
+///   "data": [
+///     {
+///       "route": ["0x..."],
+///       "path": ["0x...", "0x..."],
+///       "amount_in": 100.0,
+///       "amount_out": 45.45,
+///       "price_impact_pct": 9.09
+///     }
+///   ]
+/// }
+/// ```
+async fn best_price_handler(
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Extension(graph_cache): Extension<GraphCache>,
+) -> Result<Response, ApiError> {
+    let mut timings = QuoteTimings::start();
+    let (from, to) = match (params.get("from"), params.get("to")) {
+        (Some(from), Some(to)) if from != to => (from.clone(), to.clone()),
+        _ => {
+            return Err(ApiError::BadRequest(
+                "Query parameters `from` and `to` are required and must differ".to_string(),
+            ));
+        }
+    };
+    let amount = match params.get("amount").map(|v| v.parse::<f64>()) {
+        Some(Ok(a)) if a > 0.0 && a.is_finite() => a,
+        _ => return Err(ApiError::BadRequest("Query parameter `amount` must be a positive number".to_string())),
+    };
+
+    let fee_bps = pool_fee_bps();
+    let graph = current_graph(&graph_cache)?;
+    let options = timings.math(|| {
+        let mut options: Vec<RouteQuote> = graph
+            .find_routes(&from, &to, MAX_HOPS)
+            .iter()
+            .map(|route| simulate_route(route, amount, fee_bps))
+            .collect();
+        options.sort_by(|a, b| b.amount_out.total_cmp(&a.amount_out));
+        options.truncate(BEST_PRICE_MAX_OPTIONS);
+        options
+    });
+
+    let Some(best) = options.first() else {
+        if graph.connected_through_drained(&from, &to, MAX_HOPS) {
+            return Ok((
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Json(json!({
+                    "status": "error",
+                    "reason": ZERO_LIQUIDITY,
+                    "message": format!("Every route from {} to {} passes through a pool with no liquidity", from, to)
+                })),
+            )
+                .into_response());
+        }
+        return Err(ApiError::NotFound(format!("No route found from {} to {}", from, to)));
+    };
+
+    // Remember the recommendation so it can be compared with the realized swap
+    timings.db(|| {
+        if let Ok(conn) = conn_arc.lock() {
+            let _ = insert_route_recommendation(&conn, &from, &to, &best.route, amount, best.amount_out, now_ms());
+        }
+    });
+
+    let body = json!({
+        "status": "ok",
+        "from": from,
+        "to": to,
+        "amount_in": amount,
+        "data": options
+    });
+    Ok(timings.respond(&headers, &body))
+}
+
+/// Routing accuracy for routes with a given number of hops
+#[derive(Serialize)]
+struct RoutingAccuracy {
+    hops: i64,
+    recommendations: i64,
+    matched: i64,
+    match_rate: f64,
+    /// Mean of `realized_out / quoted_out - 1` over matched recommendations
+    mean_error: Option<f64>,
+    /// Mean of `|realized_out / quoted_out - 1|` over matched recommendations
+    mean_abs_error: Option<f64>,
+}
+
+/// Reports how closely the router's quotes matched realized on-chain output.
+/// 
+/// Each best-price request records its top route; the indexer later matches
+/// it with the swap that followed (same entry pool, similar input amount,
+/// within a few minutes). Accuracy is grouped by hop count. A negative
+/// `mean_error` means swaps realized less than quoted.
+/// 
+/// # Endpoint
+/// `GET /api/routing/accuracy`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "hops": 1,
+///       "recommendations": 120,
+///       "matched": 30,
+///       "match_rate": 0.25,
+///       "mean_error": -0.004,
+///       "mean_abs_error": 0.006
+///     }
+///   ]
+/// }
+/// ```
+async fn routing_accuracy_handler(
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+
+    let stats: Vec<RoutingAccuracy> = conn
+        .prepare(
+            "SELECT hops,
+                    COUNT(*),
+                    COUNT(matched_tx),
+                    AVG(CASE WHEN matched_tx IS NOT NULL AND quoted_out > 0
+                             THEN realized_out / quoted_out - 1 END),
+                    AVG(CASE WHEN matched_tx IS NOT NULL AND quoted_out > 0
+                             THEN ABS(realized_out / quoted_out - 1) END)
+             FROM route_recommendations
+             GROUP BY hops
+             ORDER BY hops",
+        )?
+        .query_map([], |row| {
+            let recommendations: i64 = row.get(1)?;
+            let matched: i64 = row.get(2)?;
+            Ok(RoutingAccuracy {
+                hops: row.get(0)?,
+                recommendations,
+                matched,
+                match_rate: if recommendations > 0 { matched as f64 / recommendations as f64 } else { 0.0 },
+                mean_error: row.get(3)?,
+                mean_abs_error: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(Json(json!({ "status": "ok", "data": stats })))
+}
+
+/// Quote conversion for one pool
+#[derive(Serialize)]
+struct QuoteConversion {
+    pool_id: String,
+    quotes: i64,
+    /// Quotes still valid, which may yet convert
+    pending: i64,
+    converted: i64,
+    /// `converted` over the quotes no longer pending
+    conversion_rate: f64,
+    /// Mean of `realized_out / quoted_out - 1` over converted quotes
+    mean_error: Option<f64>,
+}
+
+/// Reports how many served quotes led to an on-chain swap, per pool.
+/// 
+/// Each `/api/quote` request is recorded with a quote ID; the indexer later
+/// matches it with the first swap on the same pool and direction with a
+/// similar input amount made before the quote expired. Quotes that haven't
+/// expired are reported as `pending` and left out of `conversion_rate`.
+/// 
+/// # Endpoint
+/// `GET /api/quotes/conversion`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "pool_id": "0x...",
+///       "quotes": 120,
+///       "pending": 4,
+///       "converted": 29,
+///       "conversion_rate": 0.25,
+///       "mean_error": -0.002
+///     }
+///   ]
+/// }
+/// ```
+async fn quote_conversion_handler(
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let conn = conn_arc.lock()?;
+
+    let stats: Vec<QuoteConversion> = conn
+        .prepare(
+            "SELECT pool_id,
+                    COUNT(*),
+                    COUNT(CASE WHEN matched_tx IS NULL AND expires_at >= ?1 THEN 1 END),
+                    COUNT(matched_tx),
+                    AVG(CASE WHEN matched_tx IS NOT NULL AND quoted_out > 0
+                             THEN realized_out / quoted_out - 1 END)
+             FROM quotes
+             GROUP BY pool_id
+             ORDER BY pool_id",
+        )?
+        .query_map(params![now_ms()], |row| {
+            let quotes: i64 = row.get(1)?;
+            let pending: i64 = row.get(2)?;
+            let converted: i64 = row.get(3)?;
+            let settled = quotes - pending;
+            Ok(QuoteConversion {
+                pool_id: row.get(0)?,
+                quotes,
+                pending,
+                converted,
+                conversion_rate: if settled > 0 { converted as f64 / settled as f64 } else { 0.0 },
+                mean_error: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    Ok(Json(json!({ "status": "ok", "data": stats })))
+}
+
+/// Most transaction digests bound into one `IN (...)` query
+const CHECKPOINT_QUERY_CHUNK: usize = 500;
+
+/// A swap indexed from a checkpoint's transactions
+#[derive(Serialize)]
+struct CheckpointSwap {
+    tx_digest: String,
+    pool_id: String,
+    amount_in: f64,
+    amount_out: f64,
+    timestamp: i64,
+}
+
+/// Swap totals of one pool within a checkpoint
+#[derive(Serialize)]
+struct CheckpointPoolTotals {
+    pool_id: String,
+    swap_count: usize,
+    amount_in: f64,
+    amount_out: f64,
+}
+
+/// Lists the indexed swaps executed in a checkpoint, with totals per pool.
+/// 
+/// The checkpoint's transaction digests are fetched from the Sui RPC node and
+/// matched against indexed swaps, so the result can be reconciled with an
+/// explorer's view of the same checkpoint. A swap the node lists but the
+/// database lacks is simply absent; `transactions` is the checkpoint's full
+/// transaction count for comparison.
+/// 
+/// # Endpoint
+/// `GET /api/checkpoints/{seq}/swaps`
+/// 
+/// # Parameters
+/// * `seq` - Checkpoint sequence number
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "checkpoint": 1000,
+///   "timestamp_ms": 1751104259632,
+///   "transactions": 14,
+///   "swap_count": 1,
+///   "pools": [
+///     { "pool_id": "0x...", "swap_count": 1, "amount_in": 100.0, "amount_out": 50.0 }
+///   ],
+///   "data": [
+///     {
+///       "tx_digest": "nGy5Fp...",
+///       "pool_id": "0x...",
+///       "amount_in": 100.0,
+///       "amount_out": 50.0,
+///       "timestamp": 1751104259632
+///     }
+///   ]
+/// }
+/// ```
+async fn checkpoint_swaps_handler(
+    Path(seq): Path<u64>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let client = rpc_client();
+    let checkpoint = fetch_checkpoint(&client, &rpc_url(), seq)
+        .await
+        .map_err(|e| ApiError::Upstream(format!("Failed to fetch checkpoint {}: {}", seq, e)))?;
+
+    let conn = conn_arc.lock()?;
+    let mut swaps = Vec::new();
+    for digests in checkpoint.transactions.chunks(CHECKPOINT_QUERY_CHUNK) {
+        let sql = format!(
+            "SELECT tx_digest, pool_id, amount_in, amount_out, timestamp FROM swaps WHERE tx_digest IN ({})",
+            vec!["?"; digests.len()].join(",")
+        );
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(digests), |row| {
+            Ok(CheckpointSwap {
+                tx_digest: row.get(0)?,
+                pool_id: row.get(1)?,
+                amount_in: row.get(2)?,
+                amount_out: row.get(3)?,
+                timestamp: row.get(4)?,
+            })
+        })?;
+        for swap in rows {
+            swaps.push(swap?);
+        }
+    }
+    swaps.sort_by(|a, b| a.pool_id.cmp(&b.pool_id).then(a.tx_digest.cmp(&b.tx_digest)));
+
+    let mut pools: Vec<CheckpointPoolTotals> = Vec::new();
+    for swap in &swaps {
+        match pools.last_mut() {
+            Some(totals) if totals.pool_id == swap.pool_id => {
+                totals.swap_count += 1;
+                totals.amount_in += swap.amount_in;
+                totals.amount_out += swap.amount_out;
+            }
+            _ => pools.push(CheckpointPoolTotals {
+                pool_id: swap.pool_id.clone(),
+                swap_count: 1,
+                amount_in: swap.amount_in,
+                amount_out: swap.amount_out,
+            }),
+        }
+    }
+
+    Ok(Json(json!({
+        "status": "ok",
+        "checkpoint": checkpoint.seq,
+        "timestamp_ms": checkpoint.timestamp_ms,
+        "transactions": checkpoint.transactions.len(),
+        "swap_count": swaps.len(),
+        "pools": pools,
+        "data": swaps
+    })))
+}
+
+/// Lists the named reports with their filters.
+/// 
+/// # Endpoint
+/// `GET /api/reports`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "name": "stale_pools",
+///       "description": "Pools ordered by how long ago their state last changed",
+///       "filters": [
+///         { "param": "min_idle_secs", "kind": "number", "description": "Only pools unchanged for at least this many seconds" }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+async fn list_reports_handler() -> Json<serde_json::Value> {
+    Json(json!({ "status": "ok", "data": REPORTS }))
+}
+
+/// Runs a named report from `reports::REPORTS`.
+/// 
+/// Each report reads a SQL view maintained by the backend. Query parameters
+/// are the report's filters (listed by `GET /api/reports`) and are combined
+/// with AND; rows are paged like other list endpoints, at most
+/// `MAX_RESPONSE_ROWS` per request with a `next_cursor` when truncated.
+/// 
+/// # Endpoint
+/// `GET /api/reports/{name}?<filter>=<value>&cursor=...`
+/// 
+/// # Parameters
+/// * `name` - Report name, e.g. `fee_efficiency` or `stale_pools`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "report": "stale_pools",
+///   "data": [
+///     {
+///       "pool_id": "0x...",
+///       "token_a": "0x...",
+///       "token_b": "0x...",
+///       "reserve_a": 1000.0,
+///       "reserve_b": 500.0,
+///       "last_updated": 1751104133893,
+///       "last_swap": 1751104133893,
+///       "idle_secs": 86400
+///     }
+///   ]
+/// }
+/// ```
+async fn report_handler(
+    Path(name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Some(report) = report(&name) else {
+        return Err(ApiError::NotFound(format!("Unknown report `{}`", name)));
+    };
+    let page = Page::from_params(&params)?;
+    let (sql, values) = report_query(report, &params).map_err(ApiError::BadRequest)?;
+
+    let conn = conn_arc.lock()?;
+    let mut rows = run_report(&conn, &format!("{}{}", sql, page.sql()), &values)?;
+
+    let mut response = json!({ "status": "ok", "report": report.name });
+    page.finish(&mut rows, &mut response);
+    response["data"] = json!(rows);
+    Ok(Json(response))
+}
+
+/// Serializes an export job, with its download link once complete.
+fn export_job_json(job: &ExportJob) -> serde_json::Value {
+    let mut data = json!(job);
+    if job.status == "complete" {
+        data["download_url"] = json!(format!("/api/exports/{}/download", job.id));
+    }
+    data
+}
+
+/// Starts a background export of a dataset to a CSV file.
+/// 
+/// The export runs on its own database connection and its progress is
+/// polled with `GET /api/exports/{id}`. At most `MAX_ACTIVE_EXPORTS` jobs
+/// may be queued or running at once; further requests get 429.
+/// 
+/// # Endpoint
+/// `POST /api/exports` with body
+/// `{"dataset": "swaps", "format": "csv", "pool_id": "0x...", "from": 1751000000000, "to": 1752000000000}`
+/// 
+/// `dataset` is `swaps`, `pool_snapshots` or `pools`; `pool_id`, `from` and
+/// `to` are optional filters (`pools` has no timestamp to filter on).
+/// 
+/// # Response Format
+/// HTTP 202 with
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "id": 7,
+///     "status": "queued",
+///     "request": { "dataset": "swaps", "format": "csv", "pool_id": "0x...", "from": 1751000000000, "to": 1752000000000 },
+///     "rows": 0,
+///     "error": null,
+///     "created_at": 1751104133893,
+///     "finished_at": null
+///   }
+/// }
+/// ```
+async fn create_export_handler(
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(request): Json<ExportRequest>,
+) -> Result<Response, ApiError> {
+    request.validate().map_err(ApiError::BadRequest)?;
+    let created = create_export_job(&*conn_arc.lock()?, &request)?;
+    let Some(job) = created else {
+        return Err(ApiError::TooManyRequests(format!(
+            "{} exports are already in progress; try again later",
+            MAX_ACTIVE_EXPORTS
+        )));
+    };
+    tokio::task::spawn_blocking(move || run_export_job(job.id, request));
+    Ok((StatusCode::ACCEPTED, Json(json!({ "status": "ok", "data": export_job_json(&job) }))).into_response())
+}
+
+/// Reports the status of an export job.
+/// 
+/// Once `status` is `complete`, the response carries a `download_url`.
+/// 
+/// # Endpoint
+/// `GET /api/exports/{id}`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "id": 7,
+///     "status": "complete",
+///     "request": { "dataset": "swaps", "format": "csv", "pool_id": null, "from": null, "to": null },
+///     "rows": 120000,
+///     "error": null,
+///     "created_at": 1751104133893,
+///     "finished_at": 1751104141020,
+///     "download_url": "/api/exports/7/download"
+///   }
+/// }
+/// ```
+async fn export_status_handler(
+    Path(id): Path<i64>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let job = get_export_job(&*conn_arc.lock()?, id)?.ok_or_else(|| unknown_export(id))?;
+    Ok(Json(json!({ "status": "ok", "data": export_job_json(&job) })))
+}
+
+/// Downloads the CSV file of a completed export job, streamed from disk.
+/// 
+/// # Endpoint
+/// `GET /api/exports/{id}/download`
+async fn export_download_handler(
+    Path(id): Path<i64>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Response, ApiError> {
+    let job = get_export_job(&*conn_arc.lock()?, id)?.ok_or_else(|| unknown_export(id))?;
+    if job.status != "complete" {
+        return Err(ApiError::Conflict(format!("Export {} is {}", id, job.status)));
+    }
+    let file = tokio::fs::File::open(export_path(id))
+        .await
+        .map_err(|e| ApiError::Gone(format!("Export file is no longer available: {}", e)))?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"export-{}.csv\"", id)),
+        ],
+        Body::new(FileBody::new(file)),
+    )
+        .into_response())
+}
+
+fn unknown_export(id: i64) -> ApiError {
+    ApiError::NotFound(format!("Unknown export {}", id))
+}
+
+/// Reports the indexer's progress and how quickly events reach the database.
+/// 
+/// `ingest_latency_ms` is the delay between an event's on-chain timestamp and
+/// its rows being written, over the most recent first-time-indexed events;
+/// it is `null` until the indexer has written an event.
+/// 
+/// `storage` is the database growth forecast: each table's size and average
+/// daily growth over `DB_GROWTH_WINDOW_DAYS`, and when the database file will
+/// reach each of `DB_GROWTH_THRESHOLDS_BYTES` at that rate. Table sizes are
+/// sampled daily by the maintenance task, so rates appear after the second
+/// day; `storage` is `null` until the first maintenance pass.
+/// 
+/// # Endpoint
+/// `GET /api/indexer/status`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "last_sync_ms": 1751104133893,
+///   "lag_secs": 3,
+///   "events_processed": 1520,
+///   "gaps_detected": 0,
+///   "ingest_latency_ms": { "last": 2140, "p50": 2650, "p95": 4810, "p99": 5230, "samples": 1000 },
+///   "storage": {
+///     "computed_at": 1751104133893,
+///     "window_days": 7,
+///     "size_bytes": 734003200,
+///     "bytes_per_day": 10485760.0,
+///     "tables": [
+///       { "table": "swaps", "rows": 2140511, "bytes": 402653184, "rows_per_day": 31250.0, "bytes_per_day": 6291456.0 }
+///     ],
+///     "thresholds": [
+///       { "threshold_bytes": 1073741824, "reached": false, "days": 32.4, "eta_ms": 1753903493893 }
+///     ]
+///   }
+/// }
+/// ```
+async fn indexer_status_handler() -> Json<serde_json::Value> {
+    let latency = INGEST_LATENCY.percentiles().map(|p| {
+        json!({ "last": p.last, "p50": p.p50, "p95": p.p95, "p99": p.p99, "samples": p.samples })
+    });
+
+    Json(json!({
+        "status": "ok",
+        "last_sync_ms": INDEXER_LAST_SYNC_MS.load(Ordering::Relaxed),
+        "lag_secs": indexer_lag_secs(),
+        "events_processed": INDEXER_EVENTS_PROCESSED.load(Ordering::Relaxed),
+        "gaps_detected": INDEXER_GAPS_DETECTED.load(Ordering::Relaxed),
+        "ingest_latency_ms": latency,
+        "storage": growth::current()
+    }))
+}
+
+/// Reports error budget burn against the service level objectives.
+/// 
+/// Each objective counts good and bad events over the last `SLO_WINDOW_DAYS`
+/// (see `slo::OBJECTIVES`). `burn_rates` divide the error rate over each
+/// window by the rate the target allows, so alerts can fire on fast burn
+/// (e.g. `1h` above 14) rather than on raw error counts. Counts are kept in
+/// memory per process, from `tracked_since`.
+/// 
+/// # Endpoint
+/// `GET /api/slo`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "window_secs": 2592000,
+///   "tracked_since": 1751104133893,
+///   "data": [
+///     {
+///       "name": "availability",
+///       "description": "API requests answered without a 5xx",
+///       "target": 0.999,
+///       "good": 182340,
+///       "bad": 12,
+///       "error_rate": 0.0000658,
+///       "budget_remaining": 0.934,
+///       "burn_rates": { "1h": 0.0, "3d": 0.12, "5m": 0.0, "6h": 0.31 }
+///     }
+///   ]
+/// }
+/// ```
+async fn slo_handler() -> Json<serde_json::Value> {
+    Json(json!({
+        "status": "ok",
+        "window_secs": slo::window_secs(),
+        "tracked_since": SLO_TRACKED_SINCE_MS.load(Ordering::Relaxed),
+        "data": slo::slo_status()
+    }))
+}
+
+/// Default number of days covered by usage reports
+const DEFAULT_USAGE_DAYS: i64 = 30;
+
+/// Maximum number of days covered by usage reports
+const MAX_USAGE_DAYS: i64 = 365;
+
+/// Usage of one endpoint on one day
+#[derive(Serialize)]
+struct EndpointUsage {
+    day: i64,
+    endpoint: String,
+    requests: i64,
+    bytes_out: i64,
+}
+
+/// Usage totals for one API key
+#[derive(Serialize)]
+struct KeyUsage {
+    key_hash: String,
+    requests: i64,
+    bytes_out: i64,
+    endpoints: i64,
+    last_day: i64,
+}
+
+/// Parses the `days` query parameter of the usage endpoints into a cutoff timestamp.
+fn usage_since(params: &HashMap<String, String>) -> Result<(i64, i64), ApiError> {
+    let days = match params.get("days").map(|d| d.parse::<i64>()) {
+        None => DEFAULT_USAGE_DAYS,
+        Some(Ok(d)) if (1..=MAX_USAGE_DAYS).contains(&d) => d,
+        Some(_) => {
+            return Err(ApiError::BadRequest(format!(
+                "Query parameter `days` must be between 1 and {}",
+                MAX_USAGE_DAYS
+            )));
+        }
+    };
+    let now = now_ms();
+    let today = now - now.rem_euclid(86_400_000);
+    Ok((days, today - (days - 1) * 86_400_000))
+}
+
+/// Reports the calling API key's usage, per day and endpoint.
+/// 
+/// The key is taken from the `X-API-Key` header; only requests made with that
+/// header are counted.
+/// 
+/// # Endpoint
+/// `GET /api/keys/self/usage?days=30`
+/// 
+/// # Parameters
+/// * `days` - Number of days to report, including today (default 30, max 365)
+/// * `cursor` - Continuation cursor from a truncated response
+/// 
+/// Totals cover the whole period; `data` holds at most `MAX_RESPONSE_ROWS`
+/// rows per request.
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "days": 30,
+///   "requests": 1520,
+///   "bytes_out": 2840112,
+///   "data": [
+///     { "day": 1700006400000, "endpoint": "/api/pools", "requests": 1200, "bytes_out": 2400000 }
+///   ]
+/// }
+/// ```
+async fn key_usage_handler(
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let Some(api_key) = headers
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+    else {
+        return Err(ApiError::Unauthorized("Missing X-API-Key header".to_string()));
+    };
+    let (days, since) = usage_since(&params)?;
+    let page = Page::from_params(&params)?;
+    let key_hash = api_key_hash(api_key);
+
+    let conn = conn_arc.lock()?;
+    let (requests, bytes_out): (i64, i64) = conn.query_row(
+        "SELECT COALESCE(SUM(requests), 0), COALESCE(SUM(bytes_out), 0)
+         FROM usage
+         WHERE key_hash = ?1 AND day >= ?2",
+        params![key_hash, since],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT day, endpoint, requests, bytes_out
+         FROM usage
+         WHERE key_hash = ?1 AND day >= ?2
+         ORDER BY day DESC, requests DESC, endpoint{}",
+        page.sql()
+    ))?;
+    let mut usage: Vec<EndpointUsage> = stmt
+        .query_map(params![key_hash, since], |row| {
+            Ok(EndpointUsage {
+                day: row.get(0)?,
+                endpoint: row.get(1)?,
+                requests: row.get(2)?,
+                bytes_out: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut response = json!({
+        "status": "ok",
+        "days": days,
+        "requests": requests,
+        "bytes_out": bytes_out
+    });
+    page.finish(&mut usage, &mut response);
+    response["data"] = json!(usage);
+    Ok(Json(response))
+}
+
+/// Rolls up usage across all API keys. Requires the admin token.
+/// 
+/// Keys are identified by the SHA-256 of the key; raw keys are never stored.
+/// 
+/// # Endpoint
+/// `GET /api/admin/usage?days=30`
+/// 
+/// # Parameters
+/// * `days` - Number of days to report, including today (default 30, max 365)
+/// * `cursor` - Continuation cursor from a truncated response
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "days": 30,
+///   "data": [
+///     {
+///       "key_hash": "9f86d081884c7d65...",
+///       "requests": 1520,
+///       "bytes_out": 2840112,
+///       "endpoints": 4,
+///       "last_day": 1700006400000
+///     }
+///   ]
+/// }
+/// ```
+async fn admin_usage_handler(
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let (days, since) = usage_since(&params)?;
+    let page = Page::from_params(&params)?;
+
+    let conn = conn_arc.lock()?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT key_hash, SUM(requests), SUM(bytes_out), COUNT(DISTINCT endpoint), MAX(day)
+         FROM usage
+         WHERE day >= ?1
+         GROUP BY key_hash
+         ORDER BY SUM(requests) DESC, key_hash{}",
+        page.sql()
+    ))?;
+    let mut usage: Vec<KeyUsage> = stmt
+        .query_map([since], |row| {
+            Ok(KeyUsage {
+                key_hash: row.get(0)?,
+                requests: row.get(1)?,
+                bytes_out: row.get(2)?,
+                endpoints: row.get(3)?,
+                last_day: row.get(4)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut response = json!({ "status": "ok", "days": days });
+    page.finish(&mut usage, &mut response);
+    response["data"] = json!(usage);
+    Ok(Json(response))
+}
+
+/// Checks that a request carries the admin bearer token.
+/// 
+/// Admin endpoints are disabled entirely unless `ADMIN_TOKEN` is set; callers
+/// must then send `Authorization: Bearer <ADMIN_TOKEN>`.
+/// 
+/// # Arguments
+/// * `headers` - Request headers
+/// 
+/// # Returns
+/// * `Result<(), ApiError>` - Ok, or `Forbidden` when admin endpoints are
+///   disabled and `Unauthorized` when the token is missing or wrong
+pub fn authorize_admin(headers: &HeaderMap) -> Result<(), ApiError> {
+    let Some(expected) = &config().admin_token else {
+        return Err(ApiError::Forbidden("Admin endpoints are disabled".to_string()));
+    };
+    let provided = headers
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    if !provided.is_some_and(|provided| tokens_match(provided, expected)) {
+        return Err(ApiError::Unauthorized("Invalid or missing admin token".to_string()));
+    }
+    Ok(())
+}
+
+/// Compares two tokens in time independent of where they differ, so the
+/// admin token can't be guessed byte by byte from response times. Comparing
+/// their digests also hides the token's length.
+fn tokens_match(provided: &str, expected: &str) -> bool {
+    let (provided, expected) = (Sha256::digest(provided.as_bytes()), Sha256::digest(expected.as_bytes()));
+    provided.iter().zip(expected.iter()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Lists every feature flag and where its current state comes from.
+/// Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/features`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "name": "best_price",
+///       "description": "GET /api/best-price",
+///       "enabled": false,
+///       "source": "override"
+///     }
+///   ]
+/// }
+/// ```
+async fn list_features_handler(
+    headers: HeaderMap,
+    Extension(flags): Extension<FeatureFlags>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let guard = flags.read()?;
+    let mut states: Vec<&FeatureState> = guard.values().collect();
+    states.sort_by_key(|s| s.name);
+    Ok(Json(json!({ "status": "ok", "data": states })))
+}
+
+/// Request body for overriding a feature flag
+#[derive(Deserialize)]
+struct FeatureUpdate {
+    enabled: bool,
+}
+
+/// Enables or disables a feature at runtime. Requires the admin token.
+/// 
+/// The override is persisted and survives restarts until it is cleared.
+/// 
+/// # Endpoint
+/// `PUT /api/admin/features/:name` with body `{"enabled": false}`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": { "name": "best_price", "description": "GET /api/best-price", "enabled": false, "source": "override" }
+/// }
+/// ```
+async fn set_feature_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Extension(flags): Extension<FeatureFlags>,
+    Json(update): Json<FeatureUpdate>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let (name, description) = feature(&name).ok_or_else(|| unknown_feature(&name))?;
+
+    let conn = conn_arc.lock()?;
+    set_feature_override(&conn, name, update.enabled, now_ms())?;
+    let state = FeatureState { name, description, enabled: update.enabled, source: FlagSource::Override };
+    flags.write()?.insert(name, state.clone());
+    Ok(Json(json!({ "status": "ok", "data": state })))
+}
+
+/// Clears a feature's admin override, returning it to its configured state.
+/// Requires the admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/features/:name`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": { "name": "best_price", "description": "GET /api/best-price", "enabled": true, "source": "default" }
+/// }
+/// ```
+async fn clear_feature_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Extension(flags): Extension<FeatureFlags>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let (name, description) = feature(&name).ok_or_else(|| unknown_feature(&name))?;
+
+    let conn = conn_arc.lock()?;
+    clear_feature_override(&conn, name)?;
+    let state = configured_state(name, description);
+    flags.write()?.insert(name, state.clone());
+    Ok(Json(json!({ "status": "ok", "data": state })))
+}
+
+/// Error for a feature name that doesn't exist.
+fn unknown_feature(name: &str) -> ApiError {
+    ApiError::NotFound(format!("Unknown feature `{}`", name))
+}
+
+/// Longest display name accepted for a pool
+const MAX_DISPLAY_NAME_LEN: usize = 64;
+
+/// Longest warning text accepted for a pool
+const MAX_WARNING_LEN: usize = 500;
+
+/// Body of a pool metadata override; omitted fields are cleared
+#[derive(Deserialize)]
+struct PoolOverrideUpdate {
+    display_name: Option<String>,
+    logo_url: Option<String>,
+    #[serde(default)]
+    verified: bool,
+    warning: Option<String>,
+}
+
+/// Lists the display metadata admins have set for pools. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/pool-overrides`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "pool_id": "0x...",
+///       "display_name": "SUI/USDC",
+///       "logo_url": "https://example.com/sui-usdc.png",
+///       "verified": true,
+///       "warning": null,
+///       "updated_at": 1751104133893
+///     }
+///   ]
+/// }
+/// ```
+async fn list_pool_overrides_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let overrides = list_pool_overrides(&*conn_arc.lock()?)?;
+    Ok(Json(json!({ "status": "ok", "data": overrides })))
+}
+
+/// Sets a pool's display name, logo, verified flag and warning text, which
+/// are merged into `/api/pools`. Requires the admin token.
+/// 
+/// The body replaces any previous override for the pool. Empty strings are
+/// treated as unset, and the logo must be an `http` or `https` URL.
+/// 
+/// # Endpoint
+/// `PUT /api/admin/pool-overrides/:pool_id` with body
+/// `{"display_name": "SUI/USDC", "logo_url": "https://...", "verified": true, "warning": null}`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "pool_id": "0x...",
+///     "display_name": "SUI/USDC",
+///     "logo_url": "https://example.com/sui-usdc.png",
+///     "verified": true,
+///     "warning": null,
+///     "updated_at": 1751104133893
+///   }
+/// }
+/// ```
+async fn set_pool_override_handler(
+    headers: HeaderMap,
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(update): Json<PoolOverrideUpdate>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let non_empty = |v: Option<String>| v.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let entry = PoolOverride {
+        pool_id,
+        display_name: non_empty(update.display_name),
+        logo_url: non_empty(update.logo_url),
+        verified: update.verified,
+        warning: non_empty(update.warning),
+        updated_at: now_ms(),
+    };
+    let invalid = if entry.display_name.as_ref().is_some_and(|n| n.chars().count() > MAX_DISPLAY_NAME_LEN) {
+        Some(format!("`display_name` must be at most {} characters", MAX_DISPLAY_NAME_LEN))
+    } else if entry.warning.as_ref().is_some_and(|w| w.chars().count() > MAX_WARNING_LEN) {
+        Some(format!("`warning` must be at most {} characters", MAX_WARNING_LEN))
+    } else if entry
+        .logo_url
+        .as_ref()
+        .is_some_and(|u| !u.starts_with("https://") && !u.starts_with("http://"))
+    {
+        Some("`logo_url` must be an http or https URL".to_string())
+    } else {
+        None
+    };
+    if let Some(message) = invalid {
+        return Err(ApiError::BadRequest(message));
+    }
+
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &entry.pool_id)?;
+    set_pool_override(&conn, &entry)?;
+    Ok(Json(json!({ "status": "ok", "data": entry })))
+}
+
+/// Marks a pool verified (`PUT`) or unverified (`DELETE`), leaving the rest
+/// of its override untouched. Requires the admin token.
+/// 
+/// Verified pools carry `"verified": true` in `/api/pools` and are the ones
+/// listed with `verified_only=true`.
+/// 
+/// # Endpoint
+/// `PUT|DELETE /api/admin/pool-overrides/:pool_id/verified`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "pool_id": "0x...",
+///     "display_name": null,
+///     "logo_url": null,
+///     "verified": true,
+///     "warning": null,
+///     "updated_at": 1751104133893
+///   }
+/// }
+/// ```
+async fn pool_verified_handler(
+    method: Method,
+    headers: HeaderMap,
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let conn = conn_arc.lock()?;
+    require_pool(&conn, &pool_id)?;
+    let entry = set_pool_verified(&conn, &pool_id, method == Method::PUT, now_ms())?;
+    Ok(Json(json!({ "status": "ok", "data": entry })))
+}
+
+/// Removes a pool's metadata override. Requires the admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/pool-overrides/:pool_id`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "pool_id": "0x..." }
+/// ```
+async fn clear_pool_override_handler(
+    headers: HeaderMap,
+    Path(pool_id): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    if !clear_pool_override(&*conn_arc.lock()?, &pool_id)? {
+        return Err(ApiError::NotFound(format!("No override set for pool {}", pool_id)));
+    }
+    Ok(Json(json!({ "status": "ok", "pool_id": pool_id })))
+}
+
+/// Longest pair alias accepted
+const MAX_PAIR_ALIAS_LEN: usize = 32;
+
+/// Body of a pair alias
+#[derive(Deserialize)]
+struct PairAliasUpdate {
+    token_a: String,
+    token_b: String,
+}
+
+/// Lists the pair aliases admins have registered. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/pair-aliases`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "alias": "SUI-USDC",
+///       "token_a": "0x2::sui::SUI",
+///       "token_b": "0x...::usdc::USDC",
+///       "updated_at": 1751104133893
+///     }
+///   ]
+/// }
+/// ```
+async fn list_pair_aliases_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let aliases = list_pair_aliases(&*conn_arc.lock()?)?;
+    Ok(Json(json!({ "status": "ok", "data": aliases })))
+}
+
+/// Registers a short name for a token pair, accepted wherever a `pair`
+/// query parameter is and returned as `alias` in those responses. Requires
+/// the admin token.
+/// 
+/// Aliases are matched case-insensitively and may use letters, digits, `-`,
+/// `_` and `.`, up to `MAX_PAIR_ALIAS_LEN` characters. An alias names the
+/// pair in the given order; setting an existing alias replaces its pair. The
+/// pair isn't required to be traded yet, so aliases can be registered ahead
+/// of a pool's launch.
+/// 
+/// # Endpoint
+/// `PUT /api/admin/pair-aliases/:alias` with body
+/// `{"token_a": "0x2::sui::SUI", "token_b": "0x...::usdc::USDC"}`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "alias": "SUI-USDC",
+///     "token_a": "0x2::sui::SUI",
+///     "token_b": "0x...::usdc::USDC",
+///     "updated_at": 1751104133893
+///   }
+/// }
+/// ```
+async fn set_pair_alias_handler(
+    headers: HeaderMap,
+    Path(alias): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(update): Json<PairAliasUpdate>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let entry = PairAlias {
+        alias,
+        token_a: update.token_a.trim().to_string(),
+        token_b: update.token_b.trim().to_string(),
+        updated_at: now_ms(),
+    };
+    let valid_token = |t: &str| !t.is_empty() && !t.contains('/');
+    let invalid = if entry.alias.is_empty() || entry.alias.chars().count() > MAX_PAIR_ALIAS_LEN {
+        Some(format!("Alias must be 1 to {} characters", MAX_PAIR_ALIAS_LEN))
+    } else if !entry.alias.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+        Some("Alias may only contain letters, digits, `-`, `_` and `.`".to_string())
+    } else if !valid_token(&entry.token_a) || !valid_token(&entry.token_b) {
+        Some("`token_a` and `token_b` must be non-empty coin types".to_string())
+    } else if entry.token_a == entry.token_b {
+        Some("`token_a` and `token_b` must differ".to_string())
+    } else {
+        None
+    };
+    if let Some(message) = invalid {
+        return Err(ApiError::BadRequest(message));
+    }
+
+    set_pair_alias(&*conn_arc.lock()?, &entry)?;
+    Ok(Json(json!({ "status": "ok", "data": entry })))
+}
+
+/// Removes a pair alias. Requires the admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/pair-aliases/:alias`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "alias": "SUI-USDC" }
+/// ```
+async fn delete_pair_alias_handler(
+    headers: HeaderMap,
+    Path(alias): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    if !delete_pair_alias(&*conn_arc.lock()?, &alias)? {
+        return Err(ApiError::NotFound(format!("No pair alias `{}`", alias)));
+    }
+    Ok(Json(json!({ "status": "ok", "alias": alias })))
+}
+
+/// Checks that a path segment is an API key hash as `api_key_hash` makes it.
+fn key_hash_param(key_hash: &str) -> Result<String, ApiError> {
+    let key_hash = key_hash.trim().to_ascii_lowercase();
+    if key_hash.len() != 64 || !key_hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(ApiError::BadRequest("Key hash must be 64 hex digits, the SHA-256 of the API key".to_string()));
+    }
+    Ok(key_hash)
+}
+
+/// Lists the addresses registered for an API key. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/keys/:key_hash/addresses`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": { "key_hash": "9f86d0...", "addresses": ["0x..."] }
+/// }
+/// ```
+async fn list_key_addresses_handler(
+    headers: HeaderMap,
+    Path(key_hash): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let key_hash = key_hash_param(&key_hash)?;
+    let addresses = api_key_addresses(&*conn_arc.lock()?, &key_hash)?;
+    Ok(Json(json!({ "status": "ok", "data": { "key_hash": key_hash, "addresses": addresses } })))
+}
+
+/// Registers an address for an API key, so WebSocket clients authenticated
+/// with the key may subscribe to the address's private channel. Requires the
+/// admin token.
+/// 
+/// Keys are named by their SHA-256 hash, as in `GET /api/admin/usage`, so
+/// the key itself never appears in a URL. Registering an address twice
+/// changes nothing.
+/// 
+/// # Endpoint
+/// `PUT /api/admin/keys/:key_hash/addresses/:address`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": { "key_hash": "9f86d0...", "addresses": ["0x..."] }
+/// }
+/// ```
+async fn add_key_address_handler(
+    headers: HeaderMap,
+    Path((key_hash, address)): Path<(String, String)>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let key_hash = key_hash_param(&key_hash)?;
+    let address = normalize_address(&address)
+        .ok_or_else(|| ApiError::BadRequest("Address must be 0x followed by up to 64 hex digits".to_string()))?;
+    let conn = conn_arc.lock()?;
+    add_api_key_address(&conn, &key_hash, &address, now_ms())?;
+    let addresses = api_key_addresses(&conn, &key_hash)?;
+    Ok(Json(json!({ "status": "ok", "data": { "key_hash": key_hash, "addresses": addresses } })))
+}
+
+/// Removes an address from an API key. WebSocket connections already
+/// subscribed to its channel keep it until they reconnect. Requires the
+/// admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/keys/:key_hash/addresses/:address`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "key_hash": "9f86d0...", "address": "0x..." }
+/// ```
+async fn remove_key_address_handler(
+    headers: HeaderMap,
+    Path((key_hash, address)): Path<(String, String)>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let key_hash = key_hash_param(&key_hash)?;
+    let address = address.trim().to_ascii_lowercase();
+    if !remove_api_key_address(&*conn_arc.lock()?, &key_hash, &address)? {
+        return Err(ApiError::NotFound(format!("Address {} is not registered for the key", address)));
+    }
+    Ok(Json(json!({ "status": "ok", "key_hash": key_hash, "address": address })))
+}
+
+/// Most labels accepted in one import
+const MAX_LABEL_IMPORT: usize = 5000;
+
+/// Longest label accepted for an address
+const MAX_LABEL_LEN: usize = 64;
+
+/// Longest label category accepted
+const MAX_LABEL_CATEGORY_LEN: usize = 32;
+
+/// Body of an address label import
+#[derive(Deserialize)]
+struct LabelImport {
+    labels: Vec<LabelEntry>,
+}
+
+/// One address of a label import
+#[derive(Deserialize)]
+struct LabelEntry {
+    address: String,
+    label: String,
+    category: Option<String>,
+}
+
+/// Normalizes a Sui address to lowercase, `None` unless it is `0x`
+/// followed by 1 to 64 hex digits.
+pub fn normalize_address(address: &str) -> Option<String> {
+    let address = address.trim().to_ascii_lowercase();
+    let digits = address.strip_prefix("0x")?;
+    (!digits.is_empty() && digits.len() <= 64 && digits.chars().all(|c| c.is_ascii_hexdigit())).then_some(address)
+}
+
+/// Lists the address labels admins have imported. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/labels`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": [
+///     {
+///       "address": "0x...",
+///       "label": "Acme Market Making",
+///       "category": "market_maker",
+///       "updated_at": 1751104133893
+///     }
+///   ]
+/// }
+/// ```
+async fn list_labels_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let labels = list_labels(&*conn_arc.lock()?)?;
+    Ok(Json(json!({ "status": "ok", "data": labels })))
+}
+
+/// Imports labels for known addresses (market makers, team wallets, bots),
+/// which annotate the senders of swaps in `/api/swaps`. Requires the admin
+/// token.
+/// 
+/// Each entry creates or replaces the label of its address. Addresses are
+/// matched case-insensitively; labels are at most `MAX_LABEL_LEN` characters
+/// and categories at most `MAX_LABEL_CATEGORY_LEN`, with empty categories
+/// treated as unset. An import of up to `MAX_LABEL_IMPORT` entries is applied
+/// all or nothing: an invalid entry rejects the whole batch, naming its
+/// index.
+/// 
+/// # Endpoint
+/// `POST /api/admin/labels` with body
+/// `{"labels": [{"address": "0x...", "label": "Acme Market Making", "category": "market_maker"}]}`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "imported": 1 }
+/// ```
+async fn import_labels_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(import): Json<LabelImport>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    if import.labels.is_empty() || import.labels.len() > MAX_LABEL_IMPORT {
+        return Err(ApiError::BadRequest(format!(
+            "`labels` must hold 1 to {} entries",
+            MAX_LABEL_IMPORT
+        )));
+    }
+
+    let now = now_ms();
+    let mut labels = Vec::with_capacity(import.labels.len());
+    for (i, entry) in import.labels.into_iter().enumerate() {
+        let invalid = |message: &str| ApiError::BadRequest(format!("labels[{}]: {}", i, message));
+        let address = normalize_address(&entry.address)
+            .ok_or_else(|| invalid("`address` must be 0x followed by up to 64 hex digits"))?;
+        let label = entry.label.trim().to_string();
+        if label.is_empty() || label.chars().count() > MAX_LABEL_LEN {
+            return Err(invalid(&format!("`label` must be 1 to {} characters", MAX_LABEL_LEN)));
+        }
+        let category = entry.category.map(|c| c.trim().to_string()).filter(|c| !c.is_empty());
+        if category.as_ref().is_some_and(|c| c.chars().count() > MAX_LABEL_CATEGORY_LEN) {
+            return Err(invalid(&format!("`category` must be at most {} characters", MAX_LABEL_CATEGORY_LEN)));
+        }
+        labels.push(AddressLabel { address, label, category, updated_at: now });
+    }
+
+    import_labels(&*conn_arc.lock()?, &labels)?;
+    Ok(Json(json!({ "status": "ok", "imported": labels.len() })))
+}
+
+/// Removes the label of an address. Requires the admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/labels/:address`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "address": "0x..." }
+/// ```
+async fn delete_label_handler(
+    headers: HeaderMap,
+    Path(address): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let normalized = normalize_address(&address).unwrap_or(address);
+    if !delete_label(&*conn_arc.lock()?, &normalized)? {
+        return Err(ApiError::NotFound(format!("No label for address {}", normalized)));
+    }
+    Ok(Json(json!({ "status": "ok", "address": normalized })))
+}
+
+/// Error for a pool ID that doesn't exist.
+fn unknown_pool(pool_id: &str) -> ApiError {
+    ApiError::NotFound(format!("No pool found with id {}", pool_id))
+}
+
+/// Fails with `unknown_pool` unless the pool exists.
+fn require_pool(conn: &Connection, pool_id: &str) -> Result<(), ApiError> {
+    let exists: bool =
+        conn.query_row("SELECT EXISTS(SELECT 1 FROM pools WHERE pool_id = ?1)", [pool_id], |row| row.get(0))?;
+    if exists {
+        Ok(())
+    } else {
+        Err(unknown_pool(pool_id))
+    }
+}
+
+/// Body of a cache invalidation sent by the indexer node
+#[derive(Deserialize)]
+struct Invalidation {
+    #[serde(default)]
+    pool_ids: Vec<String>,
+    /// End of the indexer node's last completed polling cycle
+    last_sync_ms: Option<u64>,
+}
+
+/// Invalidates this replica's caches after the indexer node committed
+/// changes to the given pools. Requires the admin token.
+/// 
+/// The pools are reloaded into the pool cache (if enabled) from the shared
+/// database, and cached responses are dropped so the next request recomputes
+/// them. `last_sync_ms` is the indexer node's progress; a replica without its
+/// own indexer measures staleness from it. Sent by the indexer node to every
+/// address in `INVALIDATION_PEERS`.
+/// 
+/// # Endpoint
+/// `POST /api/admin/invalidate` with body `{"pool_ids": ["0x..."], "last_sync_ms": 1751104133893}`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "pools": 1 }
+/// ```
+async fn invalidate_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    pool_cache: Option<Extension<PoolCache>>,
+    Extension(graph_cache): Extension<GraphCache>,
+    response_cache: Option<Extension<ResponseCache>>,
+    Json(invalidation): Json<Invalidation>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    CACHE_INVALIDATIONS_RECEIVED.fetch_add(1, Ordering::Relaxed);
+    if let Some(ms) = invalidation.last_sync_ms {
+        INDEXER_LAST_SYNC_MS.fetch_max(ms, Ordering::Relaxed);
+    }
+    if invalidation.pool_ids.is_empty() {
+        return Ok(Json(json!({ "status": "ok", "pools": 0 })));
+    }
+
+    let pool_cache = pool_cache.map(|Extension(cache)| cache);
+    {
+        let conn = conn_arc.lock()?;
+        if let Some(cache) = &pool_cache {
+            reload_pools(&conn, cache, &invalidation.pool_ids)?;
+        }
+        pools_changed(&conn, pool_cache.as_ref(), &graph_cache, &invalidation.pool_ids)?;
+    }
+    notify_pool_updates();
+    if let Some(Extension(cache)) = response_cache {
+        if let Ok(mut guard) = cache.write() {
+            guard.clear();
+        }
+    }
+    Ok(Json(json!({ "status": "ok", "pools": invalidation.pool_ids.len() })))
+}
+
+/// Reports the size and staleness of the memoized routing graph.
+/// 
+/// The graph is rebuilt when a pool is created or removed, or when a pool's
+/// reserves move by more than `GRAPH_REFRESH_THRESHOLD` since the last build;
+/// smaller moves are reported as drift. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/graph`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "version": 42,
+///     "built_at": 1751104133893,
+///     "age_ms": 5120,
+///     "tokens": 12,
+///     "pools": 18,
+///     "drained_pools": 1,
+///     "drifted_pools": 2,
+///     "max_drift": 0.0004,
+///     "stale_since": 1751104135012,
+///     "refresh_threshold": 0.001
+///   }
+/// }
+/// ```
+async fn admin_graph_handler(
+    headers: HeaderMap,
+    Extension(graph_cache): Extension<GraphCache>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    Ok(Json(json!({ "status": "ok", "data": graph_stats(&graph_cache)? })))
+}
+
+/// Compacts the database on demand, e.g. during a low-traffic window.
+/// 
+/// `mode=incremental` (the default) only releases free pages; `mode=full`
+/// runs a full `VACUUM`, which rewrites the file and blocks writers while it
+/// runs. Requires the admin token.
+/// 
+/// # Endpoint
+/// `POST /api/admin/vacuum?mode=incremental|full`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "mode": "incremental",
+///   "size_before": 104857600,
+///   "size_after": 73400320,
+///   "free_bytes_after": 0
+/// }
+/// ```
+async fn vacuum_handler(
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let full = match params.get("mode").map(String::as_str) {
+        None | Some("incremental") => false,
+        Some("full") => true,
+        Some(_) => {
+            return Err(ApiError::BadRequest(
+                "Query parameter `mode` must be `incremental` or `full`".to_string(),
+            ));
+        }
+    };
+
+    let conn = conn_arc.lock()?;
+    let before = record_db_size(&conn)?;
+    compact(&conn, full)?;
+    let after = record_db_size(&conn)?;
+
+    Ok(Json(json!({
+        "status": "ok",
+        "mode": if full { "full" } else { "incremental" },
+        "size_before": before.total_bytes,
+        "size_after": after.total_bytes,
+        "free_bytes_after": after.free_bytes
+    })))
+}
+
+/// Longest name accepted for a custom metric
+const MAX_METRIC_NAME_LEN: usize = 64;
+
+/// Most custom metrics that can be registered at once
+const MAX_CUSTOM_METRICS: usize = 50;
+
+/// Body of a custom metric registration
+#[derive(Deserialize)]
+struct CustomMetricUpdate {
+    expression: String,
+}
+
+/// Lists the registered custom metrics and the columns their expressions
+/// can use. Requires the admin token.
+/// 
+/// # Endpoint
+/// `GET /api/admin/metrics/custom`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "columns": [{ "name": "tvl", "description": "Liquidity valued in token B, 2 × reserve_b" }],
+///   "data": [
+///     {
+///       "name": "turnover",
+///       "expression": "volume_b_24h / tvl",
+///       "created_at": 1751104133893,
+///       "updated_at": 1751104133893
+///     }
+///   ]
+/// }
+/// ```
+async fn list_custom_metrics_handler(
+    headers: HeaderMap,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    let metrics = list_custom_metrics(&*conn_arc.lock()?)?;
+    let columns: Vec<_> = METRIC_COLUMNS
+        .iter()
+        .map(|(name, description)| json!({ "name": name, "description": description }))
+        .collect();
+    Ok(Json(json!({ "status": "ok", "columns": columns, "data": metrics })))
+}
+
+/// Registers a custom metric, or replaces the expression of an existing
+/// one, and computes it for every pool right away. Requires the admin token.
+/// 
+/// Expressions combine numbers and the columns listed by
+/// `list_custom_metrics_handler` with `+`, `-`, `*`, `/` and parentheses.
+/// Afterwards the metric is recomputed in the background once a minute.
+/// Names are 1 to 64 lowercase letters, digits and underscores.
+/// 
+/// # Endpoint
+/// `PUT /api/admin/metrics/custom/:name` with body `{"expression": "volume_b_24h / tvl"}`
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "data": {
+///     "name": "turnover",
+///     "expression": "volume_b_24h / tvl",
+///     "created_at": 1751104133893,
+///     "updated_at": 1751104133893
+///   }
+/// }
+/// ```
+async fn set_custom_metric_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+    Json(update): Json<CustomMetricUpdate>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    if name.is_empty()
+        || name.len() > MAX_METRIC_NAME_LEN
+        || !name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Metric names must be 1 to {} lowercase letters, digits and underscores",
+            MAX_METRIC_NAME_LEN
+        )));
+    }
+    let expression = update.expression.trim();
+    Expr::parse(expression).map_err(|e| ApiError::BadRequest(format!("Invalid `expression`: {}", e)))?;
+
+    let conn = conn_arc.lock()?;
+    let metrics = list_custom_metrics(&conn)?;
+    if metrics.len() >= MAX_CUSTOM_METRICS && !metrics.iter().any(|m| m.name == name) {
+        return Err(ApiError::Conflict(format!(
+            "At most {} custom metrics can be registered",
+            MAX_CUSTOM_METRICS
+        )));
+    }
+    let now = now_ms();
+    let metric = set_custom_metric(&conn, &name, expression, now)?;
+    compute_custom_metrics(&conn, std::slice::from_ref(&metric), now)?;
+    Ok(Json(json!({ "status": "ok", "data": metric })))
+}
+
+/// Removes a custom metric and its values. Requires the admin token.
+/// 
+/// # Endpoint
+/// `DELETE /api/admin/metrics/custom/:name`
+/// 
+/// # Response Format
+/// ```json
+/// { "status": "ok", "name": "turnover" }
+/// ```
+async fn clear_custom_metric_handler(
+    headers: HeaderMap,
+    Path(name): Path<String>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    authorize_admin(&headers)?;
+    if !clear_custom_metric(&*conn_arc.lock()?, &name)? {
+        return Err(unknown_custom_metric(&name));
+    }
+    Ok(Json(json!({ "status": "ok", "name": name })))
+}
+
+/// Value of a custom metric for one pool
+#[derive(Serialize)]
+struct CustomMetricValue {
+    pool_id: String,
+    token_a: String,
+    token_b: String,
+    /// `null` if the expression is undefined for the pool, e.g. divides by zero
+    value: Option<f64>,
+}
+
+/// Serves the latest values of a custom metric registered by an admin, one
+/// per pool, highest first with undefined values last.
+/// 
+/// # Endpoint
+/// `GET /api/metrics/custom/:name`
+/// 
+/// # Query Parameters
+/// * `cursor` (optional) - Continue from a previous truncated response
+/// 
+/// # Response Format
+/// ```json
+/// {
+///   "status": "ok",
+///   "name": "turnover",
+///   "expression": "volume_b_24h / tvl",
+///   "computed_at": 1751104133893,
+///   "data": [
+///     { "pool_id": "0x...", "token_a": "0x...", "token_b": "0x...", "value": 0.42 }
+///   ]
+/// }
+/// ```
+async fn custom_metric_handler(
+    Path(name): Path<String>,
+    Query(params): Query<HashMap<String, String>>,
+    Extension(conn_arc): Extension<Arc<Mutex<Connection>>>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    let page = Page::from_params(&params)?;
+    let conn = conn_arc.lock()?;
+    let metric = custom_metric(&conn, &name)?.ok_or_else(|| unknown_custom_metric(&name))?;
+
+    let computed_at: Option<i64> = conn.query_row(
+        "SELECT MAX(computed_at) FROM custom_metric_values WHERE name = ?1",
+        [&name],
+        |row| row.get(0),
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT v.pool_id, p.token_a, p.token_b, v.value
+         FROM custom_metric_values v
+         JOIN pools p ON p.pool_id = v.pool_id
+         WHERE v.name = ?1
+         ORDER BY v.value IS NULL, v.value DESC, v.pool_id{}",
+        page.sql()
+    ))?;
+    let mut values: Vec<CustomMetricValue> = stmt
+        .query_map([&name], |row| {
+            Ok(CustomMetricValue {
+                pool_id: row.get(0)?,
+                token_a: row.get(1)?,
+                token_b: row.get(2)?,
+                value: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut response = json!({
+        "status": "ok",
+        "name": metric.name,
+        "expression": metric.expression,
+        "computed_at": computed_at
+    });
+    page.finish(&mut values, &mut response);
+    response["data"] = json!(values);
+    Ok(Json(response))
+}
+
+/// Error for a custom metric name that isn't registered.
+fn unknown_custom_metric(name: &str) -> ApiError {
+    ApiError::NotFound(format!("Unknown custom metric `{}`", name))
+}
+
+/// Creates and returns the API router with all DEX endpoints.
+/// 
+/// This function configures all the HTTP routes for the DEX API,
+/// including pools, swaps, and price calculation endpoints. Market data
+/// endpoints are wrapped in the stale-data guard from `freshness`, and
+/// routes with a configured `response_cache::CachePolicy` are served from
+/// the response cache.
+/// 
+/// # Returns
+/// * `Router` - Axum router configured with all API routes
+pub fn api_routes() -> Router {
+    // Live market data and stats, guarded against serving stale data as live
+    let market = Router::new()
+        .route("/pools", get(pools_handler))
+        .route("/pools/checksum", get(pools_checksum_handler))
+        .route("/pools/changed", get(pools_changed_handler))
+        .route("/pools/:pool_id", get(pool_detail_handler))
+        .route(
+            "/pools/:pool_id/volume",
+            get(pool_volume_handler).layer(middleware::from_fn_with_state("pool_volume", require_feature)),
+        )
+        .route(
+            "/pools/:pool_id/velocity",
+            get(velocity_handler).layer(middleware::from_fn_with_state("velocity", require_feature)),
+        )
+        .route(
+            "/tokens",
+            get(tokens_handler).layer(middleware::from_fn_with_state("tokens", require_feature)),
+        )
+        .route("/price", get(price_handler))
+        .route(
+            "/ticker",
+            get(ticker_handler).layer(middleware::from_fn_with_state("ticker", require_feature)),
+        )
+        .route(
+            "/movers",
+            get(movers_handler).layer(middleware::from_fn_with_state("movers", require_feature)),
+        )
+        .route(
+            "/graph",
+            get(graph_handler).layer(middleware::from_fn_with_state("graph", require_feature)),
+        )
+        .route(
+            "/price/wait",
+            get(price_wait_handler).layer(middleware::from_fn_with_state("price_wait", require_feature)),
+        )
+        .route(
+            "/quote",
+            get(quote_handler).layer(middleware::from_fn_with_state("quote", require_feature)),
+        )
+        .route(
+            "/best-price",
+            get(best_price_handler).layer(middleware::from_fn_with_state("best_price", require_feature)),
+        )
+        // The guard wraps the cache so that cached responses are flagged too
+        .layer(middleware::from_fn(cache_response))
+        .layer(middleware::from_fn(degrade_when_stale));
+
+    Router::new()
+        .route(
+            "/pools/:pool_id/size-histogram",
+            get(size_histogram_handler).layer(middleware::from_fn_with_state("size_histogram", require_feature)),
+        )
+        .route(
+            "/pools/:pool_id/failures",
+            get(failures_handler).layer(middleware::from_fn_with_state("failures", require_feature)),
+        )
+        .route(
+            "/pools/:pool_id/at",
+            get(pool_at_handler).layer(middleware::from_fn_with_state("pool_history", require_feature)),
+        )
+        .route("/swaps/:pool_id", get(swaps_handler))
+        .route(
+            "/candles",
+            get(candles_handler).layer(middleware::from_fn_with_state("candles", require_feature)),
+        )
+        .route(
+            "/candles/download",
+            get(candles_download_handler).layer(middleware::from_fn_with_state("candles", require_feature)),
+        )
+        .route(
+            "/candles/batch",
+            post(candles_batch_handler).layer(middleware::from_fn_with_state("candles", require_feature)),
+        )
+        .route(
+            "/tape/:pool_id",
+            get(tape_handler).layer(middleware::from_fn_with_state("tape", require_feature)),
+        )
+        .route("/indexer/status", get(indexer_status_handler))
+        .route("/slo", get(slo_handler))
+        .route(
+            "/checkpoints/:seq/swaps",
+            get(checkpoint_swaps_handler).layer(middleware::from_fn_with_state("checkpoint_swaps", require_feature)),
+        )
+        .route(
+            "/exports",
+            post(create_export_handler)
+                .layer(middleware::from_fn(idempotent))
+                .layer(middleware::from_fn_with_state("exports", require_feature)),
+        )
+        .route(
+            "/exports/:id",
+            get(export_status_handler).layer(middleware::from_fn_with_state("exports", require_feature)),
+        )
+        .route(
+            "/exports/:id/download",
+            get(export_download_handler).layer(middleware::from_fn_with_state("exports", require_feature)),
+        )
+        .route(
+            "/reports",
+            get(list_reports_handler).layer(middleware::from_fn_with_state("reports", require_feature)),
+        )
+        .route(
+            "/reports/:name",
+            get(report_handler).layer(middleware::from_fn_with_state("reports", require_feature)),
+        )
+        .route(
+            "/metrics/custom/:name",
+            get(custom_metric_handler).layer(middleware::from_fn_with_state("custom_metrics", require_feature)),
+        )
+        .route(
+            "/routing/accuracy",
+            get(routing_accuracy_handler)
+                .layer(middleware::from_fn_with_state("routing_accuracy", require_feature)),
+        )
+        .route(
+            "/quotes/conversion",
+            get(quote_conversion_handler)
+                .layer(middleware::from_fn_with_state("quote_conversion", require_feature)),
+        )
+        .route(
+            "/keys/self/usage",
+            get(key_usage_handler).layer(middleware::from_fn_with_state("usage", require_feature)),
+        )
+        .route("/admin/usage", get(admin_usage_handler))
+        .route("/admin/features", get(list_features_handler))
+        .route("/admin/features/:name", put(set_feature_handler).delete(clear_feature_handler))
+        .route("/admin/pool-overrides", get(list_pool_overrides_handler))
+        .route(
+            "/admin/pool-overrides/:pool_id",
+            put(set_pool_override_handler).delete(clear_pool_override_handler),
+        )
+        .route(
+            "/admin/pool-overrides/:pool_id/verified",
+            put(pool_verified_handler).delete(pool_verified_handler),
+        )
+        .route(
+            "/admin/labels",
+            get(list_labels_handler).merge(post(import_labels_handler).layer(middleware::from_fn(idempotent))),
+        )
+        .route("/admin/labels/:address", delete(delete_label_handler))
+        .route("/admin/pair-aliases", get(list_pair_aliases_handler))
+        .route(
+            "/admin/pair-aliases/:alias",
+            put(set_pair_alias_handler).delete(delete_pair_alias_handler),
+        )
+        .route("/admin/keys/:key_hash/addresses", get(list_key_addresses_handler))
+        .route(
+            "/admin/keys/:key_hash/addresses/:address",
+            put(add_key_address_handler).delete(remove_key_address_handler),
+        )
+        .route("/admin/invalidate", post(invalidate_handler))
+        .route("/admin/graph", get(admin_graph_handler))
+        .route("/admin/vacuum", post(vacuum_handler))
+        .route("/admin/metrics/custom", get(list_custom_metrics_handler))
+        .route(
+            "/admin/metrics/custom/:name",
+            put(set_custom_metric_handler).delete(clear_custom_metric_handler),
+        )
+        .layer(middleware::from_fn(cache_response))
+        .merge(market)
+        .layer(middleware::from_fn(debug_response))
+        .layer(middleware::from_fn(track_usage))
+        .layer(middleware::from_fn(track_availability))
+        .layer(middleware::from_fn(track_http_metrics))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pool with five swaps, whose `amount_in` and `value_quote` are their
+    /// IDs; swaps 2 and 3 share a timestamp.
+    fn swaps_db() -> Arc<Mutex<Connection>> {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        conn.execute("INSERT INTO pools (pool_id, token_a, token_b) VALUES ('0xpool', '0xa::a::A', '0xb::b::B')", [])
+            .unwrap();
+        for (id, timestamp) in [(1, 1000), (2, 2000), (3, 2000), (4, 3000), (5, 4000)] {
+            conn.execute(
+                "INSERT INTO swaps (id, pool_id, amount_in, amount_out, timestamp, tx_digest, value_quote)
+                 VALUES (?1, '0xpool', ?1, 1.0, ?2, 'tx' || ?1, ?1)",
+                params![id, timestamp],
+            )
+            .unwrap();
+        }
+        Arc::new(Mutex::new(conn))
+    }
+
+    async fn swaps_page(conn_arc: &Arc<Mutex<Connection>>, query: &[(&str, &str)]) -> Result<serde_json::Value, ApiError> {
+        let params = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        swaps_handler(Path("0xpool".to_string()), Query(params), Extension(conn_arc.clone()))
+            .await
+            .map(|Json(body)| body)
+    }
+
+    fn swap_ids(page: &serde_json::Value) -> Vec<f64> {
+        page["data"].as_array().unwrap().iter().map(|swap| swap["amount_in"].as_f64().unwrap()).collect()
+    }
+
+    #[tokio::test]
+    async fn swap_pages_walk_back_and_forth_through_history() {
+        let conn_arc = swaps_db();
+        let first = swaps_page(&conn_arc, &[("limit", "2")]).await.unwrap();
+        assert_eq!(swap_ids(&first), vec![5.0, 4.0]);
+        assert_eq!(first["total"], 5);
+        assert_eq!(first["prev_cursor"], "4000_5");
+        assert_eq!(first["next_cursor"], "3000_4");
+
+        // Swaps sharing a timestamp are ordered by ID across the page boundary
+        let second = swaps_page(&conn_arc, &[("limit", "2"), ("before", "3000_4")]).await.unwrap();
+        assert_eq!(swap_ids(&second), vec![3.0, 2.0]);
+        assert_eq!(second["next_cursor"], "2000_2");
+
+        let last = swaps_page(&conn_arc, &[("limit", "2"), ("before", "2000_2")]).await.unwrap();
+        assert_eq!(swap_ids(&last), vec![1.0]);
+        assert_eq!(last["next_cursor"], serde_json::Value::Null);
+
+        // Back from the second page's newest swap to the first page
+        let back = swaps_page(&conn_arc, &[("limit", "2"), ("after", "2000_3")]).await.unwrap();
+        assert_eq!(swap_ids(&back), vec![5.0, 4.0]);
+        assert_eq!(back["next_cursor"], "3000_4");
+        assert_eq!(back["prev_cursor"], "4000_5");
+    }
+
+    #[tokio::test]
+    async fn empty_page_after_the_newest_swap_keeps_its_prev_cursor() {
+        let conn_arc = swaps_db();
+        let page = swaps_page(&conn_arc, &[("after", "4000_5")]).await.unwrap();
+        assert!(swap_ids(&page).is_empty());
+        assert_eq!(page["prev_cursor"], "4000_5");
+        assert_eq!(page["next_cursor"], serde_json::Value::Null);
+
+        conn_arc
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO swaps (id, pool_id, amount_in, amount_out, timestamp, tx_digest)
+                 VALUES (6, '0xpool', 6, 1, 5000, 'tx6')",
+                [],
+            )
+            .unwrap();
+        let page = swaps_page(&conn_arc, &[("after", page["prev_cursor"].as_str().unwrap())]).await.unwrap();
+        assert_eq!(swap_ids(&page), vec![6.0]);
+    }
+
+    #[tokio::test]
+    async fn malformed_swap_cursors_are_rejected() {
+        let conn_arc = swaps_db();
+        let both = swaps_page(&conn_arc, &[("before", "3000_4"), ("after", "1000_1")]).await;
+        assert!(matches!(both, Err(ApiError::BadRequest(_))));
+        assert!(matches!(swaps_page(&conn_arc, &[("before", "3000")]).await, Err(ApiError::BadRequest(_))));
+        assert!(matches!(swaps_page(&conn_arc, &[("after", "x_1")]).await, Err(ApiError::BadRequest(_))));
+        assert!(matches!(swaps_page(&conn_arc, &[("limit", "0")]).await, Err(ApiError::BadRequest(_))));
+    }
+
+    async fn quote(conn_arc: &Arc<Mutex<Connection>>, query: &[(&str, &str)]) -> (StatusCode, serde_json::Value) {
+        let params = query.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let response = match quote_handler(HeaderMap::new(), Query(params), Extension(conn_arc.clone()), None).await {
+            Ok(response) => response,
+            Err(e) => e.into_response(),
+        };
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn quote_swaps_the_pool_in_the_requested_direction() {
+        let conn_arc = swaps_db();
+        conn_arc.lock().unwrap().execute("UPDATE pools SET reserve_a = 1000, reserve_b = 2000", []).unwrap();
+
+        let query = [("pool_id", "0xpool"), ("amount_in", "200"), ("direction", "b_to_a")];
+        let (status, body) = quote(&conn_arc, &query).await;
+        assert_eq!(status, StatusCode::OK);
+        let data = &body["data"];
+        assert_eq!(data["token_in"], "0xb::b::B");
+        let amount_out = quote_swap(200.0, 2000.0, 1000.0, pool_fee_bps()).amount_out;
+        assert_eq!(data["amount_out"], amount_out);
+        assert_eq!(data["slippage_bps"], DEFAULT_QUOTE_SLIPPAGE_BPS);
+        assert_eq!(data["min_amount_out"], amount_out * (10_000 - DEFAULT_QUOTE_SLIPPAGE_BPS) as f64 / 10_000.0);
+        assert!(data["quote_id"].is_i64());
+
+        let (_, body) = quote(
+            &conn_arc,
+            &[("pool_id", "0xpool"), ("amount_in", "200"), ("direction", "a_to_b"), ("slippage_bps", "10000")],
+        )
+        .await;
+        assert_eq!(body["data"]["min_amount_out"], 0.0);
+        let (status, _) = quote(
+            &conn_arc,
+            &[("pool_id", "0xpool"), ("amount_in", "200"), ("direction", "a_to_b"), ("slippage_bps", "10001")],
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn quote_against_a_drained_pool_is_unprocessable() {
+        let conn_arc = swaps_db();
+        conn_arc.lock().unwrap().execute("UPDATE pools SET reserve_a = 1000, reserve_b = 0", []).unwrap();
+        let query = [("pool_id", "0xpool"), ("amount_in", "1"), ("direction", "a_to_b")];
+        let (status, body) = quote(&conn_arc, &query).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["reason"], ZERO_LIQUIDITY);
+
+        let query = [("pool_id", "0xnone"), ("amount_in", "1"), ("direction", "a_to_b")];
+        let (status, _) = quote(&conn_arc, &query).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    async fn ticker(conn_arc: &Arc<Mutex<Connection>>, pair: &str) -> Result<serde_json::Value, ApiError> {
+        let params = HashMap::from([("pair".to_string(), pair.to_string())]);
+        ticker_handler(Query(params), Extension(conn_arc.clone()), None).await.map(|Json(body)| body["data"].clone())
+    }
+
+    #[tokio::test]
+    async fn ticker_inverts_the_candles_of_a_reversed_pair() {
+        let conn_arc = swaps_db();
+        {
+            let conn = conn_arc.lock().unwrap();
+            conn.execute("UPDATE pools SET reserve_a = 1000, reserve_b = 2000", []).unwrap();
+            let bucket = now_ms() / 300_000 * 300_000;
+            for (start, open, high, low, close, volume_a, volume_b, trades) in [
+                (bucket - 600_000, 2.0, 4.0, 1.0, 3.0, 10.0, 25.0, 2),
+                (bucket - 300_000, 3.0, 5.0, 2.0, 2.5, 5.0, 12.0, 1),
+            ] {
+                conn.execute(
+                    "INSERT INTO candles VALUES ('0xpool', ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![TICKER_CANDLE_INTERVAL, start, open, high, low, close, volume_a, volume_b, trades],
+                )
+                .unwrap();
+            }
+        }
+
+        let forward = ticker(&conn_arc, "0xa::a::A/0xb::b::B").await.unwrap();
+        assert_eq!(forward["price"], 2.5);
+        assert_eq!(forward["open_24h"], 2.0);
+        assert_eq!(forward["high_24h"], 5.0);
+        assert_eq!(forward["low_24h"], 1.0);
+        assert_eq!(forward["change_24h"], 0.25);
+        assert_eq!(forward["volume_24h"], 15.0);
+        assert_eq!(forward["trades_24h"], 3);
+
+        let reversed = ticker(&conn_arc, "0xb::b::B/0xa::a::A").await.unwrap();
+        assert_eq!(reversed["pair"], "0xb::b::B/0xa::a::A");
+        assert_eq!(reversed["pool_id"], "0xpool");
+        assert_eq!(reversed["price"], 0.4);
+        assert_eq!(reversed["open_24h"], 0.5);
+        assert_eq!(reversed["high_24h"], 1.0);
+        assert_eq!(reversed["low_24h"], 0.2);
+        assert!((reversed["change_24h"].as_f64().unwrap() + 0.2).abs() < 1e-12);
+        assert_eq!(reversed["volume_24h"], 37.0);
+        assert_eq!(reversed["trades_24h"], 3);
+        assert_eq!(reversed["last_trade"], 4000);
+
+        assert!(matches!(ticker(&conn_arc, "0xa::a::A/0xc::c::C").await, Err(ApiError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn ticker_without_recent_candles_has_no_range() {
+        let conn_arc = swaps_db();
+        let reversed = ticker(&conn_arc, "0xb::b::B/0xa::a::A").await.unwrap();
+        assert_eq!(reversed["price"], serde_json::Value::Null);
+        assert_eq!(reversed["high_24h"], serde_json::Value::Null);
+        assert_eq!(reversed["change_24h"], serde_json::Value::Null);
+        assert_eq!(reversed["volume_24h"], 0.0);
+    }
+
+    #[tokio::test]
+    async fn swaps_are_filtered_by_notional_value() {
+        let conn_arc = swaps_db();
+        let page = swaps_page(&conn_arc, &[("min_value", "2"), ("max_value", "4")]).await.unwrap();
+        assert_eq!(swap_ids(&page), vec![4.0, 3.0, 2.0]);
+        assert_eq!(page["total"], 3);
+
+        let page = swaps_page(&conn_arc, &[("min_value", "3"), ("max_value", "3")]).await.unwrap();
+        assert_eq!(swap_ids(&page), vec![3.0]);
+
+        let inverted = swaps_page(&conn_arc, &[("min_value", "4"), ("max_value", "2")]).await;
+        assert!(matches!(inverted, Err(ApiError::BadRequest(_))));
+        assert!(matches!(swaps_page(&conn_arc, &[("min_value", "-1")]).await, Err(ApiError::BadRequest(_))));
+        let unknown = swaps_page(&conn_arc, &[("value_in", "eur")]).await;
+        assert!(matches!(unknown, Err(ApiError::BadRequest(_))));
+        // Swaps not yet valued in USD never match a USD range
+        let page = swaps_page(&conn_arc, &[("min_value", "0"), ("value_in", "usd")]).await.unwrap();
+        assert_eq!(page["total"], 0);
+    }
 }
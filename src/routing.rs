@@ -0,0 +1,442 @@
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::config::config;
+use crate::error::ApiError;
+use crate::pool_cache::{PoolCache, PoolState};
+use crate::precision::audit_amount_out;
+
+/// Maximum number of pools a routed path may traverse
+pub const MAX_HOPS: usize = 3;
+
+/// Reason code given where a price or quote is unavailable because a pool
+/// has no liquidity on one side
+pub const ZERO_LIQUIDITY: &str = "zero_liquidity";
+
+/// Default fee taken from the input of every swap (in basis points).
+/// 
+/// The deployed contract is a prototype whose `swap` charges no fee, so
+/// quotes only match it at 0. `POOL_FEE_BPS` exists for the constant
+/// product pools the quote and routing endpoints model: once a package
+/// that charges a fee is indexed, setting it makes quotes net of that fee.
+pub const DEFAULT_POOL_FEE_BPS: u32 = 0;
+
+/// Returns the fee pools take from the input of every swap (in basis
+/// points), as configured by `POOL_FEE_BPS`.
+pub fn pool_fee_bps() -> u32 {
+    config().pool_fee_bps
+}
+
+/// A pool viewed as a directed edge from `token_in` to `token_out`
+#[derive(Clone)]
+pub struct PoolEdge {
+    pub pool_id: String,
+    pub token_in: String,
+    pub token_out: String,
+    pub reserve_in: f64,
+    pub reserve_out: f64,
+}
+
+/// Token connectivity graph built from all pools with liquidity.
+/// 
+/// Every pool contributes one edge in each direction, so paths can be walked
+/// from any token regardless of how the pool orders its pair.
+pub struct PoolGraph {
+    pub edges: HashMap<String, Vec<PoolEdge>>,
+    /// Each pool once, as an edge from its `token_a` to its `token_b`
+    pub pools: Vec<PoolEdge>,
+    /// Pools left out of `edges` because a reserve is zero, in the same form
+    pub drained: Vec<PoolEdge>,
+}
+
+/// A token in the serialized graph
+#[derive(Serialize)]
+pub struct GraphNode {
+    pub token: String,
+    /// Number of pools the token trades in
+    pub pools: usize,
+}
+
+/// A pool in the serialized graph, connecting its two tokens
+#[derive(Serialize)]
+pub struct GraphEdge {
+    pub pool_id: String,
+    pub token_a: String,
+    pub token_b: String,
+    pub reserve_a: f64,
+    pub reserve_b: f64,
+    /// Liquidity weight, `sqrt(reserve_a * reserve_b)`
+    pub liquidity: f64,
+}
+
+/// Token connectivity graph as served to clients
+#[derive(Serialize)]
+pub struct GraphTopology {
+    pub nodes: Vec<GraphNode>,
+    pub edges: Vec<GraphEdge>,
+}
+
+/// Expected result of swapping along one route
+#[derive(Serialize)]
+pub struct RouteQuote {
+    /// Pools traversed, in order
+    pub route: Vec<String>,
+    /// Tokens visited, starting with the input token
+    pub path: Vec<String>,
+    pub amount_in: f64,
+    pub amount_out: f64,
+    /// Shortfall of `amount_out` versus trading the input after each hop's
+    /// fee at spot prices (in percent)
+    pub price_impact_pct: f64,
+}
+
+/// Expected result of a single swap against one pool
+#[derive(Serialize)]
+pub struct SwapQuote {
+    pub amount_in: f64,
+    /// Part of `amount_in` taken as the pool's fee
+    pub fee: f64,
+    pub amount_out: f64,
+    /// Shortfall of `amount_out` versus trading the input after the fee at
+    /// the spot price (in percent)
+    pub price_impact_pct: f64,
+}
+
+/// Quotes a single swap: the pool's fee is taken from `amount_in` and the
+/// rest is swapped with the constant product formula.
+/// 
+/// # Arguments
+/// * `amount_in` - Amount of the input token
+/// * `reserve_in` - Pool reserve of the input token
+/// * `reserve_out` - Pool reserve of the output token
+/// * `fee_bps` - Fee taken from the input (in basis points)
+/// 
+/// # Returns
+/// * `SwapQuote` - Fee, expected output and price impact
+pub fn quote_swap(amount_in: f64, reserve_in: f64, reserve_out: f64, fee_bps: u32) -> SwapQuote {
+    let fee = amount_in * fee_bps as f64 / 10_000.0;
+    let amount_out = get_amount_out(amount_in - fee, reserve_in, reserve_out);
+    let spot = match spot_price(reserve_in, reserve_out) {
+        Some(price) => (amount_in - fee) * price,
+        None => 0.0,
+    };
+    SwapQuote {
+        amount_in,
+        fee,
+        amount_out,
+        price_impact_pct: if spot > 0.0 { (spot - amount_out) / spot * 100.0 } else { 0.0 },
+    }
+}
+
+/// Applies the constant product formula (x * y = k) to a single swap.
+/// 
+/// # Arguments
+/// * `amount_in` - Amount of the input token
+/// * `reserve_in` - Pool reserve of the input token
+/// * `reserve_out` - Pool reserve of the output token
+/// 
+/// # Returns
+/// * `f64` - Amount of the output token received
+pub fn get_amount_out(amount_in: f64, reserve_in: f64, reserve_out: f64) -> f64 {
+    if amount_in <= 0.0 || reserve_in <= 0.0 || reserve_out <= 0.0 {
+        return 0.0;
+    }
+    let amount_out = reserve_out * amount_in / (reserve_in + amount_in);
+    audit_amount_out("quoted output", amount_in, reserve_in, reserve_out, amount_out);
+    amount_out
+}
+
+/// Spot price of token A in units of token B from a pool's reserves.
+/// 
+/// # Returns
+/// * `Option<f64>` - The price, or `None` if either reserve is zero (see `ZERO_LIQUIDITY`)
+pub fn spot_price(reserve_a: f64, reserve_b: f64) -> Option<f64> {
+    (reserve_a > 0.0 && reserve_b > 0.0).then(|| reserve_b / reserve_a)
+}
+
+impl PoolGraph {
+    /// Builds the graph from all pools with non-zero reserves on both sides,
+    /// keeping the others aside as `drained`.
+    /// 
+    /// # Arguments
+    /// * `conn` - SQLite database connection
+    /// 
+    /// # Returns
+    /// * `rusqlite::Result<PoolGraph>` - The graph or error
+    pub fn load(conn: &Connection) -> rusqlite::Result<PoolGraph> {
+        let mut stmt = conn.prepare(
+            "SELECT pool_id, token_a, token_b, reserve_a, reserve_b
+             FROM pools
+             WHERE token_a != '' AND token_b != ''",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, f64>(3)?,
+                row.get::<_, f64>(4)?,
+            ))
+        })?;
+        Ok(PoolGraph::from_pools(rows.collect::<rusqlite::Result<Vec<_>>>()?))
+    }
+
+    /// Builds the graph from the in-memory pool cache, applying the same
+    /// filter as `load`.
+    /// 
+    /// # Arguments
+    /// * `cache` - Pool cache maintained by the indexer
+    /// 
+    /// # Returns
+    /// * `Result<PoolGraph, ApiError>` - The graph, or `Internal` if the
+    ///   cache lock was poisoned
+    pub fn from_cache(cache: &PoolCache) -> Result<PoolGraph, ApiError> {
+        let guard = cache.read()?;
+        let mut pools: Vec<&PoolState> = guard
+            .values()
+            .filter(|p| !p.token_a.is_empty() && !p.token_b.is_empty())
+            .collect();
+        pools.sort_by(|a, b| a.pool_id.cmp(&b.pool_id));
+        Ok(PoolGraph::from_pools(pools.into_iter().map(|p| {
+            (p.pool_id.clone(), p.token_a.clone(), p.token_b.clone(), p.reserve_a, p.reserve_b)
+        })))
+    }
+
+    /// Builds the graph from `(pool_id, token_a, token_b, reserve_a, reserve_b)` rows.
+    fn from_pools(rows: impl IntoIterator<Item = (String, String, String, f64, f64)>) -> PoolGraph {
+        let mut edges: HashMap<String, Vec<PoolEdge>> = HashMap::new();
+        let mut pools = Vec::new();
+        let mut drained = Vec::new();
+        for (pool_id, token_a, token_b, reserve_a, reserve_b) in rows {
+            let forward = PoolEdge {
+                pool_id: pool_id.clone(),
+                token_in: token_a.clone(),
+                token_out: token_b.clone(),
+                reserve_in: reserve_a,
+                reserve_out: reserve_b,
+            };
+            if spot_price(reserve_a, reserve_b).is_none() {
+                drained.push(forward);
+                continue;
+            }
+            pools.push(forward.clone());
+            edges.entry(token_a.clone()).or_default().push(forward);
+            edges.entry(token_b.clone()).or_default().push(PoolEdge {
+                pool_id,
+                token_in: token_b,
+                token_out: token_a,
+                reserve_in: reserve_b,
+                reserve_out: reserve_a,
+            });
+        }
+        PoolGraph { edges, pools, drained }
+    }
+
+    /// Returns the graph as tokens and undirected pool edges, ordered by
+    /// token and pool ID so the representation is stable between calls.
+    pub fn topology(&self) -> GraphTopology {
+        let mut nodes: Vec<GraphNode> = self
+            .edges
+            .iter()
+            .map(|(token, edges)| GraphNode { token: token.clone(), pools: edges.len() })
+            .collect();
+        nodes.sort_by(|a, b| a.token.cmp(&b.token));
+
+        let mut edges: Vec<GraphEdge> = self
+            .pools
+            .iter()
+            .map(|pool| GraphEdge {
+                pool_id: pool.pool_id.clone(),
+                token_a: pool.token_in.clone(),
+                token_b: pool.token_out.clone(),
+                reserve_a: pool.reserve_in,
+                reserve_b: pool.reserve_out,
+                liquidity: (pool.reserve_in * pool.reserve_out).sqrt(),
+            })
+            .collect();
+        edges.sort_by(|a, b| a.pool_id.cmp(&b.pool_id));
+
+        GraphTopology { nodes, edges }
+    }
+
+    /// Finds every simple path from `from` to `to` using at most `max_hops` pools.
+    /// 
+    /// A path never revisits a token, so cycles are excluded.
+    /// 
+    /// # Returns
+    /// * `Vec<Vec<&PoolEdge>>` - Each route as the ordered list of edges it traverses
+    pub fn find_routes(&self, from: &str, to: &str, max_hops: usize) -> Vec<Vec<&PoolEdge>> {
+        let mut routes = Vec::new();
+        let mut current = Vec::new();
+        let mut visited = vec![from.to_string()];
+        self.walk(from, to, max_hops, &mut current, &mut visited, &mut routes);
+        routes
+    }
+
+    /// Whether `from` and `to` would be connected by at most `max_hops` pools
+    /// if drained pools could be traded through, e.g. to tell a missing route
+    /// from one blocked by zero liquidity.
+    pub fn connected_through_drained(&self, from: &str, to: &str, max_hops: usize) -> bool {
+        let mut reached = vec![from];
+        let mut frontier = vec![from];
+        for _ in 0..max_hops {
+            let mut next = Vec::new();
+            for pool in self.pools.iter().chain(&self.drained) {
+                let (a, b) = (pool.token_in.as_str(), pool.token_out.as_str());
+                for (near, far) in [(a, b), (b, a)] {
+                    if frontier.contains(&near) && !reached.contains(&far) {
+                        reached.push(far);
+                        next.push(far);
+                    }
+                }
+            }
+            if reached.contains(&to) {
+                return true;
+            }
+            frontier = next;
+        }
+        false
+    }
+
+    fn walk<'a>(
+        &'a self,
+        token: &str,
+        to: &str,
+        hops_left: usize,
+        current: &mut Vec<&'a PoolEdge>,
+        visited: &mut Vec<String>,
+        routes: &mut Vec<Vec<&'a PoolEdge>>,
+    ) {
+        if hops_left == 0 {
+            return;
+        }
+        let Some(edges) = self.edges.get(token) else { return };
+        for edge in edges {
+            if visited.contains(&edge.token_out) {
+                continue;
+            }
+            current.push(edge);
+            if edge.token_out == to {
+                routes.push(current.clone());
+            } else {
+                visited.push(edge.token_out.clone());
+                self.walk(&edge.token_out, to, hops_left - 1, current, visited, routes);
+                visited.pop();
+            }
+            current.pop();
+        }
+    }
+}
+
+/// Simulates swapping `amount_in` along a route, hop by hop, taking the
+/// pool's fee from the input of each hop as `quote_swap` does.
+/// 
+/// # Arguments
+/// * `route` - Ordered edges to traverse
+/// * `amount_in` - Amount of the route's input token
+/// * `fee_bps` - Fee taken from the input of each hop (in basis points)
+/// 
+/// # Returns
+/// * `RouteQuote` - Expected output and price impact
+pub fn simulate_route(route: &[&PoolEdge], amount_in: f64, fee_bps: u32) -> RouteQuote {
+    let fee_factor = 1.0 - fee_bps as f64 / 10_000.0;
+    let mut amount = amount_in;
+    let mut spot = amount_in;
+    let mut path = Vec::with_capacity(route.len() + 1);
+    if let Some(first) = route.first() {
+        path.push(first.token_in.clone());
+    }
+    for edge in route {
+        amount = quote_swap(amount, edge.reserve_in, edge.reserve_out, fee_bps).amount_out;
+        spot *= fee_factor * edge.reserve_out / edge.reserve_in;
+        path.push(edge.token_out.clone());
+    }
+    RouteQuote {
+        route: route.iter().map(|e| e.pool_id.clone()).collect(),
+        path,
+        amount_in,
+        amount_out: amount,
+        price_impact_pct: if spot > 0.0 { (spot - amount) / spot * 100.0 } else { 0.0 },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    fn graph() -> PoolGraph {
+        let pool = |id: &str, a: &str, b: &str, reserve_a: f64, reserve_b: f64| {
+            (id.to_string(), a.to_string(), b.to_string(), reserve_a, reserve_b)
+        };
+        PoolGraph::from_pools([
+            pool("0x1", "A", "B", 1000.0, 2000.0),
+            pool("0x2", "B", "C", 4000.0, 1000.0),
+            pool("0x3", "A", "C", 500.0, 250.0),
+            pool("0x4", "C", "D", 0.0, 100.0),
+        ])
+    }
+
+    #[test]
+    fn quote_takes_the_fee_before_the_constant_product() {
+        let quote = quote_swap(100.0, 1000.0, 2000.0, 0);
+        assert_close(quote.fee, 0.0);
+        assert_close(quote.amount_out, 2000.0 * 100.0 / 1100.0);
+        // Spot output 200 less the curve's output, in percent
+        assert_close(quote.price_impact_pct, (200.0 - quote.amount_out) / 200.0 * 100.0);
+
+        let quote = quote_swap(100.0, 1000.0, 2000.0, 30);
+        assert_close(quote.fee, 0.3);
+        assert_close(quote.amount_out, 2000.0 * 99.7 / 1099.7);
+        assert_close(quote.price_impact_pct, (199.4 - quote.amount_out) / 199.4 * 100.0);
+    }
+
+    #[test]
+    fn quote_against_a_drained_pool_is_empty() {
+        let quote = quote_swap(100.0, 0.0, 2000.0, 30);
+        assert_eq!(quote.amount_out, 0.0);
+        assert_eq!(quote.price_impact_pct, 0.0);
+        assert_eq!(get_amount_out(0.0, 1000.0, 2000.0), 0.0);
+        assert_eq!(spot_price(1000.0, 0.0), None);
+        assert_eq!(spot_price(1000.0, 2000.0), Some(2.0));
+    }
+
+    #[test]
+    fn route_simulation_chains_each_hop_net_of_its_fee() {
+        let graph = graph();
+        let mut routes = graph.find_routes("A", "C", MAX_HOPS);
+        routes.sort_by_key(|route| route.len());
+        assert_eq!(routes.len(), 2);
+
+        let direct = simulate_route(&routes[0], 100.0, 30);
+        let single = quote_swap(100.0, 500.0, 250.0, 30);
+        assert_eq!(direct.route, vec!["0x3"]);
+        assert_close(direct.amount_out, single.amount_out);
+        assert_close(direct.price_impact_pct, single.price_impact_pct);
+
+        let routed = simulate_route(&routes[1], 100.0, 30);
+        let first = quote_swap(100.0, 1000.0, 2000.0, 30).amount_out;
+        let second = quote_swap(first, 4000.0, 1000.0, 30).amount_out;
+        assert_eq!(routed.route, vec!["0x1", "0x2"]);
+        assert_eq!(routed.path, vec!["A", "B", "C"]);
+        assert_close(routed.amount_out, second);
+        let spot = 100.0 * 0.997 * 2.0 * 0.997 * 0.25;
+        assert_close(routed.price_impact_pct, (spot - second) / spot * 100.0);
+    }
+
+    #[test]
+    fn routes_skip_drained_pools_and_respect_the_hop_limit() {
+        let graph = graph();
+        assert_eq!(graph.drained.len(), 1);
+        assert_eq!(graph.find_routes("A", "C", 1).len(), 1);
+        assert!(graph.find_routes("A", "D", MAX_HOPS).is_empty());
+        assert!(graph.connected_through_drained("A", "D", 2));
+        assert!(!graph.connected_through_drained("A", "D", 1));
+        // Walking from either side of a pool works
+        assert_eq!(graph.find_routes("C", "A", MAX_HOPS).len(), 2);
+    }
+}
@@ -0,0 +1,54 @@
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::watch;
+use tracing::{info, warn};
+
+/// Time in-flight requests and the indexer's current page get to finish
+/// after a shutdown signal before they are cut off (in seconds)
+pub const SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+
+/// Receiving side of the shutdown notice, held by every task that must stop cleanly
+pub type Shutdown = watch::Receiver<bool>;
+
+/// Creates the shutdown notice. Sending `true` tells every `Shutdown` to stop.
+pub fn shutdown_channel() -> (watch::Sender<bool>, Shutdown) {
+    watch::channel(false)
+}
+
+/// Waits for SIGINT or SIGTERM.
+pub async fn shutdown_signal() {
+    let mut terminate = signal(SignalKind::terminate()).expect("Failed to install SIGTERM handler");
+    tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = terminate.recv() => {}
+    }
+}
+
+/// Waits until shutdown is requested. Returns at once if it already was, and
+/// also if the sender is gone, since nothing can request it anymore.
+pub async fn requested(mut shutdown: Shutdown) {
+    let _ = shutdown.wait_for(|stopping| *stopping).await;
+}
+
+/// Checkpoints the write-ahead log into the database file and closes the
+/// connection, as the last step of shutting down.
+/// 
+/// The connection can only be closed once every task sharing it has stopped;
+/// if one still holds it, SQLite is left to close it when the process exits.
+pub fn close_database(conn_arc: Arc<Mutex<Connection>>) {
+    let conn = match Arc::try_unwrap(conn_arc) {
+        Ok(conn) => conn.into_inner().unwrap_or_else(|poisoned| poisoned.into_inner()),
+        Err(_) => {
+            warn!("database still in use, leaving it to close on exit");
+            return;
+        }
+    };
+    if let Err(e) = conn.execute_batch("PRAGMA wal_checkpoint(TRUNCATE);") {
+        warn!(error = %e, "failed to checkpoint the write-ahead log");
+    }
+    match conn.close() {
+        Ok(()) => info!("Database closed"),
+        Err((_, e)) => warn!(error = %e, "failed to close the database"),
+    }
+}
@@ -0,0 +1,239 @@
+use axum::{extract::Request, middleware::Next, response::Response};
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::time::sleep;
+
+use crate::config::{config, Config};
+use crate::freshness::indexer_lag_secs;
+
+/// Default share of API requests that must not fail with a 5xx
+pub const DEFAULT_AVAILABILITY_TARGET: f64 = 0.999;
+
+/// Default share of indexer lag samples that must be within `SLO_MAX_INDEXER_LAG_SECS`
+pub const DEFAULT_INDEXER_LAG_TARGET: f64 = 0.99;
+
+/// Default share of market-data responses that must be served from fresh data
+pub const DEFAULT_FRESH_DATA_TARGET: f64 = 0.995;
+
+/// Default indexer lag above which a sample counts against the lag SLO (in seconds)
+pub const DEFAULT_MAX_INDEXER_LAG_SECS: i64 = 30;
+
+/// Default period error budgets are computed over (in days)
+pub const DEFAULT_SLO_WINDOW_DAYS: i64 = 30;
+
+/// Interval between indexer lag samples (in seconds)
+const LAG_SAMPLE_INTERVAL_SECS: u64 = 10;
+
+/// Windows burn rates are reported over, as (label, length in minutes)
+pub const BURN_RATE_WINDOWS: [(&str, i64); 4] = [("5m", 5), ("1h", 60), ("6h", 6 * 60), ("3d", 3 * 24 * 60)];
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+/// Returns the configured SLO window (`SLO_WINDOW_DAYS`), in minutes.
+fn window_minutes() -> i64 {
+    config().slo_window_days * 24 * 60
+}
+
+/// Good and bad events of one SLO, counted per minute over the SLO window
+pub struct EventCounter {
+    /// (minute since epoch, good, bad), oldest first
+    minutes: Mutex<VecDeque<(i64, u64, u64)>>,
+}
+
+impl EventCounter {
+    const fn new() -> Self {
+        EventCounter { minutes: Mutex::new(VecDeque::new()) }
+    }
+
+    /// Counts one event, dropping minutes that have left the SLO window.
+    pub fn record(&self, good: bool) {
+        let minute = now_ms() / 60_000;
+        let Ok(mut minutes) = self.minutes.lock() else { return };
+        match minutes.back_mut() {
+            Some((m, good_count, bad_count)) if *m == minute => {
+                *good_count += good as u64;
+                *bad_count += !good as u64;
+            }
+            _ => minutes.push_back((minute, good as u64, !good as u64)),
+        }
+        let oldest = minute - window_minutes();
+        while minutes.front().is_some_and(|(m, _, _)| *m <= oldest) {
+            minutes.pop_front();
+        }
+    }
+
+    /// Good and bad events over the last `window` minutes, including the current one.
+    fn counts(&self, window: i64) -> (u64, u64) {
+        let since = now_ms() / 60_000 - window;
+        let Ok(minutes) = self.minutes.lock() else { return (0, 0) };
+        minutes
+            .iter()
+            .rev()
+            .take_while(|(m, _, _)| *m > since)
+            .fold((0, 0), |(good, bad), (_, g, b)| (good + g, bad + b))
+    }
+}
+
+/// API requests, bad when answered with a 5xx
+pub static AVAILABILITY_EVENTS: EventCounter = EventCounter::new();
+
+/// Periodic indexer lag samples, bad when the lag exceeds `SLO_MAX_INDEXER_LAG_SECS`
+pub static INDEXER_LAG_EVENTS: EventCounter = EventCounter::new();
+
+/// Market-data responses, bad when served while the data was stale
+pub static FRESH_DATA_EVENTS: EventCounter = EventCounter::new();
+
+/// Time this process started counting SLO events (ms since epoch)
+pub static SLO_TRACKED_SINCE_MS: AtomicU64 = AtomicU64::new(0);
+
+/// A service level objective
+pub struct Objective {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Reads the objective's target from the configuration
+    pub configured_target: fn(&Config) -> f64,
+    pub events: &'static EventCounter,
+}
+
+impl Objective {
+    /// Returns the configured target.
+    pub fn target(&self) -> f64 {
+        (self.configured_target)(config())
+    }
+}
+
+/// The objectives tracked by this process
+pub static OBJECTIVES: [Objective; 3] = [
+    Objective {
+        name: "availability",
+        description: "API requests answered without a 5xx",
+        configured_target: |config| config.slo_availability_target,
+        events: &AVAILABILITY_EVENTS,
+    },
+    Objective {
+        name: "indexer_lag",
+        description: "Indexer lag samples within SLO_MAX_INDEXER_LAG_SECS",
+        configured_target: |config| config.slo_indexer_lag_target,
+        events: &INDEXER_LAG_EVENTS,
+    },
+    Objective {
+        name: "fresh_data",
+        description: "Market-data responses served while indexed data was fresh",
+        configured_target: |config| config.slo_fresh_data_target,
+        events: &FRESH_DATA_EVENTS,
+    },
+];
+
+/// Error budget status of one objective over the SLO window
+#[derive(Serialize)]
+pub struct SloStatus {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub target: f64,
+    pub good: u64,
+    pub bad: u64,
+    /// Share of events that were bad (0 without events)
+    pub error_rate: f64,
+    /// Share of the error budget left; negative once overspent
+    pub budget_remaining: f64,
+    /// Error rate over each of `BURN_RATE_WINDOWS` divided by the allowed
+    /// error rate: 1 spends the budget exactly over the SLO window
+    pub burn_rates: BTreeMap<&'static str, f64>,
+}
+
+/// Bad events as a share of all events, or 0 without events.
+fn error_rate((good, bad): (u64, u64)) -> f64 {
+    if good + bad == 0 {
+        0.0
+    } else {
+        bad as f64 / (good + bad) as f64
+    }
+}
+
+/// Computes the current status of every objective.
+pub fn slo_status() -> Vec<SloStatus> {
+    let window = window_minutes();
+    OBJECTIVES
+        .iter()
+        .map(|objective| {
+            let target = objective.target();
+            let allowed = 1.0 - target;
+            let (good, bad) = objective.events.counts(window);
+            let rate = error_rate((good, bad));
+            let burn_rates = BURN_RATE_WINDOWS
+                .iter()
+                .map(|(label, minutes)| (*label, error_rate(objective.events.counts(*minutes)) / allowed))
+                .collect();
+            SloStatus {
+                name: objective.name,
+                description: objective.description,
+                target,
+                good,
+                bad,
+                error_rate: rate,
+                budget_remaining: 1.0 - rate / allowed,
+                burn_rates,
+            }
+        })
+        .collect()
+}
+
+/// Length of the SLO window in seconds.
+pub fn window_secs() -> i64 {
+    window_minutes() * 60
+}
+
+/// Middleware counting every API response towards the availability SLO.
+pub async fn track_availability(request: Request, next: Next) -> Response {
+    let response = next.run(request).await;
+    AVAILABILITY_EVENTS.record(!response.status().is_server_error());
+    response
+}
+
+/// Periodically samples the indexer lag for the indexer lag SLO.
+/// 
+/// Runs indefinitely, every `LAG_SAMPLE_INTERVAL_SECS`. Until the first
+/// polling cycle completes the lag is unbounded, so a long initial backfill
+/// spends budget.
+pub async fn run_lag_sampler() {
+    SLO_TRACKED_SINCE_MS.store(now_ms() as u64, Ordering::Relaxed);
+    let max_lag = config().slo_max_indexer_lag_secs;
+    loop {
+        INDEXER_LAG_EVENTS.record(indexer_lag_secs() <= max_lag);
+        sleep(Duration::from_secs(LAG_SAMPLE_INTERVAL_SECS)).await;
+    }
+}
+
+/// Renders the SLO gauges in the Prometheus text exposition format.
+pub fn render(out: &mut String) {
+    let status = slo_status();
+    let families = [
+        ("fooswap_slo_target", "Target share of good events, by objective"),
+        ("fooswap_slo_error_budget_remaining", "Share of the error budget left over the SLO window, by objective"),
+    ];
+    for (i, (name, help)) in families.iter().enumerate() {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for slo in &status {
+            let value = if i == 0 { slo.target } else { slo.budget_remaining };
+            let _ = writeln!(out, "{}{{slo=\"{}\"}} {}", name, slo.name, value);
+        }
+    }
+    let name = "fooswap_slo_burn_rate";
+    let _ = writeln!(out, "# HELP {} Error budget burn rate, by objective and window", name);
+    let _ = writeln!(out, "# TYPE {} gauge", name);
+    for slo in &status {
+        for (window, rate) in &slo.burn_rates {
+            let _ = writeln!(out, "{}{{slo=\"{}\",window=\"{}\"}} {}", name, slo.name, window, rate);
+        }
+    }
+}
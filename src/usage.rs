@@ -0,0 +1,65 @@
+use axum::{
+    body::HttpBody,
+    extract::{MatchedPath, Request},
+    middleware::Next,
+    response::Response,
+};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::warn;
+
+use crate::db::{api_key_hash, record_usage};
+use crate::features::{is_enabled, FeatureFlags};
+
+/// Header clients use to identify their API key
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Middleware recording per-API-key usage.
+/// 
+/// Requests carrying an `X-API-Key` header add one request and the response
+/// body size to the key's daily counter for the matched route. Requests
+/// without a key, or while the `usage` feature is disabled, are served as
+/// usual and not tracked.
+pub async fn track_usage(request: Request, next: Next) -> Response {
+    let tracking = request
+        .extensions()
+        .get::<FeatureFlags>()
+        .is_none_or(|flags| is_enabled(flags, "usage"));
+    if !tracking {
+        return next.run(request).await;
+    }
+    let api_key = request
+        .headers()
+        .get(API_KEY_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .filter(|v| !v.is_empty())
+        .map(api_key_hash);
+    let Some(key_hash) = api_key else {
+        return next.run(request).await;
+    };
+    let endpoint = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|p| p.as_str().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let conn_arc = request.extensions().get::<Arc<Mutex<Connection>>>().cloned();
+
+    let response = next.run(request).await;
+
+    // Streamed bodies report their lower bound, which is exact for JSON responses
+    let size = response.body().size_hint();
+    let bytes_out = size.exact().unwrap_or(size.lower());
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0);
+    if let Some(conn_arc) = conn_arc {
+        if let Ok(conn) = conn_arc.lock() {
+            if let Err(e) = record_usage(&conn, &key_hash, &endpoint, bytes_out, now) {
+                warn!(error = %e, "failed to record API usage");
+            }
+        }
+    }
+    response
+}
@@ -0,0 +1,333 @@
+use rusqlite::{params, Connection, OptionalExtension, Result};
+use serde::Serialize;
+use serde_json::json;
+use tracing::error;
+
+use crate::config::config;
+use crate::db::{db_path, open_db};
+use crate::routing::get_amount_out;
+
+/// Default relative difference tolerated between replayed and stored reserves
+pub const DEFAULT_TOLERANCE: f64 = 1e-9;
+
+/// Outcome of replaying one pool's history
+#[derive(Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum PoolStatus {
+    Consistent,
+    Inconsistent,
+    Skipped,
+}
+
+/// Replay result for a single pool
+#[derive(Serialize)]
+struct PoolReport {
+    pool_id: String,
+    status: PoolStatus,
+    swaps: usize,
+    replayed_reserve_a: f64,
+    replayed_reserve_b: f64,
+    stored_reserve_a: f64,
+    stored_reserve_b: f64,
+    /// When the replay started from a reconciled on-chain state instead of
+    /// the creation reserves
+    reconciled_at: Option<i64>,
+    /// Largest relative difference between a swap's recorded output and the
+    /// constant-product output for the replayed reserves
+    max_curve_deviation: f64,
+    detail: String,
+}
+
+/// Relative difference between two amounts, absolute when `expected` is zero.
+fn relative_diff(actual: f64, expected: f64) -> f64 {
+    let diff = (actual - expected).abs();
+    if expected.abs() > 0.0 {
+        diff / expected.abs()
+    } else {
+        diff
+    }
+}
+
+/// Checks a swap's reported reserves against the pool's reserves before it.
+/// 
+/// The contract moves `amount_in` into the reserve of the token sold (A for
+/// `a_to_b` swaps) and `amount_out` out of the other, so the reserve deltas
+/// must equal the amounts. The amounts themselves aren't checked against a
+/// pricing curve: the contract pays a fixed `amount_in / 2` whatever the
+/// reserves, so any curve would flag valid swaps.
+/// 
+/// # Arguments
+/// * `previous` - Stored reserves (A, B) before the swap
+/// * `amount_in` - Amount of the sold token swapped in
+/// * `amount_out` - Amount of the bought token swapped out
+/// * `new` - Reserves (A, B) the swap event reports
+/// * `direction` - `a_to_b` or `b_to_a`
+/// 
+/// # Returns
+/// * `Option<String>` - Why the swap is inconsistent, or `None` if it is consistent
+pub fn swap_inconsistency(
+    previous: (f64, f64),
+    amount_in: f64,
+    amount_out: f64,
+    new: (f64, f64),
+    direction: &str,
+) -> Option<String> {
+    let tolerance = config().verify_tolerance;
+    // Reserves (in, out) before and after, and the labels of in and out
+    let (previous, new, labels) = if direction == "b_to_a" {
+        ((previous.1, previous.0), (new.1, new.0), ("B", "A"))
+    } else {
+        (previous, new, ("A", "B"))
+    };
+    let mut problems = Vec::new();
+    if relative_diff(new.0 - previous.0, amount_in) > tolerance {
+        problems.push(format!("reserve {} moved by {} for amount_in {}", labels.0, new.0 - previous.0, amount_in));
+    }
+    if relative_diff(previous.1 - new.1, amount_out) > tolerance {
+        problems.push(format!("reserve {} moved by {} for amount_out {}", labels.1, previous.1 - new.1, amount_out));
+    }
+    (!problems.is_empty()).then(|| problems.join("; "))
+}
+
+/// Replays the swaps of one pool from its creation reserves, or from the
+/// on-chain state it was last reconciled with.
+/// 
+/// Each swap moves `amount_in` into the reserve of the token it sold and
+/// `amount_out` out of the other, as the contract does. The replayed
+/// reserves must match the stored pool row within `tolerance`. Each recorded
+/// output is also compared with the constant-product output for the reserves
+/// at that point; that deviation is reported but does not make a pool
+/// inconsistent.
+fn verify_pool(conn: &Connection, pool_id: &str, tolerance: f64) -> Result<PoolReport> {
+    let (token_a, stored_a, stored_b): (String, f64, f64) = conn.query_row(
+        "SELECT token_a, reserve_a, reserve_b FROM pools WHERE pool_id = ?1",
+        params![pool_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+    )?;
+
+    let mut report = PoolReport {
+        pool_id: pool_id.to_string(),
+        status: PoolStatus::Skipped,
+        swaps: 0,
+        replayed_reserve_a: 0.0,
+        replayed_reserve_b: 0.0,
+        stored_reserve_a: stored_a,
+        stored_reserve_b: stored_b,
+        reconciled_at: None,
+        max_curve_deviation: 0.0,
+        detail: String::new(),
+    };
+
+    // A reconciliation replaced the stored reserves as of the swap it was read
+    // after, so only the swaps ordered after that one still apply
+    let reconciliation = conn
+        .query_row(
+            "SELECT reserve_a, reserve_b, timestamp, swap_id, reconciled_at FROM pool_reconciliations
+             WHERE pool_id = ?1 ORDER BY id DESC LIMIT 1",
+            params![pool_id],
+            |row| {
+                Ok((
+                    row.get::<_, f64>(0)?,
+                    row.get::<_, f64>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, i64>(4)?,
+                ))
+            },
+        )
+        .optional()?;
+    let (mut reserve_a, mut reserve_b, since) = match reconciliation {
+        Some((reserve_a, reserve_b, timestamp, swap_id, reconciled_at)) => {
+            report.reconciled_at = Some(reconciled_at);
+            (reserve_a, reserve_b, (timestamp, swap_id))
+        }
+        None => {
+            // Pools first seen through a swap have no creation event to start from
+            let creation = conn
+                .query_row(
+                    "SELECT reserve_a, reserve_b FROM pool_snapshots
+                     WHERE pool_id = ?1 ORDER BY timestamp ASC, id ASC LIMIT 1",
+                    params![pool_id],
+                    |row| Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?)),
+                )
+                .optional()?;
+            let Some((reserve_a, reserve_b)) = creation.filter(|_| !token_a.is_empty()) else {
+                report.detail = "no creation event indexed".to_string();
+                return Ok(report);
+            };
+            (reserve_a, reserve_b, (i64::MIN, i64::MIN))
+        }
+    };
+
+    let mut stmt = conn.prepare(
+        "SELECT amount_in, amount_out, tx_digest, direction FROM swaps
+         WHERE pool_id = ?1 AND (timestamp > ?2 OR (timestamp = ?2 AND id > ?3))
+         ORDER BY timestamp ASC, id ASC",
+    )?;
+    let swaps = stmt.query_map(params![pool_id, since.0, since.1], |row| {
+        Ok((row.get::<_, f64>(0)?, row.get::<_, f64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+    })?;
+
+    let mut problems = Vec::new();
+    for swap in swaps {
+        let (amount_in, amount_out, tx_digest, direction) = swap?;
+        report.swaps += 1;
+        // The indexer couldn't match the reserves before it with any direction
+        if direction == "unknown" {
+            problems.push(format!("direction of {} is unknown", tx_digest));
+            break;
+        }
+        let (reserve_in, reserve_out, out_label) = if direction == "b_to_a" {
+            (&mut reserve_b, &mut reserve_a, "A")
+        } else {
+            (&mut reserve_a, &mut reserve_b, "B")
+        };
+        let expected_out = get_amount_out(amount_in, *reserve_in, *reserve_out);
+        report.max_curve_deviation = report.max_curve_deviation.max(relative_diff(amount_out, expected_out));
+
+        *reserve_in += amount_in;
+        *reserve_out -= amount_out;
+        if *reserve_out < 0.0 && problems.is_empty() {
+            problems.push(format!("reserve {} goes negative at {}", out_label, tx_digest));
+        }
+    }
+
+    report.replayed_reserve_a = reserve_a;
+    report.replayed_reserve_b = reserve_b;
+    if relative_diff(reserve_a, stored_a) > tolerance {
+        problems.push(format!("reserve A replays to {} but {} is stored", reserve_a, stored_a));
+    }
+    if relative_diff(reserve_b, stored_b) > tolerance {
+        problems.push(format!("reserve B replays to {} but {} is stored", reserve_b, stored_b));
+    }
+
+    if problems.is_empty() {
+        report.status = PoolStatus::Consistent;
+        report.detail = match report.reconciled_at {
+            Some(_) => format!("{} swaps since the last reconciliation replay to the stored reserves", report.swaps),
+            None => format!("{} swaps replay to the stored reserves", report.swaps),
+        };
+    } else {
+        report.status = PoolStatus::Inconsistent;
+        report.detail = problems.join("; ");
+    }
+    Ok(report)
+}
+
+/// Replays stored swap history and prints a JSON report to stdout.
+/// 
+/// For every pool (or only `pool_id`), starts from the reserves recorded by
+/// its creation event and applies each stored swap in order, then compares
+/// the result with the stored final reserves. Pools reconciled with their
+/// on-chain state start from the latest reconciliation instead and apply
+/// only the swaps indexed after it. Pools with neither are skipped.
+/// 
+/// # Arguments
+/// * `pool_id` - Verify only this pool, if given
+/// 
+/// # Returns
+/// * `bool` - `true` if no pool's history is inconsistent
+pub fn run(pool_id: Option<&str>) -> bool {
+    let conn = match open_db() {
+        Ok(c) => c,
+        Err(e) => {
+            error!(path = db_path(), error = %e, "cannot open database");
+            return false;
+        }
+    };
+    let tolerance = config().verify_tolerance;
+
+    let pool_ids: Result<Vec<String>> = match pool_id {
+        Some(id) => Ok(vec![id.to_string()]),
+        None => conn
+            .prepare("SELECT pool_id FROM pools ORDER BY pool_id")
+            .and_then(|mut stmt| stmt.query_map([], |row| row.get(0))?.collect()),
+    };
+    let pool_ids = match pool_ids {
+        Ok(ids) => ids,
+        Err(e) => {
+            error!(error = %e, "failed to list pools");
+            return false;
+        }
+    };
+
+    let mut pools = Vec::new();
+    for id in &pool_ids {
+        match verify_pool(&conn, id, tolerance) {
+            Ok(report) => pools.push(report),
+            Err(rusqlite::Error::QueryReturnedNoRows) => {
+                error!(pool_id = %id, "pool not found");
+                return false;
+            }
+            Err(e) => {
+                error!(pool_id = %id, error = %e, "failed to verify pool");
+                return false;
+            }
+        }
+    }
+
+    let inconsistent = pools.iter().filter(|p| p.status == PoolStatus::Inconsistent).count();
+    let report = json!({
+        "ok": inconsistent == 0,
+        "tolerance": tolerance,
+        "pools_checked": pools.len(),
+        "inconsistent": inconsistent,
+        "pools": pools,
+    });
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    inconsistent == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contract_swap_is_consistent() {
+        // The contract pays amount_in / 2, well above the constant-product
+        // output once reserve B has been drawn down
+        let previous = (1_000_000_000.0, 300_000_000.0);
+        let new = (1_500_000_000.0, 50_000_000.0);
+        assert_eq!(swap_inconsistency(previous, 500_000_000.0, 250_000_000.0, new, "a_to_b"), None);
+    }
+
+    #[test]
+    fn mismatched_reserve_delta_is_inconsistent() {
+        let previous = (1_000_000_000.0, 300_000_000.0);
+        let new = (1_500_000_000.0, 60_000_000.0);
+        let problem = swap_inconsistency(previous, 500_000_000.0, 250_000_000.0, new, "a_to_b").unwrap();
+        assert!(problem.contains("reserve B moved by 240000000"), "{}", problem);
+    }
+
+    #[test]
+    fn b_to_a_swap_checks_reversed_reserves() {
+        let previous = (300_000_000.0, 1_000_000_000.0);
+        let new = (50_000_000.0, 1_500_000_000.0);
+        assert_eq!(swap_inconsistency(previous, 500_000_000.0, 250_000_000.0, new, "b_to_a"), None);
+    }
+
+    #[test]
+    fn reconciled_pool_replays_from_latest_reconciliation() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        // Created at 1000/1000; the first swap at 2 left the chain at 1200/900
+        // rather than the 1100/950 it reports, and the swaps after it, one in
+        // the same millisecond, apply on top
+        crate::db::upsert_pool(&conn, "0xpool", "0xa", "0xb", 1400.0, 790.0, 3).unwrap();
+        crate::db::insert_pool_snapshot(&conn, "0xpool", 1000.0, 1000.0, 1).unwrap();
+        conn.execute_batch(
+            "INSERT INTO swaps (pool_id, amount_in, amount_out, timestamp, tx_digest) VALUES
+             ('0xpool', 100.0, 50.0, 2, '0xtx1'),
+             ('0xpool', 100.0, 50.0, 2, '0xtx2'),
+             ('0xpool', 100.0, 60.0, 3, '0xtx3');",
+        )
+        .unwrap();
+        assert!(verify_pool(&conn, "0xpool", DEFAULT_TOLERANCE).unwrap().status == PoolStatus::Inconsistent);
+
+        crate::db::insert_pool_reconciliation(&conn, "0xpool", 1200.0, 900.0, 1, 2, 10).unwrap();
+        let report = verify_pool(&conn, "0xpool", DEFAULT_TOLERANCE).unwrap();
+        assert!(report.status == PoolStatus::Consistent, "{}", report.detail);
+        assert_eq!(report.swaps, 2);
+        assert_eq!(report.reconciled_at, Some(10));
+    }
+}
@@ -0,0 +1,46 @@
+/// How far apart a wallet's two legs of a round trip may be to count as a
+/// wash trade (in milliseconds)
+pub const WASH_WINDOW_MS: i64 = 10 * 60 * 1000;
+
+/// Largest relative difference between the token A amounts of the two legs
+/// of a round trip for them to count as a wash trade
+pub const WASH_AMOUNT_TOLERANCE: f64 = 0.05;
+
+/// Amount of token A a swap moved, whichever way it traded.
+fn token_a_amount(alias: &str) -> String {
+    format!(
+        "CASE {a}.direction WHEN 'b_to_a' THEN {a}.amount_out ELSE {a}.amount_in END",
+        a = alias
+    )
+}
+
+/// SQL condition holding for swaps the wash-trading heuristics flag, for
+/// the `swaps` row aliased `alias`.
+/// 
+/// A swap is flagged when the same wallet traded the other way in the same
+/// pool within `WASH_WINDOW_MS`, moving an amount of token A within
+/// `WASH_AMOUNT_TOLERANCE` of it: a round trip that inflates volume and
+/// trade counts without changing the wallet's position. Both legs are
+/// flagged. Swaps without a known sender or direction are never flagged.
+/// 
+/// # Arguments
+/// * `alias` - Alias of the `swaps` table in the surrounding query
+/// 
+/// # Returns
+/// * `String` - The condition, to embed in a `WHERE` clause
+pub fn wash_trade_condition(alias: &str) -> String {
+    format!(
+        "({s}.sender IS NOT NULL AND EXISTS (
+             SELECT 1 FROM swaps w
+             WHERE w.pool_id = {s}.pool_id AND w.sender = {s}.sender
+               AND {s}.direction <> 'unknown' AND w.direction NOT IN ({s}.direction, 'unknown')
+               AND w.timestamp BETWEEN {s}.timestamp - {window} AND {s}.timestamp + {window}
+               AND ABS(({w_amount}) - ({s_amount})) <= {tolerance} * MAX({w_amount}, {s_amount})
+         ))",
+        s = alias,
+        window = WASH_WINDOW_MS,
+        tolerance = WASH_AMOUNT_TOLERANCE,
+        w_amount = token_a_amount("w"),
+        s_amount = token_a_amount(alias),
+    )
+}
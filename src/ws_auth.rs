@@ -0,0 +1,346 @@
+use axum::http::HeaderMap;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use rusqlite::Connection;
+use serde::Deserialize;
+use sha2::Sha256;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::config;
+use crate::db::{api_key_addresses, api_key_hash};
+use crate::error::ApiError;
+use crate::routes::normalize_address;
+use crate::usage::API_KEY_HEADER;
+
+/// Default most WebSocket connections open at once
+pub const DEFAULT_WS_MAX_CONNECTIONS: usize = 1000;
+
+/// Default most WebSocket connections open at once for one authenticated owner
+pub const DEFAULT_WS_MAX_CONNECTIONS_PER_OWNER: usize = 10;
+
+/// Prefix of the private channel streaming an address's activity, followed
+/// by the address
+pub const ADDRESS_CHANNEL_PREFIX: &str = "address:";
+
+/// Who a WebSocket connection authenticated as
+pub struct Principal {
+    /// Identity connections are counted under: `key:` and the API key's
+    /// hash, or `jwt:` and the token's subject
+    pub owner: String,
+    /// Lowercase Sui addresses whose private channels the principal may
+    /// subscribe to
+    pub addresses: BTreeSet<String>,
+}
+
+impl Principal {
+    /// Whether the principal owns an address's private channel.
+    pub fn owns(&self, address: &str) -> bool {
+        self.addresses.contains(address)
+    }
+}
+
+/// Credentials a client sends in an `auth` message: an API key or a JWT
+#[derive(Deserialize)]
+pub struct Credentials {
+    api_key: Option<String>,
+    token: Option<String>,
+}
+
+/// JOSE header of a JWT
+#[derive(Deserialize)]
+struct JwtHeader {
+    alg: String,
+}
+
+/// Claims read from a JWT
+#[derive(Deserialize)]
+struct Claims {
+    /// Owner of the token; also an owned address if it is one
+    sub: String,
+    /// Expiry (seconds since epoch)
+    exp: i64,
+    /// Further addresses the owner may follow
+    #[serde(default)]
+    addresses: Vec<String>,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+/// Authenticates a WebSocket upgrade request from its `X-API-Key` or
+/// `Authorization: Bearer <JWT>` header.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `headers` - Request headers
+/// 
+/// # Returns
+/// * `Result<Option<Principal>, ApiError>` - The principal, `None` without
+///   credentials, or `Unauthorized` for invalid ones
+pub fn authenticate_headers(conn: &Connection, headers: &HeaderMap) -> Result<Option<Principal>, ApiError> {
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok()).filter(|v| !v.is_empty());
+    if let Some(api_key) = header(API_KEY_HEADER) {
+        return authenticate_api_key(conn, api_key).map(Some);
+    }
+    match header("authorization").and_then(|v| v.strip_prefix("Bearer ")) {
+        Some(token) => authenticate_token(token).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// Authenticates the credentials of an `auth` message.
+/// 
+/// # Arguments
+/// * `conn` - SQLite database connection
+/// * `credentials` - The API key or JWT sent
+/// 
+/// # Returns
+/// * `Result<Principal, ApiError>` - The principal, or `Unauthorized` for invalid credentials
+pub fn authenticate(conn: &Connection, credentials: &Credentials) -> Result<Principal, ApiError> {
+    match (&credentials.api_key, &credentials.token) {
+        (Some(api_key), None) => authenticate_api_key(conn, api_key),
+        (None, Some(token)) => authenticate_token(token),
+        _ => Err(ApiError::Unauthorized("Send either `api_key` or `token`".to_string())),
+    }
+}
+
+/// Authenticates an API key an admin has registered addresses for.
+fn authenticate_api_key(conn: &Connection, api_key: &str) -> Result<Principal, ApiError> {
+    let key_hash = api_key_hash(api_key);
+    let addresses: BTreeSet<String> = api_key_addresses(conn, &key_hash)?.into_iter().collect();
+    if addresses.is_empty() {
+        return Err(ApiError::Unauthorized("API key has no registered addresses".to_string()));
+    }
+    Ok(Principal { owner: format!("key:{}", key_hash), addresses })
+}
+
+/// Authenticates a JWT signed with `WS_JWT_SECRET`.
+fn authenticate_token(token: &str) -> Result<Principal, ApiError> {
+    let Some(secret) = &config().ws_jwt_secret else {
+        return Err(ApiError::Unauthorized("JWT authentication is not configured".to_string()));
+    };
+    let claims = verify_jwt(token, secret.as_bytes(), now_secs())
+        .map_err(|e| ApiError::Unauthorized(format!("Invalid token: {}", e)))?;
+    let addresses = std::iter::once(&claims.sub)
+        .chain(&claims.addresses)
+        .filter_map(|address| normalize_address(address))
+        .collect();
+    Ok(Principal { owner: format!("jwt:{}", claims.sub), addresses })
+}
+
+/// Verifies an HS256-signed JWT and reads its claims.
+/// 
+/// # Arguments
+/// * `token` - The compact JWT
+/// * `secret` - HMAC key the token must be signed with
+/// * `now_secs` - Current time (seconds since epoch); expired tokens are rejected
+/// 
+/// # Returns
+/// * `Result<Claims, String>` - The claims, or why the token is invalid
+fn verify_jwt(token: &str, secret: &[u8], now_secs: i64) -> Result<Claims, String> {
+    let malformed = || "malformed token".to_string();
+    let (signed, signature) = token.rsplit_once('.').ok_or_else(malformed)?;
+    let (header, payload) = signed.split_once('.').ok_or_else(malformed)?;
+    let decode = |part: &str| URL_SAFE_NO_PAD.decode(part).map_err(|_| malformed());
+
+    let header: JwtHeader = serde_json::from_slice(&decode(header)?).map_err(|_| malformed())?;
+    if header.alg != "HS256" {
+        return Err(format!("unsupported algorithm {}", header.alg));
+    }
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).map_err(|e| e.to_string())?;
+    mac.update(signed.as_bytes());
+    mac.verify_slice(&decode(signature)?).map_err(|_| "bad signature".to_string())?;
+
+    let claims: Claims = serde_json::from_slice(&decode(payload)?).map_err(|_| malformed())?;
+    if claims.exp <= now_secs {
+        return Err("expired".to_string());
+    }
+    Ok(claims)
+}
+
+/// Checks that a client may subscribe to a channel: pool channels are open
+/// to everyone, an address's channel only to its owner.
+/// 
+/// # Arguments
+/// * `principal` - Who the connection authenticated as, if anyone
+/// * `channel` - A pool ID, or `address:` and an address
+/// 
+/// # Returns
+/// * `Result<Option<String>, ApiError>` - The normalized address of a private
+///   channel, `None` for a pool channel; `Unauthorized` without credentials,
+///   `Forbidden` for another owner's address
+pub fn authorize_channel(principal: Option<&Principal>, channel: &str) -> Result<Option<String>, ApiError> {
+    let Some(address) = channel.strip_prefix(ADDRESS_CHANNEL_PREFIX) else {
+        return Ok(None);
+    };
+    let address = normalize_address(address)
+        .ok_or_else(|| ApiError::BadRequest(format!("`{}` is not an address channel", channel)))?;
+    match principal {
+        None => Err(ApiError::Unauthorized(format!("Authenticate to subscribe to {}", channel))),
+        Some(principal) if !principal.owns(&address) => {
+            Err(ApiError::Forbidden(format!("{} belongs to another owner", channel)))
+        }
+        Some(_) => Ok(Some(address)),
+    }
+}
+
+/// Open WebSocket connections, in total and per authenticated owner
+struct OpenConnections {
+    total: usize,
+    per_owner: BTreeMap<String, usize>,
+}
+
+static OPEN_CONNECTIONS: Mutex<OpenConnections> = Mutex::new(OpenConnections { total: 0, per_owner: BTreeMap::new() });
+
+/// A WebSocket connection counted against `WS_MAX_CONNECTIONS` and, once
+/// authenticated, `WS_MAX_CONNECTIONS_PER_OWNER`. Dropping it releases it.
+pub struct ConnectionSlot {
+    owner: Option<String>,
+}
+
+impl ConnectionSlot {
+    /// Counts a new connection.
+    /// 
+    /// # Arguments
+    /// * `owner` - Owner of the connection's principal, if it authenticated
+    /// 
+    /// # Returns
+    /// * `Result<ConnectionSlot, ApiError>` - The slot, or `TooManyRequests` at either limit
+    pub fn acquire(owner: Option<&str>) -> Result<ConnectionSlot, ApiError> {
+        let mut open = OPEN_CONNECTIONS.lock()?;
+        if open.total >= config().ws_max_connections {
+            return Err(ApiError::TooManyRequests("Too many WebSocket connections".to_string()));
+        }
+        if let Some(owner) = owner {
+            reserve(&mut open, owner)?;
+        }
+        open.total += 1;
+        Ok(ConnectionSlot { owner: owner.map(str::to_string) })
+    }
+
+    /// Moves the connection to another owner after it authenticated in band.
+    /// 
+    /// # Returns
+    /// * `Result<(), ApiError>` - Success, or `TooManyRequests` if the new owner is at its limit
+    pub fn assign(&mut self, owner: &str) -> Result<(), ApiError> {
+        if self.owner.as_deref() == Some(owner) {
+            return Ok(());
+        }
+        let mut open = OPEN_CONNECTIONS.lock()?;
+        reserve(&mut open, owner)?;
+        if let Some(previous) = self.owner.replace(owner.to_string()) {
+            release(&mut open, &previous);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        if let Ok(mut open) = OPEN_CONNECTIONS.lock() {
+            open.total -= 1;
+            if let Some(owner) = &self.owner {
+                release(&mut open, owner);
+            }
+        }
+    }
+}
+
+/// Counts one more connection for an owner unless it is at its limit.
+fn reserve(open: &mut OpenConnections, owner: &str) -> Result<(), ApiError> {
+    let count = open.per_owner.entry(owner.to_string()).or_default();
+    if *count >= config().ws_max_connections_per_owner {
+        return Err(ApiError::TooManyRequests("Too many WebSocket connections for this owner".to_string()));
+    }
+    *count += 1;
+    Ok(())
+}
+
+/// Counts one connection less for an owner.
+fn release(open: &mut OpenConnections, owner: &str) {
+    if let Some(count) = open.per_owner.get_mut(owner) {
+        *count -= 1;
+        if *count == 0 {
+            open.per_owner.remove(owner);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"test-secret";
+
+    fn sign(header: &str, claims: &str, secret: &[u8]) -> String {
+        let signed = format!("{}.{}", URL_SAFE_NO_PAD.encode(header), URL_SAFE_NO_PAD.encode(claims));
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).unwrap();
+        mac.update(signed.as_bytes());
+        format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+    }
+
+    fn principal(addresses: &[&str]) -> Principal {
+        Principal { owner: "jwt:alice".to_string(), addresses: addresses.iter().map(|a| a.to_string()).collect() }
+    }
+
+    #[test]
+    fn jwt_is_accepted_until_it_expires() {
+        let token = sign(r#"{"alg":"HS256","typ":"JWT"}"#, r#"{"sub":"0xABC","exp":1000,"addresses":["0xdef"]}"#, SECRET);
+        let claims = verify_jwt(&token, SECRET, 999).unwrap();
+        assert_eq!(claims.sub, "0xABC");
+        assert_eq!(claims.addresses, vec!["0xdef"]);
+        assert_eq!(verify_jwt(&token, SECRET, 1000).err().as_deref(), Some("expired"));
+    }
+
+    #[test]
+    fn jwt_signed_otherwise_is_rejected() {
+        let claims = r#"{"sub":"0xabc","exp":1000}"#;
+        let forged = sign(r#"{"alg":"HS256"}"#, claims, b"another-secret");
+        assert_eq!(verify_jwt(&forged, SECRET, 0).err().as_deref(), Some("bad signature"));
+
+        let unsigned = format!("{}.{}.", URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#), URL_SAFE_NO_PAD.encode(claims));
+        assert_eq!(verify_jwt(&unsigned, SECRET, 0).err().as_deref(), Some("unsupported algorithm none"));
+
+        // Claims swapped under a valid signature
+        let token = sign(r#"{"alg":"HS256"}"#, claims, SECRET);
+        let (_, signature) = token.rsplit_once('.').unwrap();
+        let tampered = format!(
+            "{}.{}.{}",
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256"}"#),
+            URL_SAFE_NO_PAD.encode(r#"{"sub":"0xabc","exp":9999}"#),
+            signature
+        );
+        assert_eq!(verify_jwt(&tampered, SECRET, 0).err().as_deref(), Some("bad signature"));
+        assert!(verify_jwt("not-a-token", SECRET, 0).is_err());
+    }
+
+    #[test]
+    fn address_channels_are_open_only_to_their_owner() {
+        let owner = principal(&["0xabc"]);
+        assert_eq!(authorize_channel(None, "0xpool").unwrap(), None);
+        assert_eq!(authorize_channel(Some(&owner), "address:0xABC").unwrap().as_deref(), Some("0xabc"));
+        assert!(matches!(authorize_channel(None, "address:0xabc"), Err(ApiError::Unauthorized(_))));
+        assert!(matches!(authorize_channel(Some(&owner), "address:0xdef"), Err(ApiError::Forbidden(_))));
+        assert!(matches!(authorize_channel(Some(&owner), "address:zz"), Err(ApiError::BadRequest(_))));
+    }
+
+    #[test]
+    fn api_key_owns_the_addresses_registered_for_it() {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::db::init_schema(&conn).unwrap();
+        assert!(matches!(authenticate_api_key(&conn, "secret-key"), Err(ApiError::Unauthorized(_))));
+
+        crate::db::add_api_key_address(&conn, &api_key_hash("secret-key"), "0xabc", 0).unwrap();
+        let principal = authenticate_api_key(&conn, "secret-key").unwrap();
+        assert_eq!(principal.owner, format!("key:{}", api_key_hash("secret-key")));
+        assert!(principal.owns("0xabc"));
+        assert!(!principal.owns("0xdef"));
+    }
+}
@@ -0,0 +1,163 @@
+//! End-to-end replay of a fixture event stream through the indexer write path,
+//! the database and the HTTP API, with responses compared against golden files.
+//! 
+//! Run with `UPDATE_GOLDEN=1 cargo test --test fixture_replay` to rewrite the
+//! golden files after an intentional response change.
+
+use axum::{Extension, Router};
+use fooswap_backend::{candles, db, features, graph_cache, indexer, pricing::{self, PriceCache}, routes};
+use rusqlite::Connection;
+use serde_json::Value;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::net::TcpListener;
+
+/// Pool with the most swaps in the fixture
+const POOL_ID: &str = "0x4ec6ff55d62f5073eff873a88176a5bbb6e20aeec03948e4f2135d24f7cf07e4";
+const TOKEN_A: &str = "0xa70bf50e531ce1a817561f2f5d5b6645d4e806becf58ccc5e8cf6b8045a090a8";
+const TOKEN_B: &str = "0x49e2bb7eab54cf09b409ffafd3fa8a8a955a60eb972faacaefbed3dbd3207132";
+
+/// Events per committed page, small enough that the fixture spans several pages
+const PAGE_SIZE: usize = 3;
+
+/// Time token prices and risk are computed at, shortly after the last fixture event
+const FIXTURE_NOW_MS: i64 = 1751104600000;
+
+fn test_path(relative: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests").join(relative)
+}
+
+/// Compares a response with its golden file, or rewrites the file when
+/// `UPDATE_GOLDEN` is set.
+fn assert_golden(name: &str, actual: &Value) {
+    let path = test_path(&format!("golden/{}.json", name));
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(&path, serde_json::to_string_pretty(actual).unwrap() + "\n").unwrap();
+        return;
+    }
+    let expected: Value = serde_json::from_str(
+        &std::fs::read_to_string(&path).unwrap_or_else(|e| panic!("missing golden file {}: {}", path.display(), e)),
+    )
+    .unwrap();
+    assert_eq!(
+        actual,
+        &expected,
+        "{} differs from golden file:\n{}",
+        name,
+        serde_json::to_string_pretty(actual).unwrap()
+    );
+}
+
+#[tokio::test]
+async fn fixture_replay_matches_golden_responses() {
+    // Fixture timestamps are fixed in the past, so don't flag every response as stale
+    std::env::set_var("STALE_DATA_MODE", "off");
+
+    let events: Vec<Value> =
+        serde_json::from_str(&std::fs::read_to_string(test_path("fixtures/events.json")).unwrap()).unwrap();
+
+    let conn = Connection::open_in_memory().unwrap();
+    db::init_schema(&conn).unwrap();
+    for page in events.chunks(PAGE_SIZE) {
+        indexer::commit_events(&conn, page, 0, None).unwrap();
+    }
+    // Replaying the stream, as after a restart, must not change derived state
+    for page in events.chunks(PAGE_SIZE) {
+        indexer::commit_events(&conn, page, 0, None).unwrap();
+    }
+    let mismatches: i64 = conn
+        .query_row("SELECT COUNT(*) FROM ingest_log WHERE mismatch = 1", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(mismatches, 0, "replayed batches changed derived state");
+    while candles::aggregate_candles(&conn).unwrap() > 0 {}
+    let alias = db::PairAlias {
+        alias: "A-B".to_string(),
+        token_a: TOKEN_A.to_string(),
+        token_b: TOKEN_B.to_string(),
+        updated_at: FIXTURE_NOW_MS,
+    };
+    db::set_pair_alias(&conn, &alias).unwrap();
+    let label = db::AddressLabel {
+        address: "0x2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90".to_string(),
+        label: "Fixture Market Maker".to_string(),
+        category: Some("market_maker".to_string()),
+        updated_at: FIXTURE_NOW_MS,
+    };
+    db::import_labels(&conn, &[label]).unwrap();
+
+    let flags = features::load_feature_flags(&conn).unwrap();
+    let prices = pricing::compute_token_prices(&conn, FIXTURE_NOW_MS).unwrap();
+    let price_cache: PriceCache = Arc::new(RwLock::new(prices));
+    let graph_cache = graph_cache::load_graph_cache(&conn, None).unwrap();
+    let app = Router::new().nest(
+        "/api",
+        routes::api_routes()
+            .layer(Extension(Arc::new(Mutex::new(conn))))
+            .layer(Extension(price_cache))
+            .layer(Extension(graph_cache))
+            .layer(Extension(flags)),
+    );
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        axum::serve(listener, app.into_make_service()).await.unwrap();
+    });
+
+    let cases = [
+        ("pools", "/api/pools".to_string()),
+        ("pool", format!("/api/pools/{}", POOL_ID)),
+        ("tokens", "/api/tokens".to_string()),
+        ("swaps", format!("/api/swaps/{}", POOL_ID)),
+        ("swaps_page", format!("/api/swaps/{}?limit=2&before=1751104536847_6", POOL_ID)),
+        ("price", format!("/api/price?pair={}/{}", TOKEN_A, TOKEN_B)),
+        ("price_reversed", format!("/api/price?pair={}/{}", TOKEN_B, TOKEN_A)),
+        ("price_alias", "/api/price?pair=a-b".to_string()),
+        ("pool_at", format!("/api/pools/{}/at?ts=1751104400000", POOL_ID)),
+        ("failures", format!("/api/pools/{}/failures", POOL_ID)),
+        ("graph", "/api/graph".to_string()),
+        ("candles", format!("/api/candles?pool_id={}&interval=1h", POOL_ID)),
+        ("candles_filtered", format!("/api/candles?pool_id={}&interval=1h&filtered=true", POOL_ID)),
+        ("ticker", format!("/api/ticker?pair={}/{}", TOKEN_A, TOKEN_B)),
+        ("ticker_reversed", format!("/api/ticker?pair={}/{}", TOKEN_B, TOKEN_A)),
+        ("routing_accuracy", "/api/routing/accuracy".to_string()),
+        ("quote_conversion", "/api/quotes/conversion".to_string()),
+    ];
+    let client = reqwest::Client::new();
+    for (name, path) in cases {
+        let body: Value = client
+            .get(format!("http://{}{}", addr, path))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_golden(name, &body);
+    }
+
+    let batch = serde_json::json!([
+        { "pool_id": POOL_ID, "interval": "1h" },
+        { "pool_id": POOL_ID, "interval": "1m", "from": 1751104260000i64, "to": 1751104500000i64 }
+    ]);
+    let body: Value = client
+        .post(format!("http://{}/api/candles/batch", addr))
+        .json(&batch)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_golden("candles_batch", &body);
+
+    // Downloads are NDJSON, compared as an array of their lines
+    let ndjson_cases = [(
+        "candles_download",
+        format!("/api/candles/download?pool_id={}&interval=1m&from=0&to={}&max_points=3", POOL_ID, FIXTURE_NOW_MS),
+    )];
+    for (name, path) in ndjson_cases {
+        let body = client.get(format!("http://{}{}", addr, path)).send().await.unwrap().text().await.unwrap();
+        let lines: Vec<Value> = body.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_golden(name, &Value::Array(lines));
+    }
+}